@@ -1,15 +1,46 @@
-use crate::autocomplete::{parse_buffer_symbols, AutocompleteState};
+use crate::autocomplete::{
+    parse_includes, parse_symbol_definitions, AutocompleteState, CompletionContext, IncludeCache,
+    Suggestion, SuggestionKind,
+};
+use crate::build::pipeline::BuildOutput;
 use crate::build::Pipeline;
 use crate::config::{Config, ProjectConfig};
 use crate::diagnostics::{self, Diagnostic, DiagnosticSeverity};
 use crate::docs::{self, DocEntry};
+use crate::file_state::FileStateStore;
+use crate::history::History;
+use crate::keymap::KeyContext;
+use crate::lint;
+use crate::masm_lang::{self, Dialect};
+use crate::options::{self, SetArg};
+use crate::project;
+use crate::session::Session;
 use crate::theme::Theme;
-use crate::ui::editor::EditorState;
+use crate::ui::buffer_picker::{self, BufferPickerState};
+use crate::ui::diff_view::{changed_line_count, DiffViewState};
+use crate::ui::editor::cursor::CursorOps;
+use crate::ui::editor::{EditorState, LineEnding, Substitution};
 use crate::ui::file_tree::FileTreeState;
+use crate::ui::outline::OutlineState;
 use crate::ui::output::OutputState;
+use crate::ui::quickfix::{self, QuickfixEntry, QuickfixList};
+use crate::ui::symbol_picker::{ProjectSymbol, SymbolPickerState};
+use crate::utf8;
 use anyhow::{Context, Result};
+use ratatui::layout::Rect;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Instant;
+
+/// Result of a background build, with the pipeline that ran it
+type BuildReceiver = mpsc::Receiver<(Pipeline, Result<BuildOutput>)>;
+
+/// How many closed buffers :reopen remembers
+const MAX_CLOSED_BUFFERS: usize = 20;
+
+/// Status bar text when there is nothing more recent to report
+pub const IDLE_HINT: &str = "Press F1 for help | F5 build+run | F6 build | F7 run";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
@@ -21,6 +52,7 @@ pub enum Mode {
     InputPopup,
     Visual,
     VisualLine,
+    VisualBlock,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -30,6 +62,34 @@ pub enum PendingAction {
     CreateDir,
     Rename,
     Delete,
+    DeleteLines(usize),
+    DeleteSelection,
+    // :mkbuf prompts - name, then type, count and fill value
+    MkBufName,
+    MkBufType(String),
+    MkBufCount(String, String),
+    MkBufFill(String, String, usize),
+    // Project entry file, asked for when building a file without one
+    SetEntryFile,
+    // Saving over a file another program changed since it was opened
+    OverwriteChanged,
+}
+
+impl PendingAction {
+    /// Actions confirmed from the editor rather than the file tree
+    fn is_editor_action(&self) -> bool {
+        matches!(
+            self,
+            PendingAction::DeleteLines(_)
+                | PendingAction::DeleteSelection
+                | PendingAction::MkBufName
+                | PendingAction::MkBufType(_)
+                | PendingAction::MkBufCount(..)
+                | PendingAction::MkBufFill(..)
+                | PendingAction::SetEntryFile
+                | PendingAction::OverwriteChanged
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -37,6 +97,35 @@ pub enum FocusedPanel {
     Editor,
     FileTree,
     Output,
+    Outline,
+}
+
+impl FocusedPanel {
+    /// Order Tab moves focus in; Shift+Tab walks it backwards
+    const RING: [FocusedPanel; 4] = [
+        FocusedPanel::Editor,
+        FocusedPanel::FileTree,
+        FocusedPanel::Output,
+        FocusedPanel::Outline,
+    ];
+
+    /// The next panel in the ring that `visible` accepts. The editor is
+    /// always visible, so this never gets stuck.
+    pub fn step(self, forward: bool, visible: impl Fn(FocusedPanel) -> bool) -> FocusedPanel {
+        let len = Self::RING.len();
+        let start = Self::RING.iter().position(|&p| p == self).unwrap_or(0);
+        (1..=len)
+            .map(|i| {
+                let idx = if forward {
+                    (start + i) % len
+                } else {
+                    (start + len - i) % len
+                };
+                Self::RING[idx]
+            })
+            .find(|&panel| panel == FocusedPanel::Editor || visible(panel))
+            .unwrap_or(FocusedPanel::Editor)
+    }
 }
 
 pub struct App {
@@ -50,82 +139,198 @@ pub struct App {
     pub input_popup_title: String,
     pub input_popup_value: String,
     pub pending_action: PendingAction,
-    pub status_message: String,
+    // Set through set_status, which restarts its timeout
+    status_message: String,
+    // When the message was set and whether it stays until replaced rather
+    // than timing out
+    status_since: std::time::Instant,
+    status_sticky: bool,
     pub project_dir: PathBuf,
     pub config: Config,
     pub project_config: ProjectConfig,
     pub pipeline: Pipeline,
     pub last_build_success: bool,
+    // Build running on a background thread: it sends back the pipeline
+    // (which remembers the executable) and the result
+    pub build_job: Option<(BuildReceiver, Instant)>,
+    // Run the program once the pending build succeeds (F5, :br)
+    pub run_after_build: bool,
     pub show_file_tree: bool,
     pub show_output: bool,
     pub show_help: bool,
     pub help_scroll: usize,
+    pub show_undo_tree: bool,
+    pub undo_tree_selected: usize,
+    // Recently closed buffers as (path, line, col), most recent last (:reopen)
+    pub closed_buffers: Vec<(PathBuf, usize, usize)>,
+    // Side-by-side file comparison (:difftool)
+    pub diff_view: Option<DiffViewState>,
+    // Go to symbol in project (Ctrl+Shift+O, :symbol)
+    pub symbol_picker: Option<SymbolPickerState>,
+    // Open buffer list (:ls, :buffers)
+    pub buffer_picker: Option<BufferPickerState>,
+    // Results of the last :grep (]q/[q), and whether the list is shown
+    pub quickfix: QuickfixList,
+    pub show_quickfix: bool,
+    // Symbols of the active buffer in a sidebar (F3, :outline)
+    pub outline: OutlineState,
+    pub show_outline: bool,
     pub output_only_mode: bool, // Full-screen output view
+    // Where the editor was last drawn, to map mouse clicks to text
+    pub editor_area: Rect,
+    // Last left click as (time, buffer position, clicks in a row) for
+    // double- and triple-click selection
+    pub last_click: Option<(Instant, (usize, usize), u8)>,
     pub file_tree_width: u16,
     pub output_height: u16,
     // Vim motion support
     pub pending_count: Option<usize>,
-    pub pending_char: Option<char>,    // For f, F, t, T commands
-    pub pending_g: bool,               // For gd (go to definition) command
-    pub pending_bracket: Option<char>, // For ]e, [e (error navigation) commands
+    pub pending_char: Option<char>,     // For f, F, t, T commands
+    pub pending_g: bool,                // For gd (go to definition) command
+    pub pending_bracket: Option<char>,  // For ]e, [e (error navigation) commands
+    pub pending_operator: Option<char>, // d or c, awaiting a motion (dd, cc, diw, ciw)
+    pub pending_inner: bool,            // After di/ci, awaiting the text object
+    pub pending_at: bool,               // For @: (repeat last command)
+    pub pending_register: Option<char>, // Register named with "x for the next y/d/c/p
+    // Last `:` command line, repeated by @:
+    pub last_command: Option<String>,
+    // `:` command lines run before, recalled with Up/Down
+    pub command_history: History,
+    // `/` searches run before, recalled with Up/Down
+    pub search_history: History,
     // Autocomplete
     pub autocomplete: AutocompleteState,
+    // Symbols of INCLUDE files, reread when they change on disk
+    include_cache: IncludeCache,
     // Hover documentation
     pub show_hover: bool,
     pub hover_doc: Option<&'static DocEntry>,
+    // Signature hint shown while typing operands in insert mode
+    pub signature_hint: Option<&'static DocEntry>,
     // Diagnostics (build errors/warnings)
     pub diagnostics: Vec<Diagnostic>,
     pub current_diagnostic: usize,
+    pub show_diagnostics_panel: bool,
     // Autosave tracking
     pub last_save_time: std::time::Instant,
     pub autosave_enabled: bool,
 }
 
 impl App {
-    pub fn new(path: PathBuf) -> Result<Self> {
+    /// Start in the project given on the command line. `paths` may mix
+    /// files and a directory: the files are opened as buffers (the first
+    /// focused) and the directory, or else the first file's parent, becomes
+    /// the project root.
+    pub fn new(paths: Vec<PathBuf>) -> Result<Self> {
         let config = Config::load()?;
 
-        let project_dir = if path.is_file() {
-            path.parent()
+        let (dirs, files): (Vec<PathBuf>, Vec<PathBuf>) =
+            paths.into_iter().partition(|path| path.is_dir());
+        let project_dir = match (dirs.first(), files.first()) {
+            (Some(dir), _) => dir.clone(),
+            (None, Some(file)) => file
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
                 .map(|p| p.to_path_buf())
-                .unwrap_or_else(|| PathBuf::from("."))
-        } else {
-            path.clone()
+                .unwrap_or_else(|| PathBuf::from(".")),
+            (None, None) => PathBuf::from("."),
         };
+        let mut open_errors: Vec<String> = dirs
+            .iter()
+            .skip(1)
+            .map(|dir| format!("Ignoring extra directory {}", dir.display()))
+            .collect();
+
+        // A broken .masmide.toml (an unknown assembler, say) is reported in
+        // the output panel; the defaults are used meanwhile
+        let project_config = ProjectConfig::load(&project_dir).unwrap_or_else(|e| {
+            open_errors.push(format!("Invalid .masmide.toml: {:#}", e));
+            ProjectConfig::default()
+        });
 
-        let project_config = ProjectConfig::load(&project_dir).unwrap_or_default();
+        let mut editor = EditorState::new(config.editor.tab_size);
+        editor.apply_config(&config.editor);
+        editor.title_path = config.ui.title_path;
+        editor.project_root = project_dir.clone();
+        if config.editor.restore_file_state {
+            editor.file_states = Some(FileStateStore::load());
+        }
 
-        let file_to_open = if path.is_file() {
-            Some(path)
-        } else {
+        let mut status_message = String::from(IDLE_HINT);
+
+        // Without files on the command line, reopen the last session's buffers
+        let mut restored = 0;
+        if files.is_empty() {
+            if let Some(session) = Session::load(&project_dir) {
+                let (opened, missing) = session.restore(&mut editor, &project_dir);
+                restored = opened;
+                status_message = match missing.len() {
+                    0 => format!("Restored {} buffers from last session", opened),
+                    n => format!(
+                        "Restored {} buffers; {} no longer exist: {}",
+                        opened,
+                        n,
+                        missing
+                            .iter()
+                            .map(|p| p.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                };
+            }
+        }
+
+        let files_to_open = if restored > 0 {
+            Vec::new()
+        } else if files.is_empty() {
             let entry = project_dir.join(&project_config.entry_file);
             if entry.exists() {
-                Some(entry)
+                vec![entry]
             } else {
-                None
+                Vec::new()
             }
+        } else {
+            files
         };
 
-        let mut editor = EditorState::new(config.editor.tab_size);
-        editor.auto_indent = config.editor.auto_indent;
-
-        let mut status_message =
-            String::from("Press F1 for help | F5 build+run | F6 build | F7 run");
-
-        if let Some(file_path) = file_to_open {
-            match editor.open_file(&file_path) {
+        // A file that fails to open is reported without stopping the rest
+        let mut first_opened = None;
+        let mut opened = 0;
+        for file_path in &files_to_open {
+            match editor.open_file(file_path) {
                 Ok(_) => {
-                    status_message = format!("Opened: {}", file_path.display());
+                    first_opened.get_or_insert(editor.active_buffer);
+                    opened += 1;
                 }
                 Err(e) => {
-                    status_message = format!("Failed to open {}: {}", file_path.display(), e);
+                    open_errors.push(format!("Failed to open {}: {}", file_path.display(), e))
                 }
             }
         }
+        if let Some(idx) = first_opened {
+            editor.set_active_buffer(idx);
+            status_message = match files_to_open.len() {
+                1 => format!("Opened: {}", files_to_open[0].display()),
+                n => format!("Opened {} of {} files", opened, n),
+            };
+            if let Some(line) = editor.first_long_line() {
+                status_message = long_line_notice(line);
+            }
+        }
+        if let Some(error) = open_errors.first() {
+            status_message = error.clone();
+        }
 
         let file_tree = FileTreeState::new(&project_dir)?;
-        let output = OutputState::new();
+        let mut output = OutputState::new();
+        output.show_timestamps = config.ui.output_timestamps;
+        output.show_level_tags = config.ui.output_level_tags;
+        for error in &open_errors {
+            output.append_error(error);
+        }
         let pipeline = Pipeline::new(&config, &project_config, &project_dir);
+        let command_history = History::load(&project_dir, "command_history");
+        let search_history = History::load(&project_dir, "search_history");
 
         let file_tree_width = config.layout.file_tree_width;
         let output_height = config.layout.output_height;
@@ -142,28 +347,54 @@ impl App {
             input_popup_title: String::new(),
             input_popup_value: String::new(),
             pending_action: PendingAction::None,
+            status_since: std::time::Instant::now(),
+            status_sticky: !open_errors.is_empty(),
             status_message,
             project_dir,
             config,
             project_config,
             pipeline,
             last_build_success: false,
+            build_job: None,
+            run_after_build: false,
             show_file_tree: true,
             show_output: true,
             show_help: false,
             help_scroll: 0,
+            show_undo_tree: false,
+            undo_tree_selected: 0,
+            closed_buffers: Vec::new(),
+            diff_view: None,
+            symbol_picker: None,
+            buffer_picker: None,
+            quickfix: QuickfixList::default(),
+            show_quickfix: false,
+            outline: OutlineState::default(),
+            show_outline: false,
             output_only_mode: false,
+            editor_area: Rect::default(),
+            last_click: None,
             file_tree_width,
             output_height,
             pending_count: None,
             pending_char: None,
             pending_g: false,
             pending_bracket: None,
+            pending_operator: None,
+            pending_inner: false,
+            pending_at: false,
+            pending_register: None,
+            last_command: None,
+            command_history,
+            search_history,
             autocomplete: AutocompleteState::new(),
+            include_cache: IncludeCache::default(),
             show_hover: false,
             hover_doc: None,
+            signature_hint: None,
             diagnostics: Vec::new(),
             current_diagnostic: 0,
+            show_diagnostics_panel: false,
             last_save_time: std::time::Instant::now(),
             autosave_enabled,
         })
@@ -175,7 +406,23 @@ impl App {
 
     pub fn set_theme(&mut self, name: &str) {
         self.config.set_theme(name);
-        self.status_message = format!("Theme changed to: {}", name);
+        self.set_status(format!("Theme changed to: {}", name));
+    }
+
+    /// `:theme load <path>` / `:theme save <path>`: switch to a theme read
+    /// from a TOML file for this session, or write the current one out
+    pub fn theme_file(&mut self, load: bool, name: &str) {
+        let path = self.resolve_project_path(name);
+        let result = if load {
+            Theme::from_toml_file(&path).map(|theme| self.config.theme = theme)
+        } else {
+            self.config.theme.save_toml_file(&path)
+        };
+        self.set_status(match result {
+            Ok(()) if load => format!("Theme loaded: {}", self.config.theme.name),
+            Ok(()) => format!("Theme saved: {}", path.display()),
+            Err(e) => format!("Theme error: {}", e),
+        });
     }
 
     pub fn increase_file_tree_width(&mut self) {
@@ -213,145 +460,566 @@ impl App {
 
     pub fn execute_search(&mut self) {
         self.editor.search(&self.search_input);
+        self.search_history.push(&self.search_input);
         if let Some(status) = self.editor.search_status() {
-            self.status_message = format!("Search: {} - {}", self.search_input, status);
+            self.set_status(format!("Search: {} - {}", self.search_input, status));
         }
         self.mode = Mode::Normal;
     }
 
     pub fn cancel_search(&mut self) {
         self.search_input.clear();
+        self.search_history.reset();
         self.editor.clear_search();
         self.mode = Mode::Normal;
     }
 
+    /// Whether a command deleting `lines` lines should ask first
+    pub fn needs_delete_confirmation(&self, lines: usize) -> bool {
+        self.config.editor.confirm_large_deletes
+            && lines > self.config.editor.confirm_delete_threshold
+    }
+
+    pub fn request_delete_confirmation(&mut self, action: PendingAction, lines: usize) {
+        self.mode = Mode::InputPopup;
+        self.pending_action = action;
+        self.input_popup_title = format!("Delete {} lines? (y/n):", lines);
+        self.input_popup_value.clear();
+    }
+
+    /// Start the :mkbuf prompt sequence
+    pub fn start_mkbuf(&mut self) {
+        self.mode = Mode::InputPopup;
+        self.pending_action = PendingAction::MkBufName;
+        self.input_popup_title = String::from("Buffer name:");
+        self.input_popup_value.clear();
+    }
+
+    /// Move to the next :mkbuf prompt, or re-ask with an error
+    fn prompt_mkbuf(&mut self, action: PendingAction, title: &str) {
+        self.pending_action = action;
+        self.input_popup_title = title.to_string();
+    }
+
     pub fn execute_input_popup(&mut self) -> Result<()> {
         let value = self.input_popup_value.trim().to_string();
         self.input_popup_value.clear();
+        let return_mode = if self.pending_action.is_editor_action() {
+            Mode::Normal
+        } else {
+            Mode::FileTree
+        };
 
         match self.pending_action {
             PendingAction::CreateFile => {
                 if !value.is_empty() {
                     self.file_tree.create_file(&value)?;
-                    self.status_message = format!("Created file: {}", value);
+                    self.set_status(format!("Created file: {}", value));
                 }
             }
             PendingAction::CreateDir => {
                 if !value.is_empty() {
                     self.file_tree.create_dir(&value)?;
-                    self.status_message = format!("Created directory: {}", value);
+                    self.set_status(format!("Created directory: {}", value));
                 }
             }
             PendingAction::Rename => {
                 if !value.is_empty() {
                     self.file_tree.rename_current(&value)?;
-                    self.status_message = format!("Renamed to: {}", value);
+                    self.set_status(format!("Renamed to: {}", value));
                 }
             }
             PendingAction::Delete => {
                 if value.to_lowercase() == "y" {
                     self.file_tree.delete_current()?;
-                    self.status_message = String::from("Deleted item");
+                    self.set_status("Deleted item");
+                } else {
+                    self.set_status("Deletion cancelled");
+                }
+            }
+            PendingAction::DeleteLines(count) => {
+                if value.to_lowercase() == "y" {
+                    for _ in 0..count {
+                        self.editor.delete_line();
+                    }
+                    self.set_status(format!("Deleted {} lines", count));
+                } else {
+                    self.set_status("Deletion cancelled");
+                }
+            }
+            PendingAction::DeleteSelection => {
+                if value.to_lowercase() == "y" && self.editor.delete_selection() {
+                    self.set_status("Deleted selection");
+                } else {
+                    self.editor.clear_selection();
+                    self.set_status("Deletion cancelled");
+                }
+            }
+            PendingAction::MkBufName => {
+                if !crate::autocomplete::is_valid_identifier(&value) {
+                    self.prompt_mkbuf(PendingAction::MkBufName, "Invalid name - buffer name:");
+                } else {
+                    self.prompt_mkbuf(
+                        PendingAction::MkBufType(value),
+                        "Type (BYTE/WORD/DWORD) [BYTE]:",
+                    );
+                }
+                return Ok(());
+            }
+            PendingAction::MkBufType(ref name) => {
+                let ty = if value.is_empty() {
+                    String::from("BYTE")
+                } else {
+                    value.to_uppercase()
+                };
+                let next = if matches!(ty.as_str(), "BYTE" | "WORD" | "DWORD") {
+                    (PendingAction::MkBufCount(name.clone(), ty), "Count:")
+                } else {
+                    (
+                        PendingAction::MkBufType(name.clone()),
+                        "Type must be BYTE, WORD or DWORD:",
+                    )
+                };
+                self.prompt_mkbuf(next.0, next.1);
+                return Ok(());
+            }
+            PendingAction::MkBufCount(ref name, ref ty) => {
+                let next = match value.parse::<usize>() {
+                    Ok(count) if count > 0 => (
+                        PendingAction::MkBufFill(name.clone(), ty.clone(), count),
+                        "Fill value [?]:",
+                    ),
+                    _ => (
+                        PendingAction::MkBufCount(name.clone(), ty.clone()),
+                        "Count must be a positive integer:",
+                    ),
+                };
+                self.prompt_mkbuf(next.0, next.1);
+                return Ok(());
+            }
+            PendingAction::MkBufFill(ref name, ref ty, count) => {
+                let fill = if value.is_empty() {
+                    "?"
+                } else {
+                    value.as_str()
+                };
+                let decl = format!("{} {} {} DUP({})", name, ty, count, fill);
+                self.editor.insert_text(&decl);
+                self.set_status(format!("Inserted: {}", decl));
+            }
+            PendingAction::SetEntryFile => {
+                if value.is_empty() {
+                    self.set_status("Entry file not set");
+                } else if !self.project_dir.join(&value).is_file() {
+                    self.set_sticky_status(format!("No such file: {}", value));
                 } else {
-                    self.status_message = String::from("Deletion cancelled");
+                    self.pending_action = PendingAction::None;
+                    self.mode = return_mode;
+                    self.project_config.entry_file = PathBuf::from(&value);
+                    self.project_config.save(&self.project_dir)?;
+                    self.build()?;
+                    self.set_status(format!(
+                        "Entry file set to {}; {}",
+                        value, self.status_message
+                    ));
+                    return Ok(());
                 }
             }
+            PendingAction::OverwriteChanged => match value.to_lowercase().as_str() {
+                "y" => {
+                    self.pending_action = PendingAction::None;
+                    self.mode = return_mode;
+                    return self.write_current_file(true);
+                }
+                "d" => {
+                    if let Some(path) = self.editor.current_file().cloned() {
+                        self.open_diff_view(None, &path.to_string_lossy());
+                    }
+                    self.set_status("Not saved: buffer vs disk (:w! to overwrite)");
+                }
+                _ => self.set_status("Not saved: file changed on disk"),
+            },
             PendingAction::None => {}
         }
 
         self.pending_action = PendingAction::None;
-        self.mode = Mode::FileTree; // Return to file tree
+        self.mode = return_mode; // Back to where the popup was opened
         Ok(())
     }
 
     pub fn cancel_input_popup(&mut self) {
         self.input_popup_value.clear();
+        self.mode = if self.pending_action.is_editor_action() {
+            self.editor.clear_selection();
+            Mode::Normal
+        } else {
+            Mode::FileTree
+        };
         self.pending_action = PendingAction::None;
-        self.mode = Mode::FileTree;
-        self.status_message = String::from("Cancelled");
+        self.set_status("Cancelled");
     }
 
+    /// Start building the current file (or the project's entry file) on a
+    /// background thread. `poll_build` picks up the result.
     pub fn build(&mut self) -> Result<()> {
+        self.start_build(false)
+    }
+
+    /// Build ignoring the build cache (`:rebuild`)
+    pub fn rebuild(&mut self) -> Result<()> {
+        self.start_build(true)
+    }
+
+    /// Assemble and link every source in the project's `targets`, ignoring
+    /// the build cache (`:build all`)
+    pub fn build_all(&mut self) -> Result<()> {
+        if self.project_config.targets.is_empty() {
+            self.set_status("No targets in .masmide.toml");
+            return Ok(());
+        }
+        self.start_build(true)
+    }
+
+    fn start_build(&mut self, force: bool) -> Result<()> {
+        if self.build_job.is_some() {
+            self.set_status("Build already running");
+            return Ok(());
+        }
         self.output.clear();
         self.diagnostics.clear();
         self.current_diagnostic = 0;
-        self.status_message = String::from("Building...");
+        self.set_status("Building...");
 
-        let source_path = match self.editor.current_file().cloned() {
+        let current = match self.editor.current_file().cloned() {
             Some(p) => p,
             None => {
                 self.output.append_error("No file open to build");
-                self.status_message = String::from("Build failed: no file open");
+                self.set_status("Build failed: no file open");
                 self.last_build_success = false;
                 return Ok(());
             }
         };
 
+        if !self.current_dialect_buildable() {
+            self.last_build_success = false;
+            return Ok(());
+        }
+
         // Save before building
         self.save_current_file()?;
+        if self.pending_action == PendingAction::OverwriteChanged {
+            // Building would use the other program's version
+            return Ok(());
+        }
+        if self.config.lint.size_mismatch {
+            self.lint_current_buffer(&current);
+        }
+
+        // A project with targets links all of them, saving any open ones
+        let sources = if self.project_config.targets.is_empty() {
+            let Some(source_path) = self.build_source(current) else {
+                self.last_build_success = false;
+                return Ok(());
+            };
+            vec![source_path]
+        } else {
+            let skipped = self.save_all()?;
+            if skipped > 0 {
+                self.output.append_info(&format!(
+                    "{} buffer{} changed on disk; building the disk version",
+                    skipped,
+                    if skipped == 1 { "" } else { "s" }
+                ));
+            }
+            self.project_config
+                .targets
+                .iter()
+                .map(|target| self.project_dir.join(target))
+                .collect()
+        };
 
-        match self.pipeline.build(&source_path) {
+        self.last_build_success = false;
+        let mut pipeline = self.pipeline.clone();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = pipeline.build(&sources, force);
+            let _ = tx.send((pipeline, result));
+        });
+        self.build_job = Some((rx, Instant::now()));
+        Ok(())
+    }
+
+    /// Build, then run the program if the build succeeds
+    pub fn build_and_run(&mut self) -> Result<()> {
+        if self.build_job.is_some() {
+            self.set_status("Build already running");
+            return Ok(());
+        }
+        self.build()?;
+        self.run_after_build = self.build_job.is_some();
+        Ok(())
+    }
+
+    /// Whether a background build is running
+    pub fn building(&self) -> bool {
+        self.build_job.is_some()
+    }
+
+    /// Spinner frame for the status bar while a build runs
+    pub fn build_spinner(&self) -> Option<char> {
+        const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        let (_, started) = self.build_job.as_ref()?;
+        let frame = started.elapsed().as_millis() / 100;
+        Some(FRAMES[frame as usize % FRAMES.len()])
+    }
+
+    /// Report the background build once it has finished, and start the
+    /// program if F5 asked for it. Called from the main loop.
+    pub fn poll_build(&mut self) -> Result<()> {
+        let Some((rx, _)) = &self.build_job else {
+            return Ok(());
+        };
+        let (pipeline, result) = match rx.try_recv() {
+            Ok(done) => done,
+            Err(mpsc::TryRecvError::Empty) => return Ok(()),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.build_job = None;
+                self.run_after_build = false;
+                self.output
+                    .append_error("Build thread stopped unexpectedly");
+                self.set_sticky_status("Build failed");
+                return Ok(());
+            }
+        };
+        self.build_job = None;
+        self.pipeline = pipeline;
+        self.finish_build(result);
+        if std::mem::take(&mut self.run_after_build) && self.last_build_success {
+            self.run()?;
+        }
+        Ok(())
+    }
+
+    fn finish_build(&mut self, result: Result<BuildOutput>) {
+        match result {
             Ok(build_output) => {
-                // Parse diagnostics from both stdout and stderr (JWasm writes to both)
-                let mut all_diagnostics =
-                    diagnostics::parse_jwasm_output(&build_output.stdout, &self.project_dir);
-                all_diagnostics.extend(diagnostics::parse_jwasm_output(
+                self.echo_commands(&build_output.commands);
+
+                // Parse diagnostics from both stdout and stderr (assemblers write to both)
+                let mut all_diagnostics = diagnostics::parse_output(
+                    self.project_config.assembler,
+                    &build_output.stdout,
+                    &self.project_dir,
+                );
+                all_diagnostics.extend(diagnostics::parse_output(
+                    self.project_config.assembler,
                     &build_output.stderr,
                     &self.project_dir,
                 ));
-                self.diagnostics = all_diagnostics;
+                // Unresolved symbols from the linker, placed on a line of
+                // an open buffer that uses them
+                let sources: Vec<(&Path, &[String])> = self
+                    .editor
+                    .buffers
+                    .iter()
+                    .filter_map(|buf| Some((buf.file_path.as_deref()?, buf.lines.as_slice())))
+                    .collect();
+                all_diagnostics.extend(diagnostics::parse_link_output(
+                    &build_output.stderr,
+                    &sources,
+                ));
+                // Added to the warnings from the pre-build lint
+                self.diagnostics.extend(all_diagnostics);
 
                 let (errors, warnings) = diagnostics::count_by_severity(&self.diagnostics);
                 self.last_build_success = build_output.success;
 
-                if build_output.success {
+                if build_output.up_to_date {
+                    self.output.append_success(&build_output.stdout);
+                    self.set_status("Build skipped (up to date)");
+                } else if build_output.success {
                     // Show success message
                     self.output.append_success(&build_output.stdout);
+                    let seconds = build_output.elapsed.as_secs_f64();
+                    self.output
+                        .append_info(&format!("Assembled in {:.2}s", seconds));
                     if warnings > 0 {
                         self.output.append_stderr(&build_output.stderr);
-                        self.status_message = format!(
-                            "Build successful ({} warning{})",
+                        self.set_status(format!(
+                            "Build successful in {:.2}s ({} warning{})",
+                            seconds,
                             warnings,
                             if warnings == 1 { "" } else { "s" }
-                        );
+                        ));
                     } else {
-                        self.status_message = String::from("Build successful");
+                        self.set_status(format!("Build successful in {:.2}s", seconds));
                     }
                 } else {
                     // Show errors
                     if !build_output.stderr.is_empty() {
                         self.output.append_stderr(&build_output.stderr);
                     }
-                    self.status_message = format!(
+                    self.scroll_output_to_first_error();
+                    self.set_sticky_status(format!(
                         "Build failed: {} error{}, {} warning{}",
                         errors,
                         if errors == 1 { "" } else { "s" },
                         warnings,
                         if warnings == 1 { "" } else { "s" }
-                    );
+                    ));
                 };
             }
             Err(e) => {
                 self.output.append_error(&format!("{e}"));
-                self.status_message = String::from("Build failed");
+                self.set_sticky_status("Build failed");
                 self.last_build_success = false;
             }
         }
 
         self.show_output = true;
-        Ok(())
+    }
+
+    /// Warn about register size mismatches in the current buffer before
+    /// the assembler runs, in the output panel and the gutter
+    fn lint_current_buffer(&mut self, current: &Path) {
+        let warnings = lint::size_mismatches(
+            &self.editor.buffers[self.editor.active_buffer].lines,
+            current,
+        );
+        let name = current
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        for warning in &warnings {
+            self.output.append_stderr(&format!(
+                "{}({}): warning: {}",
+                name, warning.line, warning.message
+            ));
+        }
+        self.diagnostics.extend(warnings);
+    }
+
+    /// File to build when `current` is active. A file without an entry
+    /// point (an INCLUDE file, say) builds the project's entry file
+    /// instead; if that doesn't exist either, the user is asked for one and
+    /// None is returned.
+    fn build_source(&mut self, current: PathBuf) -> Option<PathBuf> {
+        let entry = self.project_dir.join(&self.project_config.entry_file);
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        if project::has_entry_point(&buf.lines) || same_file(&current, &entry) {
+            return Some(current);
+        }
+        if entry.is_file() {
+            self.output.append_info(&format!(
+                "No entry point here; building {}",
+                entry.display()
+            ));
+            return Some(entry);
+        }
+        if !self.config.toolchain.prompt_entry_file {
+            return Some(current);
+        }
+
+        let suggestion = project::find_entry_file(&self.project_dir)
+            .map(|path| {
+                path.strip_prefix(&self.project_dir)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string()
+            })
+            .unwrap_or_default();
+        self.mode = Mode::InputPopup;
+        self.pending_action = PendingAction::SetEntryFile;
+        self.input_popup_title = format!(
+            "{} not found. Entry file:",
+            self.project_config.entry_file.display()
+        );
+        self.input_popup_value = suggestion;
+        self.set_status("No entry point: choose the project's entry file");
+        None
+    }
+
+    /// Assemble the current buffer without linking and refresh diagnostics.
+    /// Unsaved changes are checked as they are, without saving.
+    pub fn check(&mut self) {
+        self.output.clear();
+        self.diagnostics.clear();
+        self.current_diagnostic = 0;
+        if !self.current_dialect_buildable() {
+            return;
+        }
+
+        let source_path = self
+            .editor
+            .current_file()
+            .cloned()
+            .unwrap_or_else(|| self.project_dir.join("untitled.asm"));
+        let unsaved = (self.editor.modified() || self.editor.current_file().is_none())
+            .then(|| self.editor.get_content());
+
+        match self.pipeline.check(&source_path, unsaved.as_deref()) {
+            Ok(check_output) => {
+                self.echo_commands(&check_output.commands);
+                self.diagnostics = diagnostics::parse_output(
+                    self.project_config.assembler,
+                    &check_output.stderr,
+                    &self.project_dir,
+                );
+                if !check_output.stderr.is_empty() {
+                    self.output.append_stderr(&check_output.stderr);
+                }
+                if !check_output.success {
+                    self.scroll_output_to_first_error();
+                }
+
+                let (errors, warnings) = diagnostics::count_by_severity(&self.diagnostics);
+                self.set_status(if check_output.success && warnings == 0 {
+                    String::from("Check passed")
+                } else {
+                    format!(
+                        "Check: {} error{}, {} warning{}",
+                        errors,
+                        if errors == 1 { "" } else { "s" },
+                        warnings,
+                        if warnings == 1 { "" } else { "s" }
+                    )
+                });
+            }
+            Err(e) => {
+                self.output.append_error(&format!("{e}"));
+                self.set_sticky_status("Check failed");
+            }
+        }
     }
 
     pub fn run(&mut self) -> Result<()> {
-        self.status_message = String::from("Running...");
+        let args = self.project_config.run_args.clone();
+        self.run_with(&args)
+    }
+
+    /// Run the program with `args` instead of the configured `run_args`
+    /// (`:run <args>`). Input comes from `run_stdin_file` if one is set.
+    pub fn run_with(&mut self, args: &[String]) -> Result<()> {
+        if self.build_job.is_some() {
+            self.set_status("Build in progress");
+            return Ok(());
+        }
+        self.set_status("Running...");
 
         // Add blank line separator if there's already output from build
         if !self.output.is_empty() {
             self.output.append_divider();
         }
 
-        match self.pipeline.run() {
+        let stdin = self
+            .project_config
+            .run_stdin_file
+            .as_ref()
+            .map(|path| self.project_dir.join(path));
+        match self.pipeline.run(args, stdin.as_deref()) {
             Ok(run_output) => {
+                self.echo_commands(&run_output.commands);
+
                 // Show program output
                 let trimmed_stdout = run_output.stdout.trim();
                 if !trimmed_stdout.is_empty() {
@@ -360,16 +1028,20 @@ impl App {
                 if !run_output.stderr.is_empty() {
                     self.output.append_stderr(&run_output.stderr);
                 }
-                // Show exit status in status bar only, not in output panel
-                if run_output.exit_code == 0 {
-                    self.status_message = String::from("Program finished");
+                // A crash is reported in the output panel as well; a normal
+                // exit status only shows in the status bar
+                if let Some(termination) = run_output.termination() {
+                    self.output.append_error(&termination);
+                    self.set_sticky_status(termination);
+                } else if run_output.exit_code == 0 {
+                    self.set_status("Program finished");
                 } else {
-                    self.status_message = format!("Exit code {}", run_output.exit_code);
+                    self.set_status(format!("Exit code {}", run_output.exit_code));
                 }
             }
             Err(e) => {
                 self.output.append_error(&format!("{e}"));
-                self.status_message = String::from("Run failed");
+                self.set_status("Run failed");
             }
         }
 
@@ -377,35 +1049,316 @@ impl App {
         Ok(())
     }
 
+    /// `:runstdin <file>` feeds `file` to the program on every run;
+    /// without a file, runs read from the keyboard again. Saved to the
+    /// project's `.masmide.toml`.
+    fn set_run_stdin(&mut self, file: Option<&str>) -> Result<()> {
+        match run_stdin_file(&self.project_dir, file) {
+            Err(e) => {
+                self.set_status(e.to_string());
+                return Ok(());
+            }
+            Ok(stdin) => {
+                self.set_status(match &stdin {
+                    Some(file) => format!("Runs read input from {}", file.display()),
+                    None => String::from("Runs read input from the keyboard"),
+                });
+                self.project_config.run_stdin_file = stdin;
+            }
+        }
+        self.project_config.save(&self.project_dir)
+    }
+
+    /// Assemble and run the selection (or the current line) inside the
+    /// scratch template, which dumps the registers when it finishes
+    pub fn scratch(&mut self) {
+        let snippet = match self.editor.selected_text() {
+            Some(text) => text,
+            None => {
+                let buf = &self.editor.buffers[self.editor.active_buffer];
+                buf.lines[buf.cursor_y].clone()
+            }
+        };
+        self.editor.clear_selection();
+        if snippet.trim().is_empty() {
+            self.set_status("Nothing to evaluate");
+            return;
+        }
+
+        let template = match &self.config.toolchain.scratch_template {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(template) => template,
+                Err(e) => {
+                    self.set_status(format!(
+                        "Cannot read scratch template {}: {}",
+                        path.display(),
+                        e
+                    ));
+                    return;
+                }
+            },
+            None => String::from(project::SCRATCH_TEMPLATE),
+        };
+        let program = project::scratch_program(&template, &snippet);
+
+        self.output.clear();
+        self.show_output = true;
+        self.set_status("Running scratch...");
+
+        match self.pipeline.scratch(&program) {
+            Ok((build_output, run_output)) => {
+                self.echo_commands(&build_output.commands);
+                if !build_output.stderr.is_empty() {
+                    self.output.append_stderr(&build_output.stderr);
+                }
+                match run_output {
+                    Some(run_output) => {
+                        self.echo_commands(&run_output.commands);
+                        let trimmed_stdout = run_output.stdout.trim();
+                        if !trimmed_stdout.is_empty() {
+                            self.output.append_stdout(trimmed_stdout);
+                        }
+                        if !run_output.stderr.is_empty() {
+                            self.output.append_stderr(&run_output.stderr);
+                        }
+                        self.set_status("Scratch finished");
+                    }
+                    None => self.set_status("Scratch build failed"),
+                }
+            }
+            Err(e) => {
+                self.output.append_error(&format!("{e}"));
+                self.set_status("Scratch failed");
+            }
+        }
+    }
+
+    /// List the files produced by the last build in the output panel.
+    /// With `reveal`, the first artifact is selected in the file tree.
+    pub fn show_artifacts(&mut self, reveal: bool) {
+        let artifacts = self.pipeline.artifacts();
+        if artifacts.is_empty() {
+            self.set_status("No build artifacts - build first");
+            return;
+        }
+
+        if !self.output.is_empty() {
+            self.output.append_divider();
+        }
+        self.output.append_info("Build artifacts:");
+        for path in &artifacts {
+            let name = path
+                .strip_prefix(&self.project_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            match fs::metadata(path) {
+                Ok(meta) => {
+                    let age = meta
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.elapsed().ok())
+                        .map(|d| format!("{}s ago", d.as_secs()))
+                        .unwrap_or_else(|| String::from("unknown time"));
+                    self.output.append_stdout(&format!(
+                        "{:<24} {:>10} bytes  modified {}",
+                        name,
+                        meta.len(),
+                        age
+                    ));
+                }
+                Err(e) => self.output.append_error(&format!("{}: {}", name, e)),
+            }
+        }
+        self.show_output = true;
+
+        if reveal {
+            let _ = self.file_tree.refresh();
+            if self.file_tree.select_path(&artifacts[0]) {
+                self.show_file_tree = true;
+                self.focus = FocusedPanel::FileTree;
+                self.mode = Mode::FileTree;
+            }
+        }
+        self.set_status(format!("{} artifact(s)", artifacts.len()));
+    }
+
+    /// The pipeline only drives the project's MASM assembler, so other
+    /// dialects are reported rather than fed to one that cannot read them
+    fn current_dialect_buildable(&mut self) -> bool {
+        if self.notes_active() {
+            self.set_status("Notes are not built");
+            return false;
+        }
+        let dialect = self.editor.buffers[self.editor.active_buffer].dialect;
+        if !dialect.buildable() {
+            let message = format!(
+                "No assembler backend for {} sources; only MASM is built (with {})",
+                dialect.name(),
+                self.project_config.assembler.name()
+            );
+            self.output.append_error(&message);
+            self.set_status(format!("Cannot build {} source", dialect.name()));
+            self.show_output = true;
+        }
+        dialect.buildable()
+    }
+
+    /// Bring the first error into view when `ui.scroll_to_first_error` is on
+    fn scroll_output_to_first_error(&mut self) {
+        if !self.config.ui.scroll_to_first_error {
+            return;
+        }
+        if let Some(row) = self.output.first_error_row() {
+            self.output.scroll_to_row(row);
+        }
+    }
+
+    fn echo_commands(&mut self, commands: &[String]) {
+        if self.config.ui.echo_build_commands {
+            for command in commands {
+                self.output.append_info(&format!("$ {}", command));
+            }
+        }
+    }
+
     pub fn build_succeeded(&self) -> bool {
         self.last_build_success
     }
 
     pub fn save_current_file(&mut self) -> Result<()> {
+        self.write_current_file(false)
+    }
+
+    /// Write the active buffer. Unless `force`, a file another program
+    /// changed since it was opened is only overwritten after asking.
+    pub fn write_current_file(&mut self, force: bool) -> Result<()> {
+        let buffer = &self.editor.buffers[self.editor.active_buffer];
+        if !force && buffer.changed_on_disk() {
+            let on_disk = buffer
+                .file_path
+                .as_ref()
+                .and_then(|path| fs::read_to_string(path).ok())
+                .unwrap_or_default();
+            let on_disk = on_disk.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+            let differ = changed_line_count(&on_disk, &(buffer.lines.join("\n") + "\n"));
+            self.mode = Mode::InputPopup;
+            self.pending_action = PendingAction::OverwriteChanged;
+            self.input_popup_title = format!(
+                "{} changed on disk ({} lines differ). Overwrite? (y/n, d to diff):",
+                buffer.filename(),
+                differ
+            );
+            self.input_popup_value.clear();
+            return Ok(());
+        }
+
         if let Some(path) = self.editor.current_file().cloned() {
-            let content = self.editor.get_content();
+            let content = self.editor.file_content();
             fs::write(&path, content)
                 .with_context(|| format!("Failed to save: {}", path.display()))?;
             self.editor.set_modified(false);
-            self.status_message = format!("Saved: {}", path.display());
+            let buffer = &mut self.editor.buffers[self.editor.active_buffer];
+            buffer.mark_written();
+            // An edited preview stays open like any other buffer
+            buffer.preview = false;
+            self.set_status(format!("Saved: {}", path.display()));
         } else {
-            self.status_message = String::from("No file to save");
+            self.set_status("No file to save");
         }
         Ok(())
     }
 
+    /// Show `message` until something else replaces it, instead of
+    /// reverting to the idle hint after `ui.status_timeout_secs`
+    pub fn set_sticky_status(&mut self, message: impl Into<String>) {
+        self.set_status(message);
+        self.status_sticky = true;
+    }
+
+    /// Show `message` in the status bar, restarting its timeout even when
+    /// it repeats the message already shown
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = message.into();
+        self.status_since = std::time::Instant::now();
+        self.status_sticky = false;
+    }
+
+    pub fn status_message(&self) -> &str {
+        &self.status_message
+    }
+
+    /// Called every tick: a transient message older than
+    /// `ui.status_timeout_secs` gives way to the idle hint. A timeout of 0
+    /// keeps messages until replaced.
+    pub fn expire_status_message(&mut self) {
+        let timeout = self.config.ui.status_timeout_secs;
+        if self.status_sticky || timeout == 0 || self.status_message == IDLE_HINT {
+            return;
+        }
+        if self.status_since.elapsed() >= std::time::Duration::from_secs(timeout) {
+            self.status_message = String::from(IDLE_HINT);
+        }
+    }
+
     pub fn open_file(&mut self, path: &PathBuf) -> Result<()> {
         self.editor.open_file(path)?;
-        self.status_message = format!("Opened: {}", path.display());
+        self.set_status(match self.editor.first_long_line() {
+            Some(line) => long_line_notice(line),
+            None => format!("Opened: {}", path.display()),
+        });
         self.focus = FocusedPanel::Editor;
         Ok(())
     }
 
+    /// Show a file selected while browsing the file tree, keeping focus
+    /// in the tree
+    pub fn preview_file(&mut self, path: &PathBuf) -> Result<()> {
+        self.editor.preview_file(path)?;
+        self.set_status(format!("Preview: {}", path.display()));
+        Ok(())
+    }
+
     pub fn execute_command(&mut self) -> Result<crate::input::CommandResult> {
         use crate::input::CommandResult;
 
         let cmd = self.command_input.trim().to_string();
         self.command_input.clear();
+        if !cmd.is_empty() {
+            self.last_command = Some(cmd.clone());
+        }
+        self.command_history.push(&cmd);
+
+        if self.run_command_line(&cmd, true)? == CommandResult::Quit {
+            return Ok(CommandResult::Quit);
+        }
+
+        // A command started from visual mode consumes the selection
+        self.editor.clear_selection();
+        self.mode = Mode::Normal;
+        Ok(CommandResult::Continue)
+    }
+
+    /// Run one `:` command line. Aliases from `[commands]` are only expanded
+    /// when `expand_aliases` is set, so an alias cannot call itself.
+    fn run_command_line(
+        &mut self,
+        cmd: &str,
+        expand_aliases: bool,
+    ) -> Result<crate::input::CommandResult> {
+        use crate::input::CommandResult;
+
+        if let Some(shell_cmd) = cmd.strip_prefix('!') {
+            self.run_shell(shell_cmd.trim());
+            return Ok(CommandResult::Continue);
+        }
+        if let Some(parsed) = Substitution::parse(cmd) {
+            match parsed {
+                Ok(sub) => self.substitute(&sub),
+                Err(e) => self.set_status(e.to_string()),
+            }
+            return Ok(CommandResult::Continue);
+        }
 
         // Handle commands with arguments
         let parts: Vec<&str> = cmd.split_whitespace().collect();
@@ -415,27 +1368,46 @@ impl App {
             "q" | "quit" => return Ok(CommandResult::Quit),
             "q!" => return Ok(CommandResult::Quit), // Force quit without save check
             "w" | "save" => self.save_current_file()?,
+            "w!" => self.write_current_file(true)?,
             "wq" => {
                 self.save_current_file()?;
-                return Ok(CommandResult::Quit);
+                if self.pending_action != PendingAction::OverwriteChanged {
+                    return Ok(CommandResult::Quit);
+                }
             }
+            "build" | "b" if parts.get(1) == Some(&"all") => self.build_all()?,
             "build" | "b" => self.build()?,
-            "run" | "r" => self.run()?,
-            "br" => {
-                self.build()?;
-                if self.build_succeeded() {
-                    self.run()?;
+            "rebuild" => self.rebuild()?,
+            "run" | "r" if parts.len() > 1 => {
+                let rest = cmd
+                    .trim_start()
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, rest)| rest);
+                match split_args(rest) {
+                    Ok(args) => self.run_with(&args)?,
+                    Err(e) => self.set_status(e.to_string()),
                 }
             }
+            "run" | "r" => self.run()?,
+            "runstdin" => self.set_run_stdin(parts.get(1).copied())?,
+            "br" => self.build_and_run()?,
             "tree" => self.show_file_tree = !self.show_file_tree,
             "output" => self.show_output = !self.show_output,
             "help" => self.show_help = !self.show_help,
+            "undotree" => self.open_undo_tree(),
+            "mkbuf" => self.start_mkbuf(),
+            "artifacts" => self.show_artifacts(parts.get(1) == Some(&"open")),
             "theme" => {
-                if parts.len() > 1 {
+                if matches!(parts.get(1), Some(&("load" | "save"))) {
+                    match parts.get(2) {
+                        Some(path) => self.theme_file(parts[1] == "load", path),
+                        None => self.set_status(format!("Usage: :theme {} <path>", parts[1])),
+                    }
+                } else if parts.len() > 1 {
                     self.set_theme(parts[1]);
                 } else {
                     let themes = crate::theme::Theme::available_themes().join(", ");
-                    self.status_message = format!("Available themes: {}", themes);
+                    self.set_status(format!("Available themes: {}", themes));
                 }
             }
             "e" | "edit" => {
@@ -447,53 +1419,158 @@ impl App {
                         self.project_dir.join(path)
                     };
                     if let Err(e) = self.editor.open_file(&full_path) {
-                        self.status_message = format!("Failed to open: {}", e);
+                        self.set_status(format!("Failed to open: {}", e));
                     } else {
-                        self.status_message = format!("Opened: {}", full_path.display());
+                        self.set_status(match self.editor.first_long_line() {
+                            Some(line) => long_line_notice(line),
+                            None => format!("Opened: {}", full_path.display()),
+                        });
                     }
                 } else {
-                    self.status_message = String::from("Usage: :e <filename>");
+                    self.set_status("Usage: :e <filename>");
                 }
             }
+            "err" => match parts.get(1) {
+                Some(arg) => match arg.parse::<usize>() {
+                    Ok(number) => {
+                        self.goto_diagnostic(number);
+                    }
+                    Err(_) => self.set_status(format!("Invalid number: {}", arg)),
+                },
+                None => self.show_diagnostic_counts(),
+            },
+            "clist" | "diagnostics" => self.toggle_diagnostics_panel(),
+            "annotate" => self.annotate_from_docs(),
+            "symbol" | "sym" => self.open_symbol_picker(),
+            "hardwrap" => {
+                let width = match parts.get(1) {
+                    Some(arg) => arg.parse::<usize>().ok().filter(|&w| w > 0),
+                    None => Some(self.editor.textwidth),
+                };
+                let message = match width {
+                    None => String::from("Usage: :hardwrap [width]"),
+                    Some(width) => match self.editor.hard_wrap(width) {
+                        0 => format!("No lines longer than {} columns", width),
+                        n => {
+                            self.editor.ensure_cursor_visible(20);
+                            format!("Split {} line(s) at {} columns (u to undo)", n, width)
+                        }
+                    },
+                };
+                self.set_status(message);
+            }
+            "reflow" => {
+                let width = match parts.get(1) {
+                    Some(arg) => arg.parse::<usize>().ok(),
+                    None => Some(self.editor.textwidth),
+                };
+                let message = match (width, self.editor.reflow_range()) {
+                    (None, _) => String::from("Usage: :reflow [width]"),
+                    (_, None) => String::from("No comment block to reflow"),
+                    (Some(width), Some((start, end))) => {
+                        if self.editor.reflow_comments(start, end, width) {
+                            self.editor.ensure_cursor_visible(20);
+                            format!("Reflowed comments to {} columns", width)
+                        } else {
+                            String::from("Comments already fit")
+                        }
+                    }
+                };
+                self.set_status(message);
+            }
+            "set" => self.set_options(&parts[1..]),
+            "removebom" => {
+                let message = if self.editor.remove_bom() {
+                    String::from("BOM will be removed on save")
+                } else {
+                    String::from("No BOM in this file")
+                };
+                self.set_status(message);
+            }
+            "difftool" => match parts.len() {
+                2 => self.open_diff_view(None, parts[1]),
+                3 => self.open_diff_view(Some(parts[1]), parts[2]),
+                _ => {
+                    self.set_status("Usage: :difftool [fileA] <fileB>");
+                }
+            },
             "bn" | "bnext" => {
                 self.editor.next_buffer();
-                self.status_message = format!(
+                self.set_status(format!(
                     "Buffer: {}",
                     self.editor.buffers[self.editor.active_buffer].filename()
-                );
+                ));
             }
             "bp" | "bprev" => {
                 self.editor.prev_buffer();
-                self.status_message = format!(
+                self.set_status(format!(
                     "Buffer: {}",
                     self.editor.buffers[self.editor.active_buffer].filename()
-                );
+                ));
+            }
+            "b#" => self.alternate_buffer(),
+            "grep" => match cmd.split_once(char::is_whitespace) {
+                Some((_, pattern)) if !pattern.trim().is_empty() => self.grep(pattern.trim()),
+                _ => self.set_status("Usage: :grep <text>"),
+            },
+            "foldcomments" => self.fold_comments(true),
+            "nofoldcomments" => self.fold_comments(false),
+            "copen" => {
+                if self.quickfix.entries.is_empty() {
+                    self.set_status("No grep results");
+                } else {
+                    self.show_quickfix = true;
+                }
+            }
+            "ls" | "buffers" => {
+                self.buffer_picker = Some(buffer_picker::picker(&self.editor));
             }
             "bd" | "bdelete" => {
-                if self.editor.modified() {
-                    self.status_message =
-                        String::from("Buffer has unsaved changes. Use :bd! to force close.");
-                } else if self.editor.close_buffer() {
-                    self.status_message = String::from("Buffer closed");
+                if self.close_would_discard() {
+                    self.set_status("Buffer has unsaved changes. Use :bd! to force close.");
+                } else if self.close_buffer() {
+                    self.set_status("Buffer closed");
                 } else {
-                    self.status_message = String::from("Cannot close last buffer");
+                    self.set_status("Cannot close last buffer");
                 }
             }
             "bd!" => {
-                if self.editor.close_buffer() {
-                    self.status_message = String::from("Buffer closed");
+                if self.close_buffer() {
+                    self.set_status("Buffer closed");
                 } else {
-                    self.status_message = String::from("Cannot close last buffer");
+                    self.set_status("Cannot close last buffer");
                 }
             }
+            "reopen" => self.reopen_closed_buffer(),
+            "check" => self.check(),
+            "scratch" => self.scratch(),
+            "preserve" => self.preserve_registers(&parts[1..]),
+            "sort" | "sort!" => match parts.get(1).copied() {
+                None => self.sort_lines(base_cmd == "sort!", false),
+                Some("n") => self.sort_lines(base_cmd == "sort!", true),
+                Some(_) => self.set_status("Usage: :sort[!] [n]"),
+            },
+            "trim" => self.trim_whitespace(),
+            "proc" => match parts.get(1) {
+                Some(name) => self.go_to_proc(name),
+                None => self.set_status("Usage: :proc <name>"),
+            },
+            "wrap" => self.toggle_soft_wrap(),
+            "align" => match parts.get(1).map(|arg| arg.parse::<usize>()) {
+                Some(Err(_)) => self.set_status("Usage: :align [column]"),
+                Some(Ok(column)) => self.align_comments(Some(column)),
+                None => self.align_comments(None),
+            },
+            "notes" => self.toggle_notes(),
+            "outline" => self.toggle_outline(),
             "autosave" => {
                 self.toggle_autosave();
             }
             "refresh" => {
                 if let Err(e) = self.file_tree.refresh() {
-                    self.status_message = format!("Refresh failed: {}", e);
+                    self.set_status(format!("Refresh failed: {}", e));
                 } else {
-                    self.status_message = String::from("File tree refreshed");
+                    self.set_status("File tree refreshed");
                 }
             }
             _ => {
@@ -501,17 +1578,109 @@ impl App {
                 if let Ok(line_num) = cmd.parse::<usize>() {
                     self.editor.go_to_line(line_num);
                     self.editor.ensure_cursor_visible(20);
-                    self.status_message = format!("Line {}", line_num);
+                    self.set_status(format!("Line {}", line_num));
+                } else if let Some(body) = parts
+                    .first()
+                    .and_then(|name| self.config.commands.get(*name))
+                    .filter(|_| expand_aliases)
+                    .cloned()
+                {
+                    for line in expand_command_alias(&body, &parts[1..]) {
+                        if self.run_command_line(&line, false)? == CommandResult::Quit {
+                            return Ok(CommandResult::Quit);
+                        }
+                    }
                 } else {
-                    self.status_message = format!("Unknown command: {}", cmd);
+                    self.set_status(format!("Unknown command: {}", cmd));
                 }
             }
         }
 
-        self.mode = Mode::Normal;
         Ok(CommandResult::Continue)
     }
 
+    /// `:s` on the current line or the selected lines, `:%s` on the buffer
+    fn substitute(&mut self, sub: &Substitution) {
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        let (start, end) = if sub.whole_file {
+            (0, buf.lines.len() - 1)
+        } else {
+            match self.editor.get_selection_range() {
+                Some(((start, _), (end, _))) => (start, end),
+                None => (buf.cursor_y, buf.cursor_y),
+            }
+        };
+
+        let message = match self.editor.substitute(sub, start, end) {
+            (0, _) => format!("Pattern not found: {}", sub.pattern),
+            (count, lines) => {
+                self.editor.ensure_cursor_visible(20);
+                format!(
+                    "{} substitution{} on {} line{}",
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    lines,
+                    if lines == 1 { "" } else { "s" }
+                )
+            }
+        };
+        self.set_status(message);
+    }
+
+    /// Run `command_line` with `sh` in the project directory (`:!`),
+    /// showing its output in the output panel
+    pub fn run_shell(&mut self, command_line: &str) {
+        if command_line.is_empty() {
+            self.set_status("Usage: :!<shell command>");
+            return;
+        }
+
+        if !self.output.is_empty() {
+            self.output.append_divider();
+        }
+        self.output.append_info(&format!("$ {}", command_line));
+
+        match self.pipeline.shell(command_line) {
+            Ok(shell_output) => {
+                let trimmed_stdout = shell_output.stdout.trim_end();
+                if !trimmed_stdout.is_empty() {
+                    self.output.append_stdout(trimmed_stdout);
+                }
+                if !shell_output.stderr.is_empty() {
+                    self.output.append_stderr(&shell_output.stderr);
+                }
+                if shell_output.exit_code == 0 {
+                    self.set_status("Shell command finished");
+                } else {
+                    self.set_sticky_status(format!(
+                        "Shell command exited with code {}",
+                        shell_output.exit_code
+                    ));
+                }
+            }
+            Err(e) => {
+                self.output.append_error(&format!("{e}"));
+                self.set_sticky_status("Shell command failed");
+            }
+        }
+
+        self.show_output = true;
+    }
+
+    /// Run the last `:` command line again (@:)
+    pub fn repeat_last_command(&mut self) -> Result<crate::input::CommandResult> {
+        match self.last_command.clone() {
+            Some(cmd) => {
+                self.command_input = cmd;
+                self.execute_command()
+            }
+            None => {
+                self.set_status("No previous command line");
+                Ok(crate::input::CommandResult::Continue)
+            }
+        }
+    }
+
     // ========== Autocomplete ==========
 
     pub fn trigger_autocomplete(&mut self) {
@@ -544,6 +1713,13 @@ impl App {
             }
         }
 
+        // For `var.field` only the part after the last dot is completed
+        if let Some(dot) = chars[start..col_char].iter().rposition(|&c| c == '.') {
+            if dot > 0 {
+                start += dot + 1;
+            }
+        }
+
         if start == col_char {
             self.autocomplete.hide();
             return;
@@ -551,11 +1727,65 @@ impl App {
 
         let prefix: String = chars[start..col_char].iter().collect();
 
-        // Get symbols from current buffer
-        let buffer_symbols = parse_buffer_symbols(&buf.lines);
+        // Get symbols from current buffer and the files it includes
+        let includes = parse_includes(&buf.lines);
+        let base_dir = buf
+            .file_path
+            .as_ref()
+            .and_then(|p| p.parent())
+            .map(Path::to_path_buf);
+        let included = self.included_symbols(includes, base_dir.as_deref());
+        let buf = &mut self.editor.buffers[self.editor.active_buffer];
+        let mut buffer_symbols: Vec<Suggestion> = buf.symbols().symbols().cloned().collect();
+        buffer_symbols.extend(included);
+
+        // Rank registers first when typing an instruction operand
+        let line_before: String = chars[..start].iter().collect();
+        let context = CompletionContext::detect(&line_before);
 
         self.autocomplete
-            .show(&prefix, line, start, &buffer_symbols);
+            .show_in_context(&prefix, line, start, &buffer_symbols, context);
+    }
+
+    /// Symbols from files pulled in with `INCLUDE`, followed recursively.
+    /// Files are looked up next to the including file, in the project,
+    /// in the project's include paths and in the Irvine include directory.
+    fn included_symbols(
+        &mut self,
+        includes: Vec<String>,
+        base_dir: Option<&Path>,
+    ) -> Vec<Suggestion> {
+        let mut search_dirs: Vec<PathBuf> = base_dir.map(Path::to_path_buf).into_iter().collect();
+        search_dirs.push(self.project_dir.clone());
+        search_dirs.extend(
+            self.project_config
+                .include_paths
+                .iter()
+                .map(|p| self.project_dir.join(p)),
+        );
+        search_dirs.push(self.config.toolchain.irvine_inc_path.clone());
+
+        let mut symbols = Vec::new();
+        let mut visited = std::collections::HashSet::new();
+        let mut pending = includes;
+        while let Some(name) = pending.pop() {
+            let Some(path) = search_dirs
+                .iter()
+                .map(|dir| dir.join(&name))
+                .find(|p| p.is_file())
+            else {
+                continue;
+            };
+            if !visited.insert(path.clone()) {
+                continue;
+            }
+            let Some((file_symbols, file_includes)) = self.include_cache.get(&path) else {
+                continue;
+            };
+            symbols.extend_from_slice(file_symbols);
+            pending.extend_from_slice(file_includes);
+        }
+        symbols
     }
 
     pub fn accept_autocomplete(&mut self) {
@@ -587,22 +1817,56 @@ impl App {
                 self.hover_doc = Some(doc);
                 self.show_hover = true;
             } else {
-                self.status_message = format!("No documentation for '{}'", word);
+                self.set_status(format!("No documentation for '{}'", word));
             }
         }
     }
 
+    /// Append the short description of the instruction, register or
+    /// Irvine32 procedure under the cursor as a trailing comment
+    pub fn annotate_from_docs(&mut self) {
+        let Some(word) = self.editor.get_word_under_cursor() else {
+            self.set_status("No word under cursor");
+            return;
+        };
+        let Some(doc) = docs::get_documentation(&word) else {
+            self.set_status(format!("No documentation for '{}'", word));
+            return;
+        };
+        let summary = doc.summary(self.config.editor.doc_comment_length);
+        let message = if self.editor.append_comment(&summary) {
+            format!("Annotated '{}'", word)
+        } else {
+            String::from("Line already has a comment")
+        };
+        self.set_status(message);
+    }
+
     pub fn hide_hover(&mut self) {
         self.show_hover = false;
         self.hover_doc = None;
     }
 
+    /// Look up the signature hint for the text before the cursor. A
+    /// `trigger` (space or comma) may show a new hint; otherwise a shown
+    /// hint only follows along, going away once the line no longer
+    /// reads as an operand list.
+    pub fn update_signature_hint(&mut self, trigger: bool) {
+        if !trigger && self.signature_hint.is_none() {
+            return;
+        }
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        let line = &buf.lines[buf.cursor_y];
+        let before = &line[..buf.cursor_x.min(line.len())];
+        self.signature_hint = docs::signature_at(before);
+    }
+
     // ========== Diagnostics Navigation ==========
 
     /// Navigate to the next diagnostic (error/warning)
     pub fn next_diagnostic(&mut self) -> bool {
         if self.diagnostics.is_empty() {
-            self.status_message = String::from("No diagnostics");
+            self.set_status("No diagnostics");
             return false;
         }
 
@@ -613,7 +1877,7 @@ impl App {
     /// Navigate to the previous diagnostic (error/warning)
     pub fn prev_diagnostic(&mut self) -> bool {
         if self.diagnostics.is_empty() {
-            self.status_message = String::from("No diagnostics");
+            self.set_status("No diagnostics");
             return false;
         }
 
@@ -625,6 +1889,56 @@ impl App {
         self.jump_to_diagnostic(self.current_diagnostic)
     }
 
+    /// Navigate to the diagnostic numbered `number` (1-based, as shown in
+    /// the status bar)
+    pub fn goto_diagnostic(&mut self, number: usize) -> bool {
+        if self.diagnostics.is_empty() {
+            self.set_status("No diagnostics");
+            return false;
+        }
+        if number == 0 || number > self.diagnostics.len() {
+            self.set_status(format!(
+                "No diagnostic {} (there {} {})",
+                number,
+                if self.diagnostics.len() == 1 {
+                    "is"
+                } else {
+                    "are"
+                },
+                self.diagnostics.len()
+            ));
+            return false;
+        }
+
+        self.current_diagnostic = number - 1;
+        self.jump_to_diagnostic(self.current_diagnostic)
+    }
+
+    /// Show or hide the list of every diagnostic, opened on the one last
+    /// jumped to
+    pub fn toggle_diagnostics_panel(&mut self) {
+        self.show_diagnostics_panel = !self.show_diagnostics_panel;
+        self.current_diagnostic = self
+            .current_diagnostic
+            .min(self.diagnostics.len().saturating_sub(1));
+    }
+
+    /// Summarise the current diagnostics in the status bar
+    pub fn show_diagnostic_counts(&mut self) {
+        let (errors, warnings) = diagnostics::count_by_severity(&self.diagnostics);
+        self.set_status(if errors == 0 && warnings == 0 {
+            String::from("No diagnostics")
+        } else {
+            format!(
+                "{} error{}, {} warning{}",
+                errors,
+                if errors == 1 { "" } else { "s" },
+                warnings,
+                if warnings == 1 { "" } else { "s" }
+            )
+        });
+    }
+
     /// Jump to a specific diagnostic by index
     fn jump_to_diagnostic(&mut self, index: usize) -> bool {
         if index >= self.diagnostics.len() {
@@ -640,7 +1954,7 @@ impl App {
         // Open the file if not already open
         if self.editor.current_file() != Some(&file_path) {
             if let Err(e) = self.editor.open_file(&file_path) {
-                self.status_message = format!("Cannot open file: {}", e);
+                self.set_status(format!("Cannot open file: {}", e));
                 return false;
             }
         }
@@ -654,22 +1968,141 @@ impl App {
             DiagnosticSeverity::Error => "Error",
             DiagnosticSeverity::Warning => "Warning",
         };
-        self.status_message = format!(
+        self.set_status(format!(
             "[{}/{}] {}: {}",
             index + 1,
             self.diagnostics.len(),
             severity_str,
             message
-        );
+        ));
 
         true
     }
 
-    /// Get the diagnostic for the current cursor line (if any)
-    pub fn diagnostic_at_cursor(&self) -> Option<&Diagnostic> {
+    /// Index labels, procedures, macros and constants across the project's
+    /// `.asm`/`.inc` files and show the symbol picker. Open buffers are
+    /// indexed from their current, possibly unsaved, text.
+    pub fn open_symbol_picker(&mut self) {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let notes = self.notes_path();
+        let mut sources: Vec<(PathBuf, Vec<(usize, Suggestion)>)> = self
+            .editor
+            .buffers
+            .iter_mut()
+            .filter(|buf| buf.file_path.as_ref().is_some_and(|path| *path != notes))
+            .filter_map(|buf| {
+                let path = buf.file_path.clone()?;
+                let definitions = buf
+                    .symbols()
+                    .definitions()
+                    .map(|(line, symbol)| (line, symbol.clone()))
+                    .collect();
+                Some((path, definitions))
+            })
+            .collect();
+        let open: Vec<PathBuf> = sources.iter().map(|(path, _)| canonical(path)).collect();
+        for path in project::source_files(&self.project_dir) {
+            if open.contains(&canonical(&path)) {
+                continue;
+            }
+            if let Ok(content) = fs::read_to_string(&path) {
+                let lines: Vec<String> = content.lines().map(String::from).collect();
+                sources.push((path, parse_symbol_definitions(&lines)));
+            }
+        }
+
+        let mut symbols = Vec::new();
+        for (path, definitions) in sources {
+            let location = path
+                .strip_prefix(&self.project_dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            for (line, symbol) in definitions {
+                if symbol.kind == SuggestionKind::Field {
+                    continue;
+                }
+                symbols.push(ProjectSymbol {
+                    name: symbol.text,
+                    kind: symbol.kind,
+                    path: path.clone(),
+                    line,
+                    location: location.clone(),
+                });
+            }
+        }
+
+        if symbols.is_empty() {
+            self.set_status("No symbols found in project");
+            return;
+        }
+        self.symbol_picker = Some(SymbolPickerState::new(symbols));
+    }
+
+    /// Close the symbol picker and jump to the selected definition,
+    /// remembering the current position on the jump list
+    pub fn accept_symbol_pick(&mut self) {
+        let Some(symbol) = self
+            .symbol_picker
+            .take()
+            .and_then(|picker| picker.selected().cloned())
+        else {
+            return;
+        };
+
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        if let Some(path) = buf.file_path.clone() {
+            self.editor
+                .jump_stack
+                .push((path, buf.cursor_y, buf.cursor_x));
+        }
+        if self.editor.current_file() != Some(&symbol.path) {
+            if let Err(e) = self.editor.open_file(&symbol.path) {
+                self.set_status(format!("Cannot open file: {}", e));
+                return;
+            }
+        }
+
+        self.editor.go_to_line(symbol.line + 1);
+        let buf = &mut self.editor.buffers[self.editor.active_buffer];
+        if let Some(line) = buf.lines.get(buf.cursor_y) {
+            buf.cursor_x = line.find(&symbol.name).unwrap_or(0);
+        }
+        self.editor.ensure_cursor_visible(20);
+        self.focus = FocusedPanel::Editor;
+        self.mode = Mode::Normal;
+        self.set_status(format!(
+            "{} {} ({}:{})",
+            symbol.kind.label(),
+            symbol.name,
+            symbol.location,
+            symbol.line + 1
+        ));
+    }
+
+    /// Size in bytes of the data definition on the cursor line, if it is one
+    pub fn data_size_at_cursor(&self) -> Option<usize> {
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        let line = buf.lines.get(buf.cursor_y)?;
+        if line.len() > self.editor.long_line_threshold {
+            return None;
+        }
+        masm_lang::data_definition_size(line)
+    }
+
+    /// The diagnostic to show for the cursor line: the one last jumped to
+    /// with `]e`/`[e` if it is on this line, else the first. Also returns how
+    /// many others share the line.
+    pub fn diagnostic_at_cursor(&self) -> Option<(&Diagnostic, usize)> {
         let file = self.editor.current_file()?;
         let line = self.editor.cursor_y() + 1; // diagnostics use 1-based line numbers
-        diagnostics::diagnostic_for_line(&self.diagnostics, file, line)
+        let on_line = diagnostics::diagnostics_for_line(&self.diagnostics, file, line);
+        let current = self.diagnostics.get(self.current_diagnostic);
+        let shown = on_line
+            .iter()
+            .find(|d| current.is_some_and(|c| std::ptr::eq(**d, c)))
+            .or(on_line.first())?;
+        Some((shown, on_line.len() - 1))
     }
 
     /// Get all diagnostics for the current file
@@ -717,17 +2150,502 @@ impl App {
         self.output.page_down();
     }
 
+    /// Move focus to the next visible panel (Tab)
+    pub fn focus_next(&mut self) {
+        self.cycle_focus(true);
+    }
+
+    /// Move focus to the previous visible panel (Shift+Tab)
+    pub fn focus_prev(&mut self) {
+        self.cycle_focus(false);
+    }
+
+    fn cycle_focus(&mut self, forward: bool) {
+        let (show_file_tree, show_output, show_outline) =
+            (self.show_file_tree, self.show_output, self.show_outline);
+        let next = self.focus.step(forward, |panel| match panel {
+            FocusedPanel::Editor => true,
+            FocusedPanel::FileTree => show_file_tree,
+            FocusedPanel::Output => show_output,
+            FocusedPanel::Outline => show_outline,
+        });
+        self.focus = next;
+        self.mode = if next == FocusedPanel::FileTree {
+            Mode::FileTree
+        } else {
+            Mode::Normal
+        };
+    }
+
     /// Toggle output-only fullscreen mode
     pub fn toggle_output_only_mode(&mut self) {
         self.output_only_mode = !self.output_only_mode;
         if self.output_only_mode {
             self.focus = FocusedPanel::Output;
-            self.status_message =
-                String::from("Output view (F8 or Esc to exit, F9 to save screenshot)");
+            self.set_status("Output view (F8 or Esc to exit, F9 to save screenshot)");
         } else {
             self.focus = FocusedPanel::Editor;
             self.mode = Mode::Normal;
-            self.status_message = String::from("Back to editor");
+            self.set_status("Back to editor");
+        }
+    }
+
+    fn resolve_project_path(&self, name: &str) -> PathBuf {
+        let path = PathBuf::from(name);
+        if path.is_absolute() {
+            path
+        } else {
+            self.project_dir.join(path)
+        }
+    }
+
+    /// Per-project notes file, kept out of the file tree and builds
+    pub fn notes_path(&self) -> PathBuf {
+        self.project_dir.join(".masmide").join("notes.md")
+    }
+
+    fn notes_active(&self) -> bool {
+        self.editor.current_file() == Some(&self.notes_path())
+    }
+
+    /// Open the project notes, or go back to the previous buffer when they
+    /// are already showing. Notes are saved whenever they are left.
+    pub fn toggle_notes(&mut self) {
+        self.mode = Mode::Normal;
+        if self.notes_active() {
+            match self.save_notes() {
+                Ok(()) => {
+                    self.editor.alternate_buffer();
+                    self.set_status("Notes saved");
+                }
+                Err(e) => self.set_status(format!("Failed to save notes: {}", e)),
+            }
+            return;
+        }
+
+        let path = self.notes_path();
+        if !path.exists() {
+            let created = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, ""));
+            if let Err(e) = created {
+                self.set_status(format!("Cannot create {}: {}", path.display(), e));
+                return;
+            }
+        }
+        match self.editor.open_file(&path) {
+            Ok(()) => {
+                self.focus = FocusedPanel::Editor;
+                self.set_status("Project notes (F2 to go back, saved automatically)");
+            }
+            Err(e) => self.set_status(format!("Failed to open notes: {}", e)),
+        }
+    }
+
+    /// Write the notes buffer if it has unsaved changes
+    pub fn save_notes(&mut self) -> Result<()> {
+        let path = self.notes_path();
+        if let Some(buffer) = self
+            .editor
+            .buffers
+            .iter_mut()
+            .find(|b| b.modified && b.file_path.as_ref() == Some(&path))
+        {
+            fs::write(&path, buffer.to_disk(&buffer.get_content()))
+                .with_context(|| format!("Failed to save: {}", path.display()))?;
+            buffer.modified = false;
+            buffer.mark_written();
+        }
+        Ok(())
+    }
+
+    /// Whether closing the current buffer would lose edits. Notes never
+    /// do, they are saved on close.
+    pub fn close_would_discard(&self) -> bool {
+        self.editor.modified() && !self.notes_active()
+    }
+
+    /// Close the active buffer, remembering its file and cursor for :reopen
+    pub fn close_buffer(&mut self) -> bool {
+        if self.notes_active() && self.save_notes().is_err() {
+            return false;
+        }
+        self.editor.remember_file_state(self.editor.active_buffer);
+        let closed = self
+            .editor
+            .current_file()
+            .map(|path| (path.clone(), self.editor.cursor_y, self.editor.cursor_x));
+        if !self.editor.close_buffer() {
+            return false;
+        }
+        if let Some(entry) = closed {
+            self.closed_buffers.retain(|(path, _, _)| *path != entry.0);
+            self.closed_buffers.push(entry);
+            if self.closed_buffers.len() > MAX_CLOSED_BUFFERS {
+                self.closed_buffers.remove(0);
+            }
+        }
+        true
+    }
+
+    /// Remember every open buffer's cursor and jumps and write the store
+    pub fn save_file_states(&mut self) -> Result<()> {
+        for idx in 0..self.editor.buffers.len() {
+            self.editor.remember_file_state(idx);
+        }
+        match &self.editor.file_states {
+            Some(store) => store.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Put the cursor of `path`'s buffer on 1-based `line` and `col`, as
+    /// given on the command line with `file:line[:col]`. The active buffer
+    /// stays as it is.
+    pub fn go_to_file_position(&mut self, path: &Path, line: usize, col: usize) {
+        let Some(idx) = self
+            .editor
+            .buffers
+            .iter()
+            .position(|buf| buf.file_path.as_deref() == Some(path))
+        else {
+            return;
+        };
+        let active = self.editor.active_buffer;
+        self.editor.active_buffer = idx;
+        self.editor.go_to_line(line);
+        let buf = &mut self.editor.buffers[idx];
+        let text = &buf.lines[buf.cursor_y];
+        buf.cursor_x = text
+            .char_indices()
+            .nth(col.saturating_sub(1))
+            .map_or(text.len(), |(i, _)| i);
+        CursorOps::clamp_cursor_x(buf);
+        self.editor.ensure_cursor_visible(20);
+        self.editor.active_buffer = active;
+    }
+
+    /// Write the open buffers to the project's session file. The notes
+    /// buffer is left out; F2 brings it back.
+    pub fn save_session(&self) -> Result<()> {
+        Session::capture(&self.editor, &self.project_dir, &[self.notes_path()])
+            .save(&self.project_dir)
+    }
+
+    /// Reopen the most recently closed buffer at its former cursor position
+    pub fn reopen_closed_buffer(&mut self) {
+        let Some((path, line, col)) = self.closed_buffers.pop() else {
+            self.set_status("No closed buffers");
+            return;
+        };
+        if !path.is_file() {
+            self.set_status(format!("File no longer exists: {}", path.display()));
+            return;
+        }
+        if let Err(e) = self.editor.open_file(&path) {
+            self.set_status(format!("Failed to reopen {}: {}", path.display(), e));
+            return;
+        }
+        self.editor.go_to_line(line + 1);
+        let buf = &mut self.editor.buffers[self.editor.active_buffer];
+        buf.cursor_x = utf8::clamp_to_char_boundary(&buf.lines[buf.cursor_y], col);
+        self.editor.ensure_cursor_visible(20);
+        self.focus = FocusedPanel::Editor;
+        self.set_status(format!("Reopened: {}", path.display()));
+    }
+
+    /// Search the project's `.asm`/`.inc` files for `pattern` (ignoring
+    /// case) and show the hits. Open buffers are searched as edited.
+    pub fn grep(&mut self, pattern: &str) {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut entries = Vec::new();
+        for path in project::source_files(&self.project_dir) {
+            let open = self
+                .editor
+                .buffers
+                .iter()
+                .find(|buf| buf.file_path.as_deref().map(canonical) == Some(canonical(&path)));
+            let lines = match open {
+                Some(buf) => buf.lines.clone(),
+                None => match fs::read_to_string(&path) {
+                    Ok(content) => content.lines().map(String::from).collect(),
+                    Err(_) => continue,
+                },
+            };
+            let location = path
+                .strip_prefix(&self.project_dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            for (line, col) in quickfix::grep_lines(&lines, pattern) {
+                entries.push(QuickfixEntry {
+                    path: open
+                        .and_then(|buf| buf.file_path.clone())
+                        .unwrap_or_else(|| path.clone()),
+                    line,
+                    col,
+                    location: location.clone(),
+                    text: lines[line].clone(),
+                });
+            }
+        }
+
+        if entries.is_empty() {
+            self.set_status(format!("No matches for: {}", pattern));
+            return;
+        }
+        self.set_status(format!("{} matches for: {}", entries.len(), pattern));
+        self.quickfix = QuickfixList {
+            pattern: pattern.to_string(),
+            entries,
+            current: 0,
+        };
+        self.show_quickfix = true;
+    }
+
+    /// Show the outline sidebar focused, or hide it (F3, :outline)
+    pub fn toggle_outline(&mut self) {
+        self.show_outline = !self.show_outline;
+        if self.show_outline {
+            self.refresh_outline();
+            self.focus = FocusedPanel::Outline;
+        } else if self.focus == FocusedPanel::Outline {
+            self.focus = FocusedPanel::Editor;
+        }
+        self.mode = Mode::Normal;
+    }
+
+    /// Bring the outline up to date with the active buffer while it is shown
+    pub fn refresh_outline(&mut self) {
+        if self.show_outline {
+            let idx = self.editor.active_buffer;
+            let buf = &self.editor.buffers[idx];
+            self.outline.refresh(idx, buf.revision(), &buf.lines);
+        }
+    }
+
+    /// Move the editor to the highlighted outline entry and focus it
+    pub fn jump_to_outline_selection(&mut self) {
+        let Some(line) = self.outline.selected_line() else {
+            return;
+        };
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        if let Some(path) = buf.file_path.clone() {
+            self.editor
+                .jump_stack
+                .push((path, buf.cursor_y, buf.cursor_x));
+        }
+        self.editor.go_to_line(line + 1);
+        self.editor.move_to_first_non_blank();
+        self.editor.ensure_cursor_visible(20);
+        self.focus = FocusedPanel::Editor;
+    }
+
+    /// Open the file of the current grep result at its line (Enter in the
+    /// list, ]q/[q), remembering where the cursor was for Ctrl+O
+    pub fn jump_to_quickfix(&mut self) -> bool {
+        let Some(entry) = self.quickfix.selected().cloned() else {
+            self.set_status("No grep results");
+            return false;
+        };
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        if let Some(path) = buf.file_path.clone() {
+            self.editor
+                .jump_stack
+                .push((path, buf.cursor_y, buf.cursor_x));
+        }
+        if self.editor.current_file() != Some(&entry.path) {
+            if let Err(e) = self.editor.open_file(&entry.path) {
+                self.set_status(format!("Cannot open file: {}", e));
+                return false;
+            }
+        }
+        self.editor.go_to_line(entry.line + 1);
+        let buf = &mut self.editor.buffers[self.editor.active_buffer];
+        buf.cursor_x = utf8::clamp_to_char_boundary(&buf.lines[buf.cursor_y], entry.col);
+        self.editor.ensure_cursor_visible(20);
+        self.focus = FocusedPanel::Editor;
+        self.set_status(format!(
+            "[{}/{}] {}:{}",
+            self.quickfix.current + 1,
+            self.quickfix.entries.len(),
+            entry.location,
+            entry.line + 1
+        ));
+        true
+    }
+
+    /// Jump to the next or previous grep result, wrapping around
+    pub fn step_quickfix(&mut self, forward: bool) -> bool {
+        if self.quickfix.step(forward).is_none() {
+            self.set_status("No grep results");
+            return false;
+        }
+        self.jump_to_quickfix()
+    }
+
+    /// Fold or unfold the comment blocks of the current buffer (zC / zO)
+    pub fn fold_comments(&mut self, on: bool) {
+        let folded = self.editor.set_comment_folding(on);
+        self.editor.ensure_cursor_visible(20);
+        self.set_status(match (on, folded) {
+            (false, _) => String::from("Comment blocks unfolded"),
+            (true, 0) => format!(
+                "No comment blocks of {}+ lines",
+                self.editor.comment_fold_min_lines
+            ),
+            (true, n) => format!(
+                "Folded {} comment block{}",
+                n,
+                if n == 1 { "" } else { "s" }
+            ),
+        });
+    }
+
+    /// Close the buffer list and switch to the selected buffer
+    pub fn accept_buffer_pick(&mut self) {
+        let Some(idx) = self
+            .buffer_picker
+            .take()
+            .and_then(|picker| picker.selected().map(|entry| entry.index))
+        else {
+            return;
+        };
+        self.editor.set_active_buffer(idx);
+        self.focus = FocusedPanel::Editor;
+        self.set_status(format!(
+            "Buffer: {}",
+            self.editor.display_name(self.editor.active_buffer)
+        ));
+    }
+
+    /// Swap to the previously active buffer (Ctrl+^ / :b#)
+    pub fn alternate_buffer(&mut self) {
+        if self.editor.alternate_buffer() {
+            self.set_status(format!(
+                "Buffer: {}",
+                self.editor.display_name(self.editor.active_buffer)
+            ));
+        } else {
+            self.set_status("No alternate buffer");
+        }
+    }
+
+    /// Compare two files side by side. Without `left` the current buffer
+    /// (including unsaved edits) is compared against `right`.
+    pub fn open_diff_view(&mut self, left: Option<&str>, right: &str) {
+        let (left_title, left_text) = match left {
+            Some(name) => match fs::read_to_string(self.resolve_project_path(name)) {
+                Ok(text) => (name.to_string(), text),
+                Err(e) => {
+                    self.set_status(format!("Failed to read {}: {}", name, e));
+                    return;
+                }
+            },
+            None => {
+                let buf = &self.editor.buffers[self.editor.active_buffer];
+                (buf.filename(), buf.lines.join("\n") + "\n")
+            }
+        };
+        let right_text = match fs::read_to_string(self.resolve_project_path(right)) {
+            Ok(text) => text,
+            Err(e) => {
+                self.set_status(format!("Failed to read {}: {}", right, e));
+                return;
+            }
+        };
+
+        let view = DiffViewState::new(&left_title, &left_text, right, &right_text);
+        self.set_status(if view.is_identical() {
+            String::from("Files are identical")
+        } else {
+            format!("{} change(s)", view.hunks.len())
+        });
+        self.diff_view = Some(view);
+    }
+
+    /// Keys typed so far for a command that is still waiting for more
+    /// input (like vim's showcmd), e.g. `2f`, `g` or `]`
+    pub fn pending_keys(&self) -> String {
+        let mut keys = String::new();
+        if let Some(view) = &self.diff_view {
+            keys.extend(view.pending_bracket);
+            return keys;
+        }
+        if let Some(name) = self.pending_register {
+            keys.push('"');
+            keys.push(name);
+        }
+        if let Some(count) = self.pending_count {
+            // f/F/t/T store the default count of 1 alongside the char
+            if self.pending_char.is_none() || count > 1 {
+                keys.push_str(&count.to_string());
+            }
+        }
+        keys.extend(self.pending_char);
+        if self.pending_g {
+            keys.push('g');
+        }
+        keys.extend(self.pending_operator);
+        if self.pending_inner {
+            keys.push('i');
+        }
+        keys.extend(self.pending_bracket);
+        if self.pending_at {
+            keys.push('@');
+        }
+        keys
+    }
+
+    /// A multi-key Normal mode command is partway typed
+    pub fn awaiting_key(&self) -> bool {
+        self.pending_count.is_some()
+            || self.pending_char.is_some()
+            || self.pending_g
+            || self.pending_operator.is_some()
+            || self.pending_bracket.is_some()
+            || self.pending_at
+    }
+
+    /// Keybinding context for the current mode and focus, used by help
+    pub fn key_context(&self) -> KeyContext {
+        if self.focus == FocusedPanel::Output {
+            return KeyContext::Output;
+        }
+        match self.mode {
+            Mode::FileTree => KeyContext::FileTree,
+            Mode::InputPopup => KeyContext::Input,
+            Mode::Insert => KeyContext::Insert,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => KeyContext::Visual,
+            Mode::Command => KeyContext::Command,
+            Mode::Search => KeyContext::Search,
+            Mode::Normal => KeyContext::Normal,
+        }
+    }
+
+    /// Show the undo tree panel with the current state selected
+    pub fn open_undo_tree(&mut self) {
+        let current = self.editor.undo_tree.current();
+        self.undo_tree_selected = self
+            .editor
+            .undo_tree
+            .branch_rows()
+            .iter()
+            .position(|&(idx, _)| idx == current)
+            .unwrap_or(0);
+        self.show_undo_tree = true;
+    }
+
+    /// Restore the buffer to the state of the selected undo tree node
+    pub fn restore_undo_tree_selection(&mut self) {
+        let rows = self.editor.undo_tree.branch_rows();
+        if let Some(&(idx, _)) = rows.get(self.undo_tree_selected) {
+            let seq = self.editor.undo_tree.node(idx).map(|n| n.seq).unwrap_or(0);
+            if self.editor.undo_jump_to(idx) {
+                self.editor.ensure_cursor_visible(20);
+                self.set_status(format!("Restored change {}", seq));
+            }
         }
     }
 
@@ -748,29 +2666,7 @@ impl App {
         content.push_str("                         PROGRAM OUTPUT\n");
         content.push_str("═══════════════════════════════════════════════════════════════\n\n");
 
-        // Add all output lines
-        for line in &self.output.lines {
-            match line.output_type {
-                crate::ui::output::OutputType::Success => {
-                    content.push_str(&format!("✓ {}\n", line.text));
-                }
-                crate::ui::output::OutputType::Error => {
-                    content.push_str(&format!("✗ {}\n", line.text));
-                }
-                crate::ui::output::OutputType::Stderr => {
-                    content.push_str(&format!("⚠ {}\n", line.text));
-                }
-                crate::ui::output::OutputType::Info => {
-                    content.push_str(&format!("→ {}\n", line.text));
-                }
-                crate::ui::output::OutputType::Stdout => {
-                    content.push_str(&format!("  {}\n", line.text));
-                }
-                crate::ui::output::OutputType::Divider => {
-                    content.push('\n');
-                }
-            }
-        }
+        content.push_str(&self.output.export_text());
 
         content.push_str("\n═══════════════════════════════════════════════════════════════\n");
 
@@ -782,69 +2678,581 @@ impl App {
 
     /// Check and perform autosave if needed
     pub fn check_autosave(&mut self) {
+        let interval = std::time::Duration::from_secs(self.config.editor.autosave_interval_secs);
         if !self.autosave_enabled {
+            // Notes are saved even with autosave off
+            if self.last_save_time.elapsed() >= interval {
+                if let Err(e) = self.save_notes() {
+                    self.set_sticky_status(format!("Failed to save notes: {}", e));
+                }
+                self.last_save_time = std::time::Instant::now();
+            }
             return;
         }
 
-        let interval = std::time::Duration::from_secs(self.config.editor.autosave_interval_secs);
         if self.last_save_time.elapsed() >= interval {
             // Check if any buffer is modified
             let has_unsaved = self.editor.buffers.iter().any(|b| b.modified);
             if has_unsaved {
-                if let Err(e) = self.save_all() {
-                    self.status_message = format!("Autosave failed: {}", e);
-                } else {
-                    self.status_message = String::from("Autosaved");
+                match self.save_all() {
+                    Err(e) => self.set_sticky_status(format!("Autosave failed: {}", e)),
+                    Ok(0) => self.set_status("Autosaved"),
+                    Ok(skipped) => self.set_sticky_status(format!(
+                        "Autosave skipped {} file(s) changed on disk (:w to review)",
+                        skipped
+                    )),
                 }
             }
             self.last_save_time = std::time::Instant::now();
         }
     }
 
-    /// Save all modified buffers
-    pub fn save_all(&mut self) -> anyhow::Result<()> {
+    /// Save all modified buffers, except files changed on disk since they
+    /// were opened, which are left for `:w` to ask about. Returns how many
+    /// were skipped.
+    pub fn save_all(&mut self) -> anyhow::Result<usize> {
+        let mut skipped = 0;
         for buffer in &mut self.editor.buffers {
             if buffer.modified {
+                if buffer.changed_on_disk() {
+                    skipped += 1;
+                    continue;
+                }
                 if let Some(ref path) = buffer.file_path {
-                    std::fs::write(path, buffer.lines.join("\n"))?;
+                    std::fs::write(path, buffer.to_disk(&buffer.lines.join("\n")))?;
                     buffer.modified = false;
+                    buffer.preview = false;
+                    buffer.mark_written();
                 }
             }
         }
-        Ok(())
+        Ok(skipped)
     }
 
     /// Toggle autosave
     pub fn toggle_autosave(&mut self) {
-        self.autosave_enabled = !self.autosave_enabled;
-        self.status_message = format!(
+        self.config.editor.autosave = !self.config.editor.autosave;
+        self.apply_options();
+        self.set_status(format!(
             "Autosave {}",
             if self.autosave_enabled {
                 "enabled"
             } else {
                 "disabled"
             }
-        );
+        ));
     }
 
-    /// Copy output to clipboard
-    pub fn copy_output_to_clipboard(&mut self) {
-        let mut content = String::new();
+    /// `:set` arguments in order; the status shows the last option touched.
+    /// `:set all` lists every option in the output panel.
+    pub fn set_options(&mut self, args: &[&str]) {
+        if args.is_empty() {
+            self.set_status("Usage: :set all | <option>[?|!|=value] | no<option>");
+            return;
+        }
+        if args == ["all"] {
+            self.output.append_divider();
+            for opt in options::OPTIONS {
+                let value = self.show_option(opt.name).unwrap_or_default();
+                self.output
+                    .append_info(&format!("{:<24} {}", value, opt.description));
+            }
+            self.show_output = true;
+            self.output.scroll_to_bottom();
+            self.set_status(format!("{} options", options::OPTIONS.len()));
+            return;
+        }
 
-        for line in &self.output.lines {
-            match line.output_type {
-                crate::ui::output::OutputType::Divider => {
-                    content.push('\n');
+        for arg in args {
+            match self.set_option(&arg.to_lowercase()) {
+                Ok(shown) => self.set_status(shown),
+                Err(e) => {
+                    self.set_status(e.to_string());
+                    return;
                 }
-                _ => {
-                    content.push_str(&line.text);
-                    content.push('\n');
+            }
+        }
+    }
+
+    /// Apply one `:set` argument, returning the option as it now stands
+    fn set_option(&mut self, arg: &str) -> Result<String> {
+        let arg = SetArg::parse(arg);
+        let name = options::lookup(arg.name()).map_or(arg.name(), |opt| opt.name);
+        match (name, arg) {
+            (_, SetArg::Query(_)) | ("fileformat" | "dialect", SetArg::Flag(_, Some(true))) => {}
+            ("fileformat", SetArg::Assign(_, value)) => {
+                let ending = match value {
+                    "unix" => LineEnding::Lf,
+                    "dos" => LineEnding::CrLf,
+                    _ => anyhow::bail!("fileformat is unix or dos"),
+                };
+                self.editor.set_line_ending(ending);
+            }
+            ("dialect", SetArg::Assign(_, value)) => {
+                let Some(dialect) = Dialect::from_name(value) else {
+                    anyhow::bail!("Unknown dialect: {} (masm, tasm, nasm, gas)", value);
+                };
+                self.editor.buffers[self.editor.active_buffer].dialect = dialect;
+            }
+            ("fileformat" | "dialect", _) => anyhow::bail!("{} is not a boolean option", name),
+            _ => {
+                options::set(&mut self.config, arg)?;
+                self.apply_options();
+            }
+        }
+        self.show_option(name)
+            .with_context(|| format!("Unknown option: {}", name))
+    }
+
+    /// An option's current value as `:set` prints it
+    fn show_option(&mut self, name: &str) -> Option<String> {
+        let buf = &self.editor.buffers[self.editor.active_buffer];
+        match name {
+            "fileformat" => Some(format!(
+                "fileformat={}",
+                match buf.line_ending {
+                    LineEnding::Lf => "unix",
+                    LineEnding::CrLf => "dos",
+                    LineEnding::Mixed => "mixed",
                 }
+            )),
+            "dialect" => Some(format!("dialect={}", buf.dialect.name().to_lowercase())),
+            _ => options::show(&mut self.config, name),
+        }
+    }
+
+    /// Push the global options in `config` out to the editor and timers
+    fn apply_options(&mut self) {
+        self.editor.apply_config(&self.config.editor);
+        self.autosave_enabled = self.config.editor.autosave;
+    }
+
+    /// Alt+C in the search bar: smart case, then case-sensitive, then
+    /// ignoring case. Changes the same setting as `:set ic`/`:set scs`.
+    pub fn cycle_search_case(&mut self) {
+        self.config.editor.search_case = self.config.editor.search_case.next();
+        self.apply_options();
+        self.editor.search(&self.search_input);
+    }
+
+    /// `:sort[!] [n]`: sort the selected lines, or the whole buffer
+    pub fn sort_lines(&mut self, reverse: bool, numeric: bool) {
+        let (start, end) = match self.editor.get_selection_range() {
+            Some(((start, _), (end, _))) => (start, end),
+            None => (0, self.editor.lines().len().saturating_sub(1)),
+        };
+        let moved = self.editor.sort_lines(start, end, reverse, numeric);
+        self.set_status(if moved == 0 {
+            String::from("Already sorted")
+        } else {
+            format!("Sorted {} lines", end - start + 1)
+        });
+    }
+
+    /// `:proc name`: jump to a PROC in the current buffer or the project
+    pub fn go_to_proc(&mut self, name: &str) {
+        if self.editor.go_to_proc(name) {
+            self.editor.ensure_cursor_visible(20);
+            self.focus = FocusedPanel::Editor;
+            let file = self.editor.buffers[self.editor.active_buffer].filename();
+            self.set_status(format!("Jump to: {} ({})", name, file));
+        } else {
+            self.set_status(format!("No PROC named {}", name));
+        }
+    }
+
+    /// Tab in command mode: complete the name after `:proc` from the PROCs
+    /// of the current buffer. A name that is already complete moves on to
+    /// the next one.
+    pub fn complete_command(&mut self) {
+        let Some(typed) = self.command_input.strip_prefix("proc ") else {
+            return;
+        };
+        let mut names: Vec<String> = crate::autocomplete::parse_buffer_symbols(self.editor.lines())
+            .into_iter()
+            .filter(|symbol| symbol.kind == crate::autocomplete::SuggestionKind::Procedure)
+            .map(|symbol| symbol.text)
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup();
+        let typed = typed.trim().to_string();
+        match complete_name(&names, &typed) {
+            Some(name) if name == typed => {
+                let lower = typed.to_lowercase();
+                let matches: Vec<&str> = names
+                    .iter()
+                    .filter(|name| name.to_lowercase().starts_with(&lower))
+                    .map(String::as_str)
+                    .collect();
+                self.set_status(matches.join("  "));
             }
+            Some(name) => self.command_input = format!("proc {}", name),
+            None => self.set_status(format!("No PROC starts with {}", typed)),
         }
+    }
+
+    /// `:wrap`: toggle soft wrap, the same as `:set wrap!`
+    pub fn toggle_soft_wrap(&mut self) {
+        self.config.editor.soft_wrap = !self.config.editor.soft_wrap;
+        self.apply_options();
+        self.editor.ensure_cursor_visible(20);
+        self.set_status(if self.config.editor.soft_wrap {
+            String::from("Soft wrap on")
+        } else {
+            String::from("Soft wrap off")
+        });
+    }
+
+    /// `:trim`: strip trailing whitespace from the whole buffer
+    pub fn trim_whitespace(&mut self) {
+        let changed = self.editor.trim_trailing_whitespace();
+        self.set_status(if changed == 0 {
+            String::from("No trailing whitespace")
+        } else {
+            format!(
+                "Trimmed {} line{}",
+                changed,
+                if changed == 1 { "" } else { "s" }
+            )
+        });
+    }
+
+    /// `:align [column]`: line up trailing comments in the selected lines,
+    /// or the whole buffer without a selection
+    pub fn align_comments(&mut self, column: Option<usize>) {
+        let (start, end) = match self.editor.get_selection_range() {
+            Some(((start, _), (end, _))) => (start, end),
+            None => (0, self.editor.lines().len().saturating_sub(1)),
+        };
+        let changed = self.editor.align_comments(start, end, column);
+        self.set_status(if changed == 0 {
+            String::from("Comments already aligned")
+        } else {
+            format!(
+                "Aligned {} comment{}",
+                changed,
+                if changed == 1 { "" } else { "s" }
+            )
+        });
+    }
+
+    /// `:preserve eax ebx` from visual mode: bracket the selected lines
+    /// with pushes and the matching pops, `:preserve all` with pushad/popad
+    pub fn preserve_registers(&mut self, args: &[&str]) {
+        let Some(((start, _), (end, _))) = self.editor.get_selection_range() else {
+            self.set_status("Select the lines to preserve registers around");
+            return;
+        };
+        let indent = {
+            let lines = self.editor.lines();
+            let first = (start..=end)
+                .map(|i| lines[i].as_str())
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("");
+            first[..first.len() - first.trim_start().len()].to_string()
+        };
+        let (before, after) = match preserve_lines(args, &indent) {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.set_status(e.to_string());
+                return;
+            }
+        };
+        let count = before.len();
+        self.editor.clear_selection();
+        if self.editor.surround_lines(start, end, before, after) {
+            self.set_status(format!(
+                "Preserved {} register{}",
+                count,
+                if count == 1 { "" } else { "s" }
+            ));
+        }
+    }
+
+    /// Copy output to clipboard
+    pub fn copy_output_to_clipboard(&mut self) {
+        let content = self.output.plain_text();
 
         // Use editor's clipboard functionality
         use crate::ui::editor::clipboard::YankType;
         self.editor.clipboard.copy(&content, YankType::Char);
     }
 }
+
+/// Status shown when a file with a line over the highlight limit is opened
+fn long_line_notice(line: usize) -> String {
+    format!(
+        "Line {} is very long and shown without highlighting (:hardwrap to split)",
+        line + 1
+    )
+}
+
+/// Whether two paths name the same file, however they were spelled
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Tab completion of `typed` among `names` (sorted): the longest prefix
+/// the matches share, or after an exact match the name that follows it
+fn complete_name(names: &[String], typed: &str) -> Option<String> {
+    if let Some(pos) = names.iter().position(|name| name == typed) {
+        return Some(names[(pos + 1) % names.len()].clone());
+    }
+    let lower = typed.to_lowercase();
+    let matches: Vec<&String> = names
+        .iter()
+        .filter(|name| name.to_lowercase().starts_with(&lower))
+        .collect();
+    let first = matches.first()?;
+    let shared = matches
+        .iter()
+        .map(|name| {
+            first
+                .chars()
+                .zip(name.chars())
+                .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+                .count()
+        })
+        .min()?;
+    Some(first.chars().take(shared).collect())
+}
+
+/// Split `:run` arguments at whitespace. Single or double quotes keep
+/// whitespace in an argument; inside double quotes a backslash escapes
+/// `"` and `\`.
+fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => match chars.next() {
+                            Some(e @ ('"' | '\\')) => arg.push(e),
+                            Some(e) => {
+                                arg.push('\\');
+                                arg.push(e);
+                            }
+                            None => anyhow::bail!("Unterminated {} in arguments", c),
+                        },
+                        Some(ch) => arg.push(ch),
+                        None => anyhow::bail!("Unterminated {} in arguments", c),
+                    }
+                }
+            }
+            c if c.is_whitespace() => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// The input file `:runstdin` should set: `file`, relative to
+/// `project_dir`, which must exist, or None to read from the keyboard
+fn run_stdin_file(project_dir: &Path, file: Option<&str>) -> Result<Option<PathBuf>> {
+    match file {
+        Some(file) if !project_dir.join(file).is_file() => {
+            anyhow::bail!("No such file: {}", file)
+        }
+        Some(file) => Ok(Some(PathBuf::from(file))),
+        None => Ok(None),
+    }
+}
+
+/// Registers `:preserve` can save: the 32- and 16-bit general-purpose ones
+const PRESERVABLE_REGISTERS: &[&str] = &[
+    "eax", "ebx", "ecx", "edx", "esi", "edi", "ebp", "esp", "ax", "bx", "cx", "dx", "si", "di",
+    "bp", "sp",
+];
+
+/// Push and pop lines for `:preserve`. Pops come out in reverse order of
+/// the pushes, and `all` uses pushad/popad.
+fn preserve_lines(args: &[&str], indent: &str) -> Result<(Vec<String>, Vec<String>)> {
+    if args.is_empty() {
+        anyhow::bail!("Usage: :preserve <reg>... | all");
+    }
+    if args.len() == 1 && args[0].eq_ignore_ascii_case("all") {
+        return Ok((
+            vec![format!("{}pushad", indent)],
+            vec![format!("{}popad", indent)],
+        ));
+    }
+
+    let mut regs: Vec<String> = Vec::new();
+    for arg in args {
+        let reg = arg.trim_matches(',').to_lowercase();
+        if !PRESERVABLE_REGISTERS.contains(&reg.as_str()) {
+            anyhow::bail!("Cannot push {}", arg);
+        }
+        if !regs.contains(&reg) {
+            regs.push(reg);
+        }
+    }
+    let pushes = regs
+        .iter()
+        .map(|reg| format!("{}push {}", indent, reg))
+        .collect();
+    let pops = regs
+        .iter()
+        .rev()
+        .map(|reg| format!("{}pop {}", indent, reg))
+        .collect();
+    Ok((pushes, pops))
+}
+
+/// Expand a `[commands]` alias into the command lines it runs. `$*` is
+/// replaced by the arguments, which are otherwise appended to the end.
+/// Parts are split on `|` until one starts with `!`, which takes the rest
+/// of the line so shell pipes survive.
+fn expand_command_alias(body: &str, args: &[&str]) -> Vec<String> {
+    let args = args.join(" ");
+    let body = if body.contains("$*") {
+        body.replace("$*", &args)
+    } else if args.is_empty() {
+        body.to_string()
+    } else {
+        format!("{} {}", body.trim_end(), args)
+    };
+
+    let mut lines = Vec::new();
+    let mut rest = body.as_str();
+    loop {
+        let part = rest.trim_start().trim_start_matches(':');
+        if part.starts_with('!') {
+            lines.push(part.trim_end().to_string());
+            break;
+        }
+        match part.split_once('|') {
+            Some((head, tail)) => {
+                lines.push(head.trim().to_string());
+                rest = tail;
+            }
+            None => {
+                lines.push(part.trim().to_string());
+                break;
+            }
+        }
+    }
+    lines.retain(|line| !line.is_empty());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_command_alias_expansion() {
+        assert_eq!(
+            expand_command_alias("w | !make && ./run.sh | tee log", &[]),
+            vec!["w", "!make && ./run.sh | tee log"]
+        );
+        assert_eq!(
+            expand_command_alias(":e $* | check", &["lab3.asm"]),
+            vec!["e lab3.asm", "check"]
+        );
+        assert_eq!(
+            expand_command_alias("!grep -n", &["PROC", "*.asm"]),
+            vec!["!grep -n PROC *.asm"]
+        );
+        assert!(expand_command_alias(" | ", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_run_args_keep_quoted_whitespace() {
+        assert_eq!(split_args("  a  b ").unwrap(), vec!["a", "b"]);
+        assert_eq!(
+            split_args(r#"'two words' "it's" x"y z"w "\"q\" \\n" ''"#).unwrap(),
+            vec!["two words", "it's", "xy zw", "\"q\" \\n", ""]
+        );
+        assert!(split_args("'open").is_err());
+        assert!(split_args(r#""open\""#).is_err());
+        assert!(split_args("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_stdin_file_must_exist() {
+        let dir = TempDir::new("runstdin");
+        fs::write(dir.join("input.txt"), "42\n").unwrap();
+
+        assert_eq!(
+            run_stdin_file(&dir, Some("input.txt")).unwrap(),
+            Some(PathBuf::from("input.txt"))
+        );
+        assert_eq!(run_stdin_file(&dir, None).unwrap(), None);
+        let err = run_stdin_file(&dir, Some("missing.txt")).unwrap_err();
+        assert_eq!(err.to_string(), "No such file: missing.txt");
+    }
+
+    #[test]
+    fn test_preserve_pops_in_reverse_order() {
+        let (pushes, pops) = preserve_lines(&["eax", "EBX,", "eax"], "    ").unwrap();
+        assert_eq!(pushes, vec!["    push eax", "    push ebx"]);
+        assert_eq!(pops, vec!["    pop ebx", "    pop eax"]);
+
+        let (pushes, pops) = preserve_lines(&["all"], "").unwrap();
+        assert_eq!(
+            (pushes, pops),
+            (vec!["pushad".into()], vec!["popad".into()])
+        );
+
+        assert!(preserve_lines(&["al"], "").is_err());
+        assert!(preserve_lines(&["eip"], "").is_err());
+        assert!(preserve_lines(&["eflags"], "").is_err());
+        assert!(preserve_lines(&["ds"], "").is_err());
+        assert!(preserve_lines(&[], "").is_err());
+    }
+
+    #[test]
+    fn test_proc_names_complete_to_shared_prefix() {
+        let names: Vec<String> = ["main", "PrintChar", "printLine"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        assert_eq!(complete_name(&names, "pr").as_deref(), Some("Print"));
+        assert_eq!(complete_name(&names, "ma").as_deref(), Some("main"));
+        // A complete name cycles to the next one
+        assert_eq!(complete_name(&names, "printLine").as_deref(), Some("main"));
+        assert_eq!(complete_name(&names, "x"), None);
+    }
+
+    #[test]
+    fn test_focus_ring_skips_hidden_panels() {
+        use FocusedPanel::*;
+        let all = |_: FocusedPanel| true;
+        assert_eq!(Editor.step(true, all), FileTree);
+        assert_eq!(FileTree.step(true, all), Output);
+        assert_eq!(Output.step(true, all), Outline);
+        assert_eq!(Outline.step(true, all), Editor);
+        assert_eq!(Editor.step(false, all), Outline);
+        assert_eq!(FileTree.step(false, all), Editor);
+
+        // The outline starts hidden
+        let defaults = |p: FocusedPanel| p != Outline;
+        assert_eq!(Output.step(true, defaults), Editor);
+        assert_eq!(Editor.step(false, defaults), Output);
+
+        let no_tree = |p: FocusedPanel| p != FileTree && p != Outline;
+        assert_eq!(Editor.step(true, no_tree), Output);
+        assert_eq!(Output.step(false, no_tree), Editor);
+
+        let no_output = |p: FocusedPanel| p != Output && p != Outline;
+        assert_eq!(FileTree.step(true, no_output), Editor);
+        assert_eq!(Editor.step(false, no_output), FileTree);
+
+        // Focus left on a panel that was hidden still moves on
+        let none = |_: FocusedPanel| false;
+        assert_eq!(Output.step(true, none), Editor);
+        assert_eq!(Editor.step(true, none), Editor);
+        assert_eq!(Editor.step(false, none), Editor);
+    }
+}
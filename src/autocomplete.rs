@@ -1,5 +1,7 @@
 //! Autocomplete system for MASM assembly language
 
+use crate::fuzzy::fuzzy_score;
+
 /// Kind of suggestion for display purposes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SuggestionKind {
@@ -10,6 +12,8 @@ pub enum SuggestionKind {
     Label,
     Procedure,
     Macro,
+    Constant,
+    Field,
 }
 
 impl SuggestionKind {
@@ -22,6 +26,65 @@ impl SuggestionKind {
             SuggestionKind::Label => "L",
             SuggestionKind::Procedure => "P",
             SuggestionKind::Macro => "M",
+            SuggestionKind::Constant => "C",
+            SuggestionKind::Field => "F",
+        }
+    }
+
+    /// Word for the kind, shown by the symbol picker
+    pub fn label(&self) -> &'static str {
+        match self {
+            SuggestionKind::Keyword => "keyword",
+            SuggestionKind::Register => "register",
+            SuggestionKind::Directive => "directive",
+            SuggestionKind::TypeKeyword => "type",
+            SuggestionKind::Label => "label",
+            SuggestionKind::Procedure => "proc",
+            SuggestionKind::Macro => "macro",
+            SuggestionKind::Constant => "const",
+            SuggestionKind::Field => "field",
+        }
+    }
+}
+
+/// Where in the line completion was triggered, used to rank candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionContext {
+    Any,
+    /// After an instruction mnemonic, where registers are the likely operand
+    Operand,
+    /// After `var.`, where a struct field is expected
+    Field,
+}
+
+impl CompletionContext {
+    /// Detect the context from the text before the word being completed
+    pub fn detect(line_before: &str) -> Self {
+        if line_before.ends_with('.')
+            && line_before[..line_before.len() - 1]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ']')
+        {
+            return CompletionContext::Field;
+        }
+        let code = line_before.split(';').next().unwrap_or("");
+        let mut tokens = code
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty());
+        let mut first = tokens.next();
+        // Skip a leading "label:"
+        if first.is_some_and(|t| t.ends_with(':')) {
+            first = tokens.next();
+        }
+        match first {
+            Some(token)
+                if docs::is_instruction(token)
+                    || KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(token)) =>
+            {
+                CompletionContext::Operand
+            }
+            _ => CompletionContext::Any,
         }
     }
 }
@@ -32,6 +95,9 @@ pub struct Suggestion {
     pub text: String,
     pub kind: SuggestionKind,
     pub detail: Option<String>,
+    /// Relevance to the prefix it was last offered for, see `relevance`.
+    /// Higher ranks first.
+    pub score: u32,
 }
 
 impl Suggestion {
@@ -40,6 +106,7 @@ impl Suggestion {
             text: text.into(),
             kind,
             detail: None,
+            score: 0,
         }
     }
 
@@ -57,6 +124,8 @@ pub struct AutocompleteState {
     pub trigger_pos: (usize, usize), // (line, col) where autocomplete was triggered
     pub visible: bool,
     pub scroll_offset: usize,
+    /// Word being completed, lowercased; the popup marks its characters
+    pub prefix: String,
     all_suggestions: Vec<Suggestion>, // Cached full list
 }
 
@@ -75,6 +144,7 @@ impl AutocompleteState {
             trigger_pos: (0, 0),
             visible: false,
             scroll_offset: 0,
+            prefix: String::new(),
             all_suggestions,
         }
     }
@@ -102,62 +172,89 @@ impl AutocompleteState {
             suggestions.push(Suggestion::new(*tk, SuggestionKind::TypeKeyword));
         }
 
+        // Irvine32 library procedures
+        for func in IRVINE32_FUNCTIONS {
+            suggestions
+                .push(Suggestion::new(*func, SuggestionKind::Procedure).with_detail("Irvine32"));
+        }
+
         suggestions
     }
 
     /// Show autocomplete with suggestions filtered by prefix
-    pub fn show(
+    pub fn show(&mut self, prefix: &str, line: usize, col: usize, buffer_symbols: &[Suggestion]) {
+        self.show_in_context(prefix, line, col, buffer_symbols, CompletionContext::Any);
+    }
+
+    /// Like `show`, but ranks candidates that fit `context` first.
+    /// Nothing is filtered out, only reordered.
+    pub fn show_in_context(
         &mut self,
         prefix: &str,
         line: usize,
         col: usize,
-        buffer_symbols: &[(String, SuggestionKind)],
+        buffer_symbols: &[Suggestion],
+        context: CompletionContext,
     ) {
         self.trigger_pos = (line, col);
         self.selected = 0;
         self.scroll_offset = 0;
 
         let prefix_lower = prefix.to_lowercase();
+        self.prefix = prefix_lower.clone();
 
-        // Filter and collect matching suggestions
-        let mut matches: Vec<(Suggestion, usize)> = Vec::new();
-
-        // Add buffer symbols first (labels, procedures)
-        for (name, kind) in buffer_symbols {
-            if name.to_lowercase().starts_with(&prefix_lower) {
-                let score = if name.to_lowercase() == prefix_lower {
-                    0
-                } else {
-                    1
-                };
-                matches.push((Suggestion::new(name.clone(), *kind), score));
-            }
-        }
+        // Buffer symbols (labels, procedures, constants, fields), then the
+        // built-in instructions, directives and library procedures
+        let candidates = buffer_symbols
+            .iter()
+            .map(|s| (s, true))
+            .chain(self.all_suggestions.iter().map(|s| (s, false)));
+        let mut matches: Vec<Suggestion> = candidates
+            .filter_map(|(suggestion, local)| {
+                let score = relevance(&suggestion.text, &prefix_lower, local)?;
+                Some(Suggestion {
+                    score,
+                    ..suggestion.clone()
+                })
+            })
+            .collect();
 
-        // Add built-in suggestions
-        for suggestion in &self.all_suggestions {
-            if suggestion.text.to_lowercase().starts_with(&prefix_lower) {
-                let score = if suggestion.text.to_lowercase() == prefix_lower {
-                    0
-                } else if suggestion.text.to_lowercase().starts_with(&prefix_lower) {
-                    2
-                } else {
-                    3
-                };
-                matches.push((suggestion.clone(), score));
+        // Registers go first when completing an instruction operand,
+        // struct fields after a dot
+        let context_rank = |s: &Suggestion| match context {
+            CompletionContext::Operand if s.kind == SuggestionKind::Register => 0,
+            CompletionContext::Field if s.kind == SuggestionKind::Field => 0,
+            _ => 1,
+        };
+
+        // Sort by context, then relevance, then alphabetically
+        matches.sort_by(|a, b| {
+            context_rank(a)
+                .cmp(&context_rank(b))
+                .then_with(|| b.score.cmp(&a.score))
+                .then_with(|| a.text.to_lowercase().cmp(&b.text.to_lowercase()))
+                .then_with(|| a.text.cmp(&b.text))
+        });
+
+        // Deduplicate by text. A buffer symbol that is also a library
+        // procedure (declared in an included file, say) keeps its place
+        // and borrows the library's detail if it has none.
+        let mut suggestions: Vec<Suggestion> = Vec::with_capacity(matches.len());
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for suggestion in matches {
+            match seen.get(&suggestion.text.to_lowercase()) {
+                Some(&kept) => {
+                    if suggestions[kept].detail.is_none() {
+                        suggestions[kept].detail = suggestion.detail;
+                    }
+                }
+                None => {
+                    seen.insert(suggestion.text.to_lowercase(), suggestions.len());
+                    suggestions.push(suggestion);
+                }
             }
         }
-
-        // Sort by score (exact match first), then alphabetically
-        matches.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.text.cmp(&b.0.text)));
-
-        // Deduplicate by text
-        let mut seen = std::collections::HashSet::new();
-        self.suggestions = matches
-            .into_iter()
-            .filter(|(s, _)| seen.insert(s.text.to_lowercase()))
-            .map(|(s, _)| s)
-            .collect();
+        self.suggestions = suggestions;
 
         self.visible = !self.suggestions.is_empty();
     }
@@ -166,6 +263,7 @@ impl AutocompleteState {
     pub fn hide(&mut self) {
         self.visible = false;
         self.suggestions.clear();
+        self.prefix.clear();
         self.selected = 0;
         self.scroll_offset = 0;
     }
@@ -217,52 +315,286 @@ impl AutocompleteState {
     }
 }
 
-/// Parse buffer content to extract labels and procedures
-pub fn parse_buffer_symbols(lines: &[String]) -> Vec<(String, SuggestionKind)> {
+/// Relevance of an exact match
+pub const EXACT_SCORE: u32 = 4000;
+/// Relevance of a buffer symbol starting with the prefix
+pub const LOCAL_PREFIX_SCORE: u32 = 3000;
+/// Relevance of a built-in word starting with the prefix
+pub const PREFIX_SCORE: u32 = 2000;
+/// Base relevance of a fuzzy match, plus up to 999 from its `fuzzy_score`
+pub const FUZZY_SCORE: u32 = 1000;
+
+/// How well `text` matches the lowercased `prefix`, or None if it does
+/// not. Exact and prefix matches outrank every fuzzy one, where the
+/// prefix's characters only appear in order.
+pub fn relevance(text: &str, prefix: &str, local: bool) -> Option<u32> {
+    let lower = text.to_lowercase();
+    if lower == prefix {
+        return Some(EXACT_SCORE);
+    }
+    if lower.starts_with(prefix) {
+        return Some(if local {
+            LOCAL_PREFIX_SCORE
+        } else {
+            PREFIX_SCORE
+        });
+    }
+    // A single character would fuzzily match nearly everything
+    if prefix.chars().count() < 2 {
+        return None;
+    }
+    let (score, _) = fuzzy_score(prefix, text)?;
+    Some(FUZZY_SCORE + score.clamp(0, 999) as u32)
+}
+
+/// Parse buffer content to extract labels, procedures, macros, `EQU`/`=`
+/// constants and `STRUCT` fields. Constants carry their value as detail,
+/// fields their type and owning struct.
+pub fn parse_buffer_symbols(lines: &[String]) -> Vec<Suggestion> {
+    parse_symbol_definitions(lines)
+        .into_iter()
+        .map(|(_, symbol)| symbol)
+        .collect()
+}
+
+/// Like [`parse_buffer_symbols`], paired with the 0-based line each symbol
+/// is defined on
+pub fn parse_symbol_definitions(lines: &[String]) -> Vec<(usize, Suggestion)> {
     let mut symbols = Vec::new();
+    let mut current_struct: Option<String> = None;
+    let mut line_symbols = Vec::new();
+
+    for (line_idx, line) in lines.iter().enumerate() {
+        parse_line_symbols(line, &mut current_struct, &mut line_symbols);
+        symbols.extend(line_symbols.drain(..).map(|symbol| (line_idx, symbol)));
+    }
 
-    for line in lines {
-        let trimmed = line.trim();
+    symbols
+}
 
-        // Skip empty lines and comments
-        if trimmed.is_empty() || trimmed.starts_with(';') {
-            continue;
+/// Symbols defined on one line, given the `STRUCT` (if any) the line is
+/// inside. Updates `current_struct` for the next line.
+fn parse_line_symbols(line: &str, current_struct: &mut Option<String>, out: &mut Vec<Suggestion>) {
+    // Drop trailing comments
+    let code = line.split(';').next().unwrap_or("");
+    let trimmed = code.trim();
+
+    // Skip empty lines and comments
+    if trimmed.is_empty() {
+        return;
+    }
+
+    let parts: Vec<&str> = trimmed.split_whitespace().collect();
+    let second = parts.get(1).map(|p| p.to_uppercase());
+
+    // Struct bodies: every declaration is a field until the matching ENDS
+    if let Some(struct_name) = &*current_struct {
+        if second.as_deref() == Some("ENDS") {
+            *current_struct = None;
+        } else if parts.len() >= 2 && is_valid_identifier(parts[0]) {
+            out.push(
+                Suggestion::new(parts[0], SuggestionKind::Field).with_detail(format!(
+                    "{} ({})",
+                    parts[1].to_uppercase(),
+                    struct_name
+                )),
+            );
         }
+        return;
+    }
 
-        // Check for label (word followed by colon)
-        if let Some(colon_pos) = trimmed.find(':') {
-            let potential_label = trimmed[..colon_pos].trim();
-            if is_valid_identifier(potential_label) && !potential_label.starts_with('.') {
-                symbols.push((potential_label.to_string(), SuggestionKind::Label));
+    match second.as_deref() {
+        Some("STRUCT") | Some("STRUC") if is_valid_identifier(parts[0]) => {
+            out.push(Suggestion::new(parts[0], SuggestionKind::TypeKeyword).with_detail("STRUCT"));
+            *current_struct = Some(parts[0].to_string());
+            return;
+        }
+        Some("EQU") | Some("=") | Some("TEXTEQU") if is_valid_identifier(parts[0]) => {
+            let value = parts[2..].join(" ");
+            let mut symbol = Suggestion::new(parts[0], SuggestionKind::Constant);
+            if !value.is_empty() {
+                symbol = symbol.with_detail(value);
             }
+            out.push(symbol);
+            return;
+        }
+        _ => {}
+    }
+
+    // `name=value` written without spaces
+    if let Some((name, value)) = trimmed.split_once('=') {
+        let name = name.trim();
+        if parts.len() == 1 && is_valid_identifier(name) {
+            out.push(Suggestion::new(name, SuggestionKind::Constant).with_detail(value.trim()));
+            return;
         }
+    }
 
-        // Check for procedure (word PROC)
-        let upper = trimmed.to_uppercase();
-        if upper.contains(" PROC") || upper.ends_with(" PROC") {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if let Some(name) = parts.first() {
-                if is_valid_identifier(name) {
-                    symbols.push((name.to_string(), SuggestionKind::Procedure));
-                }
+    // Check for label (word followed by colon)
+    if let Some(colon_pos) = trimmed.find(':') {
+        let potential_label = trimmed[..colon_pos].trim();
+        if is_valid_identifier(potential_label)
+            && !potential_label.starts_with('.')
+            && potential_label != "@@"
+        {
+            out.push(Suggestion::new(potential_label, SuggestionKind::Label));
+        }
+    }
+
+    // Check for procedure (word PROC)
+    let upper = trimmed.to_uppercase();
+    if upper.contains(" PROC") || upper.ends_with(" PROC") {
+        if let Some(name) = parts.first() {
+            if is_valid_identifier(name) {
+                out.push(Suggestion::new(*name, SuggestionKind::Procedure));
             }
         }
+    }
 
-        // Check for macro (word MACRO)
-        if upper.contains(" MACRO") || upper.ends_with(" MACRO") {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if let Some(name) = parts.first() {
-                if is_valid_identifier(name) {
-                    symbols.push((name.to_string(), SuggestionKind::Macro));
-                }
+    // Check for macro (word MACRO)
+    if upper.contains(" MACRO") || upper.ends_with(" MACRO") {
+        if let Some(name) = parts.first() {
+            if is_valid_identifier(name) {
+                out.push(Suggestion::new(*name, SuggestionKind::Macro));
             }
         }
     }
+}
 
-    symbols
+/// Per-buffer cache of [`parse_symbol_definitions`]. `update` compares the
+/// buffer against the lines it last saw and reparses only the changed run,
+/// plus any following lines whose `STRUCT` context the edit changed.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    lines: Vec<String>,
+    /// Symbols defined on each line
+    symbols: Vec<Vec<Suggestion>>,
+    /// Struct still open after each line
+    structs: Vec<Option<String>>,
+}
+
+impl SymbolIndex {
+    /// Bring the index up to date with `lines`. Returns how many lines
+    /// were reparsed.
+    pub fn update(&mut self, lines: &[String]) -> usize {
+        let old_len = self.lines.len();
+        let prefix = self
+            .lines
+            .iter()
+            .zip(lines)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let max_suffix = old_len.min(lines.len()) - prefix;
+        let suffix = self.lines[prefix..]
+            .iter()
+            .rev()
+            .zip(lines[prefix..].iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_end = old_len - suffix;
+        let new_end = lines.len() - suffix;
+
+        // Context the unchanged tail was parsed with
+        let mut old_entry = old_end.checked_sub(1).and_then(|i| self.structs[i].clone());
+        let mut state = prefix.checked_sub(1).and_then(|i| self.structs[i].clone());
+        let mut symbols = Vec::with_capacity(new_end - prefix);
+        let mut structs = Vec::with_capacity(new_end - prefix);
+        for line in &lines[prefix..new_end] {
+            let mut out = Vec::new();
+            parse_line_symbols(line, &mut state, &mut out);
+            symbols.push(out);
+            structs.push(state.clone());
+        }
+        self.lines
+            .splice(prefix..old_end, lines[prefix..new_end].iter().cloned());
+        self.symbols.splice(prefix..old_end, symbols);
+        self.structs.splice(prefix..old_end, structs);
+
+        let mut reparsed = new_end - prefix;
+        let mut i = new_end;
+        while i < lines.len() && state != old_entry {
+            old_entry = self.structs[i].clone();
+            self.symbols[i].clear();
+            parse_line_symbols(&lines[i], &mut state, &mut self.symbols[i]);
+            self.structs[i] = state.clone();
+            reparsed += 1;
+            i += 1;
+        }
+        reparsed
+    }
+
+    /// All symbols, in line order
+    pub fn symbols(&self) -> impl Iterator<Item = &Suggestion> {
+        self.symbols.iter().flatten()
+    }
+
+    /// All symbols with the 0-based line they are defined on
+    pub fn definitions(&self) -> impl Iterator<Item = (usize, &Suggestion)> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .flat_map(|(line, symbols)| symbols.iter().map(move |s| (line, s)))
+    }
 }
 
-fn is_valid_identifier(s: &str) -> bool {
+/// Symbols and `INCLUDE`s of files pulled in by the buffers, so each is
+/// read again only after its modification time changes
+#[derive(Debug, Default)]
+pub struct IncludeCache {
+    files: HashMap<PathBuf, CachedInclude>,
+}
+
+#[derive(Debug)]
+struct CachedInclude {
+    mtime: SystemTime,
+    symbols: Vec<Suggestion>,
+    includes: Vec<String>,
+}
+
+impl IncludeCache {
+    /// Symbols defined in `path` and the file names it includes, or None
+    /// if it cannot be read
+    pub fn get(&mut self, path: &Path) -> Option<(&[Suggestion], &[String])> {
+        let mtime = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        if self
+            .files
+            .get(path)
+            .is_none_or(|cached| cached.mtime != mtime)
+        {
+            let content = fs::read_to_string(path).ok()?;
+            let lines: Vec<String> = content.lines().map(String::from).collect();
+            let cached = CachedInclude {
+                mtime,
+                symbols: parse_buffer_symbols(&lines),
+                includes: parse_includes(&lines),
+            };
+            self.files.insert(path.to_path_buf(), cached);
+        }
+        let cached = &self.files[path];
+        Some((&cached.symbols, &cached.includes))
+    }
+}
+
+/// File names referenced by `INCLUDE` lines, in order
+pub fn parse_includes(lines: &[String]) -> Vec<String> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let code = line.split(';').next().unwrap_or("").trim();
+            let (directive, rest) = code.split_once(char::is_whitespace)?;
+            if !directive.eq_ignore_ascii_case("INCLUDE") {
+                return None;
+            }
+            let name = rest
+                .trim()
+                .trim_matches(|c| c == '<' || c == '>' || c == '"');
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+pub(crate) fn is_valid_identifier(s: &str) -> bool {
     if s.is_empty() {
         return false;
     }
@@ -274,11 +606,17 @@ fn is_valid_identifier(s: &str) -> bool {
         .all(|c| c.is_alphanumeric() || c == '_' || c == '@' || c == '?')
 }
 
-use crate::masm_lang::{DIRECTIVES, KEYWORDS, REGISTERS, TYPE_KEYWORDS};
+use crate::docs;
+use crate::masm_lang::{DIRECTIVES, IRVINE32_FUNCTIONS, KEYWORDS, REGISTERS, TYPE_KEYWORDS};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TempDir;
 
     #[test]
     fn test_parse_labels() {
@@ -290,10 +628,10 @@ mod tests {
         let symbols = parse_buffer_symbols(&lines);
         assert!(symbols
             .iter()
-            .any(|(n, k)| n == "main" && *k == SuggestionKind::Label));
+            .any(|s| s.text == "main" && s.kind == SuggestionKind::Label));
         assert!(symbols
             .iter()
-            .any(|(n, k)| n == "loop_start" && *k == SuggestionKind::Label));
+            .any(|s| s.text == "loop_start" && s.kind == SuggestionKind::Label));
     }
 
     #[test]
@@ -306,7 +644,7 @@ mod tests {
         let symbols = parse_buffer_symbols(&lines);
         assert!(symbols
             .iter()
-            .any(|(n, k)| n == "MyProc" && *k == SuggestionKind::Procedure));
+            .any(|s| s.text == "MyProc" && s.kind == SuggestionKind::Procedure));
     }
 
     #[test]
@@ -316,4 +654,193 @@ mod tests {
         assert!(state.suggestions.iter().any(|s| s.text == "mov"));
         assert!(state.suggestions.iter().any(|s| s.text == "movsx"));
     }
+
+    #[test]
+    fn test_local_symbols_rank_above_library_and_fuzzy() {
+        let mut state = AutocompleteState::new();
+        state.show("Wri", 0, 0, &[]);
+        let texts: Vec<&str> = state.suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert!(texts.contains(&"WriteString") && texts.contains(&"WriteDec"));
+
+        let symbols = vec![
+            Suggestion::new("write_total", SuggestionKind::Label),
+            // Declared by an included Irvine32.inc as well as built in
+            Suggestion::new("WriteDec", SuggestionKind::Procedure),
+        ];
+        state.show("wri", 0, 0, &symbols);
+        // Both buffer symbols first, alphabetically, then the library
+        assert_eq!(state.suggestions[0].text, "write_total");
+        assert_eq!(state.suggestions[1].text, "WriteDec");
+        assert_eq!(state.suggestions[2].text, "WriteBin");
+        assert_eq!(
+            state.suggestions[1].detail.as_deref(),
+            Some("Irvine32"),
+            "the duplicate library entry lends its detail"
+        );
+        assert_eq!(
+            state
+                .suggestions
+                .iter()
+                .filter(|s| s.text == "WriteDec")
+                .count(),
+            1
+        );
+
+        // Fuzzy matches come last
+        state.show("wstr", 0, 0, &[]);
+        assert_eq!(state.suggestions[0].text, "WriteString");
+        assert!((FUZZY_SCORE..PREFIX_SCORE).contains(&state.suggestions[0].score));
+        assert_eq!(relevance("mov", "m", false), Some(PREFIX_SCORE));
+        assert!(relevance("mov", "ov", false).is_some());
+        assert_eq!(relevance("mov", "vo", false), None);
+    }
+
+    #[test]
+    fn test_fuzzy_prefers_runs_and_word_starts() {
+        // "rs" at a word start beats the same letters scattered
+        let start = relevance("read_size", "rs", true).unwrap();
+        let scattered = relevance("ReadersX", "rs", true).unwrap();
+        assert!(start > scattered, "{} <= {}", start, scattered);
+        // A run beats gaps
+        let run = relevance("dumpstack", "st", false).unwrap();
+        let gaps = relevance("dumpsxtx", "st", false).unwrap();
+        assert!(run > gaps);
+    }
+
+    #[test]
+    fn test_registers_first_after_instruction() {
+        assert_eq!(
+            CompletionContext::detect("    mov "),
+            CompletionContext::Operand
+        );
+        assert_eq!(
+            CompletionContext::detect("loop1: add eax, "),
+            CompletionContext::Operand
+        );
+        assert_eq!(CompletionContext::detect("    "), CompletionContext::Any);
+
+        let mut state = AutocompleteState::new();
+        let symbols = vec![Suggestion::new("exit_loop", SuggestionKind::Label)];
+        state.show_in_context("e", 0, 4, &symbols, CompletionContext::Operand);
+        assert_eq!(state.suggestions[0].kind, SuggestionKind::Register);
+        // Other candidates are still offered
+        assert!(state.suggestions.iter().any(|s| s.text == "exit_loop"));
+    }
+
+    #[test]
+    fn test_parse_constants_and_struct_fields() {
+        let lines: Vec<String> = [
+            "MAX_LEN EQU 100",
+            "count = 5 ; loop count",
+            "POINT STRUCT",
+            "    x DWORD ?",
+            "    y DWORD ?",
+            "POINT ENDS",
+            "INCLUDE Irvine32.inc",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let symbols = parse_buffer_symbols(&lines);
+        let find = |name: &str| symbols.iter().find(|s| s.text == name).unwrap();
+
+        assert_eq!(find("MAX_LEN").kind, SuggestionKind::Constant);
+        assert_eq!(find("MAX_LEN").detail.as_deref(), Some("100"));
+        assert_eq!(find("count").detail.as_deref(), Some("5"));
+        assert_eq!(find("x").kind, SuggestionKind::Field);
+        assert_eq!(find("y").detail.as_deref(), Some("DWORD (POINT)"));
+        assert_eq!(parse_includes(&lines), vec!["Irvine32.inc"]);
+
+        assert_eq!(
+            CompletionContext::detect("    mov eax, pt."),
+            CompletionContext::Field
+        );
+    }
+
+    #[test]
+    fn test_symbol_index_reparses_only_edits() {
+        let mut lines: Vec<String> = [
+            "Point STRUCT",
+            "    x DWORD ?",
+            "    y DWORD ?",
+            "Point ENDS",
+            "main PROC",
+            "start:",
+            "main ENDP",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let names = |index: &SymbolIndex| -> Vec<(usize, String, SuggestionKind)> {
+            index
+                .definitions()
+                .map(|(line, s)| (line, s.text.clone(), s.kind))
+                .collect()
+        };
+        let full = |lines: &[String]| -> Vec<(usize, String, SuggestionKind)> {
+            parse_symbol_definitions(lines)
+                .into_iter()
+                .map(|(line, s)| (line, s.text, s.kind))
+                .collect()
+        };
+
+        let mut index = SymbolIndex::default();
+        assert_eq!(index.update(&lines), 7);
+        assert_eq!(index.update(&lines), 0);
+
+        lines[5] = String::from("begin:");
+        assert_eq!(index.update(&lines), 1);
+        assert_eq!(names(&index), full(&lines));
+
+        lines.insert(1, String::from("count = 3"));
+        assert_eq!(index.update(&lines), 1);
+        assert_eq!(names(&index), full(&lines));
+
+        // Dropping ENDS turns the following lines into struct fields
+        lines.remove(4);
+        assert!(index.update(&lines) > 1);
+        assert_eq!(names(&index), full(&lines));
+        assert!(index
+            .symbols()
+            .any(|s| s.text == "main" && s.kind == SuggestionKind::Field));
+    }
+
+    #[test]
+    fn test_include_cache_rereads_changed_files() {
+        let dir = TempDir::new("includes");
+        let path = dir.join("macros.inc");
+        let write = |content: &str, secs: u64| {
+            fs::write(&path, content).unwrap();
+            let mtime = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        let names = |cache: &mut IncludeCache| -> (Vec<String>, Vec<String>) {
+            let (symbols, includes) = cache.get(&path).unwrap();
+            (
+                symbols.iter().map(|s| s.text.clone()).collect(),
+                includes.to_vec(),
+            )
+        };
+
+        let mut cache = IncludeCache::default();
+        write("BUFSIZE = 64\nINCLUDE io.inc\n", 1000);
+        assert_eq!(
+            names(&mut cache),
+            (vec!["BUFSIZE".into()], vec!["io.inc".into()])
+        );
+
+        // Same modification time: the cached symbols are used
+        write("LIMIT = 8\n", 1000);
+        assert_eq!(names(&mut cache).0, vec!["BUFSIZE"]);
+
+        write("LIMIT = 8\n", 2000);
+        assert_eq!(names(&mut cache), (vec!["LIMIT".into()], vec![]));
+
+        assert!(cache.get(&dir.join("missing.inc")).is_none());
+    }
 }
@@ -1,22 +1,131 @@
-use crate::config::{Config, ProjectConfig};
+use crate::config::{Assembler, Config, ProjectConfig};
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 pub struct BuildOutput {
     pub success: bool,
+    pub up_to_date: bool, // Nothing changed since the last build, so nothing ran
     pub stdout: String,
     pub stderr: String,
+    pub commands: Vec<String>, // Command lines that were executed, in order
+    pub elapsed: Duration,     // Time spent in the assembler and linker
 }
 
 pub struct RunOutput {
     pub exit_code: i32,
+    pub signal: Option<i32>, // Signal that killed the process, if one did
     pub stdout: String,
     pub stderr: String,
+    pub commands: Vec<String>,
 }
 
+impl RunOutput {
+    /// "Terminated by SIGSEGV (11)" when a signal ended the process, or
+    /// "Terminated by EXCEPTION_ACCESS_VIOLATION (0xC0000005)" when the
+    /// exit code is an unhandled Windows exception
+    pub fn termination(&self) -> Option<String> {
+        if let Some(signal) = self.signal {
+            return Some(match signal_name(signal) {
+                Some(name) => format!("Terminated by {} ({})", name, signal),
+                None => format!("Terminated by signal {}", signal),
+            });
+        }
+        let name = exception_name(self.exit_code)?;
+        Some(format!(
+            "Terminated by {} (0x{:08X})",
+            name, self.exit_code as u32
+        ))
+    }
+}
+
+/// The signal that ended a process, if it did not exit normally
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// The signal an exit code reports: `script` and shells exit with 128+N
+/// when the program they ran was killed by signal N
+fn code_signal(code: i32) -> Option<i32> {
+    (129..=159).contains(&code).then_some(code - 128)
+}
+
+/// Name of the unhandled Windows exception an exit code reports
+fn exception_name(code: i32) -> Option<&'static str> {
+    Some(match code as u32 {
+        0x8000_0003 => "EXCEPTION_BREAKPOINT",
+        0xC000_0005 => "EXCEPTION_ACCESS_VIOLATION",
+        0xC000_001D => "EXCEPTION_ILLEGAL_INSTRUCTION",
+        0xC000_008C => "EXCEPTION_ARRAY_BOUNDS_EXCEEDED",
+        0xC000_0094 => "EXCEPTION_INT_DIVIDE_BY_ZERO",
+        0xC000_0095 => "EXCEPTION_INT_OVERFLOW",
+        0xC000_0096 => "EXCEPTION_PRIV_INSTRUCTION",
+        0xC000_00FD => "EXCEPTION_STACK_OVERFLOW",
+        0xC000_0409 => "STATUS_STACK_BUFFER_OVERRUN",
+        _ => return None,
+    })
+}
+
+/// Name of a Linux signal number
+fn signal_name(signal: i32) -> Option<&'static str> {
+    const NAMES: [&str; 15] = [
+        "SIGHUP", "SIGINT", "SIGQUIT", "SIGILL", "SIGTRAP", "SIGABRT", "SIGBUS", "SIGFPE",
+        "SIGKILL", "SIGUSR1", "SIGSEGV", "SIGUSR2", "SIGPIPE", "SIGALRM", "SIGTERM",
+    ];
+    usize::try_from(signal - 1)
+        .ok()
+        .and_then(|idx| NAMES.get(idx))
+        .copied()
+}
+
+/// Render a command as a copy-pasteable shell line
+fn format_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '\'') {
+                sh_quote(&arg)
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `arg` in single quotes for `sh`, with any quote in it escaped
+fn sh_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// A path as Wine shows it to a Windows program, on drive Z:
+fn to_wine_path(path: &Path) -> String {
+    format!("Z:{}", path.display()).replace('/', "\\")
+}
+
+/// Assembler sign-on lines, which mention neither errors nor warnings but
+/// may contain those words in a product name or notice
+fn is_banner(line: &str) -> bool {
+    ["JWasm", "UASM v", "Microsoft (R)", "Copyright"]
+        .iter()
+        .any(|marker| line.contains(marker))
+}
+
+#[derive(Clone)]
 pub struct Pipeline {
-    jwasm_path: PathBuf,
+    assembler: Assembler,
+    assembler_path: PathBuf,
     linker_path: PathBuf,
     wine_path: PathBuf,
     irvine_lib_path: PathBuf,
@@ -25,6 +134,15 @@ pub struct Pipeline {
     output_name: String,
     libs: Vec<String>,
     last_exe: Option<PathBuf>,
+    last_artifacts: Vec<PathBuf>, // Files the last build produced
+}
+
+/// FNV-1a, used for the build cache key. Unlike `DefaultHasher` its
+/// output is stable across Rust releases, so the cache survives upgrades.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
 }
 
 /// Resolve the JWasm binary path by checking multiple locations:
@@ -60,10 +178,16 @@ fn resolve_jwasm(configured: &Path) -> PathBuf {
 
 impl Pipeline {
     pub fn new(config: &Config, project_config: &ProjectConfig, project_dir: &Path) -> Self {
-        let jwasm_path = resolve_jwasm(&config.toolchain.jwasm_path);
+        let assembler = project_config.assembler;
+        let assembler_path = match assembler {
+            Assembler::Jwasm => resolve_jwasm(&config.toolchain.jwasm_path),
+            Assembler::Uasm => config.toolchain.uasm_path.clone(),
+            Assembler::Ml => config.toolchain.ml_path.clone(),
+        };
 
         Self {
-            jwasm_path,
+            assembler,
+            assembler_path,
             linker_path: config.toolchain.linker_path.clone(),
             wine_path: config.toolchain.wine_path.clone(),
             irvine_lib_path: config.toolchain.irvine_lib_path.clone(),
@@ -72,73 +196,292 @@ impl Pipeline {
             output_name: project_config.output_name.clone(),
             libs: project_config.libs.clone(),
             last_exe: None,
+            last_artifacts: Vec::new(),
         }
     }
 
-    pub fn build(&mut self, source_file: &PathBuf) -> Result<BuildOutput> {
-        let mut stderr_log = String::new();
+    /// Command assembling `input` into `obj_file`, searching `include_dirs`
+    /// for INCLUDE files
+    fn assemble_command(&self, obj_file: &Path, include_dirs: &[&Path], input: &Path) -> Command {
+        match self.assembler {
+            Assembler::Jwasm | Assembler::Uasm => {
+                let mut cmd = Command::new(&self.assembler_path);
+                if self.assembler == Assembler::Uasm {
+                    cmd.arg("-nologo");
+                }
+                cmd.arg("-coff").arg(format!("-Fo{}", obj_file.display()));
+                for dir in include_dirs {
+                    cmd.arg(format!("-I{}", dir.display()));
+                }
+                cmd.arg(input);
+                cmd
+            }
+            Assembler::Ml => {
+                // ML is a Windows program, so it runs under Wine and is
+                // given Windows paths
+                let mut cmd = Command::new(&self.wine_path);
+                cmd.arg(&self.assembler_path)
+                    .args(["/nologo", "/c", "/coff"])
+                    .arg(format!("/Fo{}", to_wine_path(obj_file)));
+                for dir in include_dirs {
+                    cmd.arg(format!("/I{}", to_wine_path(dir)));
+                }
+                cmd.arg(to_wine_path(input));
+                cmd
+            }
+        }
+    }
 
-        // Canonicalize the source file path to get absolute path
-        let source_file = if source_file.is_absolute() {
-            source_file.clone()
-        } else {
-            self.project_dir.join(source_file)
-        };
+    /// Explanation for an assembler that could not be started
+    fn assembler_missing(&self) -> String {
+        match self.assembler {
+            Assembler::Jwasm => String::from("Failed to execute jwasm. Is it installed? Run the install script or place jwasm next to the masmide binary."),
+            Assembler::Uasm => format!(
+                "Failed to execute uasm ({}). Is it installed? Set toolchain.uasm_path or put uasm on your PATH.",
+                self.assembler_path.display()
+            ),
+            Assembler::Ml => format!(
+                "Failed to run {} under Wine ({}). Is Wine installed?",
+                self.assembler_path.display(),
+                self.wine_path.display()
+            ),
+        }
+    }
+
+    /// Error and warning lines from the assembler's output. When it failed
+    /// without any (Wine not finding ml.exe, say) its raw output and exit
+    /// code are reported instead, so the failure is never silent.
+    fn assembler_messages(&self, result: &std::process::Output) -> String {
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let mut log = String::new();
+        for line in stdout.lines().chain(stderr.lines()) {
+            let lower = line.to_lowercase();
+            // Keep only actual error/warning messages, skip banner and info
+            if (lower.contains("error") || lower.contains("warning")) && !is_banner(line) {
+                log.push_str(line);
+                log.push('\n');
+            }
+        }
+        if log.is_empty() && !result.status.success() {
+            let raw = format!("{}\n{}", stdout.trim(), stderr.trim());
+            log = format!(
+                "{} failed with exit code {}\n{}",
+                self.assembler.name(),
+                result.status.code().unwrap_or(-1),
+                raw.trim()
+            );
+        }
+        log
+    }
+
+    /// Files generated by the last build that still exist on disk
+    pub fn artifacts(&self) -> Vec<PathBuf> {
+        self.last_artifacts
+            .iter()
+            .filter(|p| p.exists())
+            .cloned()
+            .collect()
+    }
+
+    /// Where the key of the last successful build is kept
+    fn cache_path(&self) -> PathBuf {
+        self.project_dir.join(".masmide").join("build_cache")
+    }
+
+    /// Directories searched for the INCLUDE files of a source in
+    /// `source_dir`, besides the directory of the including file
+    fn include_dirs<'a>(&'a self, source_dir: &'a Path) -> [&'a Path; 2] {
+        [source_dir, self.irvine_inc_path.as_path()]
+    }
 
-        // Verify the source file exists
-        if !source_file.exists() {
+    /// Where the assembler finds `include` when it's named in `includer`:
+    /// next to the including file, then in the working directory (the
+    /// project) and the `-I` directories
+    fn resolve_include(
+        &self,
+        include: &str,
+        includer: &Path,
+        source_dir: &Path,
+    ) -> Option<PathBuf> {
+        let includer_dir = includer.parent().unwrap_or(&self.project_dir);
+        [includer_dir, self.project_dir.as_path()]
+            .into_iter()
+            .chain(self.include_dirs(source_dir))
+            .map(|dir| dir.join(include))
+            .find(|path| path.is_file())
+    }
+
+    /// Hash of everything that goes into the executable: the sources, every
+    /// file they include (directly or through other includes), and the
+    /// toolchain settings. `None` when an include can't be found, since
+    /// the build then can't be known to be up to date.
+    fn build_key(&self, sources: &[PathBuf]) -> Result<Option<String>> {
+        let mut hash = 0xcbf29ce484222325;
+        for source_file in sources {
+            let source_dir = source_file.parent().unwrap_or(&self.project_dir);
+            // Files already hashed for this source, so include cycles end
+            let mut seen = std::collections::HashSet::new();
+            let mut pending = vec![source_file.clone()];
+            while let Some(file) = pending.pop() {
+                if !seen.insert(file.clone()) {
+                    continue;
+                }
+                let content = std::fs::read(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                hash = fnv1a(hash, file.to_string_lossy().as_bytes());
+                hash = fnv1a(hash, &[0]);
+                hash = fnv1a(hash, &content);
+
+                let lines: Vec<String> = String::from_utf8_lossy(&content)
+                    .lines()
+                    .map(String::from)
+                    .collect();
+                // Reversed so includes are hashed in the order they appear
+                for include in crate::autocomplete::parse_includes(&lines).iter().rev() {
+                    match self.resolve_include(include, &file, source_dir) {
+                        Some(path) => pending.push(path),
+                        None => return Ok(None),
+                    }
+                }
+            }
+        }
+
+        let settings = [
+            self.assembler.name().to_string(),
+            self.assembler_path.to_string_lossy().to_string(),
+            self.linker_path.to_string_lossy().to_string(),
+            self.irvine_lib_path.to_string_lossy().to_string(),
+            self.irvine_inc_path.to_string_lossy().to_string(),
+            self.output_name.clone(),
+            self.libs.join(","),
+        ];
+        for setting in &settings {
+            // Separator keeps ("ab", "c") and ("a", "bc") apart
+            hash = fnv1a(hash, setting.as_bytes());
+            hash = fnv1a(hash, &[0]);
+        }
+        Ok(Some(format!("{:016x}", hash)))
+    }
+
+    /// Whether `exe_file` was built from exactly these sources and
+    /// settings and nothing has touched the sources since
+    fn up_to_date(&self, key: &str, sources: &[PathBuf], exe_file: &Path) -> bool {
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        let newer = sources
+            .iter()
+            .all(|source| match (modified(exe_file), modified(source)) {
+                (Some(exe), Some(src)) => exe >= src,
+                _ => false,
+            });
+        newer
+            && std::fs::read_to_string(self.cache_path())
+                .map(|cached| cached.trim() == key)
+                .unwrap_or(false)
+    }
+
+    /// Assemble each of `sources` and link them into one executable.
+    /// Every source is assembled even after one fails, so the errors of
+    /// all of them are reported. Unless `force` is set, nothing runs when
+    /// the executable is already up to date with the sources.
+    pub fn build(&mut self, sources: &[PathBuf], force: bool) -> Result<BuildOutput> {
+        let mut stderr_log = String::new();
+        let mut commands = Vec::new();
+        let mut elapsed = Duration::ZERO;
+        self.last_artifacts.clear();
+
+        // Canonicalize the source file paths to get absolute paths
+        let sources: Vec<PathBuf> = sources
+            .iter()
+            .map(|source| {
+                if source.is_absolute() {
+                    source.clone()
+                } else {
+                    self.project_dir.join(source)
+                }
+            })
+            .collect();
+
+        // Verify the source files exist
+        if let Some(missing) = sources.iter().find(|source| !source.exists()) {
             return Ok(BuildOutput {
                 success: false,
+                up_to_date: false,
                 stdout: String::new(),
-                stderr: format!("File not found: {}", source_file.display()),
+                stderr: format!("File not found: {}", missing.display()),
+                commands,
+                elapsed,
             });
         }
 
-        // Determine output paths
-        let file_stem = source_file
-            .file_stem()
-            .context("Invalid source file name")?
-            .to_string_lossy();
-
-        let obj_file = self.project_dir.join(format!("{}.obj", file_stem));
         let exe_file = self.project_dir.join(&self.output_name);
-
-        let source_name = source_file
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        // Step 1: Assemble with JWasm
-        let jwasm_result = Command::new(&self.jwasm_path)
-            .arg("-coff")
-            .arg(format!("-Fo{}", obj_file.display()))
-            .arg(format!("-I{}", self.irvine_inc_path.display()))
-            .arg(&source_file)
-            .current_dir(&self.project_dir)
-            .output()
-            .context("Failed to execute jwasm. Is it installed? Run the install script or place jwasm next to the masmide binary.")?;
-
-        // Extract only error/warning lines from jwasm output
-        let jwasm_stdout = String::from_utf8_lossy(&jwasm_result.stdout);
-        let jwasm_stderr = String::from_utf8_lossy(&jwasm_result.stderr);
-
-        for line in jwasm_stdout.lines().chain(jwasm_stderr.lines()) {
-            let lower = line.to_lowercase();
-            // Keep only actual error/warning messages, skip banner and info
-            if (lower.contains("error") || lower.contains("warning"))
-                && !line.contains("JWasm")
-                && !line.contains("Copyright")
-            {
-                stderr_log.push_str(line);
-                stderr_log.push('\n');
+        let source_names = sources
+            .iter()
+            .map(|source| {
+                source
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let key = self.build_key(&sources)?;
+        let cached = key
+            .as_deref()
+            .is_some_and(|key| self.up_to_date(key, &sources, &exe_file));
+        if !force && cached {
+            self.last_exe = Some(exe_file.clone());
+            self.last_artifacts.push(exe_file);
+            return Ok(BuildOutput {
+                success: true,
+                up_to_date: true,
+                stdout: format!("{} is up to date with {}", self.output_name, source_names),
+                stderr: String::new(),
+                commands,
+                elapsed,
+            });
+        }
+        // Forget the old key first so a failed build is never taken as cached
+        let _ = std::fs::remove_file(self.cache_path());
+
+        // Step 1: Assemble each source to its own object file
+        let mut obj_files = Vec::new();
+        let mut assembled = true;
+        for source_file in &sources {
+            let file_stem = source_file
+                .file_stem()
+                .context("Invalid source file name")?
+                .to_string_lossy();
+            let obj_file = self.project_dir.join(format!("{}.obj", file_stem));
+
+            let source_dir = source_file.parent().unwrap_or(&self.project_dir);
+            let mut asm_cmd =
+                self.assemble_command(&obj_file, &self.include_dirs(source_dir), source_file);
+            asm_cmd.current_dir(&self.project_dir);
+            commands.push(format_command(&asm_cmd));
+
+            let started = Instant::now();
+            let asm_result = asm_cmd.output().with_context(|| self.assembler_missing())?;
+            elapsed += started.elapsed();
+            stderr_log.push_str(&self.assembler_messages(&asm_result));
+
+            if asm_result.status.success() {
+                self.last_artifacts.push(obj_file.clone());
+                obj_files.push(obj_file);
+            } else {
+                assembled = false;
             }
         }
 
-        if !jwasm_result.status.success() {
+        if !assembled {
             return Ok(BuildOutput {
                 success: false,
+                up_to_date: false,
                 stdout: String::new(),
                 stderr: stderr_log.trim().to_string(),
+                commands,
+                elapsed,
             });
         }
 
@@ -148,7 +491,7 @@ impl Pipeline {
         link_cmd
             .arg("-o")
             .arg(&exe_file)
-            .arg(&obj_file)
+            .args(&obj_files)
             .arg("--subsystem")
             .arg("console");
 
@@ -162,8 +505,11 @@ impl Pipeline {
         }
 
         link_cmd.current_dir(&self.project_dir);
+        commands.push(format_command(&link_cmd));
 
+        let started = Instant::now();
         let link_result = link_cmd.output().context("Failed to execute linker")?;
+        elapsed += started.elapsed();
 
         // Extract linker errors
         let link_stderr = String::from_utf8_lossy(&link_result.stderr);
@@ -178,24 +524,149 @@ impl Pipeline {
         if !link_result.status.success() {
             return Ok(BuildOutput {
                 success: false,
+                up_to_date: false,
                 stdout: String::new(),
                 stderr: stderr_log.trim().to_string(),
+                commands,
+                elapsed,
             });
         }
 
         self.last_exe = Some(exe_file.clone());
+        self.last_artifacts.push(exe_file.clone());
 
-        // Clean up object file
-        let _ = std::fs::remove_file(&obj_file);
+        // Clean up object files
+        for obj_file in &obj_files {
+            let _ = std::fs::remove_file(obj_file);
+        }
+        // Only what's left behind counts as produced by the build
+        self.last_artifacts.retain(|path| path.exists());
+
+        if let Some(key) = &key {
+            let cache_path = self.cache_path();
+            if let Some(parent) = cache_path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = std::fs::write(&cache_path, key);
+        }
 
         Ok(BuildOutput {
             success: true,
-            stdout: format!("Built {} → {}", source_name, self.output_name),
+            up_to_date: false,
+            stdout: format!("Built {} → {}", source_names, self.output_name),
+            stderr: stderr_log.trim().to_string(),
+            commands,
+            elapsed,
+        })
+    }
+
+    /// Assemble `source_file` without linking to report errors quickly.
+    /// When `unsaved` holds the buffer contents they are checked instead of
+    /// the file on disk. The object file goes to the temp directory and is
+    /// removed, so nothing in the project changes.
+    pub fn check(&self, source_file: &Path, unsaved: Option<&str>) -> Result<BuildOutput> {
+        let source_file = if source_file.is_absolute() {
+            source_file.to_path_buf()
+        } else {
+            self.project_dir.join(source_file)
+        };
+        let source_dir = source_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| self.project_dir.clone());
+        let file_stem = source_file
+            .file_stem()
+            .context("Invalid source file name")?
+            .to_string_lossy()
+            .to_string();
+
+        let tmp_dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let obj_file = tmp_dir.join(format!("masmide-check-{}-{}.obj", pid, file_stem));
+        let tmp_source = match unsaved {
+            Some(content) => {
+                let path = tmp_dir.join(format!("masmide-check-{}-{}.asm", pid, file_stem));
+                std::fs::write(&path, content).context("Failed to write temp file for check")?;
+                Some(path)
+            }
+            None => None,
+        };
+        let input = tmp_source.as_ref().unwrap_or(&source_file);
+
+        // Includes are resolved next to the real file even when checking a copy
+        let mut asm_cmd = self.assemble_command(&obj_file, &self.include_dirs(&source_dir), input);
+        asm_cmd.current_dir(&self.project_dir);
+        let commands = vec![format_command(&asm_cmd)];
+
+        let started = Instant::now();
+        let result = asm_cmd.output();
+        let elapsed = started.elapsed();
+        let _ = std::fs::remove_file(&obj_file);
+        if let Some(path) = &tmp_source {
+            let _ = std::fs::remove_file(path);
+        }
+        let result = result.with_context(|| self.assembler_missing())?;
+        let mut stderr_log = self.assembler_messages(&result);
+
+        // Point messages about the temp copy back at the real file
+        if let Some(path) = &tmp_source {
+            stderr_log = stderr_log
+                .replace(
+                    &path.display().to_string(),
+                    &source_file.display().to_string(),
+                )
+                .replace(&to_wine_path(path), &to_wine_path(&source_file));
+        }
+
+        Ok(BuildOutput {
+            success: result.status.success(),
+            up_to_date: false,
+            stdout: String::new(),
             stderr: stderr_log.trim().to_string(),
+            commands,
+            elapsed,
         })
     }
 
-    pub fn run(&self) -> Result<RunOutput> {
+    /// Build and run a throwaway `program` in its own temp directory, which
+    /// is removed afterwards. The run output is None when the build fails.
+    pub fn scratch(&self, program: &str) -> Result<(BuildOutput, Option<RunOutput>)> {
+        let dir = std::env::temp_dir().join(format!("masmide-scratch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).context("Failed to create scratch directory")?;
+        let source = dir.join("scratch.asm");
+
+        let mut scratch = Self {
+            assembler: self.assembler,
+            assembler_path: self.assembler_path.clone(),
+            linker_path: self.linker_path.clone(),
+            wine_path: self.wine_path.clone(),
+            irvine_lib_path: self.irvine_lib_path.clone(),
+            irvine_inc_path: self.irvine_inc_path.clone(),
+            project_dir: dir.clone(),
+            output_name: String::from("scratch.exe"),
+            libs: self.libs.clone(),
+            last_exe: None,
+            last_artifacts: Vec::new(),
+        };
+
+        let result = std::fs::write(&source, program)
+            .context("Failed to write scratch program")
+            .and_then(|_| scratch.build(std::slice::from_ref(&source), true))
+            .and_then(|build| {
+                let run = if build.success {
+                    Some(scratch.run(&[], None)?)
+                } else {
+                    None
+                };
+                Ok((build, run))
+            });
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    /// Run the last built executable with `args`, feeding it the contents
+    /// of `stdin` when given
+    pub fn run(&self, args: &[String], stdin: Option<&Path>) -> Result<RunOutput> {
         let exe_path = self
             .last_exe
             .as_ref()
@@ -223,16 +694,63 @@ impl Pipeline {
             anyhow::bail!("Executable path contains invalid characters");
         }
 
+        let input = match stdin {
+            Some(path) => Some((
+                path,
+                std::fs::read(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+            )),
+            None => None,
+        };
+
         // Use 'script' command to run wine in a PTY for proper console I/O
         // Quote the paths to handle spaces safely
-        let result = Command::new("script")
+        let mut command_line = format!("'{}' '{}'", wine_path_str, exe_path_str);
+        for arg in args {
+            command_line.push(' ');
+            command_line.push_str(&sh_quote(arg));
+        }
+        let mut script_cmd = Command::new("script");
+        script_cmd
             .arg("-q") // quiet
+            .arg("-e") // exit with the program's status
             .arg("-c") // command
-            .arg(format!("'{}' '{}'", wine_path_str, exe_path_str))
+            .arg(command_line)
             .arg(&tmp_file)
-            .current_dir(&self.project_dir)
-            .output()
-            .context("Failed to execute wine via script")?;
+            .current_dir(&self.project_dir);
+        let mut commands = vec![format_command(&script_cmd)];
+
+        // script passes its stdin on to the program as typed console input
+        let result = match input {
+            Some((path, bytes)) => {
+                commands[0].push_str(&format!(" < {}", sh_quote(&path.to_string_lossy())));
+                let mut child = script_cmd
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped())
+                    .stderr(std::process::Stdio::piped())
+                    .spawn()
+                    .context("Failed to execute wine via script")?;
+                // Written from another thread while the output is read, so
+                // neither side fills its pipe waiting for the other. A
+                // program that exits without reading it all closes the pipe.
+                let writer = child.stdin.take().map(|mut pipe| {
+                    std::thread::spawn(move || {
+                        use std::io::Write;
+                        let _ = pipe.write_all(&bytes);
+                    })
+                });
+                let output = child
+                    .wait_with_output()
+                    .context("Failed to execute wine via script")?;
+                if let Some(writer) = writer {
+                    let _ = writer.join();
+                }
+                output
+            }
+            None => script_cmd
+                .output()
+                .context("Failed to execute wine via script")?,
+        };
 
         // Read output from temp file
         let raw_output = std::fs::read_to_string(&tmp_file).unwrap_or_default();
@@ -251,10 +769,182 @@ impl Pipeline {
             .filter(|c| !c.is_control() || *c == '\n')
             .collect();
 
+        // The status is script's own, which reports the program's fate in
+        // its exit code
+        let exit_code = result.status.code().unwrap_or(-1);
         Ok(RunOutput {
-            exit_code: result.status.code().unwrap_or(-1),
+            exit_code,
+            signal: exit_signal(&result.status).or_else(|| code_signal(exit_code)),
             stdout: stdout.trim().to_string(),
             stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+            commands,
+        })
+    }
+
+    /// Run a `:!` shell command line in the project directory
+    pub fn shell(&self, command_line: &str) -> Result<RunOutput> {
+        let mut shell_cmd = Command::new("sh");
+        shell_cmd
+            .arg("-c")
+            .arg(command_line)
+            .current_dir(&self.project_dir);
+        let commands = vec![format_command(&shell_cmd)];
+
+        let result = shell_cmd.output().context("Failed to start sh")?;
+        Ok(RunOutput {
+            exit_code: result.status.code().unwrap_or(-1),
+            signal: exit_signal(&result.status),
+            stdout: String::from_utf8_lossy(&result.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&result.stderr).to_string(),
+            commands,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_build_cache_key_tracks_source_and_includes() {
+        let dir = TempDir::new("cache");
+        let source = dir.join("main.asm");
+        let exe = dir.join("main.exe");
+        std::fs::write(
+            &source,
+            "INCLUDE macros.inc\nmain PROC\nmain ENDP\nEND main\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("macros.inc"), "; v1\n").unwrap();
+
+        let pipeline = Pipeline::new(&Config::default(), &ProjectConfig::default(), &dir);
+        let sources = [source.clone()];
+        let key = pipeline.build_key(&sources).unwrap().unwrap();
+        assert_eq!(pipeline.build_key(&sources).unwrap().unwrap(), key);
+        assert!(!pipeline.up_to_date(&key, &sources, &exe));
+
+        std::fs::write(&exe, "MZ").unwrap();
+        std::fs::create_dir_all(dir.join(".masmide")).unwrap();
+        std::fs::write(pipeline.cache_path(), &key).unwrap();
+        assert!(pipeline.up_to_date(&key, &sources, &exe));
+
+        // Editing an included file changes the key
+        std::fs::write(dir.join("macros.inc"), "; v2\n").unwrap();
+        let changed = pipeline.build_key(&sources).unwrap().unwrap();
+        assert_ne!(changed, key);
+        assert!(!pipeline.up_to_date(&changed, &sources, &exe));
+
+        // So does linking in another source
+        let io = dir.join("io.asm");
+        std::fs::write(&io, "PrintIt PROC\nPrintIt ENDP\nEND\n").unwrap();
+        let both = [source.clone(), io];
+        assert_ne!(pipeline.build_key(&both).unwrap().unwrap(), changed);
+    }
+
+    #[test]
+    fn test_build_cache_key_follows_nested_includes() {
+        let dir = TempDir::new("nested");
+        let inc_dir = dir.join("inc");
+        std::fs::create_dir_all(&inc_dir).unwrap();
+        let source = dir.join("main.asm");
+        std::fs::write(&source, "INCLUDE macros.inc\nEND\n").unwrap();
+        // macros.inc and util.inc include each other
+        std::fs::write(dir.join("macros.inc"), "INCLUDE util.inc\n").unwrap();
+        std::fs::write(dir.join("util.inc"), "INCLUDE macros.inc\n; v1\n").unwrap();
+
+        let mut pipeline = Pipeline::new(&Config::default(), &ProjectConfig::default(), &dir);
+        let sources = [source.clone()];
+        let key = pipeline.build_key(&sources).unwrap().unwrap();
+
+        // Editing a file that's only included by an include changes the key
+        std::fs::write(dir.join("util.inc"), "INCLUDE macros.inc\n; v2\n").unwrap();
+        let changed = pipeline.build_key(&sources).unwrap().unwrap();
+        assert_ne!(changed, key);
+
+        // Includes are also found in the Irvine include directory
+        std::fs::write(&source, "INCLUDE Irvine32.inc\nEND\n").unwrap();
+        assert_eq!(pipeline.build_key(&sources).unwrap(), None);
+        pipeline.irvine_inc_path = inc_dir.clone();
+        std::fs::write(inc_dir.join("Irvine32.inc"), "; v1\n").unwrap();
+        let irvine = pipeline.build_key(&sources).unwrap().unwrap();
+        std::fs::write(inc_dir.join("Irvine32.inc"), "; v2\n").unwrap();
+        assert_ne!(pipeline.build_key(&sources).unwrap().unwrap(), irvine);
+    }
+
+    #[test]
+    fn test_termination_names_the_signal() {
+        let mut output = RunOutput {
+            exit_code: -1,
+            signal: Some(11),
+            stdout: String::new(),
+            stderr: String::new(),
+            commands: Vec::new(),
+        };
+        assert_eq!(
+            output.termination().as_deref(),
+            Some("Terminated by SIGSEGV (11)")
+        );
+        output.signal = Some(40);
+        assert_eq!(
+            output.termination().as_deref(),
+            Some("Terminated by signal 40")
+        );
+        output.signal = None;
+        assert_eq!(output.termination(), None);
+
+        output.exit_code = 0xC000_0005_u32 as i32;
+        assert_eq!(
+            output.termination().as_deref(),
+            Some("Terminated by EXCEPTION_ACCESS_VIOLATION (0xC0000005)")
+        );
+    }
+
+    #[test]
+    fn test_run_passes_args_and_stdin() {
+        if Command::new("script").arg("-V").output().is_err() {
+            return;
+        }
+        let dir = TempDir::new("run");
+        // Stands in for wine running the program: prints its arguments and
+        // a line of input, then more output than a pipe holds
+        let program = dir.join("program.sh");
+        std::fs::write(
+            &program,
+            "printf '[%s]' \"$@\"; echo; read line; echo \"got $line\"; seq 100000 >/dev/tty; exit 3\n",
+        )
+        .unwrap();
+        let input = dir.join("input.txt");
+        let mut canned = String::from("hello\n");
+        canned.push_str(&"x".repeat(200_000));
+        std::fs::write(&input, canned).unwrap();
+
+        let mut pipeline = Pipeline::new(&Config::default(), &ProjectConfig::default(), &dir);
+        pipeline.wine_path = PathBuf::from("/bin/sh");
+        pipeline.last_exe = Some(program);
+        let args = [String::from("two words"), String::from("it's")];
+        let output = pipeline.run(&args, Some(&input)).unwrap();
+        assert!(
+            output.stdout.contains("[two words][it's]"),
+            "{}",
+            output.stdout
+        );
+        assert!(output.stdout.contains("got hello"));
+        assert_eq!(output.exit_code, 3);
+        assert!(output.commands[0].ends_with(&format!(" < '{}'", input.display())));
+    }
+
+    #[test]
+    fn test_exit_code_decodes_the_signal() {
+        assert_eq!(code_signal(139), Some(11));
+        assert_eq!(code_signal(130), Some(2));
+        assert_eq!(code_signal(0), None);
+        assert_eq!(code_signal(1), None);
+        assert_eq!(code_signal(128), None);
+        assert_eq!(
+            exception_name(0xC000_0094_u32 as i32),
+            Some("EXCEPTION_INT_DIVIDE_BY_ZERO")
+        );
+        assert_eq!(exception_name(5), None);
+    }
+}
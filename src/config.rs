@@ -1,10 +1,12 @@
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::theme::Theme;
+use crate::ui::editor::DEFAULT_MATCH_PAIRS;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -12,6 +14,14 @@ pub struct Config {
     pub toolchain: ToolchainConfig,
     pub editor: EditorConfig,
     pub layout: LayoutConfig,
+    pub file_tree: FileTreeConfig,
+    pub ui: UiConfig,
+    pub lint: LintConfig,
+    /// User `:` commands, e.g. `asmrun = "w | !make && ./run.sh"`.
+    /// Built-in commands are separated by `|`; a `!` part runs the rest of
+    /// the line in the shell. `$*` stands for the arguments, which are
+    /// otherwise appended.
+    pub commands: BTreeMap<String, String>,
     pub theme_name: String,
     #[serde(skip)]
     pub theme: Theme,
@@ -21,20 +31,33 @@ pub struct Config {
 #[serde(default)]
 pub struct ToolchainConfig {
     pub jwasm_path: PathBuf,
+    pub uasm_path: PathBuf,
+    /// `ml.exe`, run under Wine when a project uses the `ml` assembler
+    pub ml_path: PathBuf,
     pub linker_path: PathBuf,
     pub wine_path: PathBuf,
     pub irvine_lib_path: PathBuf,
     pub irvine_inc_path: PathBuf,
+    /// Program `:scratch` wraps selections in; `{{snippet}}` marks where
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scratch_template: Option<PathBuf>,
+    /// Building a file with no `main PROC`/`END main` asks for the project
+    /// entry file when the configured one is missing, instead of failing
+    pub prompt_entry_file: bool,
 }
 
 impl Default for ToolchainConfig {
     fn default() -> Self {
         Self {
             jwasm_path: PathBuf::from("jwasm"),
+            uasm_path: PathBuf::from("uasm"),
+            ml_path: PathBuf::from("ml.exe"),
             linker_path: PathBuf::from("i686-w64-mingw32-ld"),
             wine_path: PathBuf::from("wine"),
             irvine_lib_path: PathBuf::from("/usr/local/lib/irvine"),
             irvine_inc_path: PathBuf::from("/usr/local/include/irvine"),
+            scratch_template: None,
+            prompt_entry_file: true,
         }
     }
 }
@@ -43,11 +66,58 @@ impl Default for ToolchainConfig {
 #[serde(default)]
 pub struct EditorConfig {
     pub tab_size: usize,
+    /// Tab inserts spaces rather than a literal tab character
+    #[serde(alias = "expand_tab")]
     pub insert_spaces: bool,
+    /// In leading whitespace, Tab indents to the next multiple of `tab_size`
+    pub smart_tab: bool,
     pub auto_indent: bool,
+    /// Enter after `PROC`/`MACRO`/`STRUCT`/`IF` inserts the matching closer
+    pub auto_close_blocks: bool,
+    /// Add the colon a code label at column 0 is missing (when an
+    /// instruction follows it, or on Enter) and align that instruction
+    pub auto_label: bool,
+    /// Column `auto_label` moves the instruction after a label to
+    pub instruction_column: usize,
+    /// Typing `;` after code pads the line so the comment starts at
+    /// `comment_column`
+    pub align_comments: bool,
+    pub comment_column: usize,
+    /// Shortest run of comment-only lines `zC` folds away
+    pub comment_fold_min_lines: usize,
+    /// Bracket pairs `%` jumps between, written as consecutive open/close
+    /// characters. `<>` only counts around text-macro arguments.
+    pub match_pairs: String,
+    /// Column `:reflow` wraps comment blocks at
+    pub textwidth: usize,
+    /// Lines longer than this many bytes are drawn without syntax
+    /// highlighting so a minified or generated file stays responsive
+    pub long_line_threshold: usize,
+    /// Lines wider than the editor continue on the next screen row
+    /// instead of being cut off
+    pub soft_wrap: bool,
+    /// Shade the row the cursor is on with the theme's `cursor_line`
+    pub cursor_line: bool,
+    /// Color ( ) and [ ] by nesting depth from the theme's rainbow palette
+    pub rainbow_brackets: bool,
+    /// What the gutter numbers count: lines from the top of the file or
+    /// from the cursor
+    pub line_number_mode: LineNumberMode,
+    /// Whether `/` and `:s` tell upper and lower case apart
+    pub search_case: SearchCase,
+    /// Longest description `:annotate` appends as a comment, in characters
+    pub doc_comment_length: usize,
+    /// `gc` puts the commented-out copy below the line instead of above,
+    /// leaving the cursor on the original
+    pub comment_copy_below: bool,
     pub show_line_numbers: bool,
     pub autosave: bool,
     pub autosave_interval_secs: u64,
+    /// Reopen files at the cursor position and jump list of the last session
+    pub restore_file_state: bool,
+    /// Ask before a single command deletes more than `confirm_delete_threshold` lines
+    pub confirm_large_deletes: bool,
+    pub confirm_delete_threshold: usize,
 }
 
 impl Default for EditorConfig {
@@ -55,10 +125,30 @@ impl Default for EditorConfig {
         Self {
             tab_size: 4,
             insert_spaces: true,
+            smart_tab: true,
             auto_indent: true,
+            auto_close_blocks: false,
+            auto_label: false,
+            instruction_column: 8,
+            align_comments: false,
+            comment_column: 40,
+            comment_fold_min_lines: 3,
+            match_pairs: String::from(DEFAULT_MATCH_PAIRS),
+            textwidth: 80,
+            long_line_threshold: 2000,
+            soft_wrap: false,
+            cursor_line: true,
+            rainbow_brackets: false,
+            line_number_mode: LineNumberMode::default(),
+            search_case: SearchCase::default(),
+            doc_comment_length: 60,
+            comment_copy_below: false,
             show_line_numbers: true,
             autosave: true,
             autosave_interval_secs: 30,
+            restore_file_state: true,
+            confirm_large_deletes: false,
+            confirm_delete_threshold: 10,
         }
     }
 }
@@ -74,25 +164,206 @@ pub struct LayoutConfig {
     pub output_max_height: u16,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileTreeConfig {
+    /// Show files in a single preview buffer as the selection moves with
+    /// `j`/`k`, instead of only opening them on Enter/`l`
+    pub open_on_navigate: bool,
+}
+
+/// Checks run on the source before each build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Warn about register operands of different sizes, as in `mov al, eax`
+    pub size_mismatch: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            size_mismatch: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Prefix output panel lines with time elapsed since the build started
+    pub output_timestamps: bool,
+    /// Prefix output panel lines with a level tag ([BUILD], [RUN], [ERR])
+    pub output_level_tags: bool,
+    /// Run in the terminal's alternate screen. When disabled the final
+    /// output panel contents stay in the scrollback after quitting.
+    pub alternate_screen: bool,
+    /// Echo the assembler/linker/run command lines into the output panel
+    pub echo_build_commands: bool,
+    /// After a failed build or check, scroll the output panel to the first
+    /// error instead of the bottom
+    pub scroll_to_first_error: bool,
+    /// Seconds before a status message gives way to the key hint.
+    /// Errors stay until replaced; 0 keeps every message.
+    pub status_timeout_secs: u64,
+    /// How file paths are shown in the editor title and tabs
+    pub title_path: TitlePath,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumberMode {
+    #[default]
+    Absolute,
+    /// Distance from the cursor line, as a count for `5j` or `3dd`
+    Relative,
+    /// Relative, except the cursor line shows its own number
+    Hybrid,
+}
+
+impl LineNumberMode {
+    /// Number the gutter shows for 0-based `line`
+    pub fn number(self, line: usize, cursor: usize) -> usize {
+        match self {
+            LineNumberMode::Absolute => line + 1,
+            LineNumberMode::Hybrid if line == cursor => line + 1,
+            LineNumberMode::Relative | LineNumberMode::Hybrid => line.abs_diff(cursor),
+        }
+    }
+
+    /// `:set rnu` / `:set nornu`. Turning relative numbers on keeps
+    /// hybrid numbering if it is already in use.
+    pub fn set_relative(&mut self, on: bool) {
+        *self = match (on, *self) {
+            (false, _) => LineNumberMode::Absolute,
+            (true, LineNumberMode::Absolute) => LineNumberMode::Relative,
+            (true, mode) => mode,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchCase {
+    Insensitive,
+    Sensitive,
+    /// Case-sensitive only when the query has an uppercase letter
+    #[default]
+    Smart,
+}
+
+impl SearchCase {
+    /// Whether a search for `query` ignores case
+    pub fn ignores_case(self, query: &str) -> bool {
+        match self {
+            SearchCase::Insensitive => true,
+            SearchCase::Sensitive => false,
+            SearchCase::Smart => !query.chars().any(char::is_uppercase),
+        }
+    }
+
+    /// `:set ignorecase` / `:set noignorecase`. Turning it on keeps smart
+    /// case if it is already in use.
+    pub fn set_ignore_case(&mut self, on: bool) {
+        *self = match (on, *self) {
+            (false, _) => SearchCase::Sensitive,
+            (true, SearchCase::Sensitive) => SearchCase::Insensitive,
+            (true, mode) => mode,
+        };
+    }
+
+    /// `:set smartcase` / `:set nosmartcase`, which falls back to ignoring
+    /// case
+    pub fn set_smart_case(&mut self, on: bool) {
+        *self = match (on, *self) {
+            (true, _) => SearchCase::Smart,
+            (false, SearchCase::Smart) => SearchCase::Insensitive,
+            (false, mode) => mode,
+        };
+    }
+
+    /// The mode after this one, for the search bar toggle
+    pub fn next(self) -> Self {
+        match self {
+            SearchCase::Smart => SearchCase::Sensitive,
+            SearchCase::Sensitive => SearchCase::Insensitive,
+            SearchCase::Insensitive => SearchCase::Smart,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitlePath {
+    /// Just the filename, with parent directories added when names clash
+    #[default]
+    Filename,
+    /// Path relative to the project directory
+    Relative,
+    Absolute,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            output_timestamps: false,
+            output_level_tags: false,
+            alternate_screen: true,
+            echo_build_commands: false,
+            scroll_to_first_error: false,
+            status_timeout_secs: 5,
+            title_path: TitlePath::default(),
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             toolchain: ToolchainConfig {
                 jwasm_path: PathBuf::from("jwasm"),
+                uasm_path: PathBuf::from("uasm"),
+                ml_path: PathBuf::from("ml.exe"),
                 linker_path: PathBuf::from("i686-w64-mingw32-ld"),
                 wine_path: PathBuf::from("wine"),
                 irvine_lib_path: PathBuf::from("/usr/local/lib/irvine"),
                 irvine_inc_path: PathBuf::from("/usr/local/include/irvine"),
+                scratch_template: None,
+                prompt_entry_file: true,
             },
             editor: EditorConfig {
                 tab_size: 4,
                 insert_spaces: true,
+                smart_tab: true,
                 auto_indent: true,
+                auto_close_blocks: false,
+                auto_label: false,
+                instruction_column: 8,
+                align_comments: false,
+                comment_column: 40,
+                comment_fold_min_lines: 3,
+                match_pairs: String::from(DEFAULT_MATCH_PAIRS),
+                textwidth: 80,
+                long_line_threshold: 2000,
+                soft_wrap: false,
+                cursor_line: true,
+                rainbow_brackets: false,
+                line_number_mode: LineNumberMode::default(),
+                search_case: SearchCase::default(),
+                doc_comment_length: 60,
+                comment_copy_below: false,
                 show_line_numbers: true,
                 autosave: true,
                 autosave_interval_secs: 30,
+                restore_file_state: true,
+                confirm_large_deletes: false,
+                confirm_delete_threshold: 10,
             },
             layout: LayoutConfig::default(),
+            file_tree: FileTreeConfig::default(),
+            ui: UiConfig::default(),
+            lint: LintConfig::default(),
+            commands: BTreeMap::new(),
             theme_name: String::from("gruvbox"),
             theme: Theme::gruvbox(),
         }
@@ -155,14 +426,47 @@ impl Config {
     }
 }
 
+/// Assembler a project builds with. Each has its own command line and
+/// diagnostic format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Assembler {
+    #[default]
+    Jwasm,
+    Uasm,
+    Ml,
+}
+
+impl Assembler {
+    pub fn name(self) -> &'static str {
+        match self {
+            Assembler::Jwasm => "JWasm",
+            Assembler::Uasm => "UASM",
+            Assembler::Ml => "ML",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectConfig {
     pub name: String,
     pub entry_file: PathBuf,
     pub output_name: String,
+    #[serde(default)]
+    pub assembler: Assembler,
     pub include_paths: Vec<PathBuf>,
     pub lib_paths: Vec<PathBuf>,
     pub libs: Vec<String>,
+    /// Sources assembled and linked together into `output_name`. Empty
+    /// builds just the current file (or `entry_file`).
+    #[serde(default)]
+    pub targets: Vec<PathBuf>,
+    /// Arguments the program is run with
+    #[serde(default)]
+    pub run_args: Vec<String>,
+    /// File whose contents are typed into the program when it runs
+    #[serde(default)]
+    pub run_stdin_file: Option<PathBuf>,
 }
 
 impl Default for ProjectConfig {
@@ -171,6 +475,7 @@ impl Default for ProjectConfig {
             name: String::from("untitled"),
             entry_file: PathBuf::from("main.asm"),
             output_name: String::from("main.exe"),
+            assembler: Assembler::default(),
             include_paths: vec![],
             lib_paths: vec![],
             libs: vec![
@@ -178,6 +483,9 @@ impl Default for ProjectConfig {
                 String::from("kernel32"),
                 String::from("user32"),
             ],
+            targets: vec![],
+            run_args: vec![],
+            run_stdin_file: None,
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::config::Assembler;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,6 +51,15 @@ impl Diagnostic {
     }
 }
 
+/// Parse the output of the project's assembler for errors and warnings
+pub fn parse_output(assembler: Assembler, output: &str, project_dir: &Path) -> Vec<Diagnostic> {
+    match assembler {
+        Assembler::Jwasm => parse_jwasm_output(output, project_dir),
+        Assembler::Uasm => parse_uasm_output(output, project_dir),
+        Assembler::Ml => parse_ml_output(output, project_dir),
+    }
+}
+
 /// Parse JWasm assembler output for errors and warnings.
 ///
 /// JWasm error format examples:
@@ -76,6 +86,51 @@ pub fn parse_jwasm_output(output: &str, project_dir: &Path) -> Vec<Diagnostic> {
     diagnostics
 }
 
+/// Parse UASM output. UASM grew out of JWasm and keeps its layout, but
+/// capitalizes the severity and reports where a macro or INCLUDE was
+/// entered on lines of their own:
+/// - `main.asm(15) : Error A2209: Syntax error: mov`
+/// - `main.asm(4) : Warning A4012: line too long`
+/// - `macros.inc(3): Included by` (context only, skipped)
+pub fn parse_uasm_output(output: &str, project_dir: &Path) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter(|line| {
+            let lower = line.to_lowercase();
+            !lower.contains(": included by") && !lower.contains(": macro called from")
+        })
+        .filter_map(|line| {
+            parse_jwasm_line(line, project_dir)
+                .or_else(|| parse_jwasm_fatal_error(line, project_dir))
+        })
+        .collect()
+}
+
+/// Parse ML (MASM) output, produced under Wine, so file names are Windows
+/// paths that are mapped back to the Unix ones:
+/// - `Z:\\home\\me\\lab\\main.asm(15) : error A2008:syntax error : mov`
+/// - `MASM : fatal error A1000:cannot open file : main.asm`
+pub fn parse_ml_output(output: &str, project_dir: &Path) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix("MASM :") {
+                return parse_jwasm_fatal_error(rest, project_dir);
+            }
+            let line = match line.split_once('(') {
+                Some((file, rest)) => {
+                    let file = file.strip_prefix("Z:").unwrap_or(file).replace('\\', "/");
+                    format!("{}({}", file, rest)
+                }
+                None => line.to_string(),
+            };
+            parse_jwasm_line(&line, project_dir)
+                .or_else(|| parse_jwasm_fatal_error(&line, project_dir))
+        })
+        .collect()
+}
+
 /// Parse fatal/file-level errors without line numbers
 /// Format: `Fatal error A1106: message` or `error A1000: message`
 fn parse_jwasm_fatal_error(line: &str, project_dir: &Path) -> Option<Diagnostic> {
@@ -210,20 +265,114 @@ fn parse_jwasm_line(line: &str, project_dir: &Path) -> Option<Diagnostic> {
     Some(diag)
 }
 
+/// Parse the linker's unresolved-symbol errors. `ld` names the missing
+/// symbol but no source line:
+/// - `main.obj:(.text+0x1b): undefined reference to `WriteStrin@0'`
+///
+/// so each is placed on the first line of `sources` (path and lines of
+/// the open buffers) that uses the symbol, preferring a use over an
+/// `EXTERN`/`PROTO` declaration. Symbols none of them mention are left to
+/// the output panel.
+pub fn parse_link_output(output: &str, sources: &[(&Path, &[String])]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in output.lines() {
+        let Some(symbol) = undefined_symbol(line) else {
+            continue;
+        };
+        let find = |declaration: bool| {
+            sources.iter().find_map(|(path, lines)| {
+                let idx = lines.iter().position(|text| {
+                    is_declaration(text) == declaration && references(text, symbol)
+                })?;
+                Some((path.to_path_buf(), idx + 1))
+            })
+        };
+        let Some((file, line_num)) = find(false).or_else(|| find(true)) else {
+            continue;
+        };
+        if diagnostics
+            .iter()
+            .any(|d| d.file == file && d.line == line_num)
+        {
+            continue;
+        }
+        diagnostics.push(Diagnostic::error(
+            file,
+            line_num,
+            format!("undefined symbol at link time: {}", symbol),
+        ));
+    }
+    diagnostics
+}
+
+/// The symbol of an `undefined reference to` line, without the `@N`
+/// stdcall suffix
+fn undefined_symbol(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("undefined reference to ")?;
+    let rest = rest.trim_start_matches(['`', '\'', '"']);
+    let symbol = &rest[..rest.find(['`', '\'', '"']).unwrap_or(rest.len())];
+    let symbol = match symbol.rsplit_once('@') {
+        Some((name, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => symbol,
+    };
+    (!symbol.is_empty()).then_some(symbol)
+}
+
+/// Whether the code of `text` uses `symbol`, ignoring case as MASM does.
+/// The leading `_` of a C-decorated name may be left off in the source.
+fn references(text: &str, symbol: &str) -> bool {
+    let code = text.split(';').next().unwrap_or("");
+    let undecorated = symbol.strip_prefix('_');
+    code.split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '@' | '?' | '$')))
+        .any(|word| {
+            word.eq_ignore_ascii_case(symbol)
+                || undecorated.is_some_and(|name| word.eq_ignore_ascii_case(name))
+        })
+}
+
+/// `EXTERN name:PROC` or `name PROTO ...`
+fn is_declaration(text: &str) -> bool {
+    let mut words = text.split_whitespace();
+    let first = words.next().unwrap_or("");
+    ["extern", "extrn", "externdef"]
+        .iter()
+        .any(|kw| first.eq_ignore_ascii_case(kw))
+        || words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("proto"))
+}
+
 /// Get diagnostics for a specific file
 pub fn diagnostics_for_file<'a>(diagnostics: &'a [Diagnostic], file: &Path) -> Vec<&'a Diagnostic> {
     diagnostics.iter().filter(|d| d.file == file).collect()
 }
 
-/// Get diagnostic for a specific line in a file (if any)
-pub fn diagnostic_for_line<'a>(
+/// Get every diagnostic for a specific line in a file, in report order
+pub fn diagnostics_for_line<'a>(
     diagnostics: &'a [Diagnostic],
     file: &Path,
     line: usize,
-) -> Option<&'a Diagnostic> {
+) -> Vec<&'a Diagnostic> {
     diagnostics
         .iter()
-        .find(|d| d.file == file && d.line == line)
+        .filter(|d| d.file == file && d.line == line)
+        .collect()
+}
+
+/// The most severe of `diagnostics`, used to color a line's gutter mark
+pub fn worst_severity<'a>(
+    diagnostics: impl IntoIterator<Item = &'a Diagnostic>,
+) -> Option<DiagnosticSeverity> {
+    diagnostics
+        .into_iter()
+        .map(|d| d.severity)
+        .reduce(|worst, severity| {
+            if severity == DiagnosticSeverity::Error {
+                severity
+            } else {
+                worst
+            }
+        })
 }
 
 /// Count errors and warnings
@@ -298,6 +447,56 @@ main.asm(15): warning A4031: constant too large"#;
         assert_eq!(d.line, 0); // File-level error has no line
     }
 
+    #[test]
+    fn test_parse_uasm_and_ml_output() {
+        let project_dir = PathBuf::from("/project");
+        let uasm = "main.asm(15) : Error A2209: Syntax error: mov\n\
+                    macros.inc(3): Included by\n\
+                    main.asm(4) : Warning A4012: line too long";
+        let diagnostics = parse_output(Assembler::Uasm, uasm, &project_dir);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("A2209"));
+        assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+
+        let ml = "Z:\\project\\main.asm(15) : error A2008:syntax error : mov\n\
+                  MASM : fatal error A1000:cannot open file : lib.asm";
+        let diagnostics = parse_output(Assembler::Ml, ml, &project_dir);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, PathBuf::from("/project/main.asm"));
+        assert_eq!(diagnostics[0].line, 15);
+        assert_eq!(diagnostics[1].code.as_deref(), Some("A1000"));
+        assert_eq!(diagnostics[1].line, 0);
+    }
+
+    #[test]
+    fn test_parse_link_output_finds_the_reference() {
+        let main: Vec<String> = [
+            "PrintIt PROTO",
+            "main PROC",
+            "    ; call PrintIt later",
+            "    call PrintIt",
+            "    call _helper",
+            "main ENDP",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let main_path = PathBuf::from("/project/main.asm");
+        let sources = [(main_path.as_path(), main.as_slice())];
+        let output = "ld: main.obj:(.text+0x5): undefined reference to `PrintIt@0'\n\
+                      ld: main.obj:(.text+0x9): undefined reference to `PrintIt@0'\n\
+                      ld: main.obj:(.text+0xe): undefined reference to `_HELPER'\n\
+                      ld: main.obj:(.text+0x13): undefined reference to `Missing'";
+        let diagnostics = parse_link_output(output, &sources);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, main_path);
+        assert_eq!(diagnostics[0].line, 4);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+        assert!(diagnostics[0].message.ends_with("PrintIt"));
+        assert_eq!(diagnostics[1].line, 5);
+        assert!(parse_link_output("ld: cannot find -lfoo", &sources).is_empty());
+    }
+
     #[test]
     fn test_parse_stdout_error_format() {
         // JWasm sometimes writes errors to stdout with space before colon
@@ -310,4 +509,23 @@ main.asm(15): warning A4031: constant too large"#;
         assert_eq!(d.line, 15);
         assert_eq!(d.severity, DiagnosticSeverity::Error);
     }
+
+    #[test]
+    fn test_line_keeps_every_diagnostic() {
+        let file = PathBuf::from("main.asm");
+        let diagnostics = vec![
+            Diagnostic::warning(file.clone(), 4, String::from("cascading note")),
+            Diagnostic::error(file.clone(), 4, String::from("syntax error")),
+            Diagnostic::warning(file.clone(), 9, String::from("unused")),
+        ];
+        let on_line = diagnostics_for_line(&diagnostics, &file, 4);
+        assert_eq!(on_line.len(), 2);
+        assert_eq!(on_line[0].message, "cascading note");
+        assert_eq!(worst_severity(on_line), Some(DiagnosticSeverity::Error));
+        assert_eq!(
+            worst_severity(&diagnostics[2..]),
+            Some(DiagnosticSeverity::Warning)
+        );
+        assert_eq!(worst_severity(&[]), None);
+    }
 }
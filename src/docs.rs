@@ -26,6 +26,28 @@ impl DocEntry {
             example,
         }
     }
+
+    /// Description cut to at most `max_len` characters at a word boundary,
+    /// ending in `...` when shortened
+    pub fn summary(&self, max_len: usize) -> String {
+        if self.description.chars().count() <= max_len {
+            return self.description.to_string();
+        }
+        let mut out = String::new();
+        for word in self.description.split_whitespace() {
+            let sep = usize::from(!out.is_empty());
+            if out.chars().count() + sep + word.chars().count() + 3 > max_len {
+                break;
+            }
+            if sep == 1 {
+                out.push(' ');
+            }
+            out.push_str(word);
+        }
+        out.truncate(out.trim_end_matches(['.', ',', ';', ':']).len());
+        out.push_str("...");
+        out
+    }
 }
 
 /// Get documentation for a symbol (instruction, register, or Irvine32 function)
@@ -34,6 +56,56 @@ pub fn get_documentation(symbol: &str) -> Option<&'static DocEntry> {
     DOCS.get(lower.as_str()).copied()
 }
 
+/// Width in bits of a general-purpose register. None for anything else,
+/// including segment registers, EIP and the flags.
+pub fn register_size(name: &str) -> Option<u32> {
+    match name.to_lowercase().as_str() {
+        "eax" | "ebx" | "ecx" | "edx" | "esi" | "edi" | "esp" | "ebp" => Some(32),
+        "ax" | "bx" | "cx" | "dx" | "si" | "di" | "sp" | "bp" => Some(16),
+        "al" | "ah" | "bl" | "bh" | "cl" | "ch" | "dl" | "dh" => Some(8),
+        _ => None,
+    }
+}
+
+/// Whether a symbol is a documented x86 instruction mnemonic
+pub fn is_instruction(symbol: &str) -> bool {
+    let lower = symbol.to_lowercase();
+    INSTRUCTION_DOCS.iter().any(|doc| doc.name == lower)
+}
+
+/// Documentation to hint at while `before_cursor`, the start of a line up
+/// to the cursor, is typing an operand list: that of the procedure after
+/// `call`/`invoke` once the name is complete, else that of the
+/// instruction. None outside an operand list or inside a comment.
+pub fn signature_at(before_cursor: &str) -> Option<&'static DocEntry> {
+    if before_cursor.contains(';') {
+        return None;
+    }
+    let mut text = before_cursor.trim_start();
+    let first = text.split_whitespace().next()?;
+    if first.ends_with(':') {
+        text = text[first.len()..].trim_start();
+    }
+    let mnemonic_len = text.find(char::is_whitespace)?;
+    let (mnemonic, operands) = text.split_at(mnemonic_len);
+    let operands = operands.trim_start();
+    if mnemonic.eq_ignore_ascii_case("call") || mnemonic.eq_ignore_ascii_case("invoke") {
+        let target_len = operands.find(|c: char| c == ',' || c.is_whitespace());
+        if let Some(doc) = target_len.and_then(|len| signature_doc(&operands[..len])) {
+            return Some(doc);
+        }
+    }
+    signature_doc(mnemonic)
+}
+
+/// An instruction or Irvine32 procedure; registers have no signature
+fn signature_doc(name: &str) -> Option<&'static DocEntry> {
+    INSTRUCTION_DOCS
+        .iter()
+        .chain(IRVINE32_DOCS)
+        .find(|doc| doc.name.eq_ignore_ascii_case(name))
+}
+
 static DOCS: LazyLock<HashMap<&'static str, &'static DocEntry>> = LazyLock::new(|| {
     let mut map = HashMap::new();
 
@@ -464,12 +536,41 @@ mod tests {
         assert!(doc.description.contains("accumulator"));
     }
 
+    #[test]
+    fn test_register_size_only_for_general_purpose_registers() {
+        assert_eq!(register_size("eax"), Some(32));
+        assert_eq!(register_size("ax"), Some(16));
+        assert_eq!(register_size("al"), Some(8));
+        assert_eq!(register_size("eip"), None);
+        assert_eq!(register_size("eflags"), None);
+    }
+
     #[test]
     fn test_get_irvine_doc() {
         let doc = get_documentation("WriteString").unwrap();
         assert!(doc.description.contains("string"));
     }
 
+    #[test]
+    fn test_summary_truncates_at_word() {
+        let doc = get_documentation("cdq").unwrap();
+        assert_eq!(doc.summary(34), "Convert Doubleword to Quadword...");
+        assert_eq!(doc.summary(500), doc.description);
+    }
+
+    #[test]
+    fn test_signature_follows_call_target() {
+        let name = |text| signature_at(text).map(|doc| doc.name);
+        assert_eq!(name("    invoke WriteString,"), Some("writestring"));
+        assert_eq!(name("done: call Crlf "), Some("crlf"));
+        assert_eq!(name("    call MyProc "), Some("call"));
+        assert_eq!(name("    invoke Write"), Some("invoke"));
+        assert_eq!(name("    mov eax, "), Some("mov"));
+        assert_eq!(name("    mov"), None);
+        assert_eq!(name("    mov eax ; copy "), None);
+        assert_eq!(name("    eax "), None);
+    }
+
     #[test]
     fn test_case_insensitive() {
         assert!(get_documentation("MOV").is_some());
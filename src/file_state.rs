@@ -0,0 +1,116 @@
+//! Per-file editor state remembered between sessions (cursor position,
+//! jump list, marks and closed folds), in the spirit of vim's viminfo.
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many files are remembered before the oldest are dropped
+const MAX_FILES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    /// Jump list entries within this file as (line, col), oldest first
+    #[serde(default)]
+    pub jumps: Vec<(usize, usize)>,
+    /// `m{a-z}` marks as (name, line, col)
+    #[serde(default)]
+    pub marks: Vec<(char, usize, usize)>,
+    /// First lines of the PROC and segment folds closed with `za`
+    #[serde(default)]
+    pub closed_folds: Vec<usize>,
+}
+
+impl FileState {
+    /// Drop whatever no longer fits a file of `line_count` lines.
+    /// Returns None when even the cursor position is stale.
+    pub fn validated(mut self, line_count: usize) -> Option<Self> {
+        if self.line >= line_count {
+            return None;
+        }
+        self.jumps.retain(|&(line, _)| line < line_count);
+        self.marks.retain(|&(_, line, _)| line < line_count);
+        self.closed_folds.retain(|&line| line < line_count);
+        Some(self)
+    }
+}
+
+/// Remembered state for recently edited files, most recent last
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileStateStore {
+    #[serde(default)]
+    pub files: Vec<FileState>,
+}
+
+impl FileStateStore {
+    pub fn load() -> Self {
+        Self::store_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::store_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string(self)?;
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&FileState> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Remember `state`, replacing any older entry for the same file
+    pub fn record(&mut self, state: FileState) {
+        self.files.retain(|f| f.path != state.path);
+        self.files.push(state);
+        if self.files.len() > MAX_FILES {
+            let excess = self.files.len() - MAX_FILES;
+            self.files.drain(..excess);
+        }
+    }
+
+    fn store_path() -> Result<PathBuf> {
+        let proj_dirs = ProjectDirs::from("com", "masmide", "masmide")
+            .context("Could not determine data directory")?;
+        Ok(proj_dirs.data_dir().join("file_state.toml"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_entries_are_discarded() {
+        let state = FileState {
+            path: PathBuf::from("/p/main.asm"),
+            line: 5,
+            col: 2,
+            jumps: vec![(1, 0), (40, 3)],
+            marks: vec![('a', 9, 0), ('b', 10, 0)],
+            closed_folds: vec![4, 12],
+        };
+        let valid = state.clone().validated(10).unwrap();
+        assert_eq!(valid.jumps, vec![(1, 0)]);
+        assert_eq!(valid.marks, vec![('a', 9, 0)]);
+        assert_eq!(valid.closed_folds, vec![4]);
+        assert!(state.validated(3).is_none());
+
+        let mut store = FileStateStore::default();
+        store.record(valid.clone());
+        store.record(FileState { line: 7, ..valid });
+        assert_eq!(store.files.len(), 1);
+        assert_eq!(store.get(Path::new("/p/main.asm")).unwrap().line, 7);
+    }
+}
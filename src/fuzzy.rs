@@ -0,0 +1,53 @@
+//! Fuzzy matching shared by completion and the pickers
+
+/// Score `candidate` against `query` as a case-insensitive subsequence,
+/// with the character indices of `candidate` each query character matched
+/// (at its first possible place). Runs of consecutive characters and
+/// matches at the start of a word (after `_`, `.` or `@`, or a camelCase
+/// boundary) rank higher; skipped characters cost a little. None when the
+/// query doesn't match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut positions = Vec::with_capacity(query.len());
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for q in query.chars() {
+        let found = (pos..chars.len()).find(|&i| chars[i].eq_ignore_ascii_case(&q))?;
+        positions.push(found);
+        score += 1;
+        if found > 0 && prev == Some(found - 1) {
+            score += 5;
+        }
+        if found == 0
+            || matches!(chars[found - 1], '_' | '.' | '@')
+            || (chars[found].is_uppercase() && chars[found - 1].is_lowercase())
+        {
+            score += 3;
+        }
+        if prev.is_some() {
+            score -= (found - pos) as i64;
+        }
+        prev = Some(found);
+        pos = found + 1;
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_ranks_word_starts() {
+        assert!(fuzzy_score("pn", "PrintNumber").is_some());
+        assert!(fuzzy_score("xyz", "PrintNumber").is_none());
+        assert!(
+            fuzzy_score("rd", "read_dec").unwrap().0 > fuzzy_score("rd", "buffer_end").unwrap().0
+        );
+        let positions = |query, candidate| fuzzy_score(query, candidate).map(|(_, p)| p);
+        assert_eq!(positions("wstr", "WriteString"), Some(vec![0, 5, 6, 7]));
+        assert_eq!(positions("mo", "mov"), Some(vec![0, 1]));
+        assert_eq!(positions("om", "mov"), None);
+    }
+}
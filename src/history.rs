@@ -0,0 +1,175 @@
+//! Recall of earlier `:` command lines and `/` searches with Up and Down,
+//! each kept in its own file under `.masmide` between runs.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Most entries kept in the history file
+pub const HISTORY_SIZE: usize = 200;
+
+#[derive(Debug, Default)]
+pub struct History {
+    /// File under `.masmide` the entries are kept in
+    name: &'static str,
+    /// Oldest first
+    pub entries: Vec<String>,
+    /// Entry being shown while browsing with Up/Down
+    index: Option<usize>,
+    /// What was typed before browsing started; only entries starting with
+    /// it are offered, and Down past the newest brings it back
+    prefix: String,
+}
+
+impl History {
+    /// The history `name` saved for `project_dir`, empty if there is none
+    pub fn load(project_dir: &Path, name: &'static str) -> Self {
+        let mut history = Self {
+            name,
+            ..Self::default()
+        };
+        history.entries = fs::read_to_string(history.path(project_dir))
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        history
+    }
+
+    /// Write the newest `HISTORY_SIZE` entries, if there are any
+    pub fn save(&self, project_dir: &Path) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let path = self.path(project_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let start = self.entries.len().saturating_sub(HISTORY_SIZE);
+        let mut content = self.entries[start..].join("\n");
+        content.push('\n');
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Record a line that was run, unless it repeats the newest entry
+    pub fn push(&mut self, line: &str) {
+        self.reset();
+        if line.is_empty() || line.contains('\n') {
+            return;
+        }
+        if self.entries.last().map(String::as_str) != Some(line) {
+            self.entries.push(line.to_string());
+        }
+    }
+
+    /// Stop browsing, so the next Up starts again from the newest entry
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.prefix.clear();
+    }
+
+    /// The next older entry starting with what was typed (`current` when
+    /// browsing starts), or None at the oldest
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        let end = match self.index {
+            Some(idx) => idx,
+            None => {
+                self.prefix = current.to_string();
+                self.entries.len()
+            }
+        };
+        let idx = self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.starts_with(&self.prefix))?;
+        self.index = Some(idx);
+        Some(&self.entries[idx])
+    }
+
+    /// The next newer matching entry; past the newest, the typed text.
+    /// None when not browsing.
+    pub fn next(&mut self) -> Option<&str> {
+        let start = self.index? + 1;
+        match self.entries[start..]
+            .iter()
+            .position(|entry| entry.starts_with(&self.prefix))
+        {
+            Some(offset) => {
+                self.index = Some(start + offset);
+                Some(&self.entries[start + offset])
+            }
+            None => {
+                self.index = None;
+                Some(&self.prefix)
+            }
+        }
+    }
+
+    fn path(&self, project_dir: &Path) -> PathBuf {
+        project_dir.join(".masmide").join(self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_history_filters_by_prefix_and_round_trips() {
+        let mut history = History {
+            name: "test_history",
+            ..History::default()
+        };
+        for line in ["w", "set ts=8", "set ts=8", "proc main", "set rnu"] {
+            history.push(line);
+        }
+        assert_eq!(history.entries, ["w", "set ts=8", "proc main", "set rnu"]);
+
+        assert_eq!(history.prev("set"), Some("set rnu"));
+        assert_eq!(history.prev("ignored"), Some("set ts=8"));
+        assert_eq!(history.prev(""), None);
+        assert_eq!(history.next(), Some("set rnu"));
+        assert_eq!(history.next(), Some("set"));
+        assert_eq!(history.next(), None);
+
+        // Typing resets browsing to the newest entry
+        history.prev("");
+        history.reset();
+        assert_eq!(history.prev(""), Some("set rnu"));
+
+        let dir = TempDir::new("history");
+        history.save(&dir).unwrap();
+        assert_eq!(History::load(&dir, "test_history").entries, history.entries);
+    }
+
+    #[test]
+    fn test_empty_command_history_is_not_written() {
+        let dir = TempDir::new("history-empty");
+        History::load(&dir, "command_history").save(&dir).unwrap();
+        assert!(!dir.join(".masmide").exists());
+
+        // A file left with a blank line by older versions loads as empty
+        fs::create_dir_all(dir.join(".masmide")).unwrap();
+        fs::write(dir.join(".masmide/command_history"), "\n").unwrap();
+        assert!(History::load(&dir, "command_history").entries.is_empty());
+    }
+
+    #[test]
+    fn test_empty_search_history_is_not_written() {
+        let dir = TempDir::new("search-empty");
+        // Enter on an empty `/` prompt records nothing
+        let mut searches = History::load(&dir, "search_history");
+        searches.push("");
+        searches.save(&dir).unwrap();
+        assert!(!dir.join(".masmide").exists());
+
+        fs::create_dir_all(dir.join(".masmide")).unwrap();
+        fs::write(dir.join(".masmide/search_history"), "\nmov\n\n").unwrap();
+        assert_eq!(History::load(&dir, "search_history").entries, ["mov"]);
+    }
+}
@@ -1,13 +1,21 @@
 use crate::app::{App, FocusedPanel, Mode, PendingAction};
+use crate::ui::picker::{PickerItem, PickerState};
 use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
-use std::time::Duration;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::layout::{Margin, Position};
+use std::time::{Duration, Instant};
+
+/// Clicks closer together than this count as a double or triple click
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     None,
     Quit,
     Build,
+    Check,
     Run,
     BuildAndRun,
     Save,
@@ -25,11 +33,126 @@ pub fn handle_event(app: &mut App) -> Result<Option<Action>> {
         return Ok(Some(Action::None));
     }
 
-    if let Event::Key(key) = event::read()? {
-        return handle_key(app, key);
+    let was_insert = app.mode == Mode::Insert;
+    let undo_start = app.editor.undo_tree.current();
+    let action = match event::read()? {
+        Event::Key(key) => handle_key(app, key),
+        Event::Mouse(mouse) => {
+            handle_mouse(app, mouse);
+            Ok(Some(Action::None))
+        }
+        _ => return Ok(Some(Action::None)),
+    };
+
+    // Each insert session is one undo step, including the edit that
+    // opened it (the newline from o/O, the deletion from cc/s)
+    match (was_insert, app.mode == Mode::Insert) {
+        (false, true) => app.editor.undo_tree.begin_group(undo_start),
+        (true, false) => app.editor.undo_tree.end_group(),
+        _ => {}
+    }
+    action
+}
+
+/// Click to place the cursor, drag to select, double-click for a word and
+/// triple-click for a line. Only the editor text takes mouse input, and
+/// not while a popup or panel has the keyboard.
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    let popup_open = app.show_help
+        || app.show_undo_tree
+        || app.show_quickfix
+        || app.show_diagnostics_panel
+        || app.output_only_mode
+        || app.diff_view.is_some()
+        || app.symbol_picker.is_some()
+        || app.buffer_picker.is_some();
+    let editing_mode = matches!(
+        app.mode,
+        Mode::Normal | Mode::Insert | Mode::Visual | Mode::VisualLine | Mode::VisualBlock
+    );
+    if popup_open || !editing_mode {
+        return;
     }
 
-    Ok(Some(Action::None))
+    let text = app.editor_area.inner(Margin::new(1, 1));
+    let gutter = app.editor.gutter_width() as u16;
+    if text.height == 0 || text.width <= gutter {
+        return;
+    }
+    let screen_pos = |app: &App, column: u16, row: u16| {
+        let row = row.clamp(text.y, text.bottom() - 1) - text.y;
+        let col = column.saturating_sub(text.x + gutter);
+        app.editor.position_at_screen(row as usize, col as usize)
+    };
+
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            if !text.contains(Position::new(mouse.column, mouse.row)) {
+                return;
+            }
+            let pos = screen_pos(app, mouse.column, mouse.row);
+            let clicks = match app.last_click {
+                Some((at, last, n)) if last == pos && at.elapsed() < MULTI_CLICK_INTERVAL => {
+                    n % 3 + 1
+                }
+                _ => 1,
+            };
+            app.last_click = Some((Instant::now(), pos, clicks));
+            app.focus = FocusedPanel::Editor;
+            app.editor.clear_selection();
+
+            match clicks {
+                1 => {
+                    app.editor.set_cursor_position(pos);
+                    if app.mode != Mode::Insert {
+                        app.mode = Mode::Normal;
+                    }
+                }
+                2 => {
+                    if app.editor.select_word_at(pos) {
+                        app.mode = Mode::Visual;
+                    }
+                }
+                _ => {
+                    app.editor.select_line_at(pos.0);
+                    app.mode = Mode::VisualLine;
+                }
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if app.focus != FocusedPanel::Editor {
+                return;
+            }
+            // Dragging past the top or bottom edge scrolls a line at a time
+            let buf = &mut app.editor.buffers[app.editor.active_buffer];
+            if mouse.row < text.y {
+                buf.scroll_offset = buf.scroll_offset.saturating_sub(1);
+            } else if mouse.row >= text.bottom() && buf.scroll_offset + 1 < buf.lines.len() {
+                buf.scroll_offset += 1;
+            }
+            let pos = screen_pos(app, mouse.column, mouse.row);
+            // The anchor is where the button went down, i.e. the cursor
+            if !matches!(
+                app.mode,
+                Mode::Visual | Mode::VisualLine | Mode::VisualBlock
+            ) {
+                app.editor.start_visual_selection();
+                app.mode = Mode::Visual;
+            }
+            app.editor.set_cursor_position(pos);
+            app.editor.update_visual_selection();
+        }
+        // A drag that ended where it began selects nothing
+        MouseEventKind::Up(MouseButton::Left) if app.mode == Mode::Visual => {
+            if let Some((start, end)) = app.editor.get_selection_range() {
+                if start == end {
+                    app.editor.clear_selection();
+                    app.mode = Mode::Normal;
+                }
+            }
+        }
+        _ => {}
+    }
 }
 
 fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
@@ -50,7 +173,7 @@ fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
                 app.help_scroll = 0;
             }
             KeyCode::Char('G') | KeyCode::End => {
-                app.help_scroll = crate::ui::help::total_lines();
+                app.help_scroll = crate::ui::help::total_lines(app.key_context());
             }
             KeyCode::PageDown => {
                 app.help_scroll = app.help_scroll.saturating_add(10);
@@ -63,12 +186,144 @@ fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         return Ok(Some(Action::None));
     }
 
+    // Undo tree panel is modal as well
+    if app.show_undo_tree {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.show_undo_tree = false;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                let rows = app.editor.undo_tree.branch_rows().len();
+                if app.undo_tree_selected + 1 < rows {
+                    app.undo_tree_selected += 1;
+                }
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                app.undo_tree_selected = app.undo_tree_selected.saturating_sub(1);
+            }
+            KeyCode::Enter => {
+                app.restore_undo_tree_selection();
+            }
+            _ => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    // Pickers take typed characters as their query
+    if let Some(picker) = app.symbol_picker.as_mut() {
+        match picker_key(picker, key) {
+            PickerKey::Close => app.symbol_picker = None,
+            PickerKey::Accept => app.accept_symbol_pick(),
+            PickerKey::Handled => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    if let Some(picker) = app.buffer_picker.as_mut() {
+        match picker_key(picker, key) {
+            PickerKey::Close => app.buffer_picker = None,
+            PickerKey::Accept => app.accept_buffer_pick(),
+            PickerKey::Handled => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    // Grep results list
+    if app.show_quickfix {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => app.show_quickfix = false,
+            KeyCode::Enter => {
+                app.show_quickfix = false;
+                app.jump_to_quickfix();
+            }
+            KeyCode::Down | KeyCode::Char('j') => app.quickfix.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => app.quickfix.select_prev(),
+            KeyCode::Char('g') => app.quickfix.current = 0,
+            KeyCode::Char('G') => {
+                app.quickfix.current = app.quickfix.entries.len().saturating_sub(1)
+            }
+            _ => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    // Diagnostics list; rebuilding keeps it open on the fresh results
+    if app.show_diagnostics_panel {
+        let last = app.diagnostics.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => app.show_diagnostics_panel = false,
+            KeyCode::Enter if !app.diagnostics.is_empty() => {
+                app.show_diagnostics_panel = false;
+                app.goto_diagnostic(app.current_diagnostic + 1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.current_diagnostic = (app.current_diagnostic + 1).min(last)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.current_diagnostic = app.current_diagnostic.saturating_sub(1)
+            }
+            KeyCode::Char('g') => app.current_diagnostic = 0,
+            KeyCode::Char('G') => app.current_diagnostic = last,
+            KeyCode::F(4) => return Ok(Some(Action::Check)),
+            KeyCode::F(6) => return Ok(Some(Action::Build)),
+            _ => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    // Side-by-side diff view replaces the editor until closed
+    if let Some(view) = app.diff_view.as_mut() {
+        if let Some(bracket) = view.pending_bracket.take() {
+            if key.code == KeyCode::Char('c') {
+                let moved = if bracket == ']' {
+                    view.next_hunk()
+                } else {
+                    view.prev_hunk()
+                };
+                if !moved {
+                    app.set_status("No more changes");
+                }
+            }
+            return Ok(Some(Action::None));
+        }
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.diff_view = None;
+                app.set_status("Closed diff view");
+            }
+            KeyCode::Char('j') | KeyCode::Down => view.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => view.scroll_up(1),
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                view.scroll_down(view.visible_height / 2)
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                view.scroll_up(view.visible_height / 2)
+            }
+            KeyCode::PageDown => view.scroll_down(view.visible_height),
+            KeyCode::PageUp => view.scroll_up(view.visible_height),
+            KeyCode::Char('g') | KeyCode::Home => view.scroll_to_top(),
+            KeyCode::Char('G') | KeyCode::End => view.scroll_to_bottom(),
+            KeyCode::Char(c @ (']' | '[')) => view.pending_bracket = Some(c),
+            _ => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
     // Global keybindings (work in any mode except when help is shown)
     match key.code {
         KeyCode::F(1) => {
             app.show_help = true;
             return Ok(Some(Action::None));
         }
+        KeyCode::F(2) => {
+            app.toggle_notes();
+            return Ok(Some(Action::None));
+        }
+        KeyCode::F(3) => {
+            app.toggle_outline();
+            return Ok(Some(Action::None));
+        }
+        KeyCode::F(4) => return Ok(Some(Action::Check)),
         KeyCode::F(5) => return Ok(Some(Action::BuildAndRun)),
         KeyCode::F(6) => return Ok(Some(Action::Build)),
         KeyCode::F(7) => return Ok(Some(Action::Run)),
@@ -79,14 +334,22 @@ fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::F(9) => {
             match app.export_output() {
                 Ok(path) => {
-                    app.status_message = format!("Output saved to: {}", path.display());
+                    app.set_status(format!("Output saved to: {}", path.display()));
                 }
                 Err(e) => {
-                    app.status_message = format!("Failed to save output: {}", e);
+                    app.set_sticky_status(format!("Failed to save output: {}", e));
                 }
             }
             return Ok(Some(Action::None));
         }
+        KeyCode::Char('o' | 'O')
+            if key
+                .modifiers
+                .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) =>
+        {
+            app.open_symbol_picker();
+            return Ok(Some(Action::None));
+        }
         KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             return Ok(Some(Action::Save));
         }
@@ -100,6 +363,9 @@ fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     if app.focus == FocusedPanel::Output {
         return handle_output_panel(app, key);
     }
+    if app.focus == FocusedPanel::Outline {
+        return handle_outline_panel(app, key);
+    }
 
     match app.mode {
         Mode::Normal => handle_normal_mode(app, key),
@@ -110,9 +376,51 @@ fn handle_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         Mode::InputPopup => handle_input_popup_mode(app, key),
         Mode::Visual => handle_visual_mode(app, key),
         Mode::VisualLine => handle_visual_line_mode(app, key),
+        Mode::VisualBlock => handle_visual_block_mode(app, key),
     }
 }
 
+/// What a key did to an open picker
+enum PickerKey {
+    Handled,
+    Close,
+    Accept,
+}
+
+/// Keys shared by every picker: Enter picks, Esc closes, arrows, Tab and
+/// Ctrl+N/P/J/K move, and anything else typed edits the query
+fn picker_key<T: PickerItem>(picker: &mut PickerState<T>, key: KeyEvent) -> PickerKey {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => return PickerKey::Close,
+        KeyCode::Enter => return PickerKey::Accept,
+        KeyCode::Down | KeyCode::Tab => picker.select_next(),
+        KeyCode::Up | KeyCode::BackTab => picker.select_prev(),
+        KeyCode::Char('n' | 'j') if ctrl => picker.select_next(),
+        KeyCode::Char('p' | 'k') if ctrl => picker.select_prev(),
+        KeyCode::Backspace => picker.backspace(),
+        KeyCode::Char(c) if !ctrl => picker.push_char(c),
+        _ => {}
+    }
+    PickerKey::Handled
+}
+
+fn handle_outline_panel(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => app.outline.select_next(),
+        KeyCode::Char('k') | KeyCode::Up => app.outline.select_prev(),
+        KeyCode::Char('g') | KeyCode::Home => app.outline.select_first(),
+        KeyCode::Char('G') | KeyCode::End => app.outline.select_last(),
+        KeyCode::Enter => app.jump_to_outline_selection(),
+        KeyCode::Char('q') => app.toggle_outline(),
+        KeyCode::Tab => app.focus_next(),
+        KeyCode::BackTab => app.focus_prev(),
+        KeyCode::Esc => app.focus = FocusedPanel::Editor,
+        _ => {}
+    }
+    Ok(Some(Action::None))
+}
+
 fn handle_output_panel(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     // Handle resize with Ctrl+arrows
     if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -156,22 +464,19 @@ fn handle_output_panel(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if app.output_only_mode {
                 app.copy_output_to_clipboard();
-                app.status_message = String::from("Output copied to clipboard");
+                app.set_status("Output copied to clipboard");
             } else {
                 app.output.clear();
-                app.status_message = String::from("Output cleared");
+                app.set_status("Output cleared");
             }
         }
 
         // Switch focus / exit output-only mode
-        KeyCode::Tab => {
-            if app.output_only_mode {
-                app.toggle_output_only_mode();
-            } else {
-                app.focus = FocusedPanel::Editor;
-                app.mode = Mode::Normal;
-            }
+        KeyCode::Tab | KeyCode::BackTab if app.output_only_mode => {
+            app.toggle_output_only_mode();
         }
+        KeyCode::Tab => app.focus_next(),
+        KeyCode::BackTab => app.focus_prev(),
         KeyCode::Esc => {
             if app.output_only_mode {
                 app.toggle_output_only_mode();
@@ -181,8 +486,10 @@ fn handle_output_panel(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             }
         }
 
-        // Toggle output panel
-        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+        // Toggle output panel (Ctrl+O still works here, there is no jump list)
+        KeyCode::Char('t') | KeyCode::Char('o')
+            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
             app.show_output = !app.show_output;
             if !app.show_output {
                 app.focus = FocusedPanel::Editor;
@@ -192,7 +499,7 @@ fn handle_output_panel(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         // Yank (copy) in output-only mode
         KeyCode::Char('y') if app.output_only_mode => {
             app.copy_output_to_clipboard();
-            app.status_message = String::from("Output copied to clipboard");
+            app.set_status("Output copied to clipboard");
         }
 
         _ => {}
@@ -220,6 +527,19 @@ fn handle_input_popup_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action
 }
 
 fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
+    // A "x register stays selected while the rest of the command is typed
+    // and applies to the yank, delete or paste that completes it
+    let register = app.pending_register;
+    app.editor.register = register;
+    let result = handle_normal_key(app, key);
+    app.editor.register = None;
+    if register.is_some() && !app.awaiting_key() {
+        app.pending_register = None;
+    }
+    result
+}
+
+fn handle_normal_key(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     // Hide hover on any key
     if app.show_hover {
         app.hide_hover();
@@ -231,9 +551,20 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         if let KeyCode::Char('d') = key.code {
             if let Some(symbol) = app.editor.go_to_definition() {
                 app.editor.ensure_cursor_visible(20);
-                app.status_message = format!("Jump to: {}", symbol);
+                app.set_status(format!("Jump to: {}", symbol));
+            } else {
+                app.set_status("No definition found");
+            }
+            return Ok(Some(Action::None));
+        } else if let KeyCode::Char('D') = key.code {
+            // gD - definition anywhere in the project
+            if let Some((symbol, path)) = app.editor.go_to_project_definition() {
+                app.editor.ensure_cursor_visible(20);
+                app.focus = FocusedPanel::Editor;
+                let file = path.file_name().unwrap_or_default().to_string_lossy();
+                app.set_status(format!("Jump to: {} ({})", symbol, file));
             } else {
-                app.status_message = String::from("No definition found");
+                app.set_status("No definition found in project");
             }
             return Ok(Some(Action::None));
         } else if let KeyCode::Char('g') = key.code {
@@ -243,11 +574,106 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             buf.cursor_x = 0;
             buf.scroll_offset = 0;
             return Ok(Some(Action::None));
+        } else if let KeyCode::Char(c @ ('-' | '+')) = key.code {
+            // g- / g+ - move backward/forward in time through the undo tree
+            let forward = c == '+';
+            if app.editor.undo_time_step(forward) {
+                app.editor.ensure_cursor_visible(20);
+                app.set_status(format!("Change {}", app.editor.undo_tree.current_seq()));
+            } else if forward {
+                app.set_status("Already at newest change");
+            } else {
+                app.set_status("Already at oldest change");
+            }
+            return Ok(Some(Action::None));
+        } else if let KeyCode::Char('b') = key.code {
+            // gb - cycle the number under the cursor through dec/hex/binary
+            let message = match app.editor.cycle_number_base() {
+                Some(literal) => format!("Number: {}", literal),
+                None => String::from("No number under cursor"),
+            };
+            app.set_status(message);
+            return Ok(Some(Action::None));
+        } else if let KeyCode::Char('c') = key.code {
+            // gc - comment out the line and keep a live copy to edit
+            let below = app.config.editor.comment_copy_below;
+            if app.editor.comment_and_duplicate(below) {
+                app.editor.ensure_cursor_visible(20);
+            } else {
+                app.set_status("Nothing to comment");
+            }
+            return Ok(Some(Action::None));
+        } else if let KeyCode::Char('!') = key.code {
+            // g! - error/warning totals from the last build or check
+            app.show_diagnostic_counts();
+            return Ok(Some(Action::None));
+        } else if let KeyCode::Char('E') = key.code {
+            // gE - list every diagnostic
+            app.toggle_diagnostics_panel();
+            return Ok(Some(Action::None));
         }
         // Other g commands could be added here
         return Ok(Some(Action::None));
     }
 
+    // Handle pending operator (dd/cc on lines, diw/ciw on the word)
+    if let Some(op) = app.pending_operator {
+        if !app.pending_inner && matches!(op, 'c' | 'd') {
+            if let KeyCode::Char('i') = key.code {
+                app.pending_inner = true;
+                return Ok(Some(Action::None));
+            }
+        }
+        let inner = std::mem::take(&mut app.pending_inner);
+        app.pending_operator = None;
+        let count = app.pending_count.take().unwrap_or(1);
+        match key.code {
+            KeyCode::Char('w') if inner => {
+                if app.editor.delete_inner_word().is_none() {
+                    app.set_status("No word under cursor");
+                } else if op == 'c' {
+                    app.mode = Mode::Insert;
+                }
+            }
+            KeyCode::Char(c @ ('>' | '<')) if c == op => {
+                let start = app.editor.cursor_y();
+                let end = start + count - 1;
+                if c == '>' {
+                    app.editor.indent_lines(start, end);
+                } else {
+                    app.editor.dedent_lines(start, end);
+                }
+            }
+            KeyCode::Char(c) if c == op && !inner => {
+                if op == 'c' {
+                    app.editor.change_lines(count);
+                    app.mode = Mode::Insert;
+                } else if app.needs_delete_confirmation(count) {
+                    app.request_delete_confirmation(PendingAction::DeleteLines(count), count);
+                } else {
+                    for _ in 0..count {
+                        app.editor.delete_line();
+                    }
+                }
+            }
+            _ => {}
+        }
+        return Ok(Some(Action::None));
+    }
+
+    // Handle pending @ command (@: or @@ - repeat the last command line)
+    if app.pending_at {
+        app.pending_at = false;
+        if let KeyCode::Char(':' | '@') = key.code {
+            match app.repeat_last_command() {
+                Ok(CommandResult::Quit) => return Ok(Some(Action::Quit)),
+                Ok(CommandResult::Continue) => {}
+                Err(e) => app.set_status(format!("Error: {}", e)),
+            }
+        }
+        return Ok(Some(Action::None));
+    }
+
     // Handle pending bracket command (for ]e - next error, [e - prev error)
     if let Some(bracket) = app.pending_bracket {
         app.pending_bracket = None;
@@ -263,12 +689,56 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             }
             return Ok(Some(Action::None));
         }
+        if let KeyCode::Char('q') = key.code {
+            app.step_quickfix(bracket == ']');
+            return Ok(Some(Action::None));
+        }
         // Other bracket commands could be added here (]w - next warning, etc.)
         return Ok(Some(Action::None));
     }
 
-    // Handle pending char for f/F/t/T commands
+    // Handle pending char for f/F/t/T commands and "x registers
     if let Some(cmd) = app.pending_char {
+        if let (KeyCode::Char(c), '"') = (key.code, cmd) {
+            app.pending_char = None;
+            if c.is_ascii_lowercase() {
+                app.pending_register = Some(c);
+            } else {
+                app.pending_count = None;
+                app.set_status(format!("Invalid register: {}", c));
+            }
+            return Ok(Some(Action::None));
+        }
+        if let (KeyCode::Char(c), 'm' | '`' | '\'') = (key.code, cmd) {
+            app.pending_char = None;
+            app.pending_count = None;
+            if !c.is_ascii_lowercase() {
+                app.set_status(format!("Invalid mark: {}", c));
+            } else if cmd == 'm' {
+                app.editor.set_mark(c);
+                app.set_status(format!("Mark {} set", c));
+            } else if app.editor.jump_to_mark(c, cmd == '\'') {
+                app.editor.ensure_cursor_visible(20);
+            } else {
+                app.set_status(format!("Mark not set: {}", c));
+            }
+            return Ok(Some(Action::None));
+        }
+        if let (KeyCode::Char(c @ ('C' | 'O')), 'z') = (key.code, cmd) {
+            app.pending_char = None;
+            app.pending_count = None;
+            app.fold_comments(c == 'C');
+            return Ok(Some(Action::None));
+        }
+        if let (KeyCode::Char('a'), 'z') = (key.code, cmd) {
+            app.pending_char = None;
+            app.pending_count = None;
+            match app.editor.toggle_fold() {
+                Some(_) => app.editor.ensure_cursor_visible(20),
+                None => app.set_status("No PROC or segment to fold here"),
+            }
+            return Ok(Some(Action::None));
+        }
         if let KeyCode::Char(c) = key.code {
             let count = app.pending_count.unwrap_or(1);
             for _ in 0..count {
@@ -299,7 +769,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
 
     // Handle count prefix (1-9 for first digit, 0-9 for subsequent)
     if let KeyCode::Char(c) = key.code {
-        if c.is_ascii_digit() {
+        if c.is_ascii_digit() && !key.modifiers.contains(KeyModifiers::CONTROL) {
             if c == '0' && app.pending_count.is_none() {
                 // '0' alone is move to line start
                 app.editor.move_to_line_start();
@@ -341,7 +811,8 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             app.editor.move_cursor_up();
             app.mode = Mode::Insert;
         }
-        KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+        // Ctrl+O is jump back (as in vim), so the output panel lives on Ctrl+T
+        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.show_output = !app.show_output;
         }
         KeyCode::Char(':') => {
@@ -352,10 +823,16 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             app.start_search();
         }
 
+        // Block visual mode (Ctrl+V is taken by paste)
+        KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::ALT) => {
+            app.editor.start_block_selection();
+            app.mode = Mode::VisualBlock;
+        }
+
         // Ctrl+V to paste (non-vim users)
         KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.editor.paste_after();
-            app.status_message = String::from("Pasted");
+            app.set_status("Pasted");
         }
 
         // Visual mode
@@ -371,62 +848,73 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         // Undo/Redo
         KeyCode::Char('u') => {
             if app.editor.undo() {
-                app.status_message = String::from("Undo");
+                app.set_status("Undo");
             } else {
-                app.status_message = String::from("Already at oldest change");
+                app.set_status("Already at oldest change");
             }
         }
         KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if app.editor.redo() {
-                app.status_message = String::from("Redo");
+                app.set_status("Redo");
             } else {
-                app.status_message = String::from("Already at newest change");
+                app.set_status("Already at newest change");
             }
         }
         // Ctrl+Z for undo (non-vim users)
         KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if app.editor.undo() {
-                app.status_message = String::from("Undo");
+                app.set_status("Undo");
             } else {
-                app.status_message = String::from("Already at oldest change");
+                app.set_status("Already at oldest change");
             }
         }
         // Ctrl+Y for redo (non-vim users)
         KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if app.editor.redo() {
-                app.status_message = String::from("Redo");
+                app.set_status("Redo");
             } else {
-                app.status_message = String::from("Already at newest change");
+                app.set_status("Already at newest change");
             }
         }
 
         // Ctrl+C to copy line (non-vim users)
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.editor.yank_line();
-            app.status_message = String::from("Copied line");
+            app.set_status("Copied line");
         }
         // Ctrl+X to cut line (non-vim users)
         KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.editor.delete_line();
-            app.status_message = String::from("Cut line");
+            app.set_status("Cut line");
         }
 
         // Yank and paste (vim style)
         KeyCode::Char('y') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.editor.yank_line();
-            app.status_message = String::from("Yanked line");
+            app.set_status(match app.editor.register {
+                Some(name) => format!("Yanked line into \"{}", name),
+                None => String::from("Yanked line"),
+            });
+        }
+        KeyCode::Char('p' | 'P')
+            if app
+                .editor
+                .register
+                .is_some_and(|name| !app.editor.registers.contains_key(&name)) =>
+        {
+            app.set_status("Register is empty");
         }
         KeyCode::Char('p') => {
             for _ in 0..count {
                 app.editor.paste_after();
             }
-            app.status_message = String::from("Pasted");
+            app.set_status("Pasted");
         }
         KeyCode::Char('P') => {
             for _ in 0..count {
                 app.editor.paste_before();
             }
-            app.status_message = String::from("Pasted before");
+            app.set_status("Pasted before");
         }
 
         // Search navigation
@@ -435,7 +923,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
                 app.editor.find_next();
             }
             if let Some(status) = app.editor.search_status() {
-                app.status_message = format!("Search: {}", status);
+                app.set_status(format!("Search: {}", status));
             }
             app.editor.ensure_cursor_visible(20);
         }
@@ -444,7 +932,7 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
                 app.editor.find_prev();
             }
             if let Some(status) = app.editor.search_status() {
-                app.status_message = format!("Search: {}", status);
+                app.set_status(format!("Search: {}", status));
             }
             app.editor.ensure_cursor_visible(20);
         }
@@ -490,14 +978,22 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             }
         }
 
+        // Alternate buffer (terminals report Ctrl+^ as Ctrl+6)
+        KeyCode::Char('^') | KeyCode::Char('6')
+            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            app.alternate_buffer();
+        }
+
         // Line motions
         KeyCode::Char('^') => app.editor.move_to_first_non_blank(),
         KeyCode::Char('$') => app.editor.move_to_line_end(),
 
         // Bracket matching
         KeyCode::Char('%') => {
-            if !app.editor.find_matching_bracket() {
-                app.status_message = String::from("No matching bracket");
+            let found = app.editor.find_matching_bracket();
+            if !found {
+                app.set_status("No matching bracket");
             }
         }
 
@@ -510,12 +1006,31 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             if app.editor.go_back() {
                 app.editor.ensure_cursor_visible(20);
-                app.status_message = String::from("Jump back");
+                app.set_status("Jump back");
             } else {
-                app.status_message = String::from("Jump stack empty");
+                app.set_status("Jump stack empty");
+            }
+        }
+
+        // Register for the next yank, delete or paste
+        KeyCode::Char('"') => {
+            app.pending_char = Some('"');
+            if count > 1 {
+                app.pending_count = Some(count);
             }
         }
 
+        // Marks: m{a-z} sets one, `{a-z} jumps to it and '{a-z} to its line
+        KeyCode::Char(c @ ('m' | '`' | '\'')) => {
+            app.pending_char = Some(c);
+        }
+
+        // Folding: zC folds comment blocks, zO opens them, za toggles the
+        // PROC or segment under the cursor
+        KeyCode::Char('z') => {
+            app.pending_char = Some('z');
+        }
+
         // Char finding
         KeyCode::Char('f') => {
             app.pending_char = Some('f');
@@ -538,6 +1053,9 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Char('g') => {
             app.pending_g = true;
         }
+        KeyCode::Char('@') => {
+            app.pending_at = true;
+        }
         // Error navigation: ]e next error, [e prev error
         KeyCode::Char(']') => {
             app.pending_bracket = Some(']');
@@ -563,44 +1081,37 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
                 app.editor.delete_char();
             }
         }
-        KeyCode::Char('d') => {
-            for _ in 0..count {
-                app.editor.delete_line();
-            }
+        // Operators: dd/cc whole lines and diw/ciw the word under the cursor,
+        // >>/<< shift lines. Change: S whole line (keeping indent), C to end
+        // of line, s chars
+        KeyCode::Char(op @ ('c' | 'd' | '>' | '<')) => {
+            app.pending_operator = Some(op);
+            app.pending_count = Some(count);
+        }
+        KeyCode::Char('S') => {
+            app.editor.change_lines(count);
+            app.mode = Mode::Insert;
+        }
+        KeyCode::Char('C') => {
+            app.editor.change_to_line_end(count);
+            app.mode = Mode::Insert;
+        }
+        KeyCode::Char('s') => {
+            app.editor.substitute_chars(count);
+            app.mode = Mode::Insert;
         }
 
         // Panel focus
-        KeyCode::Tab if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.focus = match app.focus {
-                FocusedPanel::Editor => {
-                    if app.show_file_tree {
-                        app.mode = Mode::FileTree;
-                        FocusedPanel::FileTree
-                    } else if app.show_output {
-                        FocusedPanel::Output
-                    } else {
-                        FocusedPanel::Editor
-                    }
-                }
-                FocusedPanel::FileTree => {
-                    app.mode = Mode::Normal;
-                    if app.show_output {
-                        FocusedPanel::Output
-                    } else {
-                        FocusedPanel::Editor
-                    }
-                }
-                FocusedPanel::Output => FocusedPanel::Editor,
-            };
-        }
+        KeyCode::Tab if !key.modifiers.contains(KeyModifiers::CONTROL) => app.focus_next(),
+        KeyCode::BackTab => app.focus_prev(),
 
         // Buffer switching with Ctrl+Tab
         KeyCode::Tab if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.editor.next_buffer();
-            app.status_message = format!(
+            app.set_status(format!(
                 "Buffer: {}",
                 app.editor.buffers[app.editor.active_buffer].filename()
-            );
+            ));
         }
 
         // Toggle panels
@@ -624,11 +1135,10 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
 
         // Close buffer
         KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            if app.editor.modified() {
-                app.status_message =
-                    String::from("Buffer has unsaved changes. Save first or use :bd!");
-            } else if app.editor.close_buffer() {
-                app.status_message = String::from("Buffer closed");
+            if app.close_would_discard() {
+                app.set_status("Buffer has unsaved changes. Save first or use :bd!");
+            } else if app.close_buffer() {
+                app.set_status("Buffer closed");
             }
         }
 
@@ -637,7 +1147,10 @@ fn handle_normal_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             app.pending_count = None;
             app.pending_char = None;
             app.pending_bracket = None;
-            app.status_message = String::from("Press F1 for help");
+            app.pending_operator = None;
+            app.pending_inner = false;
+            app.pending_register = None;
+            app.set_status(crate::app::IDLE_HINT);
         }
 
         _ => {}
@@ -652,6 +1165,7 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         match key.code {
             KeyCode::Esc => {
                 app.autocomplete.hide();
+                app.signature_hint = None;
                 return Ok(Some(Action::None));
             }
             KeyCode::Tab | KeyCode::Enter => {
@@ -681,7 +1195,7 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             KeyCode::Char('c') => {
                 // Copy current line if no selection
                 app.editor.yank_line();
-                app.status_message = String::from("Copied line");
+                app.set_status("Copied line");
                 return Ok(Some(Action::None));
             }
             KeyCode::Char('v') => {
@@ -690,30 +1204,30 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
                     let buf = &mut app.editor.buffers[app.editor.active_buffer];
                     crate::ui::editor::clipboard::paste_text_inline(
                         buf,
-                        &mut app.editor.undo_stack,
+                        &mut app.editor.undo_tree,
                         &text,
                     );
-                    app.status_message = String::from("Pasted from clipboard");
+                    app.set_status("Pasted from clipboard");
                 } else {
-                    app.status_message = String::from("Clipboard empty");
+                    app.set_status("Clipboard empty");
                 }
                 return Ok(Some(Action::None));
             }
             KeyCode::Char('x') => {
                 // Cut current line
                 app.editor.delete_line();
-                app.status_message = String::from("Cut line");
+                app.set_status("Cut line");
                 return Ok(Some(Action::None));
             }
             KeyCode::Char('z') => {
                 if app.editor.undo() {
-                    app.status_message = String::from("Undo");
+                    app.set_status("Undo");
                 }
                 return Ok(Some(Action::None));
             }
             KeyCode::Char('y') => {
                 if app.editor.redo() {
-                    app.status_message = String::from("Redo");
+                    app.set_status("Redo");
                 }
                 return Ok(Some(Action::None));
             }
@@ -725,6 +1239,11 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Esc => {
             app.mode = Mode::Normal;
             app.autocomplete.hide();
+            app.signature_hint = None;
+            let copies = app.editor.finish_block_insert();
+            if copies > 0 {
+                app.set_status(format!("Inserted on {} more lines", copies));
+            }
         }
         KeyCode::Char(' ') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             // Manual autocomplete trigger
@@ -780,6 +1299,9 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Tab => {
             app.editor.insert_tab();
         }
+        KeyCode::BackTab => {
+            app.editor.dedent_line();
+        }
         KeyCode::Left => app.editor.move_cursor_left(),
         KeyCode::Right => app.editor.move_cursor_right(),
         KeyCode::Up => {
@@ -794,6 +1316,11 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::End => app.editor.move_to_line_end(),
         _ => {}
     }
+    if app.mode == Mode::Insert {
+        let trigger = matches!(key.code, KeyCode::Char(' ' | ','))
+            && !key.modifiers.contains(KeyModifiers::CONTROL);
+        app.update_signature_hint(trigger);
+    }
 
     Ok(Some(Action::None))
 }
@@ -801,8 +1328,10 @@ fn handle_insert_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
 fn handle_command_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     match key.code {
         KeyCode::Esc => {
+            app.editor.clear_selection();
             app.mode = Mode::Normal;
             app.command_input.clear();
+            app.command_history.reset();
         }
         KeyCode::Enter => match app.execute_command() {
             Ok(CommandResult::Quit) => {
@@ -810,15 +1339,29 @@ fn handle_command_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             }
             Ok(CommandResult::Continue) => {}
             Err(e) => {
-                app.status_message = format!("Error: {}", e);
+                app.set_status(format!("Error: {}", e));
             }
         },
         KeyCode::Char(c) => {
             app.command_input.push(c);
+            app.command_history.reset();
+        }
+        KeyCode::Tab => app.complete_command(),
+        KeyCode::Up => {
+            if let Some(line) = app.command_history.prev(&app.command_input) {
+                app.command_input = line.to_string();
+            }
+        }
+        KeyCode::Down => {
+            if let Some(line) = app.command_history.next() {
+                app.command_input = line.to_string();
+            }
         }
         KeyCode::Backspace => {
             app.command_input.pop();
+            app.command_history.reset();
             if app.command_input.is_empty() {
+                app.editor.clear_selection();
                 app.mode = Mode::Normal;
             }
         }
@@ -828,6 +1371,27 @@ fn handle_command_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     Ok(Some(Action::None))
 }
 
+/// With `file_tree.open_on_navigate`, show the newly selected text file in
+/// the preview buffer
+fn preview_selected_file(app: &mut App) {
+    if !app.config.file_tree.open_on_navigate {
+        return;
+    }
+    if let Some(entry) = app.file_tree.selected_entry() {
+        if !entry.is_dir {
+            let name = &entry.name;
+            if !name.ends_with(".exe")
+                && !name.ends_with(".obj")
+                && !name.ends_with(".lib")
+                && !name.ends_with(".o")
+            {
+                let path = entry.path.clone();
+                let _ = app.preview_file(&path);
+            }
+        }
+    }
+}
+
 fn handle_file_tree_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     // Handle resize with Ctrl+arrows (global in file tree mode too)
     if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -860,50 +1424,24 @@ fn handle_file_tree_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>>
         // Refresh file tree
         KeyCode::Char('R') => {
             if let Err(e) = app.file_tree.refresh() {
-                app.status_message = format!("Refresh failed: {}", e);
+                app.set_status(format!("Refresh failed: {}", e));
             } else {
-                app.status_message = String::from("File tree refreshed");
+                app.set_status("File tree refreshed");
             }
         }
         KeyCode::Char('j') | KeyCode::Down => {
             app.file_tree.move_down();
-            // Auto-open text files on navigation
-            if let Some(entry) = app.file_tree.selected_entry() {
-                if !entry.is_dir {
-                    let name = &entry.name;
-                    if !name.ends_with(".exe")
-                        && !name.ends_with(".obj")
-                        && !name.ends_with(".lib")
-                        && !name.ends_with(".o")
-                    {
-                        let path = entry.path.clone();
-                        let _ = app.open_file(&path);
-                    }
-                }
-            }
+            preview_selected_file(app);
         }
         KeyCode::Char('k') | KeyCode::Up => {
             app.file_tree.move_up();
-            // Auto-open text files on navigation
-            if let Some(entry) = app.file_tree.selected_entry() {
-                if !entry.is_dir {
-                    let name = &entry.name;
-                    if !name.ends_with(".exe")
-                        && !name.ends_with(".obj")
-                        && !name.ends_with(".lib")
-                        && !name.ends_with(".o")
-                    {
-                        let path = entry.path.clone();
-                        let _ = app.open_file(&path);
-                    }
-                }
-            }
+            preview_selected_file(app);
         }
         KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => {
             if let Ok(Some(file_path)) = app.file_tree.toggle_expand() {
                 // It's a file, open it
                 if let Err(e) = app.open_file(&file_path) {
-                    app.status_message = format!("Error: {}", e);
+                    app.set_status(format!("Error: {}", e));
                 }
                 app.mode = Mode::Normal;
             }
@@ -916,14 +1454,8 @@ fn handle_file_tree_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>>
                 }
             }
         }
-        KeyCode::Tab => {
-            app.mode = Mode::Normal;
-            app.focus = if app.show_output {
-                FocusedPanel::Output
-            } else {
-                FocusedPanel::Editor
-            };
-        }
+        KeyCode::Tab => app.focus_next(),
+        KeyCode::BackTab => app.focus_prev(),
         KeyCode::Char('a') => {
             app.mode = Mode::InputPopup;
             app.pending_action = PendingAction::CreateFile;
@@ -966,13 +1498,41 @@ fn handle_search_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         KeyCode::Enter => {
             app.execute_search();
         }
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.editor.search_is_regex = !app.editor.search_is_regex;
+            app.editor.search(&app.search_input);
+        }
+        KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.editor.search_whole_word = !app.editor.search_whole_word;
+            app.editor.search(&app.search_input);
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+            app.cycle_search_case();
+        }
         KeyCode::Char(c) => {
             app.search_input.push(c);
+            app.search_history.reset();
             // Live search as you type
             app.editor.search(&app.search_input);
         }
+        KeyCode::Up | KeyCode::Down => {
+            let recalled = if key.code == KeyCode::Up {
+                app.search_history.prev(&app.search_input)
+            } else {
+                app.search_history.next()
+            };
+            if let Some(term) = recalled {
+                app.search_input = term.to_string();
+                if app.search_input.is_empty() {
+                    app.editor.clear_search();
+                } else {
+                    app.editor.search(&app.search_input);
+                }
+            }
+        }
         KeyCode::Backspace => {
             app.search_input.pop();
+            app.search_history.reset();
             if app.search_input.is_empty() {
                 app.editor.clear_search();
             } else {
@@ -985,11 +1545,25 @@ fn handle_search_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     Ok(Some(Action::None))
 }
 
+/// Ctrl+V or Alt+v, which switch visual modes to block selection
+fn is_block_key(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('v')
+        && key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
+
 fn handle_visual_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
+    if is_block_key(&key) {
+        app.editor.buffers[app.editor.active_buffer].block_selection = true;
+        app.mode = Mode::VisualBlock;
+        return Ok(Some(Action::None));
+    }
+
     // Ctrl+C to copy selection
     if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
         if app.editor.yank_selection() {
-            app.status_message = String::from("Copied selection");
+            app.set_status("Copied selection");
         }
         app.editor.clear_selection();
         app.mode = Mode::Normal;
@@ -1009,6 +1583,12 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
             app.mode = Mode::VisualLine;
         }
 
+        // Command on the selection (e.g. :scratch)
+        KeyCode::Char(':') => {
+            app.mode = Mode::Command;
+            app.command_input.clear();
+        }
+
         // Navigation - extends selection
         KeyCode::Char('h') | KeyCode::Left => {
             app.editor.move_cursor_left();
@@ -1079,13 +1659,22 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
         // Operations on selection
         KeyCode::Char('y') => {
             if app.editor.yank_selection() {
-                app.status_message = String::from("Yanked selection");
+                app.set_status("Yanked selection");
             }
             app.mode = Mode::Normal;
         }
         KeyCode::Char('d') | KeyCode::Char('x') => {
+            let lines = app
+                .editor
+                .get_selection_range()
+                .map(|((start, _), (end, _))| end - start + 1)
+                .unwrap_or(0);
+            if app.needs_delete_confirmation(lines) {
+                app.request_delete_confirmation(PendingAction::DeleteSelection, lines);
+                return Ok(Some(Action::None));
+            }
             if app.editor.delete_selection() {
-                app.status_message = String::from("Deleted selection");
+                app.set_status("Deleted selection");
             }
             app.mode = Mode::Normal;
         }
@@ -1096,6 +1685,82 @@ fn handle_visual_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     Ok(Some(Action::None))
 }
 
+fn handle_visual_block_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
+    if is_block_key(&key) {
+        app.editor.clear_selection();
+        app.mode = Mode::Normal;
+        return Ok(Some(Action::None));
+    }
+
+    match key.code {
+        KeyCode::Esc => {
+            app.editor.clear_selection();
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Char('v') => {
+            app.editor.start_visual_selection();
+            app.mode = Mode::Visual;
+        }
+        KeyCode::Char('V') => {
+            app.editor.start_visual_line_selection();
+            app.mode = Mode::VisualLine;
+        }
+
+        // Navigation - moves the block's corner
+        KeyCode::Char('h') | KeyCode::Left => app.editor.move_cursor_left(),
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.editor.move_cursor_down();
+            app.editor.ensure_cursor_visible(20);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.editor.move_cursor_up();
+            app.editor.ensure_cursor_visible(20);
+        }
+        KeyCode::Char('l') | KeyCode::Right => app.editor.move_cursor_right(),
+        KeyCode::Char('w') => app.editor.move_word_forward(),
+        KeyCode::Char('b') => app.editor.move_word_backward(),
+        KeyCode::Char('e') => app.editor.move_word_end(),
+        KeyCode::Char('0') => app.editor.move_to_line_start(),
+        KeyCode::Char('$') => app.editor.move_to_line_end(),
+        KeyCode::Char('^') => app.editor.move_to_first_non_blank(),
+
+        // Insert the same text at the block's left edge on every line
+        KeyCode::Char('I') => {
+            app.editor.begin_block_insert();
+            app.mode = Mode::Insert;
+            return Ok(Some(Action::None));
+        }
+        KeyCode::Char('y') => {
+            if app.editor.yank_selection() {
+                app.set_status("Yanked block");
+            }
+            app.editor.clear_selection();
+            app.mode = Mode::Normal;
+            return Ok(Some(Action::None));
+        }
+        KeyCode::Char('d') | KeyCode::Char('x') => {
+            let lines = app
+                .editor
+                .block_range()
+                .map(|(top, bottom, _, _)| bottom - top + 1)
+                .unwrap_or(0);
+            if app.needs_delete_confirmation(lines) {
+                app.request_delete_confirmation(PendingAction::DeleteSelection, lines);
+                return Ok(Some(Action::None));
+            }
+            if app.editor.delete_selection() {
+                app.set_status("Deleted block");
+            }
+            app.mode = Mode::Normal;
+            return Ok(Some(Action::None));
+        }
+        _ => {}
+    }
+
+    app.editor.update_selection();
+    Ok(Some(Action::None))
+}
+
 fn handle_visual_line_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action>> {
     match key.code {
         // Exit visual mode
@@ -1110,6 +1775,12 @@ fn handle_visual_line_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action
             app.mode = Mode::Visual;
         }
 
+        // Command on the selection (e.g. :scratch)
+        KeyCode::Char(':') => {
+            app.mode = Mode::Command;
+            app.command_input.clear();
+        }
+
         // Navigation - extends selection (line-wise)
         KeyCode::Char('j') | KeyCode::Down => {
             app.editor.move_cursor_down();
@@ -1144,13 +1815,33 @@ fn handle_visual_line_mode(app: &mut App, key: KeyEvent) -> Result<Option<Action
         // Operations on selection
         KeyCode::Char('y') => {
             if app.editor.yank_selection() {
-                app.status_message = String::from("Yanked lines");
+                app.set_status("Yanked lines");
             }
             app.mode = Mode::Normal;
         }
         KeyCode::Char('d') | KeyCode::Char('x') => {
+            let lines = app
+                .editor
+                .get_selection_range()
+                .map(|((start, _), (end, _))| end - start + 1)
+                .unwrap_or(0);
+            if app.needs_delete_confirmation(lines) {
+                app.request_delete_confirmation(PendingAction::DeleteSelection, lines);
+                return Ok(Some(Action::None));
+            }
             if app.editor.delete_selection() {
-                app.status_message = String::from("Deleted lines");
+                app.set_status("Deleted lines");
+            }
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Char(c @ ('>' | '<')) => {
+            if let Some(((start, _), (end, _))) = app.editor.get_selection_range() {
+                app.editor.clear_selection();
+                if c == '>' {
+                    app.editor.indent_lines(start, end);
+                } else {
+                    app.editor.dedent_lines(start, end);
+                }
             }
             app.mode = Mode::Normal;
         }
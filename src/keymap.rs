@@ -0,0 +1,426 @@
+// Keybinding registry - the single description of what each key does.
+// The help popup is generated from this table, so anything added here
+// shows up in F1 automatically.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Global,
+    Normal,
+    Insert,
+    Visual,
+    FileTree,
+    Output,
+    Command,
+    /// Typing a `/` search
+    Search,
+    /// Answering a prompt such as a file name or a confirmation
+    Input,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCategory {
+    Build,
+    Panels,
+    Motion,
+    Editing,
+    Search,
+    FileTree,
+    Output,
+    Commands,
+}
+
+impl KeyCategory {
+    pub const ALL: [KeyCategory; 8] = [
+        KeyCategory::Build,
+        KeyCategory::Panels,
+        KeyCategory::Motion,
+        KeyCategory::Editing,
+        KeyCategory::Search,
+        KeyCategory::FileTree,
+        KeyCategory::Output,
+        KeyCategory::Commands,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            KeyCategory::Build => "BUILD",
+            KeyCategory::Panels => "PANELS",
+            KeyCategory::Motion => "MOTION",
+            KeyCategory::Editing => "EDITING",
+            KeyCategory::Search => "SEARCH",
+            KeyCategory::FileTree => "FILES",
+            KeyCategory::Output => "OUTPUT",
+            KeyCategory::Commands => "COMMANDS",
+        }
+    }
+
+    /// Category most relevant to the given context, shown first in help
+    pub fn primary_for(context: KeyContext) -> KeyCategory {
+        match context {
+            KeyContext::FileTree => KeyCategory::FileTree,
+            KeyContext::Output => KeyCategory::Output,
+            KeyContext::Insert | KeyContext::Visual => KeyCategory::Editing,
+            KeyContext::Command | KeyContext::Input => KeyCategory::Commands,
+            KeyContext::Search => KeyCategory::Search,
+            KeyContext::Global | KeyContext::Normal => KeyCategory::Build,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBinding {
+    pub keys: &'static str,
+    pub description: &'static str,
+    pub category: KeyCategory,
+    pub context: KeyContext,
+}
+
+const fn bind(
+    keys: &'static str,
+    description: &'static str,
+    category: KeyCategory,
+    context: KeyContext,
+) -> KeyBinding {
+    KeyBinding {
+        keys,
+        description,
+        category,
+        context,
+    }
+}
+
+use KeyCategory as Cat;
+use KeyContext as Ctx;
+
+pub const DEFAULT_BINDINGS: &[KeyBinding] = &[
+    // Build
+    bind("F4", "Syntax check", Cat::Build, Ctx::Global),
+    bind("F5", "Build+Run", Cat::Build, Ctx::Global),
+    bind("F6", "Build", Cat::Build, Ctx::Global),
+    bind("F7", "Run", Cat::Build, Ctx::Global),
+    bind(":rebuild", "Build ignoring cache", Cat::Build, Ctx::Command),
+    bind(":build all", "Build all targets", Cat::Build, Ctx::Command),
+    bind(":run args", "Run with arguments", Cat::Build, Ctx::Command),
+    bind(
+        ":runstdin file",
+        "Feed file as input",
+        Cat::Build,
+        Ctx::Command,
+    ),
+    bind("]e/[e", "Next/prev error", Cat::Build, Ctx::Normal),
+    bind("g!", "Error/warning counts", Cat::Build, Ctx::Normal),
+    bind(":err N", "Go to Nth diagnostic", Cat::Build, Ctx::Command),
+    bind("gE", "Diagnostics list", Cat::Build, Ctx::Normal),
+    bind(":clist", "Diagnostics list", Cat::Build, Ctx::Command),
+    // Panels
+    bind("F1", "Help", Cat::Panels, Ctx::Global),
+    bind("F2", "Project notes", Cat::Panels, Ctx::Global),
+    bind("F3", "Symbol outline", Cat::Panels, Ctx::Global),
+    bind("F8", "Output view", Cat::Panels, Ctx::Global),
+    bind("F9", "Save output", Cat::Panels, Ctx::Global),
+    bind("Ctrl+S", "Save", Cat::Panels, Ctx::Global),
+    bind("Ctrl+Q", "Quit", Cat::Panels, Ctx::Global),
+    bind("Ctrl+E", "File tree", Cat::Panels, Ctx::Normal),
+    bind("Ctrl+T", "Output", Cat::Panels, Ctx::Normal),
+    bind("Tab/S-Tab", "Cycle focus", Cat::Panels, Ctx::Normal),
+    bind("Ctrl+^", "Alternate buffer", Cat::Panels, Ctx::Normal),
+    bind("Ctrl+←→", "Tree width", Cat::Panels, Ctx::Normal),
+    bind("Ctrl+↑↓", "Output height", Cat::Panels, Ctx::Normal),
+    // Motion
+    bind("hjkl", "←↓↑→", Cat::Motion, Ctx::Normal),
+    bind("w/b/e", "Word motions", Cat::Motion, Ctx::Normal),
+    bind("0/$", "Line start/end", Cat::Motion, Ctx::Normal),
+    bind("gg/G", "File start/end", Cat::Motion, Ctx::Normal),
+    bind("f/F/t/T", "Find char", Cat::Motion, Ctx::Normal),
+    bind("%", "Matching bracket", Cat::Motion, Ctx::Normal),
+    bind("gd", "Go to definition", Cat::Motion, Ctx::Normal),
+    bind("gD", "Definition in project", Cat::Motion, Ctx::Normal),
+    bind(
+        "Ctrl+Shift+O",
+        "Go to symbol in project",
+        Cat::Motion,
+        Ctx::Global,
+    ),
+    bind("Ctrl+O", "Jump back", Cat::Motion, Ctx::Normal),
+    bind("m{a-z}", "Set mark", Cat::Motion, Ctx::Normal),
+    bind(
+        "`{a-z} '{a-z}",
+        "Jump to mark/its line",
+        Cat::Motion,
+        Ctx::Normal,
+    ),
+    // Editing
+    bind("i/a/A", "Insert", Cat::Editing, Ctx::Normal),
+    bind("o/O", "New line ↓/↑", Cat::Editing, Ctx::Normal),
+    bind("v/V", "Visual mode", Cat::Editing, Ctx::Normal),
+    bind("Alt+v", "Block visual mode", Cat::Editing, Ctx::Normal),
+    bind("x/dd", "Delete char/line", Cat::Editing, Ctx::Normal),
+    bind("diw/ciw", "Delete/change word", Cat::Editing, Ctx::Normal),
+    bind(">>/<<", "Indent/dedent line", Cat::Editing, Ctx::Normal),
+    bind(
+        "cc/S C s",
+        "Change line/to end/char",
+        Cat::Editing,
+        Ctx::Normal,
+    ),
+    bind("y/p/P", "Yank/paste", Cat::Editing, Ctx::Normal),
+    bind("\"a-\"z", "Register for y/d/c/p", Cat::Editing, Ctx::Normal),
+    bind("u/Ctrl+R", "Undo/redo", Cat::Editing, Ctx::Normal),
+    bind("g-/g+", "Undo in time", Cat::Editing, Ctx::Normal),
+    bind("gb", "Cycle number base", Cat::Editing, Ctx::Normal),
+    bind("gc", "Comment + live copy", Cat::Editing, Ctx::Normal),
+    bind(
+        "zC/zO",
+        "Fold/open comment blocks",
+        Cat::Editing,
+        Ctx::Normal,
+    ),
+    bind("za", "Fold/open PROC or segment", Cat::Editing, Ctx::Normal),
+    bind("K", "Hover docs", Cat::Editing, Ctx::Normal),
+    bind("Drag", "Select with mouse", Cat::Editing, Ctx::Normal),
+    bind("2x/3x click", "Select word/line", Cat::Editing, Ctx::Normal),
+    bind("Esc", "Normal mode", Cat::Editing, Ctx::Insert),
+    bind("Ctrl+Space", "Autocomplete", Cat::Editing, Ctx::Insert),
+    bind("Tab/S-Tab", "Indent/dedent", Cat::Editing, Ctx::Insert),
+    bind("Ctrl+C/V/X", "Copy/paste/cut", Cat::Editing, Ctx::Insert),
+    bind("Ctrl+Z/Y", "Undo/redo", Cat::Editing, Ctx::Insert),
+    bind("y/d", "Yank/delete", Cat::Editing, Ctx::Visual),
+    bind(">/<", "Shift lines (V)", Cat::Editing, Ctx::Visual),
+    bind("Ctrl+V", "Block selection", Cat::Editing, Ctx::Visual),
+    bind("I", "Insert on block lines", Cat::Editing, Ctx::Visual),
+    bind("Ctrl+C", "Copy", Cat::Editing, Ctx::Visual),
+    bind(":", "Command on selection", Cat::Editing, Ctx::Visual),
+    // Search
+    bind("/", "Search", Cat::Search, Ctx::Normal),
+    bind("n/N", "Next/prev match", Cat::Search, Ctx::Normal),
+    bind("Ctrl+R", "Toggle regex (in /)", Cat::Search, Ctx::Search),
+    bind(
+        "Ctrl+W",
+        "Toggle whole word (in /)",
+        Cat::Search,
+        Ctx::Search,
+    ),
+    bind(
+        "Alt+C",
+        "Cycle case matching (in /)",
+        Cat::Search,
+        Ctx::Search,
+    ),
+    bind("Up/Down", "Search history (in /)", Cat::Search, Ctx::Search),
+    bind(
+        ":grep text",
+        "Search project files",
+        Cat::Search,
+        Ctx::Command,
+    ),
+    bind("]q/[q", "Next/prev grep result", Cat::Search, Ctx::Normal),
+    bind(":copen", "Reopen grep results", Cat::Search, Ctx::Command),
+    // File tree
+    bind("j/k h/l", "Navigate", Cat::FileTree, Ctx::FileTree),
+    bind("Enter", "Open", Cat::FileTree, Ctx::FileTree),
+    bind("a/A", "New file/dir", Cat::FileTree, Ctx::FileTree),
+    bind("r/d", "Rename/delete", Cat::FileTree, Ctx::FileTree),
+    bind("R", "Refresh", Cat::FileTree, Ctx::FileTree),
+    // Output
+    bind("jk/gG", "Scroll/jump", Cat::Output, Ctx::Output),
+    bind("Ctrl+C", "Clear/copy", Cat::Output, Ctx::Output),
+    bind("y", "Copy (F8)", Cat::Output, Ctx::Output),
+    // Commands
+    bind(":", "Command", Cat::Commands, Ctx::Normal),
+    bind("@:", "Repeat last command", Cat::Commands, Ctx::Normal),
+    bind("Up/Down", "Command history", Cat::Commands, Ctx::Command),
+    bind(":w :q :wq", "Save/quit", Cat::Commands, Ctx::Command),
+    bind(
+        ":w!",
+        "Save over changes on disk",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":e file", "Open", Cat::Commands, Ctx::Command),
+    bind(":bn :bp :bd :b#", "Buffers", Cat::Commands, Ctx::Command),
+    bind(":ls", "Pick open buffer", Cat::Commands, Ctx::Command),
+    bind(
+        ":reopen",
+        "Reopen closed buffer",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":proc name",
+        "Jump to PROC (Tab completes)",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":trim",
+        "Strip trailing spaces",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":wrap", "Toggle soft wrap", Cat::Commands, Ctx::Command),
+    bind(":theme n", "Theme", Cat::Commands, Ctx::Command),
+    bind(
+        ":theme load/save f",
+        "Theme from/to TOML file",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":autosave", "Toggle", Cat::Commands, Ctx::Command),
+    bind(":undotree", "Undo history", Cat::Commands, Ctx::Command),
+    bind(
+        ":difftool a b",
+        "Compare files",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":mkbuf", "Insert DUP buffer", Cat::Commands, Ctx::Command),
+    bind(":artifacts", "Build outputs", Cat::Commands, Ctx::Command),
+    bind(":check", "Assemble only", Cat::Commands, Ctx::Command),
+    bind(":scratch", "Run selection", Cat::Commands, Ctx::Command),
+    bind(
+        ":preserve eax ebx",
+        "Push/pop around selection",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":s/a/b/g",
+        "Substitute on line",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":%s/a/b/g",
+        "Substitute in file",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":annotate", "Doc as comment", Cat::Commands, Ctx::Command),
+    bind(
+        ":symbol",
+        "Find project symbol",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":reflow [w]",
+        "Rewrap comments",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":hardwrap [w]",
+        "Split long lines",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":set ff=unix|dos",
+        "Line endings",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":set aligncomments",
+        "Snap ; to comment column",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(
+        ":set rnu|nornu",
+        "Relative numbers",
+        Cat::Commands,
+        Ctx::Command,
+    ),
+    bind(":set all", "List options", Cat::Commands, Ctx::Command),
+    bind(":set opt?", "Show an option", Cat::Commands, Ctx::Command),
+    bind(":removebom", "Drop UTF-8 BOM", Cat::Commands, Ctx::Command),
+    bind(":refresh", "File tree", Cat::Commands, Ctx::Command),
+    bind(":!cmd", "Shell command", Cat::Commands, Ctx::Command),
+    bind(
+        "Enter/Esc",
+        "Answer/cancel prompt",
+        Cat::Commands,
+        Ctx::Input,
+    ),
+];
+
+/// The bindings currently in effect
+pub fn active_bindings() -> &'static [KeyBinding] {
+    DEFAULT_BINDINGS
+}
+
+/// Active bindings grouped by category, with the category most relevant
+/// to `context` first. Empty categories are skipped.
+pub fn grouped(context: KeyContext) -> Vec<(KeyCategory, Vec<&'static KeyBinding>)> {
+    let primary = KeyCategory::primary_for(context);
+    let order = std::iter::once(primary).chain(
+        KeyCategory::ALL
+            .iter()
+            .copied()
+            .filter(move |&c| c != primary),
+    );
+
+    order
+        .map(|category| {
+            let bindings: Vec<&KeyBinding> = active_bindings()
+                .iter()
+                .filter(|b| b.category == category)
+                .collect();
+            (category, bindings)
+        })
+        .filter(|(_, bindings)| !bindings.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_category_listed_first() {
+        let groups = grouped(KeyContext::FileTree);
+        assert_eq!(groups[0].0, KeyCategory::FileTree);
+        assert!(groups[0].1.iter().any(|b| b.keys == "Enter"));
+
+        // Every binding appears exactly once regardless of context
+        let total: usize = grouped(KeyContext::Normal)
+            .iter()
+            .map(|(_, b)| b.len())
+            .sum();
+        assert_eq!(total, active_bindings().len());
+    }
+
+    #[test]
+    fn test_no_duplicate_keys_per_context() {
+        let mut seen = std::collections::HashSet::new();
+        for b in active_bindings() {
+            assert!(
+                seen.insert((b.keys, b.context)),
+                "{} is bound twice in {:?}",
+                b.keys,
+                b.context
+            );
+        }
+        assert!(active_bindings()
+            .iter()
+            .any(|b| b.keys == "Ctrl+O" && b.description == "Jump back"));
+
+        // Search-bar keys don't clash with the Normal mode ones
+        let contexts = |keys: &str| -> Vec<KeyContext> {
+            active_bindings()
+                .iter()
+                .filter(|b| b.keys == keys)
+                .map(|b| b.context)
+                .collect()
+        };
+        assert_eq!(contexts("Ctrl+R"), vec![KeyContext::Search]);
+        assert!(!contexts("Up/Down").contains(&KeyContext::Normal));
+        assert_eq!(contexts("Ctrl+W"), vec![KeyContext::Search]);
+        assert_eq!(contexts("Alt+C"), vec![KeyContext::Search]);
+        assert_eq!(
+            KeyCategory::primary_for(KeyContext::Search),
+            KeyCategory::Search
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Quick checks on the source run before a build, for mistakes beginners
+//! make often. They only look at one line at a time, so they report a
+//! subset of what the assembler would, but right away and in the gutter.
+
+use crate::diagnostics::Diagnostic;
+use crate::docs::register_size;
+use std::path::Path;
+
+/// Instructions whose two operands must be the same size
+const SAME_SIZE: &[&str] = &[
+    "mov", "add", "sub", "adc", "sbb", "and", "or", "xor", "cmp", "test", "xchg", "cmpxchg", "xadd",
+];
+
+/// Shifts and rotates, whose count is an immediate or CL
+const SHIFTS: &[&str] = &["shl", "shr", "sal", "sar", "rol", "ror", "rcl", "rcr"];
+
+/// Warnings for register operands whose sizes cannot go together, such as
+/// `mov al, eax`. Memory and immediate operands are not checked.
+pub fn size_mismatches(lines: &[String], file: &Path) -> Vec<Diagnostic> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let message = check_line(line)?;
+            Some(Diagnostic::warning(file.to_path_buf(), idx + 1, message))
+        })
+        .collect()
+}
+
+/// Problem with the operand sizes of one line, if any
+fn check_line(line: &str) -> Option<String> {
+    let code = line.split(';').next().unwrap_or("").trim();
+    // Skip a leading `label:` so `next: mov al, eax` is still checked
+    let code = match code.split_once(':') {
+        Some((label, rest)) if !label.contains(char::is_whitespace) => rest.trim(),
+        _ => code,
+    };
+    let (mnemonic, operands) = code.split_once(char::is_whitespace)?;
+    let mnemonic = mnemonic.to_lowercase();
+    let (dest, src) = operands.split_once(',')?;
+    let (dest, src) = (dest.trim(), src.trim());
+    let dest_size = register_size(dest);
+    let src_size = register_size(src);
+
+    if SAME_SIZE.contains(&mnemonic.as_str()) {
+        let (d, s) = (dest_size?, src_size?);
+        return (d != s).then(|| {
+            format!(
+                "Operand size mismatch: {} is {}-bit but {} is {}-bit",
+                dest, d, src, s
+            )
+        });
+    }
+    if mnemonic == "movzx" || mnemonic == "movsx" {
+        let (d, s) = (dest_size?, src_size?);
+        return (d <= s).then(|| {
+            format!(
+                "{} needs a smaller source: {} is {}-bit and {} is {}-bit",
+                mnemonic, src, s, dest, d
+            )
+        });
+    }
+    if SHIFTS.contains(&mnemonic.as_str()) {
+        src_size?;
+        return (!src.eq_ignore_ascii_case("cl"))
+            .then(|| format!("{} takes its count in CL, not {}", mnemonic, src));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_size_mismatches() {
+        assert_eq!(register_size("EAX"), Some(32));
+        assert_eq!(register_size("ah"), Some(8));
+        assert_eq!(register_size("ds"), None);
+
+        let lines: Vec<String> = [
+            "    mov al, eax",
+            "L1: add EBX, cx ; comment",
+            "    mov eax, ebx",
+            "    mov al, [esi]",
+            "    movzx eax, bl",
+            "    movzx ax, eax",
+            "    shl eax, cl",
+            "    shr eax, ecx",
+            "    msg BYTE \"a, b\", 0",
+            "    ; mov al, eax",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let warnings = size_mismatches(&lines, Path::new("main.asm"));
+        let lines: Vec<usize> = warnings.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![1, 2, 6, 8]);
+        assert!(warnings[0].message.contains("al is 8-bit"));
+    }
+}
@@ -6,12 +6,22 @@ mod build;
 mod config;
 mod diagnostics;
 mod docs;
+mod file_state;
+mod fuzzy;
+mod history;
 mod input;
+mod keymap;
+mod lint;
 mod masm_lang;
+mod options;
 mod project;
+mod session;
 mod syntax;
+#[cfg(test)]
+mod test_util;
 mod theme;
 mod ui;
+mod utf8;
 
 use anyhow::Result;
 use app::App;
@@ -29,13 +39,18 @@ use std::path::PathBuf;
 #[command(name = "masmide")]
 #[command(author, version, about = "TUI IDE for MASM development on Linux", long_about = None)]
 struct Args {
-    /// File or directory to open
+    /// Files to open, each optionally as file:line[:col], and optionally
+    /// the project directory
     #[arg(default_value = ".")]
-    path: PathBuf,
+    paths: Vec<PathBuf>,
 
     /// Create a new project with the given name
     #[arg(short, long)]
     new: Option<String>,
+
+    /// Stay on the main screen so the output remains in the scrollback
+    #[arg(long)]
+    no_altscreen: bool,
 }
 
 fn main() -> Result<()> {
@@ -48,24 +63,60 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    let (paths, positions): (Vec<PathBuf>, Vec<_>) =
+        args.paths.into_iter().map(split_position).unzip();
+    let mut app = App::new(paths.clone())?;
+    for (path, position) in paths.iter().zip(positions) {
+        if let Some((line, col)) = position {
+            app.go_to_file_position(path, line, col);
+        }
+    }
+    let alternate_screen = app.config.ui.alternate_screen && !args.no_altscreen;
+
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    if alternate_screen {
+        execute!(stdout, EnterAlternateScreen)?;
+    }
+    execute!(stdout, EnableMouseCapture)?;
 
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(args.path)?;
     let result = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    if alternate_screen {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    } else {
+        // Wipe the UI so only the output transcript is left behind
+        terminal.clear()?;
+    }
+    execute!(terminal.backend_mut(), DisableMouseCapture)?;
     terminal.show_cursor()?;
 
+    if !alternate_screen {
+        for line in &app.output.lines {
+            println!("{}{}", app.output.line_prefix(line), line.text);
+        }
+    }
+
+    if let Err(e) = app.save_notes() {
+        eprintln!("Could not save notes: {e}");
+    }
+    if let Err(e) = app.save_file_states() {
+        eprintln!("Could not save file state: {e}");
+    }
+    if let Err(e) = app.save_session() {
+        eprintln!("Could not save session: {e}");
+    }
+    if let Err(e) = app.command_history.save(&app.project_dir) {
+        eprintln!("Could not save command history: {e}");
+    }
+    if let Err(e) = app.search_history.save(&app.project_dir) {
+        eprintln!("Could not save search history: {e}");
+    }
+
     if let Err(e) = result {
         eprintln!("Error: {e}");
         std::process::exit(1);
@@ -74,8 +125,42 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Split a trailing `:line` or `:line:col` off a command-line path. Only
+/// all-digit segments count, and a path that exists as written is left
+/// alone, so colons elsewhere in a name are kept.
+fn split_position(path: PathBuf) -> (PathBuf, Option<(usize, usize)>) {
+    if path.exists() {
+        return (path, None);
+    }
+    let Some(text) = path.to_str() else {
+        return (path, None);
+    };
+    // `parse` alone would also take a sign
+    let number = |part: &str| -> Option<usize> {
+        if !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        part.parse().ok()
+    };
+    let Some((rest, last)) = text
+        .rsplit_once(':')
+        .and_then(|(rest, last)| Some((rest, number(last)?)))
+    else {
+        return (path, None);
+    };
+    match rest
+        .rsplit_once(':')
+        .and_then(|(file, line)| Some((file, number(line)?)))
+    {
+        Some((file, line)) if !file.is_empty() => (PathBuf::from(file), Some((line, last))),
+        _ if !rest.is_empty() => (PathBuf::from(rest), Some((last, 1))),
+        _ => (path, None),
+    }
+}
+
 fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     loop {
+        app.refresh_outline();
         terminal.draw(|frame| ui::render(frame, app))?;
 
         // Update editor visible height for proper scroll handling after resize
@@ -84,18 +169,16 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()>
 
         // Check autosave
         app.check_autosave();
+        app.poll_build()?;
+        app.expire_status_message();
 
         if let Some(action) = input::handle_event(app)? {
             match action {
                 input::Action::Quit => break,
                 input::Action::Build => app.build()?,
+                input::Action::Check => app.check(),
                 input::Action::Run => app.run()?,
-                input::Action::BuildAndRun => {
-                    app.build()?;
-                    if app.build_succeeded() {
-                        app.run()?;
-                    }
-                }
+                input::Action::BuildAndRun => app.build_and_run()?,
                 input::Action::Save => {
                     app.save_current_file()?;
                     app.last_save_time = std::time::Instant::now();
@@ -106,3 +189,29 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()>
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_position_takes_trailing_numbers() {
+        let split = |text: &str| split_position(PathBuf::from(text));
+        assert_eq!(
+            split("no/such/main.asm:42"),
+            (PathBuf::from("no/such/main.asm"), Some((42, 1)))
+        );
+        assert_eq!(
+            split("main.asm:42:7"),
+            (PathBuf::from("main.asm"), Some((42, 7)))
+        );
+        assert_eq!(
+            split(r"C:\src\main.asm:3"),
+            (PathBuf::from(r"C:\src\main.asm"), Some((3, 1)))
+        );
+        assert_eq!(split("main.asm:x"), (PathBuf::from("main.asm:x"), None));
+        assert_eq!(split("main.asm:"), (PathBuf::from("main.asm:"), None));
+        assert_eq!(split(":12"), (PathBuf::from(":12"), None));
+        assert_eq!(split("."), (PathBuf::from("."), None));
+    }
+}
@@ -58,6 +58,11 @@ pub const DIRECTIVES: &[&str] = &[
     "uses",
     "segment",
     "ends",
+    "struct",
+    "struc",
+    "union",
+    "record",
+    "typedef",
     "assume",
     "org",
     "align",
@@ -85,6 +90,13 @@ pub const TYPE_KEYWORDS: &[&str] = &[
     "fortran", "basic",
 ];
 
+/// Words that, following a name, make it a data or structure definition
+pub const DEFINITION_KEYWORDS: &[&str] = &[
+    "byte", "sbyte", "word", "sword", "dword", "sdword", "qword", "sqword", "real4", "real8",
+    "real10", "tbyte", "fword", "oword", "xmmword", "ymmword", "db", "dw", "dd", "df", "dq", "dt",
+    "struct", "struc", "union", "record", "typedef",
+];
+
 /// Irvine32 library functions for autocomplete
 pub const IRVINE32_FUNCTIONS: &[&str] = &[
     "WriteString",
@@ -127,3 +139,320 @@ pub const IRVINE32_FUNCTIONS: &[&str] = &[
     "ReadFromFile",
     "WriteToFile",
 ];
+
+/// NASM directives and preprocessor words not shared with MASM
+pub const NASM_DIRECTIVES: &[&str] = &[
+    "section",
+    "segment",
+    "global",
+    "extern",
+    "bits",
+    "default",
+    "resb",
+    "resw",
+    "resd",
+    "resq",
+    "rest",
+    "times",
+    "incbin",
+    "%define",
+    "%undef",
+    "%macro",
+    "%endmacro",
+    "%include",
+    "%if",
+    "%ifdef",
+    "%ifndef",
+    "%else",
+    "%endif",
+    "%assign",
+    "%rep",
+    "%endrep",
+];
+
+/// Assembly syntax a buffer is written in. Selects highlighting rules and
+/// whether the JWasm pipeline can build it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    #[default]
+    Masm,
+    Tasm,
+    Nasm,
+    Gas,
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [Dialect::Masm, Dialect::Tasm, Dialect::Nasm, Dialect::Gas];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dialect::Masm => "MASM",
+            Dialect::Tasm => "TASM",
+            Dialect::Nasm => "NASM",
+            Dialect::Gas => "GAS",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|d| d.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the bundled JWasm pipeline assembles this syntax
+    pub fn buildable(&self) -> bool {
+        *self == Dialect::Masm
+    }
+
+    /// Guess the dialect from the file extension and its first lines:
+    /// `.s` is GAS, `section .text` means NASM, `IDEAL` means TASM and
+    /// everything else (including `.model`) is MASM.
+    pub fn detect(extension: Option<&str>, content: &str) -> Self {
+        if matches!(extension, Some("s") | Some("S")) {
+            return Dialect::Gas;
+        }
+        for line in content.lines().take(200) {
+            let line = line.trim().to_ascii_lowercase();
+            let first = line.split_whitespace().next().unwrap_or("");
+            match first {
+                ".model" => return Dialect::Masm,
+                "ideal" => return Dialect::Tasm,
+                "section" | "segment" | "global" | "bits" | "%define" | "%include" => {
+                    return Dialect::Nasm
+                }
+                _ => {}
+            }
+        }
+        Dialect::Masm
+    }
+}
+
+/// Radix of a numeric literal, given by its suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+    Octal,
+}
+
+impl Radix {
+    /// Next base when cycling a literal: decimal → hex → binary → decimal
+    pub fn next(self) -> Self {
+        match self {
+            Radix::Decimal => Radix::Hex,
+            Radix::Hex => Radix::Binary,
+            Radix::Binary | Radix::Octal => Radix::Decimal,
+        }
+    }
+}
+
+/// Parse a MASM integer literal such as `255`, `0FFh`, `1010b`, `17o` or
+/// `100d`. Unsuffixed literals are decimal (the default `.RADIX`).
+pub fn parse_number(literal: &str) -> Option<(u64, Radix)> {
+    let literal = literal.replace('_', "");
+    let last = literal.chars().last()?;
+    let (digits, radix) = match last.to_ascii_lowercase() {
+        'h' => (&literal[..literal.len() - 1], Radix::Hex),
+        'b' | 'y' => (&literal[..literal.len() - 1], Radix::Binary),
+        'o' | 'q' => (&literal[..literal.len() - 1], Radix::Octal),
+        'd' | 't' => (&literal[..literal.len() - 1], Radix::Decimal),
+        _ => (literal.as_str(), Radix::Decimal),
+    };
+    // MASM literals always start with a digit, even hex ones
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let base = match radix {
+        Radix::Decimal => 10,
+        Radix::Hex => 16,
+        Radix::Binary => 2,
+        Radix::Octal => 8,
+    };
+    u64::from_str_radix(digits, base)
+        .ok()
+        .map(|value| (value, radix))
+}
+
+/// Format `value` as a MASM literal in `radix`. Hex gets a leading zero
+/// when it would otherwise start with a letter (`0FFh`, not `FFh`).
+pub fn format_number(value: u64, radix: Radix) -> String {
+    match radix {
+        Radix::Decimal => value.to_string(),
+        Radix::Hex => {
+            let hex = format!("{:X}", value);
+            if hex.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                format!("0{}h", hex)
+            } else {
+                format!("{}h", hex)
+            }
+        }
+        Radix::Binary => format!("{:b}b", value),
+        Radix::Octal => format!("{:o}o", value),
+    }
+}
+
+/// Bytes per element for a data-definition directive (`BYTE`, `DW`, ...)
+pub fn data_type_size(directive: &str) -> Option<usize> {
+    let size = match directive.to_ascii_uppercase().as_str() {
+        "BYTE" | "SBYTE" | "DB" => 1,
+        "WORD" | "SWORD" | "DW" => 2,
+        "DWORD" | "SDWORD" | "DD" | "REAL4" => 4,
+        "FWORD" | "DF" => 6,
+        "QWORD" | "DQ" | "REAL8" => 8,
+        "TBYTE" | "DT" | "REAL10" => 10,
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// Split `text` at top-level commas, ignoring those inside quotes or
+/// parentheses. Stops at a `;` comment.
+fn split_initializers(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                items.push(text[start..i].trim());
+                start = i + 1;
+            }
+            (None, ';') => {
+                items.push(text[start..i].trim());
+                return items;
+            }
+            _ => {}
+        }
+    }
+    items.push(text[start..].trim());
+    items
+}
+
+/// Bytes taken by an initializer list whose elements are `size` bytes.
+/// None if it cannot be parsed or the size does not fit in a `usize`.
+fn initializer_bytes(text: &str, size: usize) -> Option<usize> {
+    let mut total: usize = 0;
+    for item in split_initializers(text) {
+        if item.is_empty() {
+            return None;
+        }
+        let first = item.chars().next()?;
+        if first == '"' || first == '\'' {
+            // Doubled quotes stand for one quote character
+            let inner = item.get(1..item.len().checked_sub(1)?)?;
+            let quote = first.to_string();
+            let chars = inner.replace(&quote.repeat(2), &quote).chars().count();
+            // Wider types pack a short string into a single element
+            total = total.checked_add(if size == 1 { chars } else { size })?;
+        } else if let Some(pos) = item.to_ascii_uppercase().find(" DUP") {
+            let (count, _) = parse_number(item[..pos].trim())?;
+            let inner = item[pos + 4..].trim();
+            let inner = inner.strip_prefix('(')?.strip_suffix(')')?;
+            let count = usize::try_from(count).ok()?;
+            total = total.checked_add(count.checked_mul(initializer_bytes(inner, size)?)?)?;
+        } else {
+            total = total.checked_add(size)?;
+        }
+    }
+    Some(total)
+}
+
+/// Size in bytes of the data definition on `line`, such as
+/// `msg BYTE "Hi", 0` (3) or `buf DWORD 10 DUP(?)` (40). Instructions
+/// with a sized operand (`mov BYTE PTR [esi], 0`) are not definitions.
+pub fn data_definition_size(line: &str) -> Option<usize> {
+    let mut rest = line.trim_start();
+    for n in 0..2 {
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..word_end];
+        if word.starts_with(';') {
+            return None;
+        }
+        if let Some(size) = data_type_size(word) {
+            let items = &rest[word_end..];
+            let after_type = items.trim_start().get(..3);
+            if after_type.is_some_and(|w| w.eq_ignore_ascii_case("PTR")) {
+                return None;
+            }
+            return initializer_bytes(items, size);
+        }
+        if n == 0
+            && (KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word))
+                || crate::docs::is_instruction(word))
+        {
+            return None;
+        }
+        // First word may be the variable name
+        rest = rest[word_end..].trim_start();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_base_cycle() {
+        assert_eq!(parse_number("255"), Some((255, Radix::Decimal)));
+        assert_eq!(parse_number("0FFh"), Some((255, Radix::Hex)));
+        assert_eq!(parse_number("1111_1111b"), Some((255, Radix::Binary)));
+        assert_eq!(parse_number("FFh"), None);
+        assert_eq!(parse_number("eax"), None);
+
+        assert_eq!(format_number(255, Radix::Hex), "0FFh");
+        assert_eq!(format_number(16, Radix::Hex), "10h");
+        assert_eq!(format_number(255, Radix::Binary), "11111111b");
+        assert_eq!(Radix::Binary.next(), Radix::Decimal);
+    }
+
+    #[test]
+    fn test_data_definition_size() {
+        assert_eq!(
+            data_definition_size("msg BYTE \"Hello\", 0dh, 0ah, 0"),
+            Some(8)
+        );
+        assert_eq!(data_definition_size("buf DWORD 10 DUP(?) ; ten"), Some(40));
+        assert_eq!(
+            data_definition_size("grid WORD 3 DUP(2 DUP(0), 1)"),
+            Some(18)
+        );
+        assert_eq!(data_definition_size("  BYTE 'It''s', 0"), Some(5));
+        assert_eq!(data_definition_size("s BYTE \"a;b\""), Some(3));
+        assert_eq!(data_definition_size("mov eax, 1"), None);
+        assert_eq!(data_definition_size("mov BYTE PTR [esi],0"), None);
+        assert_eq!(data_definition_size("  inc WORD PTR count"), None);
+        assert_eq!(data_definition_size("p DWORD PTR 0"), None);
+        assert_eq!(
+            data_definition_size("x QWORD 4000000000 DUP(4000000000 DUP(4000000000 DUP(?)))"),
+            None
+        );
+        assert_eq!(data_definition_size("; BYTE 1"), None);
+    }
+
+    #[test]
+    fn test_dialect_detection() {
+        assert_eq!(Dialect::detect(Some("s"), ".text"), Dialect::Gas);
+        assert_eq!(
+            Dialect::detect(Some("asm"), "; hi\nsection .text\nglobal _start"),
+            Dialect::Nasm
+        );
+        assert_eq!(
+            Dialect::detect(Some("asm"), ".386\n.model flat, stdcall"),
+            Dialect::Masm
+        );
+        assert_eq!(
+            Dialect::detect(Some("asm"), "IDEAL\nMODEL small"),
+            Dialect::Tasm
+        );
+        assert_eq!(Dialect::detect(Some("inc"), "x EQU 5"), Dialect::Masm);
+        assert_eq!(Dialect::from_name("nasm"), Some(Dialect::Nasm));
+    }
+}
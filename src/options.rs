@@ -0,0 +1,359 @@
+//! Options `:set` can read and change. Global options live in `Config`,
+//! which stays the one copy of their values: `:set` writes there and the
+//! editor is refreshed from it, the same way it is set up at startup.
+//! `fileformat` and `dialect` belong to the current buffer and are
+//! handled by the app.
+
+use crate::config::{Config, LineNumberMode, SearchCase};
+use anyhow::{bail, Result};
+
+pub struct OptionInfo {
+    pub name: &'static str,
+    /// Vim-style abbreviation, if the option has one
+    pub short: Option<&'static str>,
+    pub description: &'static str,
+}
+
+pub const OPTIONS: &[OptionInfo] = &[
+    OptionInfo {
+        name: "aligncomments",
+        short: None,
+        description: "Align a comment at commentcolumn when ; is typed",
+    },
+    OptionInfo {
+        name: "autoclose",
+        short: None,
+        description: "Enter after PROC/MACRO/IF inserts the closer",
+    },
+    OptionInfo {
+        name: "autoindent",
+        short: Some("ai"),
+        description: "New lines keep the indent of the line above",
+    },
+    OptionInfo {
+        name: "autolabel",
+        short: None,
+        description: "Add a missing label colon and align what follows",
+    },
+    OptionInfo {
+        name: "autosave",
+        short: None,
+        description: "Save modified buffers periodically",
+    },
+    OptionInfo {
+        name: "commentbelow",
+        short: None,
+        description: "gc puts the commented copy below the line",
+    },
+    OptionInfo {
+        name: "commentcolumn",
+        short: None,
+        description: "Column aligncomments moves comments to",
+    },
+    OptionInfo {
+        name: "confirmdeletes",
+        short: None,
+        description: "Ask before deleting more than deletethreshold lines",
+    },
+    OptionInfo {
+        name: "cursorline",
+        short: Some("cul"),
+        description: "Shade the row the cursor is on",
+    },
+    OptionInfo {
+        name: "deletethreshold",
+        short: None,
+        description: "Lines one command may delete without asking",
+    },
+    OptionInfo {
+        name: "dialect",
+        short: None,
+        description: "Assembler dialect of the buffer (masm, tasm, nasm, gas)",
+    },
+    OptionInfo {
+        name: "expandtab",
+        short: Some("et"),
+        description: "Tab inserts spaces",
+    },
+    OptionInfo {
+        name: "fileformat",
+        short: Some("ff"),
+        description: "Line endings of the buffer (unix, dos)",
+    },
+    OptionInfo {
+        name: "foldminlines",
+        short: None,
+        description: "Shortest comment run zC folds",
+    },
+    OptionInfo {
+        name: "ignorecase",
+        short: Some("ic"),
+        description: "Searches and :s ignore case",
+    },
+    OptionInfo {
+        name: "instructioncolumn",
+        short: None,
+        description: "Column autolabel moves instructions to",
+    },
+    OptionInfo {
+        name: "longline",
+        short: None,
+        description: "Lines longer than this are not highlighted",
+    },
+    OptionInfo {
+        name: "matchpairs",
+        short: Some("mps"),
+        description: "Bracket pairs % jumps between",
+    },
+    OptionInfo {
+        name: "rainbow",
+        short: None,
+        description: "Color brackets by nesting depth",
+    },
+    OptionInfo {
+        name: "relativenumber",
+        short: Some("rnu"),
+        description: "Line numbers count from the cursor line",
+    },
+    OptionInfo {
+        name: "smartcase",
+        short: Some("scs"),
+        description: "Ignore case unless the search has an uppercase letter",
+    },
+    OptionInfo {
+        name: "smarttab",
+        short: Some("sta"),
+        description: "Tab in indentation goes to the next tab stop",
+    },
+    OptionInfo {
+        name: "tabsize",
+        short: Some("ts"),
+        description: "Width of a tab stop",
+    },
+    OptionInfo {
+        name: "textwidth",
+        short: Some("tw"),
+        description: "Column :reflow wraps comments at",
+    },
+    OptionInfo {
+        name: "wrap",
+        short: None,
+        description: "Long lines continue on the next screen row",
+    },
+];
+
+/// Find an option by name or abbreviation
+pub fn lookup(name: &str) -> Option<&'static OptionInfo> {
+    OPTIONS
+        .iter()
+        .find(|opt| opt.name == name || opt.short == Some(name))
+}
+
+/// Where a global option's value is stored in `Config`
+pub enum Slot<'a> {
+    Bool(&'a mut bool),
+    Number(&'a mut usize),
+    Text(&'a mut String),
+    /// `relativenumber`, on for relative and hybrid numbering
+    Relative(&'a mut LineNumberMode),
+    /// `ignorecase`, off only for case-sensitive search
+    IgnoreCase(&'a mut SearchCase),
+    /// `smartcase`
+    SmartCase(&'a mut SearchCase),
+}
+
+/// The `Config` field behind `name` (a full option name). None for the
+/// buffer-local options.
+pub fn slot<'a>(config: &'a mut Config, name: &str) -> Option<Slot<'a>> {
+    let editor = &mut config.editor;
+    Some(match name {
+        "aligncomments" => Slot::Bool(&mut editor.align_comments),
+        "autoclose" => Slot::Bool(&mut editor.auto_close_blocks),
+        "autoindent" => Slot::Bool(&mut editor.auto_indent),
+        "autolabel" => Slot::Bool(&mut editor.auto_label),
+        "autosave" => Slot::Bool(&mut editor.autosave),
+        "commentbelow" => Slot::Bool(&mut editor.comment_copy_below),
+        "commentcolumn" => Slot::Number(&mut editor.comment_column),
+        "confirmdeletes" => Slot::Bool(&mut editor.confirm_large_deletes),
+        "cursorline" => Slot::Bool(&mut editor.cursor_line),
+        "deletethreshold" => Slot::Number(&mut editor.confirm_delete_threshold),
+        "expandtab" => Slot::Bool(&mut editor.insert_spaces),
+        "foldminlines" => Slot::Number(&mut editor.comment_fold_min_lines),
+        "ignorecase" => Slot::IgnoreCase(&mut editor.search_case),
+        "instructioncolumn" => Slot::Number(&mut editor.instruction_column),
+        "longline" => Slot::Number(&mut editor.long_line_threshold),
+        "matchpairs" => Slot::Text(&mut editor.match_pairs),
+        "rainbow" => Slot::Bool(&mut editor.rainbow_brackets),
+        "relativenumber" => Slot::Relative(&mut editor.line_number_mode),
+        "smartcase" => Slot::SmartCase(&mut editor.search_case),
+        "smarttab" => Slot::Bool(&mut editor.smart_tab),
+        "tabsize" => Slot::Number(&mut editor.tab_size),
+        "textwidth" => Slot::Number(&mut editor.textwidth),
+        "wrap" => Slot::Bool(&mut editor.soft_wrap),
+        _ => return None,
+    })
+}
+
+/// One `:set` argument, split into the option it names and what to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetArg<'a> {
+    /// `name?`, or a bare non-boolean `name`
+    Query(&'a str),
+    /// `name`, `noname`, or `invname`/`name!` (None toggles)
+    Flag(&'a str, Option<bool>),
+    /// `name=value`
+    Assign(&'a str, &'a str),
+}
+
+impl<'a> SetArg<'a> {
+    pub fn parse(arg: &'a str) -> Self {
+        if let Some((name, value)) = arg.split_once('=') {
+            return SetArg::Assign(name, value);
+        }
+        if let Some(name) = arg.strip_suffix('?') {
+            return SetArg::Query(name);
+        }
+        if let Some(name) = arg.strip_suffix('!') {
+            return SetArg::Flag(name, None);
+        }
+        if let Some(name) = arg
+            .strip_prefix("inv")
+            .filter(|name| lookup(name).is_some())
+        {
+            return SetArg::Flag(name, None);
+        }
+        if let Some(name) = arg.strip_prefix("no").filter(|name| lookup(name).is_some()) {
+            return SetArg::Flag(name, Some(false));
+        }
+        SetArg::Flag(arg, Some(true))
+    }
+
+    /// The option as written, possibly abbreviated
+    pub fn name(&self) -> &'a str {
+        match *self {
+            SetArg::Query(name) | SetArg::Flag(name, _) | SetArg::Assign(name, _) => name,
+        }
+    }
+}
+
+/// Apply a `:set` argument to a global option. Returns the option's full
+/// name; the value is read back with `show`.
+pub fn set(config: &mut Config, arg: SetArg) -> Result<&'static str> {
+    let Some(info) = lookup(arg.name()) else {
+        bail!("Unknown option: {}", arg.name());
+    };
+    let Some(slot) = slot(config, info.name) else {
+        bail!("{} is not a global option", info.name);
+    };
+    match (slot, arg) {
+        (_, SetArg::Query(_)) => {}
+        (Slot::Bool(value), SetArg::Flag(_, on)) => *value = on.unwrap_or(!*value),
+        (Slot::Bool(value), SetArg::Assign(_, text)) => *value = parse_flag(info.name, text)?,
+        (Slot::Relative(mode), SetArg::Flag(_, on)) => {
+            mode.set_relative(on.unwrap_or(*mode == LineNumberMode::Absolute))
+        }
+        (Slot::Relative(mode), SetArg::Assign(_, text)) => {
+            mode.set_relative(parse_flag(info.name, text)?)
+        }
+        (Slot::IgnoreCase(case), SetArg::Flag(_, on)) => {
+            case.set_ignore_case(on.unwrap_or(*case == SearchCase::Sensitive))
+        }
+        (Slot::IgnoreCase(case), SetArg::Assign(_, text)) => {
+            case.set_ignore_case(parse_flag(info.name, text)?)
+        }
+        (Slot::SmartCase(case), SetArg::Flag(_, on)) => {
+            case.set_smart_case(on.unwrap_or(*case != SearchCase::Smart))
+        }
+        (Slot::SmartCase(case), SetArg::Assign(_, text)) => {
+            case.set_smart_case(parse_flag(info.name, text)?)
+        }
+        // A bare number or text option shows its value, as in vim
+        (_, SetArg::Flag(_, Some(true))) => {}
+        (_, SetArg::Flag(..)) => bail!("{} is not a boolean option", info.name),
+        (Slot::Number(value), SetArg::Assign(_, text)) => match text.parse() {
+            Ok(n) if n > 0 => *value = n,
+            _ => bail!("{} needs a number of at least 1", info.name),
+        },
+        (Slot::Text(value), SetArg::Assign(_, text)) => *value = text.to_string(),
+    }
+    Ok(info.name)
+}
+
+/// Value of `name=on` or `name=off` for a boolean option
+fn parse_flag(name: &str, text: &str) -> Result<bool> {
+    Ok(match text {
+        "on" | "true" | "1" => true,
+        "off" | "false" | "0" => false,
+        _ => bail!("{} is on or off", name),
+    })
+}
+
+/// A global option as `:set` prints it: `name=value`, or `name`/`noname`
+pub fn show(config: &mut Config, name: &str) -> Option<String> {
+    Some(match slot(config, name)? {
+        Slot::Bool(true) => name.to_string(),
+        Slot::Bool(false) => format!("no{}", name),
+        Slot::Relative(mode) if *mode != LineNumberMode::Absolute => name.to_string(),
+        Slot::Relative(_) => format!("no{}", name),
+        Slot::IgnoreCase(case) if *case != SearchCase::Sensitive => name.to_string(),
+        Slot::SmartCase(case) if *case == SearchCase::Smart => name.to_string(),
+        Slot::IgnoreCase(_) | Slot::SmartCase(_) => format!("no{}", name),
+        Slot::Number(value) => format!("{}={}", name, value),
+        Slot::Text(value) => format!("{}={}", name, value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_arguments_change_config() {
+        let mut config = Config::default();
+        assert_eq!(set(&mut config, SetArg::parse("ts=8")).unwrap(), "tabsize");
+        assert_eq!(config.editor.tab_size, 8);
+        set(&mut config, SetArg::parse("noet")).unwrap();
+        assert_eq!(show(&mut config, "expandtab").unwrap(), "noexpandtab");
+        set(&mut config, SetArg::parse("invexpandtab")).unwrap();
+        let auto_label = config.editor.auto_label;
+        set(&mut config, SetArg::parse("autolabel!")).unwrap();
+        assert!(config.editor.insert_spaces);
+        assert_eq!(config.editor.auto_label, !auto_label);
+
+        set(&mut config, SetArg::parse("rnu")).unwrap();
+        assert_eq!(config.editor.line_number_mode, LineNumberMode::Relative);
+        assert_eq!(config.editor.line_number_mode.number(3, 7), 4);
+        config.editor.line_number_mode = LineNumberMode::Hybrid;
+        set(&mut config, SetArg::parse("rnu")).unwrap();
+        assert_eq!(config.editor.line_number_mode.number(7, 7), 8);
+        set(&mut config, SetArg::parse("nornu")).unwrap();
+        assert_eq!(
+            show(&mut config, "relativenumber").unwrap(),
+            "norelativenumber"
+        );
+
+        assert_eq!(config.editor.search_case, SearchCase::Smart);
+        set(&mut config, SetArg::parse("noscs")).unwrap();
+        assert_eq!(config.editor.search_case, SearchCase::Insensitive);
+        set(&mut config, SetArg::parse("noic")).unwrap();
+        assert_eq!(config.editor.search_case, SearchCase::Sensitive);
+        assert_eq!(show(&mut config, "smartcase").unwrap(), "nosmartcase");
+        set(&mut config, SetArg::parse("ic")).unwrap();
+        set(&mut config, SetArg::parse("smartcase")).unwrap();
+        assert!(config.editor.search_case.ignores_case("eax"));
+        assert!(!config.editor.search_case.ignores_case("EAX"));
+
+        assert_eq!(SetArg::parse("tw?"), SetArg::Query("tw"));
+        assert_eq!(SetArg::parse("notes"), SetArg::Flag("notes", Some(true)));
+        assert!(set(&mut config, SetArg::parse("ts=0")).is_err());
+        assert!(set(&mut config, SetArg::parse("nots")).is_err());
+        assert!(set(&mut config, SetArg::parse("ff=dos")).is_err());
+        assert!(set(&mut config, SetArg::parse("bogus")).is_err());
+
+        // Every listed option is either global or handled per buffer
+        for opt in OPTIONS {
+            let local = matches!(opt.name, "fileformat" | "dialect");
+            assert_eq!(show(&mut config, opt.name).is_none(), local, "{}", opt.name);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::config::ProjectConfig;
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const HELLO_TEMPLATE: &str = r#"; =============================================================================
 ; Program: Hello World
@@ -27,6 +27,31 @@ main ENDP
 END main
 "#;
 
+/// Program wrapped around a `:scratch` snippet. `{{snippet}}` is replaced
+/// by the selected instructions.
+pub const SCRATCH_TEMPLATE: &str = r#"INCLUDE Irvine32.inc
+
+.code
+main PROC
+{{snippet}}
+    call DumpRegs
+    exit
+main ENDP
+
+END main
+"#;
+
+/// Build a runnable program from `template` with `snippet` spliced in.
+/// Snippet lines are indented to sit inside the procedure body.
+pub fn scratch_program(template: &str, snippet: &str) -> String {
+    let body = snippet
+        .lines()
+        .map(|line| format!("    {}", line.trim()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    template.replace("{{snippet}}", &body)
+}
+
 pub fn create_new_project(name: &str) -> Result<()> {
     let project_dir = PathBuf::from(name);
 
@@ -46,6 +71,7 @@ pub fn create_new_project(name: &str) -> Result<()> {
         name: name.to_string(),
         entry_file: PathBuf::from("main.asm"),
         output_name: format!("{}.exe", name),
+        assembler: Default::default(),
         include_paths: vec![],
         lib_paths: vec![],
         libs: vec![
@@ -53,6 +79,9 @@ pub fn create_new_project(name: &str) -> Result<()> {
             String::from("kernel32"),
             String::from("user32"),
         ],
+        targets: vec![],
+        run_args: vec![],
+        run_stdin_file: None,
     };
     config.save(&project_dir)?;
 
@@ -97,3 +126,89 @@ wine {}.exe
 
     Ok(())
 }
+
+/// `.asm` and `.inc` files under `root`, skipping hidden entries and
+/// `target` like the file tree does
+pub fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || name == "target" {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("asm") || e.eq_ignore_ascii_case("inc"))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Whether `lines` look like a program's entry file: a `main PROC` or an
+/// `END <label>` naming the start address
+pub fn has_entry_point<S: AsRef<str>>(lines: &[S]) -> bool {
+    lines.iter().any(|line| {
+        let code = line.as_ref().split(';').next().unwrap_or("");
+        let words: Vec<&str> = code.split_whitespace().collect();
+        match words.as_slice() {
+            [name, kw, ..] if kw.eq_ignore_ascii_case("proc") => name.eq_ignore_ascii_case("main"),
+            [kw, _] => kw.eq_ignore_ascii_case("end"),
+            _ => false,
+        }
+    })
+}
+
+/// First `.asm` file under `root` with an entry point, as a suggestion
+/// when the project's entry file is missing
+pub fn find_entry_file(root: &Path) -> Option<PathBuf> {
+    source_files(root)
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("asm"))
+        })
+        .find(|path| {
+            fs::read_to_string(path)
+                .map(|content| has_entry_point(&content.lines().collect::<Vec<_>>()))
+                .unwrap_or(false)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_program_wraps_snippet() {
+        let program = scratch_program(SCRATCH_TEMPLATE, "mov eax, 5\n  add eax, 3");
+        assert!(program.contains("main PROC\n    mov eax, 5\n    add eax, 3\n    call DumpRegs"));
+        assert!(!program.contains("{{snippet}}"));
+    }
+
+    #[test]
+    fn test_entry_point_detection() {
+        assert!(has_entry_point(&["main PROC", "    exit", "main ENDP"]));
+        assert!(has_entry_point(&["start:", "    ret", "END start ; go"]));
+        assert!(!has_entry_point(&[
+            "PrintIt PROC",
+            "    ret",
+            "PrintIt ENDP",
+            "END"
+        ]));
+        assert!(!has_entry_point(&["; main PROC lives in main.asm"]));
+        assert!(has_entry_point(&HELLO_TEMPLATE.lines().collect::<Vec<_>>()));
+    }
+}
@@ -0,0 +1,134 @@
+//! The set of open buffers for a project, written to
+//! `.masmide/session.json` on quit so the next start picks up where the
+//! last one left off.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ui::editor::EditorState;
+use crate::utf8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionBuffer {
+    /// Relative to the project directory when the file is inside it
+    pub path: PathBuf,
+    pub cursor_y: usize,
+    pub cursor_x: usize,
+    pub scroll_offset: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub buffers: Vec<SessionBuffer>,
+    /// Index into `buffers` of the buffer that was active
+    #[serde(default)]
+    pub active: usize,
+}
+
+impl Session {
+    /// Record every file-backed buffer in `editor` except those in `skip`
+    pub fn capture(editor: &EditorState, project_dir: &Path, skip: &[PathBuf]) -> Self {
+        let mut session = Self::default();
+        for (idx, buf) in editor.buffers.iter().enumerate() {
+            let Some(path) = buf.file_path.as_ref().filter(|p| !skip.contains(p)) else {
+                continue;
+            };
+            if idx == editor.active_buffer {
+                session.active = session.buffers.len();
+            }
+            session.buffers.push(SessionBuffer {
+                path: path.strip_prefix(project_dir).unwrap_or(path).to_path_buf(),
+                cursor_y: buf.cursor_y,
+                cursor_x: buf.cursor_x,
+                scroll_offset: buf.scroll_offset,
+            });
+        }
+        session
+    }
+
+    /// The saved session for `project_dir`, if there is one with buffers
+    pub fn load(project_dir: &Path) -> Option<Self> {
+        fs::read_to_string(Self::path(project_dir))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|session| !session.buffers.is_empty())
+    }
+
+    pub fn save(&self, project_dir: &Path) -> Result<()> {
+        let path = Self::path(project_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Open the session's buffers in `editor` and put their cursors back.
+    /// Returns how many were opened and the files that no longer exist.
+    pub fn restore(&self, editor: &mut EditorState, project_dir: &Path) -> (usize, Vec<PathBuf>) {
+        let mut opened = 0;
+        let mut missing = Vec::new();
+        let mut active = None;
+        for (i, saved) in self.buffers.iter().enumerate() {
+            let path = project_dir.join(&saved.path);
+            if !path.is_file() || editor.open_file(&path).is_err() {
+                missing.push(saved.path.clone());
+                continue;
+            }
+            opened += 1;
+            let buf = &mut editor.buffers[editor.active_buffer];
+            buf.cursor_y = saved.cursor_y.min(buf.lines.len().saturating_sub(1));
+            buf.cursor_x = utf8::clamp_to_char_boundary(&buf.lines[buf.cursor_y], saved.cursor_x);
+            buf.scroll_offset = saved.scroll_offset.min(buf.cursor_y);
+            if i == self.active {
+                active = Some(editor.active_buffer);
+            }
+        }
+        if let Some(idx) = active {
+            editor.set_active_buffer(idx);
+        }
+        (opened, missing)
+    }
+
+    fn path(project_dir: &Path) -> PathBuf {
+        project_dir.join(".masmide").join("session.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_session_round_trip_skips_deleted_files() {
+        let dir = TempDir::new("session");
+        let main = dir.join("main.asm");
+        let io = dir.join("io.inc");
+        fs::write(&main, "a\nbb\nccc\n").unwrap();
+        fs::write(&io, "x\n").unwrap();
+
+        let mut editor = EditorState::new(4);
+        editor.open_file(&main).unwrap();
+        editor.open_file(&io).unwrap();
+        editor.buffers[0].cursor_y = 2;
+        editor.buffers[0].cursor_x = 1;
+        editor.set_active_buffer(0);
+        let session = Session::capture(&editor, &dir, &[]);
+        assert_eq!(session.buffers[0].path, PathBuf::from("main.asm"));
+        session.save(&dir).unwrap();
+
+        fs::remove_file(&io).unwrap();
+        let loaded = Session::load(&dir).unwrap();
+        assert_eq!(loaded, session);
+        let mut restored = EditorState::new(4);
+        let (opened, missing) = loaded.restore(&mut restored, &dir);
+        assert_eq!((opened, missing), (1, vec![PathBuf::from("io.inc")]));
+        let buf = &restored.buffers[restored.active_buffer];
+        assert_eq!((buf.cursor_y, buf.cursor_x), (2, 1));
+    }
+}
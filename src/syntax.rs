@@ -1,6 +1,8 @@
-use crate::masm_lang::{DIRECTIVES, KEYWORDS, REGISTERS, TYPE_KEYWORDS};
-use crate::theme::{SyntaxColors, ThemeColor};
-use ratatui::style::Style;
+use crate::masm_lang::{
+    Dialect, DEFINITION_KEYWORDS, DIRECTIVES, KEYWORDS, NASM_DIRECTIVES, REGISTERS, TYPE_KEYWORDS,
+};
+use crate::theme::{SyntaxColors, TextStyle, ThemeColor};
+use crate::utf8;
 use ratatui::text::Span;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,17 +37,97 @@ impl Token {
 
 pub struct Highlighter;
 
+/// End of the number starting at `pos`: `0x1F`, or digits with an optional
+/// radix suffix (h, b/y, o/q, d/t, or r for the hex encoding of a real),
+/// or a real with a fraction and maybe an exponent (3.14, 1.5E+3)
+fn number_end(chars: &[char], mut pos: usize) -> usize {
+    let digits = |pos: &mut usize, digit: fn(&char) -> bool| {
+        while chars.get(*pos).is_some_and(digit) {
+            *pos += 1;
+        }
+    };
+    if chars[pos] == '0' && matches!(chars.get(pos + 1), Some('x' | 'X')) {
+        pos += 2;
+        digits(&mut pos, char::is_ascii_hexdigit);
+        return pos;
+    }
+    // Hex digits take in the b and d suffixes as well
+    digits(&mut pos, |c| c.is_ascii_hexdigit() || *c == '_');
+    if chars.get(pos) == Some(&'.') && chars.get(pos + 1).is_some_and(char::is_ascii_digit) {
+        pos += 1;
+        digits(&mut pos, char::is_ascii_digit);
+        let sign = usize::from(matches!(chars.get(pos + 1), Some('+' | '-')));
+        if matches!(chars.get(pos), Some('e' | 'E'))
+            && chars.get(pos + 1 + sign).is_some_and(char::is_ascii_digit)
+        {
+            pos += 1 + sign;
+            digits(&mut pos, char::is_ascii_digit);
+        }
+    } else if chars
+        .get(pos)
+        .is_some_and(|c| matches!(c.to_ascii_lowercase(), 'h' | 'y' | 'o' | 'q' | 't' | 'r'))
+    {
+        pos += 1;
+    }
+    pos
+}
+
+/// Whether an operand can start after `tokens`: at the start of the line,
+/// after a mnemonic or directive, or after an operator other than a
+/// closing bracket
+fn starts_operand(tokens: &[Token]) -> bool {
+    match tokens.iter().rev().find(|t| !t.text.trim().is_empty()) {
+        None => true,
+        Some(t) => match t.token_type {
+            TokenType::Operator => !matches!(t.text.as_str(), ")" | "]"),
+            TokenType::Keyword | TokenType::Directive | TokenType::TypeKeyword => true,
+            _ => false,
+        },
+    }
+}
+
+/// Color the name in `pi REAL8 3.14` or `POINT STRUCT` as a label, the
+/// way a `name:` code label is
+fn mark_definition_name(tokens: &mut [Token]) {
+    let mut words = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.text.trim().is_empty());
+    let (Some((name, first)), Some((_, second))) = (words.next(), words.next()) else {
+        return;
+    };
+    let is_name = first.token_type == TokenType::Plain
+        && first
+            .text
+            .starts_with(|c: char| c.is_alphabetic() || matches!(c, '_' | '@' | '?'));
+    if is_name && DEFINITION_KEYWORDS.contains(&second.text.to_lowercase().as_str()) {
+        tokens[name].token_type = TokenType::Label;
+    }
+}
+
 impl Highlighter {
     pub fn tokenize_line(line: &str) -> Vec<Token> {
+        Self::tokenize_line_as(line, Dialect::Masm)
+    }
+
+    /// Tokenize `line` using the rules of `dialect`. GAS comments start
+    /// with `#` and prefix registers with `%` and immediates with `$`.
+    pub fn tokenize_line_as(line: &str, dialect: Dialect) -> Vec<Token> {
         let mut tokens = Vec::new();
         let chars: Vec<char> = line.chars().collect();
         let mut pos = 0;
+        let gas = dialect == Dialect::Gas;
 
         while pos < chars.len() {
             let ch = chars[pos];
 
-            // Comment - everything from ; to end of line
-            if ch == ';' {
+            // Comment - everything from ; (# in GAS) to end of line
+            let comment_start = if gas {
+                ch == '#' || (ch == '/' && chars.get(pos + 1) == Some(&'/'))
+            } else {
+                ch == ';'
+            };
+            if comment_start {
                 let comment: String = chars[pos..].iter().collect();
                 tokens.push(Token::new(comment, TokenType::Comment));
                 break;
@@ -81,43 +163,36 @@ impl Highlighter {
                 continue;
             }
 
-            // Numbers (hex, binary, decimal)
-            if ch.is_ascii_digit()
-                || (ch == '0'
-                    && pos + 1 < chars.len()
-                    && (chars[pos + 1] == 'x' || chars[pos + 1] == 'X'))
-            {
+            // GAS immediates: $10, $0x1f
+            if gas && ch == '$' && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit()) {
                 let start = pos;
-
-                // Check for 0x prefix
-                if ch == '0'
-                    && pos + 1 < chars.len()
-                    && (chars[pos + 1] == 'x' || chars[pos + 1] == 'X')
+                pos += 1;
+                while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_')
                 {
-                    pos += 2;
-                    while pos < chars.len() && chars[pos].is_ascii_hexdigit() {
-                        pos += 1;
-                    }
-                } else {
-                    // Regular number, could end with h (hex), b (binary), d (decimal), o (octal)
-                    while pos < chars.len() && (chars[pos].is_ascii_hexdigit() || chars[pos] == '_')
-                    {
-                        pos += 1;
-                    }
-                    // Check for suffix
-                    if pos < chars.len()
-                        && matches!(chars[pos].to_ascii_lowercase(), 'h' | 'b' | 'd' | 'o')
-                    {
-                        pos += 1;
-                    }
+                    pos += 1;
                 }
                 let num: String = chars[start..pos].iter().collect();
                 tokens.push(Token::new(num, TokenType::Number));
                 continue;
             }
 
+            // Numbers in any radix, and reals. A minus where an operand
+            // starts is part of the number (mov eax, -1) rather than an
+            // operator (ebp-4).
+            let negative = ch == '-'
+                && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit())
+                && starts_operand(&tokens);
+            if ch.is_ascii_digit() || negative {
+                let start = pos;
+                pos = number_end(&chars, pos + usize::from(negative));
+                let num: String = chars[start..pos].iter().collect();
+                tokens.push(Token::new(num, TokenType::Number));
+                continue;
+            }
+
             // Identifier or keyword
-            if ch.is_alphabetic() || ch == '_' || ch == '.' || ch == '@' {
+            let sigil = ch == '%' && dialect != Dialect::Masm && dialect != Dialect::Tasm;
+            if ch.is_alphabetic() || ch == '_' || ch == '.' || ch == '@' || sigil {
                 let start = pos;
                 pos += 1;
                 while pos < chars.len()
@@ -130,6 +205,12 @@ impl Highlighter {
                 }
                 let word: String = chars[start..pos].iter().collect();
                 let lower = word.to_lowercase();
+                // GAS writes registers as %eax
+                let bare = if gas {
+                    lower.trim_start_matches('%')
+                } else {
+                    lower.as_str()
+                };
 
                 // Check if followed by colon (label)
                 let is_label = pos < chars.len() && chars[pos] == ':';
@@ -140,13 +221,20 @@ impl Highlighter {
                     let label: String = chars[start..pos].iter().collect();
                     tokens.push(Token::new(label, TokenType::Label));
                     continue;
-                } else if KEYWORDS.contains(&lower.as_str()) {
+                } else if !gas && matches!(bare, "@b" | "@f") {
+                    // References to the nearest anonymous @@: label
+                    TokenType::Label
+                } else if KEYWORDS.contains(&bare) {
                     TokenType::Keyword
-                } else if REGISTERS.contains(&lower.as_str()) {
+                } else if REGISTERS.contains(&bare) {
                     TokenType::Register
-                } else if DIRECTIVES.contains(&lower.as_str()) {
+                } else if match dialect {
+                    Dialect::Masm | Dialect::Tasm => DIRECTIVES.contains(&bare),
+                    Dialect::Nasm => NASM_DIRECTIVES.contains(&bare) || DIRECTIVES.contains(&bare),
+                    Dialect::Gas => bare.starts_with('.'),
+                } {
                     TokenType::Directive
-                } else if TYPE_KEYWORDS.contains(&lower.as_str()) {
+                } else if TYPE_KEYWORDS.contains(&bare) {
                     TokenType::TypeKeyword
                 } else {
                     TokenType::Plain
@@ -172,56 +260,84 @@ impl Highlighter {
             pos += 1;
         }
 
+        if matches!(dialect, Dialect::Masm | Dialect::Tasm) {
+            mark_definition_name(&mut tokens);
+        }
         tokens
     }
 
     pub fn highlight_line<'a>(line: &str, syntax_colors: &SyntaxColors) -> Vec<Span<'a>> {
-        let tokens = Self::tokenize_line(line);
+        Self::highlight_line_as(line, Dialect::Masm, syntax_colors)
+    }
+
+    pub fn highlight_line_as<'a>(
+        line: &str,
+        dialect: Dialect,
+        syntax_colors: &SyntaxColors,
+    ) -> Vec<Span<'a>> {
+        let tokens = Self::tokenize_line_as(line, dialect);
 
         tokens
             .into_iter()
             .map(|token| {
-                let color = match token.token_type {
-                    TokenType::Keyword => &syntax_colors.keyword,
-                    TokenType::Register => &syntax_colors.register,
-                    TokenType::Directive => &syntax_colors.directive,
-                    TokenType::Number => &syntax_colors.number,
-                    TokenType::String => &syntax_colors.string,
-                    TokenType::Comment => &syntax_colors.comment,
-                    TokenType::Label => &syntax_colors.label,
-                    TokenType::Operator => &syntax_colors.operator,
-                    TokenType::TypeKeyword => &syntax_colors.type_kw,
-                    TokenType::MacroCall => &syntax_colors.macro_call,
-                    TokenType::Plain => &syntax_colors.operator, // Use operator color for plain text (usually foreground)
+                let styles = &syntax_colors.styles;
+                let (color, text_style) = match token.token_type {
+                    TokenType::Keyword => (&syntax_colors.keyword, styles.keyword),
+                    TokenType::Register => (&syntax_colors.register, styles.register),
+                    TokenType::Directive => (&syntax_colors.directive, styles.directive),
+                    TokenType::Number => (&syntax_colors.number, styles.number),
+                    TokenType::String => (&syntax_colors.string, styles.string),
+                    TokenType::Comment => (&syntax_colors.comment, styles.comment),
+                    TokenType::Label => (&syntax_colors.label, styles.label),
+                    TokenType::Operator => (&syntax_colors.operator, styles.operator),
+                    TokenType::TypeKeyword => (&syntax_colors.type_kw, styles.type_kw),
+                    TokenType::MacroCall => (&syntax_colors.macro_call, styles.macro_call),
+                    // Plain text uses the operator color (usually foreground) without modifiers
+                    TokenType::Plain => (&syntax_colors.operator, TextStyle::default()),
                 };
-                Span::styled(token.text, Style::default().fg(color.to_color()))
+                Span::styled(token.text, text_style.to_style(color))
             })
             .collect()
     }
 
+    /// Recolor the ( ) and [ ] spans of a highlighted line by nesting
+    /// depth, cycling through `palette`. Brackets are operator tokens, so
+    /// each is a span of its own and strings and comments are left alone.
+    pub fn rainbow_brackets<'a>(mut spans: Vec<Span<'a>>, palette: &[ThemeColor]) -> Vec<Span<'a>> {
+        if palette.is_empty() {
+            return spans;
+        }
+        let mut depth: usize = 0;
+        for span in &mut spans {
+            let level = match span.content.as_ref() {
+                "(" | "[" => {
+                    depth += 1;
+                    depth - 1
+                }
+                ")" | "]" => {
+                    depth = depth.saturating_sub(1);
+                    depth
+                }
+                _ => continue,
+            };
+            span.style = span.style.fg(palette[level % palette.len()].to_color());
+        }
+        spans
+    }
+
     /// Highlight line with search matches
+    #[allow(clippy::too_many_arguments)]
     pub fn highlight_line_with_search<'a>(
         line: &str,
+        dialect: Dialect,
         syntax_colors: &SyntaxColors,
-        search_query: Option<&str>,
+        matches: &[(usize, usize)], // byte ranges of search matches in this line
         search_match_color: &ThemeColor,
         current_match_positions: &[(usize, usize)], // (line, col) of current matches
         line_index: usize,
         current_match_color: &ThemeColor,
     ) -> Vec<Span<'a>> {
-        let base_spans = Self::highlight_line(line, syntax_colors);
-
-        let query = match search_query {
-            Some(q) if !q.is_empty() => q,
-            _ => return base_spans,
-        };
-
-        // Find all matches in this line
-        let matches: Vec<(usize, usize)> = line
-            .to_lowercase()
-            .match_indices(&query.to_lowercase())
-            .map(|(start, _)| (start, start + query.len()))
-            .collect();
+        let base_spans = Self::highlight_line_as(line, dialect, syntax_colors);
 
         if matches.is_empty() {
             return base_spans;
@@ -238,27 +354,30 @@ impl Highlighter {
             let base_style = span.style;
 
             let mut current_pos = 0;
-            let span_chars: Vec<char> = span_text.chars().collect();
 
-            for &(match_start, match_end) in &matches {
+            for &(match_start, match_end) in matches {
                 // Check if match overlaps with this span
                 if match_end <= span_start || match_start >= span_end {
                     continue;
                 }
 
                 // Calculate overlap
-                let overlap_start = match_start.saturating_sub(span_start);
-                let overlap_end = (match_end - span_start).min(span_text.len());
+                // Offsets are bytes, kept on character boundaries
+                let overlap_start = utf8::clamp_to_char_boundary(
+                    &span_text,
+                    match_start.saturating_sub(span_start),
+                );
+                let overlap_end = utf8::clamp_to_char_boundary(&span_text, match_end - span_start);
 
                 // Add text before match
                 if overlap_start > current_pos {
-                    let before: String = span_chars[current_pos..overlap_start].iter().collect();
+                    let before = span_text[current_pos..overlap_start].to_string();
                     result.push(Span::styled(before, base_style));
                 }
 
                 // Add matched text with highlight
                 if overlap_end > overlap_start {
-                    let matched: String = span_chars[overlap_start..overlap_end].iter().collect();
+                    let matched = span_text[overlap_start..overlap_end].to_string();
                     let is_current = current_match_positions
                         .iter()
                         .any(|&(l, c)| l == line_index && c == match_start);
@@ -271,7 +390,7 @@ impl Highlighter {
 
                     result.push(Span::styled(
                         matched,
-                        Style::default()
+                        base_style
                             .bg(highlight_color.to_color())
                             .fg(base_style.fg.unwrap_or(ratatui::style::Color::Black)),
                     ));
@@ -281,7 +400,7 @@ impl Highlighter {
 
             // Add remaining text
             if current_pos < span_text.len() {
-                let remaining: String = span_chars[current_pos..].iter().collect();
+                let remaining = span_text[current_pos..].to_string();
                 result.push(Span::styled(remaining, base_style));
             }
 
@@ -323,6 +442,75 @@ mod tests {
         assert!(tokens.iter().any(|t| t.token_type == TokenType::Label));
     }
 
+    #[test]
+    fn test_tokenize_anonymous_labels() {
+        let tokens = Highlighter::tokenize_line("@@: dec ecx");
+        assert_eq!(tokens[0].text, "@@:");
+        assert_eq!(tokens[0].token_type, TokenType::Label);
+
+        let tokens = Highlighter::tokenize_line("    jnz @B");
+        assert!(tokens
+            .iter()
+            .any(|t| t.token_type == TokenType::Label && t.text == "@B"));
+    }
+
+    #[test]
+    fn test_tokenize_reals_and_structures() {
+        let kinds = |line: &str| -> Vec<(String, TokenType)> {
+            Highlighter::tokenize_line(line)
+                .into_iter()
+                .filter(|t| !t.text.trim().is_empty())
+                .map(|t| (t.text, t.token_type))
+                .collect()
+        };
+        assert_eq!(
+            kinds("pi REAL8 3.14159"),
+            vec![
+                (String::from("pi"), TokenType::Label),
+                (String::from("REAL8"), TokenType::TypeKeyword),
+                (String::from("3.14159"), TokenType::Number),
+            ]
+        );
+        assert_eq!(kinds("big REAL10 1.5E+30")[2].0, "1.5E+30");
+        assert_eq!(
+            kinds("one REAL4 3F800000r")[2],
+            (String::from("3F800000r"), TokenType::Number)
+        );
+        assert_eq!(
+            kinds("POINT STRUCT"),
+            vec![
+                (String::from("POINT"), TokenType::Label),
+                (String::from("STRUCT"), TokenType::Directive),
+            ]
+        );
+        assert_eq!(kinds("POINT ENDS")[0].1, TokenType::Plain);
+        assert_eq!(kinds("    mov eax, ebx")[0].1, TokenType::Keyword);
+    }
+
+    #[test]
+    fn test_tokenize_number_radixes() {
+        let numbers = |line: &str| -> Vec<String> {
+            Highlighter::tokenize_line(line)
+                .into_iter()
+                .filter(|t| t.token_type == TokenType::Number)
+                .map(|t| t.text)
+                .collect()
+        };
+        assert_eq!(numbers("mov eax, 0FFFFh"), ["0FFFFh"]);
+        assert_eq!(numbers("and al, 1100b"), ["1100b"]);
+        assert_eq!(numbers("mov ecx, 777q"), ["777q"]);
+        assert_eq!(
+            numbers("db 777o, 1010y, 100t, 99d, 0x1F"),
+            ["777o", "1010y", "100t", "99d", "0x1F"]
+        );
+        assert_eq!(numbers("mov eax, -1"), ["-1"]);
+        assert_eq!(numbers("val SDWORD -20"), ["-20"]);
+        // Binary minus stays an operator, and h alone is a name
+        assert_eq!(numbers("mov eax, [ebp-4]"), ["4"]);
+        assert_eq!(numbers("sub eax, (2)-1"), ["2", "1"]);
+        assert!(numbers("mov al, h").is_empty());
+    }
+
     #[test]
     fn test_tokenize_string() {
         let tokens = Highlighter::tokenize_line("msg BYTE \"Hello\", 0");
@@ -330,4 +518,53 @@ mod tests {
             .iter()
             .any(|t| t.token_type == TokenType::String && t.text == "\"Hello\""));
     }
+
+    #[test]
+    fn test_highlight_applies_style_modifiers() {
+        use ratatui::style::Modifier;
+
+        let mut colors = crate::theme::Theme::dark().syntax;
+        colors.styles.keyword.bold = true;
+        colors.styles.comment.italic = true;
+
+        let spans = Highlighter::highlight_line("mov eax, 1 ; note", &colors);
+        let mov = spans.iter().find(|s| s.content == "mov").unwrap();
+        assert!(mov.style.add_modifier.contains(Modifier::BOLD));
+        let reg = spans.iter().find(|s| s.content == "eax").unwrap();
+        assert!(reg.style.add_modifier.is_empty());
+        let comment = spans.iter().find(|s| s.content.starts_with(';')).unwrap();
+        assert!(comment.style.add_modifier.contains(Modifier::ITALIC));
+    }
+
+    #[test]
+    fn test_rainbow_brackets_color_by_depth() {
+        let colors = crate::theme::Theme::dark().syntax;
+        let spans = Highlighter::highlight_line("mov eax, [ebx+(ecx*4)] ; [x]", &colors);
+        let spans = Highlighter::rainbow_brackets(spans, &colors.rainbow);
+        let fg = |text: &str| {
+            let brackets: Vec<_> = spans.iter().filter(|s| s.content == text).collect();
+            assert_eq!(brackets.len(), 1, "{}", text);
+            brackets[0].style.fg
+        };
+        assert_eq!(fg("["), Some(colors.rainbow[0].to_color()));
+        assert_eq!(fg("("), Some(colors.rainbow[1].to_color()));
+        assert_ne!(fg("["), fg("("));
+        assert_eq!(fg("]"), fg("["));
+        assert_eq!(fg(")"), fg("("));
+    }
+
+    #[test]
+    fn test_tokenize_gas_and_nasm() {
+        let tokens = Highlighter::tokenize_line_as("movl $5, %eax # five", Dialect::Gas);
+        assert!(tokens
+            .iter()
+            .any(|t| t.token_type == TokenType::Register && t.text == "%eax"));
+        assert!(tokens
+            .iter()
+            .any(|t| t.token_type == TokenType::Number && t.text == "$5"));
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::Comment);
+
+        let tokens = Highlighter::tokenize_line_as("section .text", Dialect::Nasm);
+        assert_eq!(tokens[0].token_type, TokenType::Directive);
+    }
 }
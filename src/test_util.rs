@@ -0,0 +1,33 @@
+//! Helpers shared by the unit tests
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir, removed again when it's
+/// dropped, so a failing test doesn't leave it behind
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` keeps tests running in parallel apart
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("masmide-{}-{}", name, std::process::id()));
+        // Left over from a run that was killed
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
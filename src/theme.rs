@@ -1,5 +1,8 @@
-use ratatui::style::Color;
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::{Color, Modifier, Style};
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
@@ -22,6 +25,10 @@ pub struct UiColors {
     pub selection_fg: ThemeColor,
     pub search_match: ThemeColor,
     pub search_match_current: ThemeColor,
+    /// Background of the bracket under the cursor and its pair
+    pub bracket_match: ThemeColor,
+    /// Background of trailing whitespace
+    pub whitespace_marker: ThemeColor,
 
     // Status bar
     pub status_bar_bg: ThemeColor,
@@ -75,6 +82,67 @@ pub struct SyntaxColors {
     pub operator: ThemeColor,   // +, -, *, OFFSET, PTR
     pub type_kw: ThemeColor,    // BYTE, WORD, DWORD, etc.
     pub macro_call: ThemeColor, // macro invocations
+    #[serde(default)]
+    pub styles: SyntaxStyles, // bold/italic/underline per category
+    #[serde(default = "default_rainbow")]
+    pub rainbow: Vec<ThemeColor>, // brackets by nesting depth, cycling
+}
+
+/// Rainbow bracket colors for themes that don't list their own
+fn default_rainbow() -> Vec<ThemeColor> {
+    vec![
+        ThemeColor::rgb(255, 215, 0),
+        ThemeColor::rgb(218, 112, 214),
+        ThemeColor::rgb(23, 159, 255),
+    ]
+}
+
+/// Text modifiers for one syntax category
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Skip the category color and use the default foreground
+    pub no_color: bool,
+}
+
+impl TextStyle {
+    /// Build the style for a token of this category
+    pub fn to_style(self, color: &ThemeColor) -> Style {
+        let mut style = Style::default();
+        if !self.no_color {
+            style = style.fg(color.to_color());
+        }
+        if self.bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if self.italic {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        if self.underline {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        style
+    }
+}
+
+/// Per-category text modifiers, mirroring `SyntaxColors`.
+/// All off by default, which is plain colored text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyntaxStyles {
+    pub keyword: TextStyle,
+    pub register: TextStyle,
+    pub directive: TextStyle,
+    pub number: TextStyle,
+    pub string: TextStyle,
+    pub comment: TextStyle,
+    pub label: TextStyle,
+    pub operator: TextStyle,
+    pub type_kw: TextStyle,
+    pub macro_call: TextStyle,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,6 +212,8 @@ impl Theme {
                 selection_fg: ThemeColor::rgb(255, 255, 255),
                 search_match: ThemeColor::rgb(100, 80, 0),
                 search_match_current: ThemeColor::rgb(150, 120, 0),
+                bracket_match: ThemeColor::rgb(75, 75, 75),
+                whitespace_marker: ThemeColor::rgb(90, 30, 30),
 
                 status_bar_bg: ThemeColor::rgb(25, 25, 25),
                 status_bar_fg: ThemeColor::rgb(150, 150, 150),
@@ -190,6 +260,8 @@ impl Theme {
                 operator: ThemeColor::rgb(212, 212, 212),   // White
                 type_kw: ThemeColor::rgb(78, 201, 176),     // Teal
                 macro_call: ThemeColor::rgb(220, 220, 170), // Yellow
+                styles: SyntaxStyles::default(),
+                rainbow: default_rainbow(),
             },
         }
     }
@@ -210,6 +282,8 @@ impl Theme {
                 selection_fg: ThemeColor::rgb(0, 0, 0),
                 search_match: ThemeColor::rgb(255, 235, 150),
                 search_match_current: ThemeColor::rgb(255, 215, 0),
+                bracket_match: ThemeColor::rgb(210, 210, 210),
+                whitespace_marker: ThemeColor::rgb(255, 210, 210),
 
                 status_bar_bg: ThemeColor::rgb(240, 240, 240),
                 status_bar_fg: ThemeColor::rgb(80, 80, 80),
@@ -256,6 +330,12 @@ impl Theme {
                 operator: ThemeColor::rgb(30, 30, 30),    // Black
                 type_kw: ThemeColor::rgb(38, 127, 153),   // Teal
                 macro_call: ThemeColor::rgb(121, 94, 38), // Brown
+                styles: SyntaxStyles::default(),
+                rainbow: vec![
+                    ThemeColor::rgb(4, 49, 250),  // Blue
+                    ThemeColor::rgb(49, 147, 49), // Green
+                    ThemeColor::rgb(123, 56, 20), // Brown
+                ],
             },
         }
     }
@@ -276,6 +356,8 @@ impl Theme {
                 selection_fg: ThemeColor::rgb(248, 248, 242),
                 search_match: ThemeColor::rgb(241, 250, 140),
                 search_match_current: ThemeColor::rgb(255, 184, 108),
+                bracket_match: ThemeColor::rgb(98, 114, 164),
+                whitespace_marker: ThemeColor::rgb(110, 40, 50),
 
                 status_bar_bg: ThemeColor::rgb(33, 34, 44),
                 status_bar_fg: ThemeColor::rgb(248, 248, 242),
@@ -322,6 +404,12 @@ impl Theme {
                 operator: ThemeColor::rgb(248, 248, 242),  // White
                 type_kw: ThemeColor::rgb(139, 233, 253),   // Cyan
                 macro_call: ThemeColor::rgb(80, 250, 123), // Green
+                styles: SyntaxStyles::default(),
+                rainbow: vec![
+                    ThemeColor::rgb(241, 250, 140), // Yellow
+                    ThemeColor::rgb(255, 121, 198), // Pink
+                    ThemeColor::rgb(139, 233, 253), // Cyan
+                ],
             },
         }
     }
@@ -342,6 +430,8 @@ impl Theme {
                 selection_fg: ThemeColor::rgb(235, 219, 178),
                 search_match: ThemeColor::rgb(215, 153, 33),
                 search_match_current: ThemeColor::rgb(250, 189, 47),
+                bracket_match: ThemeColor::rgb(102, 92, 84),
+                whitespace_marker: ThemeColor::rgb(110, 40, 35),
 
                 status_bar_bg: ThemeColor::rgb(50, 48, 47),
                 status_bar_fg: ThemeColor::rgb(168, 153, 132),
@@ -388,6 +478,12 @@ impl Theme {
                 operator: ThemeColor::rgb(235, 219, 178),   // Fg
                 type_kw: ThemeColor::rgb(254, 128, 25),     // Orange
                 macro_call: ThemeColor::rgb(131, 165, 152), // Aqua
+                styles: SyntaxStyles::default(),
+                rainbow: vec![
+                    ThemeColor::rgb(250, 189, 47),  // Yellow
+                    ThemeColor::rgb(142, 192, 124), // Aqua
+                    ThemeColor::rgb(211, 134, 155), // Purple
+                ],
             },
         }
     }
@@ -408,6 +504,8 @@ impl Theme {
                 selection_fg: ThemeColor::rgb(236, 239, 244),
                 search_match: ThemeColor::rgb(235, 203, 139),
                 search_match_current: ThemeColor::rgb(208, 135, 112),
+                bracket_match: ThemeColor::rgb(76, 86, 106),
+                whitespace_marker: ThemeColor::rgb(100, 55, 60),
 
                 status_bar_bg: ThemeColor::rgb(59, 66, 82),
                 status_bar_fg: ThemeColor::rgb(229, 233, 240),
@@ -454,6 +552,12 @@ impl Theme {
                 operator: ThemeColor::rgb(236, 239, 244),   // White
                 type_kw: ThemeColor::rgb(208, 135, 112),    // Orange
                 macro_call: ThemeColor::rgb(136, 192, 208), // Cyan
+                styles: SyntaxStyles::default(),
+                rainbow: vec![
+                    ThemeColor::rgb(136, 192, 208), // Frost
+                    ThemeColor::rgb(235, 203, 139), // Yellow
+                    ThemeColor::rgb(180, 142, 173), // Purple
+                ],
             },
         }
     }
@@ -471,6 +575,32 @@ impl Theme {
     pub fn available_themes() -> Vec<&'static str> {
         vec!["dark", "light", "dracula", "gruvbox", "nord"]
     }
+
+    /// Read a theme written by `save_toml_file`, or by hand in the same
+    /// shape. Every color is required; a missing or malformed one is
+    /// reported on a single line, fit for the status bar.
+    pub fn from_toml_file(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        toml::from_str(&content).map_err(|e| {
+            let line = e
+                .span()
+                .map(|span| content[..span.start].matches('\n').count() + 1);
+            match line {
+                Some(line) => anyhow!("{}:{}: {}", path.display(), line, e.message()),
+                None => anyhow!("{}: {}", path.display(), e.message()),
+            }
+        })
+    }
+
+    /// Write the theme as TOML, to be edited and loaded back
+    pub fn save_toml_file(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(self)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content).with_context(|| format!("Cannot write {}", path.display()))
+    }
 }
 
 impl Default for Theme {
@@ -478,3 +608,39 @@ impl Default for Theme {
         Self::gruvbox()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_theme_file_round_trip() {
+        let dir = TempDir::new("theme");
+        let path = dir.join("mine.toml");
+        let mut theme = Theme::nord();
+        theme.name = String::from("mine");
+        theme.ui.background = ThemeColor::Named(String::from("#101010"));
+        theme.save_toml_file(&path).unwrap();
+
+        let loaded = Theme::from_toml_file(&path).unwrap();
+        assert_eq!(loaded.name, "mine");
+        assert_eq!(loaded.ui.background.to_color(), Color::Rgb(16, 16, 16));
+        assert_eq!(loaded.syntax.rainbow.len(), theme.syntax.rainbow.len());
+
+        // A theme without one of its colors names the missing field
+        fs::write(&path, "name = \"bare\"\n\n[ui]\nbackground = \"black\"\n").unwrap();
+        let err = Theme::from_toml_file(&path).unwrap_err().to_string();
+        assert!(err.contains("foreground"), "{}", err);
+        assert!(!err.contains('\n'));
+
+        // Errors point at the offending line
+        fs::write(&path, "name = \"bad\"\n\n[ui]\n!oops = 1\n").unwrap();
+        let err = Theme::from_toml_file(&path).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("{}:4: ", path.display())),
+            "{}",
+            err
+        );
+    }
+}
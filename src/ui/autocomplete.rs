@@ -6,6 +6,7 @@ use ratatui::{
 };
 
 use crate::autocomplete::{AutocompleteState, SuggestionKind};
+use crate::fuzzy::fuzzy_score;
 use crate::theme::Theme;
 
 /// Render the autocomplete popup
@@ -25,11 +26,11 @@ pub fn render(
     // Calculate popup dimensions
     let max_text_width = visible
         .iter()
-        .map(|s| s.text.len() + 4) // +4 for kind icon and padding
+        .map(|s| s.text.len() + 4 + detail_width(s.detail.as_deref())) // +4 for kind icon and padding
         .max()
         .unwrap_or(20) as u16;
 
-    let popup_width = max_text_width.clamp(15, 40);
+    let popup_width = max_text_width.clamp(15, 56);
     let popup_height = (visible.len() as u16 + 2).min(12); // +2 for borders
 
     // Position popup below cursor, or above if not enough space
@@ -62,6 +63,8 @@ pub fn render(
             SuggestionKind::Label => Style::default().fg(theme.syntax.label.to_color()),
             SuggestionKind::Procedure => Style::default().fg(theme.syntax.label.to_color()),
             SuggestionKind::Macro => Style::default().fg(theme.syntax.macro_call.to_color()),
+            SuggestionKind::Constant => Style::default().fg(theme.syntax.number.to_color()),
+            SuggestionKind::Field => Style::default().fg(theme.syntax.label.to_color()),
         };
 
         let base_style = if is_selected {
@@ -77,13 +80,48 @@ pub fn render(
             if is_selected { base_style } else { kind_style },
         );
 
-        let text = Span::styled(&suggestion.text, base_style);
+        // Characters the typed prefix matched are underlined in bold
+        let matched = fuzzy_score(&autocomplete.prefix, &suggestion.text)
+            .map(|(_, positions)| positions)
+            .unwrap_or_default();
+        let match_style = base_style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let mut text: Vec<Span> = Vec::new();
+        let mut run = String::new();
+        let mut run_matched = false;
+        for (idx, c) in suggestion.text.chars().enumerate() {
+            let is_match = matched.contains(&idx);
+            if is_match != run_matched && !run.is_empty() {
+                let style = if run_matched { match_style } else { base_style };
+                text.push(Span::styled(std::mem::take(&mut run), style));
+            }
+            run_matched = is_match;
+            run.push(c);
+        }
+        let style = if run_matched { match_style } else { base_style };
+        text.push(Span::styled(run, style));
+
+        // Constant values and field types are shown dimmed on the right
+        let room = (popup_width as usize).saturating_sub(suggestion.text.len() + 5);
+        let detail: String = match &suggestion.detail {
+            Some(d) if room > 3 => d.chars().take(room).collect(),
+            _ => String::new(),
+        };
+        let detail_style = if is_selected {
+            base_style
+        } else {
+            Style::default().fg(theme.ui.line_numbers.to_color())
+        };
 
         // Pad to fill width
-        let padding_len = (popup_width as usize).saturating_sub(suggestion.text.len() + 4);
+        let padding_len = (popup_width as usize)
+            .saturating_sub(suggestion.text.len() + 4 + detail.chars().count());
         let padding = Span::styled(" ".repeat(padding_len), base_style);
 
-        lines.push(Line::from(vec![icon, text, padding]));
+        let mut spans = vec![icon];
+        spans.extend(text);
+        spans.push(padding);
+        spans.push(Span::styled(detail, detail_style));
+        lines.push(Line::from(spans));
     }
 
     // Show scroll indicator if there are more items
@@ -104,3 +142,8 @@ pub fn render(
 
     frame.render_widget(paragraph, popup_area);
 }
+
+/// Columns reserved for a suggestion's detail text, including the gap
+fn detail_width(detail: Option<&str>) -> usize {
+    detail.map_or(0, |d| d.chars().count() + 2)
+}
@@ -0,0 +1,113 @@
+use crate::fuzzy::fuzzy_score;
+use crate::theme::Theme;
+use crate::ui::editor::EditorState;
+use crate::ui::picker::{PickerItem, PickerState, PickerStyles};
+use ratatui::prelude::*;
+
+/// One open buffer as listed by `:ls`
+#[derive(Debug, Clone)]
+pub struct BufferEntry {
+    /// Index into `EditorState::buffers`
+    pub index: usize,
+    pub name: String,
+    pub modified: bool,
+    pub current: bool,
+    pub alternate: bool,
+}
+
+pub type BufferPickerState = PickerState<BufferEntry>;
+
+/// Picker over the open buffers, starting on the alternate buffer, the
+/// most likely next pick
+pub fn picker(editor: &EditorState) -> BufferPickerState {
+    let alternate = editor.recent_buffers.last().copied();
+    let entries = editor
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buf)| BufferEntry {
+            index,
+            name: editor.display_name(index),
+            modified: buf.modified,
+            current: index == editor.active_buffer,
+            alternate: Some(index) == alternate,
+        })
+        .collect();
+    let mut state = PickerState::new(entries);
+    state.select_first(|entry| entry.alternate);
+    state
+}
+
+impl PickerItem for BufferEntry {
+    const TITLE: &'static str = "Buffers";
+    const ACTION: &'static str = "switch";
+    const EMPTY: &'static str = " No matching buffers";
+    const MAX_SIZE: (u16, u16) = (72, 20);
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A numeric query picks the buffer with that (1-based) number, as in
+    /// `:b N`; anything else is matched fuzzily against the name
+    fn score(&self, query: &str) -> Option<i64> {
+        match query.parse::<usize>() {
+            Ok(number) => (self.index + 1 == number).then_some(0),
+            Err(_) => fuzzy_score(query, &self.name).map(|(score, _)| score),
+        }
+    }
+
+    fn row<'a>(&'a self, styles: &PickerStyles, theme: &Theme) -> Line<'a> {
+        let modified_style = Style::default().fg(theme.ui.diagnostic_warning.to_color());
+        // Same markers as vim's :ls - % current, # alternate, + modified
+        let marker = if self.current {
+            "%"
+        } else if self.alternate {
+            "#"
+        } else {
+            " "
+        };
+        Line::from(vec![
+            Span::styled(format!(" {:>3} ", self.index + 1), styles.dim),
+            Span::styled(format!("{} ", marker), styles.key),
+            Span::styled(if self.modified { "+ " } else { "  " }, modified_style),
+            Span::styled(self.name.as_str(), styles.desc),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: usize, name: &str) -> BufferEntry {
+        BufferEntry {
+            index,
+            name: name.to_string(),
+            modified: false,
+            current: index == 0,
+            alternate: false,
+        }
+    }
+
+    #[test]
+    fn test_buffer_picker_by_number_or_name() {
+        let mut picker = BufferPickerState::new(vec![
+            entry(0, "main.asm"),
+            entry(1, "io.inc"),
+            entry(2, "macros.inc"),
+        ]);
+        assert_eq!(picker.matches.len(), 3);
+
+        picker.push_char('3');
+        assert_eq!(picker.selected().map(|e| e.index), Some(2));
+        picker.push_char('0');
+        assert_eq!(picker.selected().map(|e| e.index), None);
+
+        picker.query.clear();
+        for c in "io".chars() {
+            picker.push_char(c);
+        }
+        assert_eq!(picker.selected().map(|e| e.index), Some(1));
+    }
+}
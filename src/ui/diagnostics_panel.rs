@@ -0,0 +1,123 @@
+use crate::diagnostics::{Diagnostic, DiagnosticSeverity};
+use crate::theme::Theme;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::path::Path;
+
+/// `file:line severity` prefix of a diagnostic's row, with the file
+/// relative to the project root
+pub fn location(diagnostic: &Diagnostic, project_dir: &Path) -> String {
+    let file = diagnostic
+        .file
+        .strip_prefix(project_dir)
+        .unwrap_or(&diagnostic.file);
+    let severity = match diagnostic.severity {
+        DiagnosticSeverity::Error => "error",
+        DiagnosticSeverity::Warning => "warning",
+    };
+    format!("{}:{} {}", file.display(), diagnostic.line, severity)
+}
+
+/// Every diagnostic of the last build or check, one per row, with
+/// `selected` highlighted
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    diagnostics: &[Diagnostic],
+    selected: usize,
+    project_dir: &Path,
+    theme: &Theme,
+) {
+    let popup_width = (area.width * 90 / 100).min(110);
+    let popup_height = (area.height * 70 / 100).min(24);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(popup_width)) / 2,
+        area.y + (area.height.saturating_sub(popup_height)) / 3,
+        popup_width,
+        popup_height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let key_style = Style::default()
+        .fg(theme.ui.title_focused.to_color())
+        .add_modifier(Modifier::BOLD);
+    let desc_style = Style::default().fg(theme.ui.foreground.to_color());
+    let dim_style = Style::default().fg(theme.ui.line_numbers.to_color());
+
+    let visible_rows = popup_height.saturating_sub(2) as usize;
+    let scroll = selected.saturating_sub(visible_rows.saturating_sub(1));
+
+    let mut lines: Vec<Line> = diagnostics
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(visible_rows)
+        .map(|(row, diagnostic)| {
+            let color = match diagnostic.severity {
+                DiagnosticSeverity::Error => &theme.ui.diagnostic_error,
+                DiagnosticSeverity::Warning => &theme.ui.diagnostic_warning,
+            };
+            let mut line = Line::from(vec![
+                Span::styled(
+                    format!(" {} ", location(diagnostic, project_dir)),
+                    Style::default().fg(color.to_color()),
+                ),
+                Span::styled(diagnostic.message.as_str(), desc_style),
+            ]);
+            if row == selected {
+                line = line.style(Style::default().bg(theme.ui.selection.to_color()));
+            }
+            line
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(" No diagnostics", dim_style)));
+    }
+
+    let block = Block::default()
+        .title(Span::styled(
+            format!(" Diagnostics ({}) ", diagnostics.len()),
+            key_style,
+        ))
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" jk ", key_style),
+                Span::styled("move ", desc_style),
+                Span::styled("Enter ", key_style),
+                Span::styled("open ", desc_style),
+                Span::styled("F4/F6 ", key_style),
+                Span::styled("rebuild ", desc_style),
+                Span::styled("Esc ", key_style),
+                Span::styled("close ", desc_style),
+            ])
+            .right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.ui.border_focused.to_color()))
+        .style(Style::default().bg(theme.ui.background.to_color()));
+
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_location_is_relative_to_the_project() {
+        let project_dir = PathBuf::from("/project");
+        let error = Diagnostic::error(
+            project_dir.join("src/main.asm"),
+            12,
+            String::from("undefined symbol : count"),
+        );
+        assert_eq!(location(&error, &project_dir), "src/main.asm:12 error");
+        let warning = Diagnostic::warning(PathBuf::from("/lib/io.inc"), 3, String::new());
+        assert_eq!(location(&warning, &project_dir), "/lib/io.inc:3 warning");
+    }
+}
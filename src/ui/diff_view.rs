@@ -0,0 +1,347 @@
+use crate::syntax::Highlighter;
+use crate::theme::Theme;
+use ratatui::{
+    prelude::*,
+    text::Span,
+    widgets::{Block, Borders, Paragraph},
+};
+use similar::{DiffTag, TextDiff};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Equal,
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One row of the side-by-side view. Either side may be missing when a
+/// line only exists in one of the files.
+#[derive(Debug, Clone)]
+pub struct DiffRow {
+    pub left: Option<(usize, String)>,
+    pub right: Option<(usize, String)>,
+    pub kind: DiffKind,
+}
+
+pub struct DiffViewState {
+    pub left_title: String,
+    pub right_title: String,
+    pub rows: Vec<DiffRow>,
+    /// Row indices where a run of changes starts
+    pub hunks: Vec<usize>,
+    pub scroll_offset: usize,
+    pub visible_height: usize,
+    pub pending_bracket: Option<char>, // For ]c, [c hunk navigation
+}
+
+impl DiffViewState {
+    pub fn new(left_title: &str, left: &str, right_title: &str, right: &str) -> Self {
+        let diff = TextDiff::from_lines(left, right);
+        let old: Vec<&str> = left.lines().collect();
+        let new: Vec<&str> = right.lines().collect();
+
+        let line = |lines: &[&str], idx: usize| (idx, lines.get(idx).unwrap_or(&"").to_string());
+
+        let mut rows = Vec::new();
+        for op in diff.ops() {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            match tag {
+                DiffTag::Equal => {
+                    for (o, n) in old_range.zip(new_range) {
+                        rows.push(DiffRow {
+                            left: Some(line(&old, o)),
+                            right: Some(line(&new, n)),
+                            kind: DiffKind::Equal,
+                        });
+                    }
+                }
+                DiffTag::Delete => {
+                    for o in old_range {
+                        rows.push(DiffRow {
+                            left: Some(line(&old, o)),
+                            right: None,
+                            kind: DiffKind::Removed,
+                        });
+                    }
+                }
+                DiffTag::Insert => {
+                    for n in new_range {
+                        rows.push(DiffRow {
+                            left: None,
+                            right: Some(line(&new, n)),
+                            kind: DiffKind::Added,
+                        });
+                    }
+                }
+                DiffTag::Replace => {
+                    // Pair up replaced lines, leftovers become plain adds/removes
+                    let count = old_range.len().max(new_range.len());
+                    for i in 0..count {
+                        let o = (i < old_range.len()).then(|| line(&old, old_range.start + i));
+                        let n = (i < new_range.len()).then(|| line(&new, new_range.start + i));
+                        let kind = match (&o, &n) {
+                            (Some(_), Some(_)) => DiffKind::Changed,
+                            (Some(_), None) => DiffKind::Removed,
+                            _ => DiffKind::Added,
+                        };
+                        rows.push(DiffRow {
+                            left: o,
+                            right: n,
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+
+        let hunks = rows
+            .iter()
+            .enumerate()
+            .filter(|(i, row)| {
+                row.kind != DiffKind::Equal && (*i == 0 || rows[i - 1].kind == DiffKind::Equal)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        Self {
+            left_title: left_title.to_string(),
+            right_title: right_title.to_string(),
+            rows,
+            hunks,
+            scroll_offset: 0,
+            visible_height: 20,
+            pending_bracket: None,
+        }
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.rows.len().saturating_sub(self.visible_height)
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll_offset = self.scroll_offset.saturating_sub(lines);
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll_offset = (self.scroll_offset + lines).min(self.max_scroll());
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_offset = 0;
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_offset = self.max_scroll();
+    }
+
+    /// Jump to the next change hunk (]c). Returns false if there is none.
+    pub fn next_hunk(&mut self) -> bool {
+        if self.scroll_offset >= self.max_scroll() {
+            return false;
+        }
+        match self.hunks.iter().find(|&&h| h > self.scroll_offset) {
+            Some(&h) => {
+                self.scroll_offset = h.min(self.max_scroll());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Jump to the previous change hunk ([c). Returns false if there is none.
+    pub fn prev_hunk(&mut self) -> bool {
+        match self.hunks.iter().rev().find(|&&h| h < self.scroll_offset) {
+            Some(&h) => {
+                self.scroll_offset = h;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// One side of a row: line number, a `+`/`-`/`~` marker in the theme's
+/// added, removed or changed color, and the highlighted text, shaded
+/// when it differs from the other side
+fn side_line<'a>(
+    side: &Option<(usize, String)>,
+    kind: DiffKind,
+    number_width: usize,
+    theme: &Theme,
+    added: bool,
+) -> Line<'a> {
+    let number_style = Style::default().fg(theme.ui.line_numbers.to_color());
+    let Some((num, text)) = side else {
+        return Line::from(Span::styled(
+            format!("{:>width$} ", "~", width = number_width),
+            number_style,
+        ));
+    };
+    let marker = match kind {
+        DiffKind::Equal => Span::raw("  "),
+        DiffKind::Changed => Span::styled(
+            "~ ",
+            Style::default().fg(theme.ui.diagnostic_warning.to_color()),
+        ),
+        _ if added => Span::styled(
+            "+ ",
+            Style::default().fg(theme.ui.mode_insert_bg.to_color()),
+        ),
+        _ => Span::styled(
+            "- ",
+            Style::default().fg(theme.ui.diagnostic_error.to_color()),
+        ),
+    };
+    let mut spans = vec![
+        Span::styled(
+            format!("{:>width$} ", num + 1, width = number_width),
+            number_style,
+        ),
+        marker,
+    ];
+    let highlighted = Highlighter::highlight_line(text, &theme.syntax);
+    if kind == DiffKind::Equal {
+        spans.extend(highlighted);
+    } else {
+        let shade = theme.ui.cursor_line.to_color();
+        spans.extend(highlighted.into_iter().map(|span| {
+            let style = span.style.bg(shade);
+            span.style(style)
+        }));
+    }
+    Line::from(spans)
+}
+
+/// Rows of the side-by-side view that are not equal, i.e. how many lines
+/// differ between `old` and `new`
+pub fn changed_line_count(old: &str, new: &str) -> usize {
+    TextDiff::from_lines(old, new)
+        .ops()
+        .iter()
+        .map(|op| match op.as_tag_tuple() {
+            (DiffTag::Equal, ..) => 0,
+            (_, old_range, new_range) => old_range.len().max(new_range.len()),
+        })
+        .sum()
+}
+
+pub fn render(frame: &mut Frame, area: Rect, state: &mut DiffViewState, theme: &Theme) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let title_style = Style::default()
+        .fg(theme.ui.title_focused.to_color())
+        .add_modifier(Modifier::BOLD);
+    let block = |title: &str| {
+        Block::default()
+            .title(Span::styled(format!(" {} ", title), title_style))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(theme.ui.border_focused.to_color()))
+            .style(Style::default().bg(theme.ui.background.to_color()))
+    };
+
+    let left_block = block(&state.left_title).title_bottom(
+        Line::from(Span::styled(
+            format!(" {} change(s) ", state.hunks.len()),
+            Style::default().fg(theme.ui.line_numbers.to_color()),
+        ))
+        .right_aligned(),
+    );
+    let right_block = block(&state.right_title).title_bottom(
+        Line::from(vec![
+            Span::styled(" ]c/[c ", title_style),
+            Span::styled(
+                "hunks ",
+                Style::default().fg(theme.ui.foreground.to_color()),
+            ),
+            Span::styled("q ", title_style),
+            Span::styled(
+                "close ",
+                Style::default().fg(theme.ui.foreground.to_color()),
+            ),
+        ])
+        .right_aligned(),
+    );
+
+    // Both panes scroll together since rows are already aligned
+    state.visible_height = left_block.inner(columns[0]).height as usize;
+    state.scroll_offset = state.scroll_offset.min(state.max_scroll());
+
+    let number_width = format!("{}", state.rows.len()).len().max(3);
+    let visible = state
+        .rows
+        .iter()
+        .skip(state.scroll_offset)
+        .take(state.visible_height);
+
+    let mut left_lines = Vec::new();
+    let mut right_lines = Vec::new();
+    for row in visible {
+        left_lines.push(side_line(&row.left, row.kind, number_width, theme, false));
+        right_lines.push(side_line(&row.right, row.kind, number_width, theme, true));
+    }
+
+    frame.render_widget(Paragraph::new(left_lines).block(left_block), columns[0]);
+    frame.render_widget(Paragraph::new(right_lines).block(right_block), columns[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_align_changes() {
+        let state = DiffViewState::new(
+            "a",
+            "mov eax, 1\nadd eax, 2\nret\n",
+            "b",
+            "mov eax, 1\nsub eax, 2\nret\nnop\n",
+        );
+        let kinds: Vec<DiffKind> = state.rows.iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiffKind::Equal,
+                DiffKind::Changed,
+                DiffKind::Equal,
+                DiffKind::Added
+            ]
+        );
+        assert_eq!(state.hunks, vec![1, 3]);
+        assert_eq!(
+            changed_line_count(
+                "mov eax, 1\nadd eax, 2\nret\n",
+                "mov eax, 1\nsub eax, 2\nret\nnop\n"
+            ),
+            2
+        );
+        assert_eq!(changed_line_count("ret\n", "ret\n"), 0);
+    }
+
+    #[test]
+    fn test_sides_are_highlighted_with_theme_colors() {
+        let theme = Theme::default();
+        let side = Some((0, String::from("mov eax, 1")));
+        let line = side_line(&side, DiffKind::Added, 3, &theme, true);
+        assert_eq!(line.spans[1].content, "+ ");
+        assert_eq!(
+            line.spans[1].style.fg,
+            Some(theme.ui.mode_insert_bg.to_color())
+        );
+        let eax = line.spans.iter().find(|s| s.content == "eax").unwrap();
+        assert_eq!(eax.style.fg, Some(theme.syntax.register.to_color()));
+        assert_eq!(eax.style.bg, Some(theme.ui.cursor_line.to_color()));
+
+        let line = side_line(&side, DiffKind::Equal, 3, &theme, false);
+        let eax = line.spans.iter().find(|s| s.content == "eax").unwrap();
+        assert_eq!(eax.style.bg, None);
+    }
+}
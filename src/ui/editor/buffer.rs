@@ -1,7 +1,52 @@
+use super::edit::EditOps;
+use crate::autocomplete::SymbolIndex;
+use crate::masm_lang::Dialect;
 use anyhow::Result;
 use ropey::Rope;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+const BOM: char = '\u{feff}';
+
+/// Source of buffer revisions, shared so no two buffers ever have the same
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Line terminators a file was loaded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    /// Both styles present; lines keep their `\r` so saving is lossless
+    Mixed,
+}
+
+impl LineEnding {
+    pub fn detect(content: &str) -> Self {
+        let crlf = content.matches("\r\n").count();
+        let lf = content.matches('\n').count() - crlf;
+        match (crlf, lf) {
+            (0, _) => LineEnding::Lf,
+            (_, 0) => LineEnding::CrLf,
+            _ => LineEnding::Mixed,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Mixed => "MIXED",
+        }
+    }
+}
 
 /// A single buffer representing an open file
 /// Now using ropey::Rope for proper text editing semantics
@@ -13,13 +58,51 @@ pub struct Buffer {
     pub scroll_offset: usize,
     pub file_path: Option<PathBuf>,
     pub modified: bool,
+    pub line_ending: LineEnding,
+    /// File started with a UTF-8 byte order mark, written back on save
+    pub has_bom: bool,
+    /// Syntax used for highlighting and to decide whether it can be built
+    pub dialect: Dialect,
+    /// Opened by browsing the file tree; replaced by the next file browsed
+    /// until it is edited or opened explicitly
+    pub preview: bool,
+    /// Runs of comment-only lines are drawn folded (zC / zO)
+    pub fold_comments: bool,
     // Selection state for visual mode
     pub selection_start: Option<(usize, usize)>, // (line, col_byte)
     pub selection_end: Option<(usize, usize)>,   // (line, col_byte)
+    /// The selection is a rectangle between its corners (Alt+v)
+    pub block_selection: bool,
+    /// `m{a-z}` marks as (line, byte col). Kept roughly in place as lines
+    /// are added or removed, see `shift_line_refs`
+    pub marks: HashMap<char, (usize, usize)>,
+    /// First lines of the PROC and segment folds closed with `za`
+    pub closed_folds: Vec<usize>,
+    /// Modification time of the file when it was last read or written
+    /// here, to notice another program changing it
+    pub disk_mtime: Option<SystemTime>,
 
     // COMPATIBILITY: Provide Vec<String> interface for existing code
     pub lines: Vec<String>, // Cached copy of lines for compatibility
     lines_dirty: bool,      // Track if cache needs refresh
+    // Line count at the last sync, to tell how many lines an edit added
+    synced_len: usize,
+    // Symbols defined in `lines`, brought up to date on demand
+    symbol_index: SymbolIndex,
+    // Changes with every sync, so views of the text can tell it was edited
+    revision: u64,
+    // Fold candidates found in `lines`, rescanned once the revision moves
+    // on. Behind a RefCell since folds are looked up through `&self`.
+    fold_cache: RefCell<FoldCache>,
+}
+
+/// What `Buffer::comment_runs` and `Buffer::fold_regions` found at one
+/// revision of the text, each scanned on first use
+#[derive(Debug, Clone, Default)]
+struct FoldCache {
+    revision: u64,
+    comment_runs: Option<Vec<(usize, usize)>>,
+    regions: Option<Vec<(usize, usize)>>,
 }
 
 impl Buffer {
@@ -33,10 +116,23 @@ impl Buffer {
             scroll_offset: 0,
             file_path: None,
             modified: false,
+            line_ending: LineEnding::Lf,
+            has_bom: false,
+            dialect: Dialect::Masm,
+            preview: false,
+            fold_comments: false,
             selection_start: None,
             selection_end: None,
+            block_selection: false,
+            marks: HashMap::new(),
+            closed_folds: Vec::new(),
+            disk_mtime: None,
+            synced_len: lines.len(),
             lines,
             lines_dirty: false,
+            symbol_index: SymbolIndex::default(),
+            revision: next_revision(),
+            fold_cache: RefCell::default(),
         }
     }
 
@@ -51,13 +147,115 @@ impl Buffer {
                 s.trim_end_matches('\n').to_string()
             })
             .collect();
+        self.synced_len = self.lines.len();
         self.lines_dirty = false;
+        self.revision = next_revision();
     }
 
-    /// Sync rope from lines cache (call after modifying lines)
+    /// Sync rope from lines cache (call after modifying lines). Marks and
+    /// closed folds follow a change in line count not already reported
+    /// with `shift_line_refs`, taken to start at the first changed line.
     pub fn sync_rope(&mut self) {
+        let delta = self.lines.len() as isize - self.synced_len as isize;
+        if delta != 0 {
+            self.shift_line_refs(self.first_changed_line(), delta);
+        }
+        self.synced_len = self.lines.len();
         self.text = Rope::from(self.lines.join("\n") + "\n");
         self.lines_dirty = false;
+        self.revision = next_revision();
+    }
+
+    /// Identifies the text as of the last sync; any edit changes it
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Runs of two or more comment-only lines, as inclusive line ranges
+    pub fn comment_runs(&self) -> Vec<(usize, usize)> {
+        let mut cache = self.fold_cache();
+        cache
+            .comment_runs
+            .get_or_insert_with(|| {
+                let mut runs = Vec::new();
+                let mut run_start = None;
+                for idx in 0..=self.lines.len() {
+                    let comment = self
+                        .lines
+                        .get(idx)
+                        .is_some_and(|line| line.trim_start().starts_with(';'));
+                    match (comment, run_start) {
+                        (true, None) => run_start = Some(idx),
+                        (false, Some(start)) => {
+                            run_start = None;
+                            if idx - start >= 2 {
+                                runs.push((start, idx - 1));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                runs
+            })
+            .clone()
+    }
+
+    /// PROC and segment regions that can be folded, as found by
+    /// `EditOps::fold_regions`
+    pub fn fold_regions(&self) -> Vec<(usize, usize)> {
+        let mut cache = self.fold_cache();
+        cache
+            .regions
+            .get_or_insert_with(|| EditOps::fold_regions(&self.lines))
+            .clone()
+    }
+
+    /// The fold cache, emptied first if the text changed since it was
+    /// filled
+    fn fold_cache(&self) -> RefMut<'_, FoldCache> {
+        let mut cache = self.fold_cache.borrow_mut();
+        if cache.revision != self.revision {
+            *cache = FoldCache {
+                revision: self.revision,
+                ..FoldCache::default()
+            };
+        }
+        cache
+    }
+
+    /// First line of `lines` that differs from the text at the last sync
+    fn first_changed_line(&self) -> usize {
+        self.text
+            .lines()
+            .zip(&self.lines)
+            .position(|(old, new)| old.slice(..old.len_chars().saturating_sub(1)) != new.as_str())
+            .unwrap_or_else(|| self.lines.len().min(self.synced_len))
+    }
+
+    /// Move marks and closed folds for `delta` lines inserted (or, when
+    /// negative, removed) at line `at`. Edits that add or remove lines in
+    /// more than one place call this for each before `sync_rope`.
+    /// References to removed lines land on `at`.
+    pub fn shift_line_refs(&mut self, at: usize, delta: isize) {
+        self.synced_len = self.synced_len.saturating_add_signed(delta);
+        if delta == 0 {
+            return;
+        }
+        let last = self.lines.len().saturating_sub(1);
+        let shift = |line: &mut usize| {
+            if *line >= at {
+                *line = if delta > 0 {
+                    *line + delta as usize
+                } else {
+                    line.saturating_sub(delta.unsigned_abs()).max(at)
+                };
+            }
+            *line = (*line).min(last);
+        };
+        self.marks.values_mut().for_each(|(line, _)| shift(line));
+        self.closed_folds.iter_mut().for_each(shift);
+        self.closed_folds.sort_unstable();
+        self.closed_folds.dedup();
     }
 
     /// Get reference to the rope (for advanced operations)
@@ -93,6 +291,19 @@ impl Buffer {
             return Err(anyhow::anyhow!("Cannot open binary file"));
         }
 
+        // Lines are edited without BOM or CR; to_disk() puts them back
+        let has_bom = content.starts_with(BOM);
+        let content = content.strip_prefix(BOM).unwrap_or(&content);
+        let line_ending = LineEnding::detect(content);
+        let content = if line_ending == LineEnding::CrLf {
+            content.replace("\r\n", "\n")
+        } else {
+            content.to_string()
+        };
+
+        let extension = path.extension().and_then(|e| e.to_str());
+        let dialect = Dialect::detect(extension, &content);
+
         // Create rope from file content
         let text = if content.is_empty() {
             Rope::from("\n") // Empty file = one blank line
@@ -116,17 +327,75 @@ impl Buffer {
             scroll_offset: 0,
             file_path: Some(path.clone()),
             modified: false,
+            line_ending,
+            has_bom,
+            dialect,
+            preview: false,
+            fold_comments: false,
             selection_start: None,
             selection_end: None,
+            block_selection: false,
+            marks: HashMap::new(),
+            closed_folds: Vec::new(),
+            disk_mtime: metadata.modified().ok(),
+            synced_len: lines.len(),
             lines,
             lines_dirty: false,
+            symbol_index: SymbolIndex::default(),
+            revision: next_revision(),
+            fold_cache: RefCell::default(),
         })
     }
 
+    /// Whether the file was modified on disk since it was read or written
+    /// here
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.file_path else {
+            return false;
+        };
+        match (
+            self.disk_mtime,
+            fs::metadata(path).and_then(|m| m.modified()),
+        ) {
+            (Some(seen), Ok(now)) => now > seen,
+            _ => false,
+        }
+    }
+
+    /// Remember the file's modification time after writing it
+    pub fn mark_written(&mut self) {
+        self.disk_mtime = self
+            .file_path
+            .as_ref()
+            .and_then(|path| fs::metadata(path).and_then(|m| m.modified()).ok());
+    }
+
+    /// Labels, procedures, macros, constants and fields defined in the
+    /// buffer. Only lines edited since the last call are reparsed.
+    pub fn symbols(&mut self) -> &SymbolIndex {
+        self.symbol_index.update(&self.lines);
+        &self.symbol_index
+    }
+
     pub fn get_content(&self) -> String {
         self.text.to_string()
     }
 
+    /// `content` as it should be written to disk, restoring the BOM and
+    /// line endings the file was loaded with
+    pub fn to_disk(&self, content: &str) -> String {
+        let mut out = String::with_capacity(content.len() + 3);
+        if self.has_bom {
+            out.push(BOM);
+        }
+        if self.line_ending == LineEnding::CrLf {
+            out.push_str(&content.replace('\n', "\r\n"));
+        } else {
+            out.push_str(content);
+        }
+        out
+    }
+
     pub fn filename(&self) -> String {
         self.file_path
             .as_ref()
@@ -1,6 +1,6 @@
 use super::buffer::Buffer;
-use super::cursor::CursorOps;
-use super::undo::{EditorAction, UndoStack};
+use super::undo::{EditorAction, UndoTree};
+use crate::utf8;
 
 /// Whether a yank was line-wise or character-wise
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -137,7 +137,7 @@ impl Clipboard {
 
 /// Insert text inline (may span multiple lines) as a single undo action.
 /// Works like vim/nano: splits at newlines, joins prefix/suffix properly.
-pub fn paste_text_inline(buf: &mut Buffer, undo_stack: &mut UndoStack, text: &str) {
+pub fn paste_text_inline(buf: &mut Buffer, undo_tree: &mut UndoTree, text: &str) {
     if text.is_empty() {
         return;
     }
@@ -150,9 +150,8 @@ pub fn paste_text_inline(buf: &mut Buffer, undo_stack: &mut UndoStack, text: &st
     }
 
     let current_line = &buf.lines[start_line];
-    let insert_pos =
-        CursorOps::clamp_to_char_boundary(current_line, start_col.min(current_line.len()));
-    let start_col_char = CursorOps::char_index_at_byte(current_line, insert_pos);
+    let insert_pos = utf8::clamp_to_char_boundary(current_line, start_col.min(current_line.len()));
+    let start_col_char = utf8::char_index_at_byte(current_line, insert_pos);
 
     let prefix = buf.lines[start_line][..insert_pos].to_string();
     let suffix = buf.lines[start_line][insert_pos..].to_string();
@@ -184,7 +183,7 @@ pub fn paste_text_inline(buf: &mut Buffer, undo_stack: &mut UndoStack, text: &st
 
     let end_col_char = last_pasted_line.chars().count();
 
-    undo_stack.push(EditorAction::InsertText {
+    undo_tree.push(EditorAction::InsertText {
         start_line,
         start_col: start_col_char,
         end_line,
@@ -200,7 +199,7 @@ pub fn undo_insert_text(buf: &mut Buffer, start_line: usize, start_col: usize, t
     if lines.len() == 1 {
         if start_line < buf.lines.len() {
             let line = &mut buf.lines[start_line];
-            let start_byte = CursorOps::byte_index_of_char(line, start_col);
+            let start_byte = utf8::byte_index_of_char(line, start_col);
             let end_byte = start_byte + text.len();
             if end_byte <= line.len() {
                 line.drain(start_byte..end_byte);
@@ -214,7 +213,7 @@ pub fn undo_insert_text(buf: &mut Buffer, start_line: usize, start_col: usize, t
         if end_line < buf.lines.len() {
             let prefix = if start_line < buf.lines.len() {
                 let line = &buf.lines[start_line];
-                let start_byte = CursorOps::byte_index_of_char(line, start_col);
+                let start_byte = utf8::byte_index_of_char(line, start_col);
                 line[..start_byte].to_string()
             } else {
                 String::new()
@@ -238,7 +237,7 @@ pub fn undo_insert_text(buf: &mut Buffer, start_line: usize, start_col: usize, t
             }
 
             buf.cursor_y = start_line;
-            buf.cursor_x = CursorOps::byte_index_of_char(&buf.lines[start_line], start_col);
+            buf.cursor_x = utf8::byte_index_of_char(&buf.lines[start_line], start_col);
             buf.modified = true;
         }
     }
@@ -251,7 +250,7 @@ pub fn redo_insert_text(buf: &mut Buffer, start_line: usize, start_col: usize, t
     if lines.len() == 1 {
         if start_line < buf.lines.len() {
             let line = &mut buf.lines[start_line];
-            let insert_pos = CursorOps::byte_index_of_char(line, start_col);
+            let insert_pos = utf8::byte_index_of_char(line, start_col);
             line.insert_str(insert_pos, text);
             buf.cursor_x = insert_pos + text.len();
             buf.cursor_y = start_line;
@@ -259,7 +258,7 @@ pub fn redo_insert_text(buf: &mut Buffer, start_line: usize, start_col: usize, t
         }
     } else if start_line < buf.lines.len() {
         let current_line = &buf.lines[start_line];
-        let insert_pos = CursorOps::byte_index_of_char(current_line, start_col);
+        let insert_pos = utf8::byte_index_of_char(current_line, start_col);
 
         let prefix = current_line[..insert_pos].to_string();
         let suffix = current_line[insert_pos..].to_string();
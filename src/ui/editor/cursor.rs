@@ -1,69 +1,41 @@
 use super::buffer::Buffer;
+use crate::utf8;
 
 /// Cursor movement utilities using ropey
 /// Much simpler now - ropey handles UTF-8 automatically!
 pub struct CursorOps;
 
 impl CursorOps {
-    /// DEPRECATED: No longer needed with ropey
-    pub fn clamp_to_char_boundary(s: &str, idx: usize) -> usize {
-        let idx = idx.min(s.len());
-        if s.is_char_boundary(idx) {
-            return idx;
+    /// Screen column after drawing `c` at column `col`. Tabs advance to
+    /// the next multiple of `tab_size`.
+    pub fn advance_col(col: usize, c: char, tab_size: usize) -> usize {
+        if c == '\t' {
+            let tab_size = tab_size.max(1);
+            (col / tab_size + 1) * tab_size
+        } else {
+            col + 1
         }
-
-        // Search left for the nearest boundary
-        let mut i = idx;
-        while i > 0 {
-            i -= 1;
-            if s.is_char_boundary(i) {
-                return i;
-            }
-        }
-        0
-    }
-
-    /// Find the byte offset of the previous character boundary
-    pub fn prev_char_boundary(s: &str, idx: usize) -> usize {
-        let idx = Self::clamp_to_char_boundary(s, idx);
-        if idx == 0 {
-            return 0;
-        }
-
-        // Find the start byte offset of the previous char
-        let mut i = idx - 1;
-        while i > 0 && !s.is_char_boundary(i) {
-            i -= 1;
-        }
-        i
-    }
-
-    /// Find the byte offset of the next character boundary
-    pub fn next_char_boundary(s: &str, idx: usize) -> usize {
-        let idx = Self::clamp_to_char_boundary(s, idx);
-        if idx >= s.len() {
-            return s.len();
-        }
-
-        let ch = s[idx..].chars().next().unwrap_or('\0');
-        (idx + ch.len_utf8()).min(s.len())
     }
 
-    /// Convert byte index to character index
-    pub fn char_index_at_byte(s: &str, byte_idx: usize) -> usize {
-        let byte_idx = Self::clamp_to_char_boundary(s, byte_idx);
-        s[..byte_idx].chars().count()
+    /// Convert byte index to the screen column it is drawn at
+    pub fn display_col(s: &str, byte_idx: usize, tab_size: usize) -> usize {
+        let byte_idx = utf8::clamp_to_char_boundary(s, byte_idx);
+        s[..byte_idx]
+            .chars()
+            .fold(0, |col, c| Self::advance_col(col, c, tab_size))
     }
 
-    /// Convert character index to byte index
-    pub fn byte_index_of_char(s: &str, char_idx: usize) -> usize {
-        if char_idx == 0 {
-            return 0;
-        }
-        match s.char_indices().nth(char_idx) {
-            Some((b, _)) => b,
-            None => s.len(),
+    /// Convert a screen column to the byte index of the character drawn
+    /// there (the start of a tab covering it), or the line end
+    pub fn byte_at_display_col(s: &str, col: usize, tab_size: usize) -> usize {
+        let mut width = 0;
+        for (i, c) in s.char_indices() {
+            width = Self::advance_col(width, c, tab_size);
+            if width > col {
+                return i;
+            }
         }
+        s.len()
     }
 
     /// Ensure cursor_x is on a valid character boundary
@@ -73,7 +45,7 @@ impl CursorOps {
             return;
         }
         let line = &buf.lines[buf.cursor_y];
-        buf.cursor_x = Self::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
     }
 
     /// Clamp cursor_x to valid range for current line
@@ -86,7 +58,7 @@ impl CursorOps {
         let line = &buf.lines[buf.cursor_y];
         buf.cursor_x = buf.cursor_x.min(line.len());
         // With ropey, UTF-8 is handled automatically, but keep the check for safety
-        buf.cursor_x = Self::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
     }
 
     /// Move cursor up one line
@@ -114,10 +86,10 @@ impl CursorOps {
         }
 
         let line = &buf.lines[buf.cursor_y];
-        buf.cursor_x = Self::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
 
         if buf.cursor_x > 0 {
-            buf.cursor_x = Self::prev_char_boundary(line, buf.cursor_x);
+            buf.cursor_x = utf8::prev_char_boundary(line, buf.cursor_x);
         } else if buf.cursor_y > 0 {
             buf.cursor_y -= 1;
             buf.cursor_x = buf.lines[buf.cursor_y].len();
@@ -134,10 +106,10 @@ impl CursorOps {
         }
 
         let line = &buf.lines[buf.cursor_y];
-        buf.cursor_x = Self::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
 
         if buf.cursor_x < line.len() {
-            buf.cursor_x = Self::next_char_boundary(line, buf.cursor_x);
+            buf.cursor_x = utf8::next_char_boundary(line, buf.cursor_x);
         } else if buf.cursor_y + 1 < buf.lines.len() {
             buf.cursor_y += 1;
             buf.cursor_x = 0;
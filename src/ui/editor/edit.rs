@@ -1,7 +1,9 @@
 use super::buffer::Buffer;
 use super::clipboard::{Clipboard, YankType};
 use super::cursor::CursorOps;
-use super::undo::{EditorAction, UndoStack};
+use super::undo::{EditorAction, UndoTree};
+use crate::masm_lang::{DIRECTIVES, KEYWORDS, REGISTERS, TYPE_KEYWORDS};
+use crate::utf8;
 
 /// Editing operations (insert, delete, etc.)
 pub struct EditOps;
@@ -27,10 +29,325 @@ impl EditOps {
         }
     }
 
+    /// The line that closes the block `line` opens, if any: `name ENDP`
+    /// for `name PROC`, `ENDM` for `MACRO`, `name ENDS` for `STRUCT` and
+    /// `ENDIF` for the `IF` family. Keeps the opener's keyword case.
+    pub fn block_closer(line: &str) -> Option<String> {
+        let code = line.split(';').next().unwrap_or("");
+        let mut tokens = code.split_whitespace();
+        let first = tokens.next()?;
+        let second = tokens.next();
+
+        // Lowercase openers get lowercase closers
+        let cased = |keyword: &str, closer: &str| {
+            if keyword.chars().any(|c| c.is_ascii_lowercase()) {
+                closer.to_lowercase()
+            } else {
+                closer.to_string()
+            }
+        };
+
+        if let Some(keyword) = second {
+            match keyword.to_uppercase().as_str() {
+                "PROC" => return Some(format!("{} {}", first, cased(keyword, "ENDP"))),
+                "STRUCT" | "STRUC" => return Some(format!("{} {}", first, cased(keyword, "ENDS"))),
+                "MACRO" => return Some(cased(keyword, "ENDM")),
+                _ => {}
+            }
+        }
+
+        match first.to_uppercase().as_str() {
+            "IF" | "IFE" | "IFDEF" | "IFNDEF" | "IFB" | "IFNB" | "IFIDN" | "IFDIF" => {
+                Some(cased(first, "ENDIF"))
+            }
+            ".IF" => Some(cased(first, ".ENDIF")),
+            _ => None,
+        }
+    }
+
+    /// Regions `za` can fold, as inclusive line ranges sorted by first
+    /// line: `name PROC` to its `ENDP`, and a segment directive (`.data`,
+    /// `.code`, ...) to the line before the next one or `END`
+    pub fn fold_regions(lines: &[String]) -> Vec<(usize, usize)> {
+        const SEGMENTS: &[&str] = &[
+            ".code",
+            ".data",
+            ".data?",
+            ".const",
+            ".stack",
+            ".fardata",
+            ".fardata?",
+        ];
+        let mut regions = Vec::new();
+        let mut segment: Option<usize> = None;
+        let mut proc: Option<usize> = None;
+        for (idx, line) in lines.iter().enumerate() {
+            let code = line.split(';').next().unwrap_or("");
+            let mut tokens = code.split_whitespace();
+            let first = tokens.next().unwrap_or("").to_lowercase();
+            let second = tokens.next().unwrap_or("").to_lowercase();
+            if SEGMENTS.contains(&first.as_str()) || first == "end" {
+                if let Some(start) = segment.take().filter(|&start| idx > start + 1) {
+                    regions.push((start, idx - 1));
+                }
+                if first != "end" {
+                    segment = Some(idx);
+                }
+            } else if second == "proc" {
+                proc = Some(idx);
+            } else if second == "endp" {
+                if let Some(start) = proc.take() {
+                    regions.push((start, idx));
+                }
+            }
+        }
+        if let Some(start) = segment.filter(|&start| lines.len() > start + 1) {
+            regions.push((start, lines.len() - 1));
+        }
+        regions.sort_unstable();
+        regions
+    }
+
+    /// Split a line starting with a label at column 0 into the name, its
+    /// colons (none, `:` or `::`) and the rest. None when the first word
+    /// is not a plain identifier or is a reserved word.
+    fn split_label(line: &str) -> Option<(&str, &str, &str)> {
+        let end = line.find(char::is_whitespace).unwrap_or(line.len());
+        let (label, rest) = line.split_at(end);
+        let name = label.trim_end_matches(':');
+        let colons = &label[name.len()..];
+        let is_ident = |c: char| c.is_alphanumeric() || matches!(c, '_' | '@' | '?' | '$');
+        let valid = name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && name.chars().all(is_ident)
+            && colons.len() <= 2;
+        let lower = name.to_lowercase();
+        let reserved = [KEYWORDS, DIRECTIVES, REGISTERS, TYPE_KEYWORDS]
+            .iter()
+            .any(|words| words.contains(&lower.as_str()));
+        (valid && !reserved).then_some((name, colons, rest))
+    }
+
+    /// `name` alone on a line gets the colon MASM needs for a code label
+    pub fn label_colon(line: &str) -> Option<String> {
+        let (name, colons, rest) = Self::split_label(line)?;
+        (colons.is_empty() && rest.trim().is_empty()).then(|| format!("{}:", name))
+    }
+
+    /// After the space that follows `name mnemonic`, add the missing colon
+    /// and move the mnemonic to `column`. Only instruction mnemonics count,
+    /// so `PROC`, `EQU`, `=` and data definitions are left alone.
+    pub fn align_label(line: &str, column: usize) -> Option<String> {
+        let (name, colons, rest) = Self::split_label(line)?;
+        let mnemonic = rest.trim();
+        if !rest.ends_with(' ')
+            || mnemonic.contains(char::is_whitespace)
+            || !KEYWORDS.contains(&mnemonic.to_lowercase().as_str())
+        {
+            return None;
+        }
+        let head = format!("{}{}", name, if colons.is_empty() { ":" } else { colons });
+        let pad = column.saturating_sub(head.len()).max(1);
+        let aligned = format!("{}{}{} ", head, " ".repeat(pad), mnemonic);
+        (aligned != line).then_some(aligned)
+    }
+
+    /// Pad the code before the `;` at byte `semicolon` so the comment
+    /// starts at display `column`. None for a full-line comment, a `;`
+    /// that doesn't start the comment (inside a string literal or the
+    /// comment itself), or code that already reaches the column.
+    pub fn align_comment(
+        line: &str,
+        semicolon: usize,
+        column: usize,
+        tab_size: usize,
+    ) -> Option<String> {
+        if Self::comment_start(line) != Some(semicolon) {
+            return None;
+        }
+        let code = line[..semicolon].trim_end();
+        if code.trim().is_empty() {
+            return None;
+        }
+        let width = CursorOps::display_col(code, code.len(), tab_size);
+        if width >= column {
+            return None;
+        }
+        let aligned = format!(
+            "{}{}{}",
+            code,
+            " ".repeat(column - width),
+            &line[semicolon..]
+        );
+        (aligned != line).then_some(aligned)
+    }
+
+    /// Whether `<`/`>` on this line delimit a text-macro argument
+    /// (`TEXTEQU <...>`, `IRP x, <...>`) rather than acting as operators
+    pub fn angle_brackets_allowed(line: &str) -> bool {
+        const TEXT_DIRECTIVES: &[&str] = &[
+            "TEXTEQU", "CATSTR", "SUBSTR", "INSTR", "SIZESTR", "EQU", "IRP", "IRPC", "FOR", "FORC",
+            "ECHO", "%OUT",
+        ];
+        let code = line.split(';').next().unwrap_or("");
+        code.split(|c: char| c.is_whitespace() || c == ',')
+            .any(|token| {
+                TEXT_DIRECTIVES
+                    .iter()
+                    .any(|d| d.eq_ignore_ascii_case(token))
+            })
+    }
+
+    /// Split a full-line `;` comment into its prefix (indent, `;` and the
+    /// spacing after it) and text. None for code lines.
+    pub fn comment_parts(line: &str) -> Option<(&str, &str)> {
+        let indent = line.len() - line.trim_start().len();
+        let rest = line[indent..].strip_prefix(';')?;
+        let text = rest.trim_start();
+        Some(line.split_at(line.len() - text.len()))
+    }
+
+    /// `line` commented out with `; ` after its indent
+    pub fn comment_out(line: &str) -> String {
+        let indent = line.len() - line.trim_start().len();
+        format!("{}; {}", &line[..indent], &line[indent..])
+    }
+
+    /// `line` with `; text` appended as a trailing comment, or None when
+    /// the line is empty or already has a comment
+    pub fn append_comment(line: &str, text: &str) -> Option<String> {
+        let code = line.trim_end();
+        if code.is_empty() || Self::comment_start(code).is_some() {
+            return None;
+        }
+        Some(format!("{}  ; {}", code, text))
+    }
+
+    /// Byte offset of the `;` starting the line's comment, skipping any
+    /// inside string literals like `BYTE ";",0`
+    pub fn comment_start(line: &str) -> Option<usize> {
+        let mut quote = None;
+        for (i, c) in line.char_indices() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '\'' || c == '"' => quote = Some(c),
+                None if c == ';' => return Some(i),
+                None => {}
+            }
+        }
+        None
+    }
+
+    /// Pieces of `line` at most `width` characters long where it can be
+    /// split: at whitespace or after a comma, outside strings and the
+    /// comment. A stretch with no such place stays whole. The whitespace
+    /// at a split is dropped.
+    pub fn hard_wrap_line(line: &str, width: usize) -> Vec<String> {
+        // (end of a piece, start of the next) as (byte, char) offsets
+        let code_end = Self::comment_start(line).unwrap_or(line.len());
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let skip_space = |mut ci: usize| {
+            while chars.get(ci).is_some_and(|(_, c)| c.is_whitespace()) {
+                ci += 1;
+            }
+            ci
+        };
+        let mut splits = Vec::new();
+        let mut quote = None;
+        let mut ci = skip_space(0);
+        while ci < chars.len() && chars[ci].0 < code_end {
+            let c = chars[ci].1;
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c == '\'' || c == '"' => quote = Some(c),
+                None if c == ',' => splits.push((ci + 1, skip_space(ci + 1))),
+                None if c.is_whitespace() => {
+                    let next = skip_space(ci);
+                    splits.push((ci, next));
+                    ci = next;
+                    continue;
+                }
+                None => {}
+            }
+            ci += 1;
+        }
+        splits.retain(|&(_, next)| next < chars.len());
+        splits.dedup_by_key(|split| split.1);
+
+        let byte = |ci: usize| chars.get(ci).map_or(line.len(), |&(i, _)| i);
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        let mut splits = splits.into_iter().peekable();
+        while chars.len() - start > width {
+            let mut chosen = None;
+            while let Some(&(end, next)) = splits.peek() {
+                if chosen.is_some() && end - start > width {
+                    break;
+                }
+                chosen = Some((end, next));
+                splits.next();
+            }
+            let Some((end, next)) = chosen else {
+                break;
+            };
+            pieces.push(line[byte(start)..byte(end)].to_string());
+            start = next;
+        }
+        pieces.push(line[byte(start)..].to_string());
+        pieces
+    }
+
+    /// Rewrap runs of comment lines to `width` columns. Code lines, empty
+    /// comments and rulers like `;-----` are kept as they are and end a
+    /// paragraph, as does a change of indentation.
+    pub fn reflow_comments(lines: &[String], width: usize) -> Vec<String> {
+        fn prose(line: &str) -> Option<(&str, &str)> {
+            EditOps::comment_parts(line).filter(|(_, text)| text.chars().any(char::is_alphanumeric))
+        }
+
+        let mut out = Vec::with_capacity(lines.len());
+        let mut i = 0;
+        while i < lines.len() {
+            let Some((prefix, _)) = prose(&lines[i]) else {
+                out.push(lines[i].clone());
+                i += 1;
+                continue;
+            };
+
+            let mut words = Vec::new();
+            while let Some((p, text)) = lines.get(i).and_then(|line| prose(line)) {
+                if p.trim_end() != prefix.trim_end() {
+                    break;
+                }
+                words.extend(text.split_whitespace());
+                i += 1;
+            }
+
+            let mut line = String::from(prefix);
+            let mut has_word = false;
+            for word in words {
+                let fits =
+                    line.chars().count() + usize::from(has_word) + word.chars().count() <= width;
+                if has_word && !fits {
+                    out.push(std::mem::replace(&mut line, String::from(prefix)));
+                    has_word = false;
+                }
+                if has_word {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                has_word = true;
+            }
+            out.push(line);
+        }
+        out
+    }
+
     /// Insert a character at the cursor position
     pub fn insert_char(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         c: char,
         clear_search_fn: &mut dyn FnMut(),
     ) {
@@ -39,20 +356,20 @@ impl EditOps {
         }
 
         let line = &mut buf.lines[buf.cursor_y];
-        buf.cursor_x = CursorOps::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
         if buf.cursor_x > line.len() {
             return;
         }
 
         let ln = buf.cursor_y;
         let col_b = buf.cursor_x;
-        let col_c = CursorOps::char_index_at_byte(line, col_b);
+        let col_c = utf8::char_index_at_byte(line, col_b);
 
         line.insert(col_b, c);
         buf.cursor_x = col_b + c.len_utf8();
         buf.modified = true;
 
-        undo_stack.push(EditorAction::InsertChar {
+        undo_tree.push(EditorAction::InsertChar {
             line: ln,
             col: col_c,
             ch: c,
@@ -64,7 +381,7 @@ impl EditOps {
     /// Insert a newline at the cursor position
     pub fn insert_newline(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         auto_indent: bool,
         clear_search_fn: &mut dyn FnMut(),
     ) {
@@ -74,9 +391,9 @@ impl EditOps {
 
         let ln = buf.cursor_y;
         let current_line = &buf.lines[buf.cursor_y];
-        buf.cursor_x = CursorOps::clamp_to_char_boundary(current_line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(current_line, buf.cursor_x);
         let col_b = buf.cursor_x;
-        let col_c = CursorOps::char_index_at_byte(current_line, col_b);
+        let col_c = utf8::char_index_at_byte(current_line, col_b);
 
         let remainder = current_line[col_b..].to_string();
         buf.lines[buf.cursor_y] = current_line[..col_b].to_string();
@@ -93,7 +410,7 @@ impl EditOps {
         buf.cursor_x = indent.len();
         buf.modified = true;
 
-        undo_stack.push(EditorAction::SplitLine {
+        undo_tree.push(EditorAction::SplitLine {
             line: ln,
             col: col_c,
         });
@@ -104,15 +421,15 @@ impl EditOps {
     /// Delete character before cursor (backspace)
     pub fn backspace(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         clear_search_fn: &mut dyn FnMut(),
     ) {
         let action = if buf.cursor_y >= buf.lines.len() {
             None
         } else if buf.cursor_x > 0 {
             let line = &mut buf.lines[buf.cursor_y];
-            buf.cursor_x = CursorOps::clamp_to_char_boundary(line, buf.cursor_x);
-            let start = CursorOps::prev_char_boundary(line, buf.cursor_x);
+            buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
+            let start = utf8::prev_char_boundary(line, buf.cursor_x);
             let end = buf.cursor_x;
 
             if start == end {
@@ -120,7 +437,7 @@ impl EditOps {
             } else {
                 let ch = line[start..end].chars().next().unwrap_or(' ');
                 let line_num = buf.cursor_y;
-                let col_char = CursorOps::char_index_at_byte(line, start);
+                let col_char = utf8::char_index_at_byte(line, start);
 
                 line.drain(start..end);
                 buf.cursor_x = start;
@@ -154,7 +471,7 @@ impl EditOps {
         };
 
         if let Some(act) = action {
-            undo_stack.push(act);
+            undo_tree.push(act);
         }
         clear_search_fn();
     }
@@ -162,7 +479,7 @@ impl EditOps {
     /// Delete character at cursor
     pub fn delete_char(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         clear_search_fn: &mut dyn FnMut(),
     ) {
         let action = if buf.cursor_y >= buf.lines.len() {
@@ -173,14 +490,14 @@ impl EditOps {
 
             let cursor_x = {
                 let line_ref = &buf.lines[cursor_y];
-                CursorOps::clamp_to_char_boundary(line_ref, buf.cursor_x)
+                utf8::clamp_to_char_boundary(line_ref, buf.cursor_x)
             };
             buf.cursor_x = cursor_x;
 
             if cursor_x < line_len {
                 let end = {
                     let line_ref = &buf.lines[cursor_y];
-                    CursorOps::next_char_boundary(line_ref, cursor_x)
+                    utf8::next_char_boundary(line_ref, cursor_x)
                 };
 
                 if end <= cursor_x {
@@ -192,7 +509,7 @@ impl EditOps {
                     };
                     let col_char = {
                         let line_ref = &buf.lines[cursor_y];
-                        CursorOps::char_index_at_byte(line_ref, cursor_x)
+                        utf8::char_index_at_byte(line_ref, cursor_x)
                     };
 
                     {
@@ -224,7 +541,7 @@ impl EditOps {
         };
 
         if let Some(act) = action {
-            undo_stack.push(act);
+            undo_tree.push(act);
         }
         clear_search_fn();
     }
@@ -232,7 +549,7 @@ impl EditOps {
     /// Delete entire line (also copies to clipboard)
     pub fn delete_line(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         clipboard: &mut Clipboard,
         clear_search_fn: &mut dyn FnMut(),
     ) {
@@ -259,14 +576,14 @@ impl EditOps {
 
         if was_single {
             if !content.is_empty() {
-                undo_stack.push(EditorAction::ReplaceLine {
+                undo_tree.push(EditorAction::ReplaceLine {
                     line_num,
                     old: content,
                     new: String::new(),
                 });
             }
         } else {
-            undo_stack.push(EditorAction::DeleteLine { line_num, content });
+            undo_tree.push(EditorAction::DeleteLine { line_num, content });
         }
         clear_search_fn();
     }
@@ -274,12 +591,12 @@ impl EditOps {
     /// Insert tab (as spaces)
     pub fn insert_tab(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         tab_size: usize,
         clear_search_fn: &mut dyn FnMut(),
     ) {
         for _ in 0..tab_size {
-            Self::insert_char(buf, undo_stack, ' ', clear_search_fn);
+            Self::insert_char(buf, undo_tree, ' ', clear_search_fn);
         }
     }
 }
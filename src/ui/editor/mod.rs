@@ -9,33 +9,164 @@ pub mod selection;
 pub mod undo;
 
 // Public re-exports for API compatibility
-pub use buffer::Buffer;
-pub use undo::{EditorAction, UndoStack};
+pub use buffer::{Buffer, LineEnding};
+pub use search::Substitution;
+pub use undo::{EditorAction, UndoStep, UndoTree};
 
 use anyhow::Result;
-use std::path::PathBuf;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{EditorConfig, LineNumberMode, SearchCase, TitlePath};
+use crate::file_state::{FileState, FileStateStore};
+use crate::masm_lang;
+use crate::utf8;
 
 use clipboard::{Clipboard, YankType};
 use cursor::CursorOps;
 use edit::EditOps;
 use selection::SelectionOps;
 
+/// What kind of symbol a line defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Label,
+    Proc,
+    Macro,
+    Equ,
+}
+
+/// The symbol `line` defines as a label, PROC, MACRO or EQU, with the
+/// byte column of its name
+pub fn line_definition(line: &str) -> Option<(&str, usize, DefinitionKind)> {
+    let trimmed = line.trim();
+    let col = line.len() - line.trim_start().len();
+
+    if let Some(colon_pos) = trimmed.find(':') {
+        let label = trimmed[..colon_pos].trim_end();
+        let is_ident = |c: char| c.is_alphanumeric() || matches!(c, '_' | '@' | '?' | '$');
+        if !label.is_empty() && label.chars().all(is_ident) {
+            return Some((label, col, DefinitionKind::Label));
+        }
+    }
+
+    let mut parts = trimmed.split_whitespace();
+    let name = parts.next()?;
+    let kind = match parts.next()?.to_lowercase().as_str() {
+        "proc" => DefinitionKind::Proc,
+        "macro" => DefinitionKind::Macro,
+        "equ" => DefinitionKind::Equ,
+        _ => return None,
+    };
+    Some((name, col, kind))
+}
+
+/// Line and column where `symbol` is defined in `lines` as a label,
+/// PROC, MACRO or EQU
+fn find_definition(lines: &[String], symbol: &str) -> Option<(usize, usize)> {
+    find_definition_of(lines, symbol, |_| true)
+}
+
+/// Like `find_definition`, for `name PROC` only
+fn find_proc(lines: &[String], name: &str) -> Option<(usize, usize)> {
+    find_definition_of(lines, name, |kind| kind == DefinitionKind::Proc)
+}
+
+fn find_definition_of(
+    lines: &[String],
+    symbol: &str,
+    accept: impl Fn(DefinitionKind) -> bool,
+) -> Option<(usize, usize)> {
+    lines.iter().enumerate().find_map(|(line_idx, line)| {
+        line_definition(line)
+            .filter(|(name, _, kind)| accept(*kind) && name.eq_ignore_ascii_case(symbol))
+            .map(|(_, col, _)| (line_idx, col))
+    })
+}
+
+/// Whether `line` is hidden by `fold`, i.e. inside it past the summary line
+pub fn folded_away(fold: &(usize, usize), line: usize) -> bool {
+    line > fold.0 && line <= fold.1
+}
+
+/// Last `n` components of a path, joined with '/'
+fn path_suffix(path: &Path, n: usize) -> String {
+    let parts: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    parts[parts.len().saturating_sub(n)..].join("/")
+}
+
+fn suffix_len(suffix: &str) -> usize {
+    suffix.split('/').count()
+}
+
 /// Main editor state managing multiple buffers
 pub struct EditorState {
     pub buffers: Vec<Buffer>,
     pub active_buffer: usize,
+    // Previously active buffers, most recent last (for Ctrl+^)
+    pub recent_buffers: Vec<usize>,
     pub tab_size: usize,
     pub auto_indent: bool,
+    // Tab key: spaces vs literal '\t', and indent to the next stop at line start
+    pub insert_spaces: bool,
+    pub smart_tab: bool,
+    // Enter after PROC/MACRO/IF inserts the matching ENDP/ENDM/ENDIF
+    pub auto_close_blocks: bool,
+    // Add missing label colons and align the instruction after a label
+    pub auto_label: bool,
+    pub instruction_column: usize,
+    // Move an inline comment to comment_column as its `;` is typed
+    pub align_comments: bool,
+    pub comment_column: usize,
+    // Shortest comment run folded by zC
+    pub comment_fold_min_lines: usize,
+    // Bracket pairs the % motion jumps between
+    pub match_pairs: Vec<(char, char)>,
+    // Column :reflow wraps comments at
+    pub textwidth: usize,
+    // Lines longer than this (bytes) are drawn plain, without highlighting
+    pub long_line_threshold: usize,
+    // Long lines continue on the next screen row, text_width columns each
+    pub soft_wrap: bool,
+    // Shade the cursor's row
+    pub cursor_line: bool,
+    // Color brackets by nesting depth
+    pub rainbow_brackets: bool,
+    // Columns of text the editor was last laid out with
+    pub text_width: usize,
+    // Absolute, relative or hybrid gutter numbers
+    pub line_number_mode: LineNumberMode,
     // Search state
     pub search_query: String,
     pub search_matches: Vec<(usize, usize)>,
     pub current_match: usize,
+    // Regex search mode (toggled from the search bar) and its compiled query
+    pub search_is_regex: bool,
+    search_regex: Option<Regex>,
+    // Only match whole identifiers (toggled from the search bar)
+    pub search_whole_word: bool,
+    pub search_case: SearchCase,
     // Undo/Redo
-    pub undo_stack: UndoStack,
-    // Clipboard
+    pub undo_tree: UndoTree,
+    // Clipboard, plus the named registers "a-"z
     pub clipboard: Clipboard,
+    pub registers: HashMap<char, (String, YankType)>,
+    // Register the current yank/delete/paste uses, set from a "x prefix
+    pub register: Option<char>,
     // Jump stack for go-to-definition navigation
     pub jump_stack: Vec<(PathBuf, usize, usize)>,
+    // Cursor and jumps remembered per file across sessions (None = off)
+    pub file_states: Option<FileStateStore>,
+    // Block insert (I in block mode) waiting for Esc: lines and the
+    // display column the text typed on the first line is copied to
+    pub block_insert: Option<(usize, usize, usize)>,
+    // Title display
+    pub title_path: TitlePath,
+    pub project_root: PathBuf,
 }
 
 impl EditorState {
@@ -43,19 +174,68 @@ impl EditorState {
         Self {
             buffers: vec![Buffer::new()],
             active_buffer: 0,
+            recent_buffers: Vec::new(),
             tab_size,
             auto_indent: true,
+            insert_spaces: true,
+            smart_tab: true,
+            auto_close_blocks: false,
+            auto_label: false,
+            instruction_column: 8,
+            align_comments: false,
+            comment_column: 40,
+            comment_fold_min_lines: 3,
+            match_pairs: parse_match_pairs(DEFAULT_MATCH_PAIRS),
+            textwidth: 80,
+            long_line_threshold: 2000,
+            soft_wrap: false,
+            cursor_line: true,
+            rainbow_brackets: false,
+            text_width: 0,
+            line_number_mode: LineNumberMode::default(),
             search_query: String::new(),
             search_matches: Vec::new(),
             current_match: 0,
-            undo_stack: UndoStack::default(),
+            search_is_regex: false,
+            search_regex: None,
+            search_whole_word: false,
+            search_case: SearchCase::default(),
+            undo_tree: UndoTree::default(),
             clipboard: Clipboard::new(),
+            registers: HashMap::new(),
+            register: None,
             jump_stack: Vec::new(),
+            file_states: None,
+            block_insert: None,
+            title_path: TitlePath::default(),
+            project_root: PathBuf::from("."),
         }
     }
 
     // ========== Buffer Accessors ==========
 
+    /// Take the editing options from `config`, at startup and after `:set`
+    pub fn apply_config(&mut self, config: &EditorConfig) {
+        self.tab_size = config.tab_size;
+        self.auto_indent = config.auto_indent;
+        self.insert_spaces = config.insert_spaces;
+        self.smart_tab = config.smart_tab;
+        self.auto_close_blocks = config.auto_close_blocks;
+        self.auto_label = config.auto_label;
+        self.instruction_column = config.instruction_column;
+        self.align_comments = config.align_comments;
+        self.comment_column = config.comment_column;
+        self.comment_fold_min_lines = config.comment_fold_min_lines;
+        self.match_pairs = parse_match_pairs(&config.match_pairs);
+        self.textwidth = config.textwidth;
+        self.long_line_threshold = config.long_line_threshold;
+        self.soft_wrap = config.soft_wrap;
+        self.cursor_line = config.cursor_line;
+        self.rainbow_brackets = config.rainbow_brackets;
+        self.line_number_mode = config.line_number_mode;
+        self.search_case = config.search_case;
+    }
+
     fn buf(&self) -> &Buffer {
         &self.buffers[self.active_buffer]
     }
@@ -107,9 +287,10 @@ impl EditorState {
 
     pub fn open_file(&mut self, path: &PathBuf) -> Result<()> {
         // Check if file is already open
-        for (idx, buf) in self.buffers.iter().enumerate() {
+        for (idx, buf) in self.buffers.iter_mut().enumerate() {
             if buf.file_path.as_ref() == Some(path) {
-                self.active_buffer = idx;
+                buf.preview = false;
+                self.set_active_buffer(idx);
                 return Ok(());
             }
         }
@@ -126,12 +307,152 @@ impl EditorState {
             self.buffers[0] = buffer;
         } else {
             self.buffers.push(buffer);
-            self.active_buffer = self.buffers.len() - 1;
+            self.set_active_buffer(self.buffers.len() - 1);
+        }
+        self.restore_file_state(path);
+
+        Ok(())
+    }
+
+    /// Show `path` while browsing the file tree. It takes the place of the
+    /// previous preview buffer unless that one has been edited since, so
+    /// scrolling through a directory leaves at most one extra buffer.
+    pub fn preview_file(&mut self, path: &PathBuf) -> Result<()> {
+        if let Some(idx) = self
+            .buffers
+            .iter()
+            .position(|buf| buf.file_path.as_ref() == Some(path))
+        {
+            self.set_active_buffer(idx);
+            return Ok(());
         }
 
+        let reuse = self
+            .buffers
+            .iter()
+            .position(|buf| buf.preview && !buf.modified);
+        match reuse {
+            Some(idx) => {
+                let mut buffer = Buffer::from_file(path)?;
+                buffer.preview = true;
+                self.remember_file_state(idx);
+                self.buffers[idx] = buffer;
+                self.set_active_buffer(idx);
+                self.restore_file_state(path);
+            }
+            None => {
+                self.open_file(path)?;
+                self.buf_mut().preview = true;
+            }
+        }
         Ok(())
     }
 
+    /// Put the cursor, jump list, marks and closed folds back where they
+    /// were when `path` was last closed, skipping anything past the end of
+    /// the file
+    fn restore_file_state(&mut self, path: &Path) {
+        let line_count = self.buf().lines.len();
+        let Some(state) = self
+            .file_states
+            .as_ref()
+            .and_then(|store| store.get(path))
+            .cloned()
+            .and_then(|state| state.validated(line_count))
+        else {
+            return;
+        };
+
+        let buf = self.buf_mut();
+        buf.cursor_y = state.line;
+        buf.cursor_x = state.col;
+        CursorOps::clamp_cursor_x(buf);
+        buf.marks = state
+            .marks
+            .iter()
+            .map(|&(name, line, col)| (name, (line, col)))
+            .collect();
+        buf.closed_folds = state.closed_folds;
+
+        // Older than anything recorded this session. A file reopened in
+        // the same session already has its jumps on the stack.
+        let jumps: Vec<_> = state
+            .jumps
+            .iter()
+            .map(|&(line, col)| (path.to_path_buf(), line, col))
+            .filter(|jump| !self.jump_stack.contains(jump))
+            .collect();
+        self.jump_stack.splice(0..0, jumps);
+    }
+
+    /// Record the cursor, jump list, marks and closed folds of buffer `idx`
+    /// for next time
+    pub fn remember_file_state(&mut self, idx: usize) {
+        let Some(buf) = self.buffers.get(idx) else {
+            return;
+        };
+        let (Some(store), Some(path)) = (self.file_states.as_mut(), buf.file_path.as_ref()) else {
+            return;
+        };
+        let jumps = self
+            .jump_stack
+            .iter()
+            .filter(|(p, _, _)| p == path)
+            .map(|&(_, line, col)| (line, col))
+            .collect();
+        let mut marks: Vec<_> = buf
+            .marks
+            .iter()
+            .map(|(&name, &(line, col))| (name, line, col))
+            .collect();
+        marks.sort_unstable();
+        store.record(FileState {
+            path: path.clone(),
+            line: buf.cursor_y,
+            col: buf.cursor_x,
+            jumps,
+            marks,
+            closed_folds: buf.closed_folds.clone(),
+        });
+    }
+
+    /// Name shown for a buffer in the title and tab bar. In filename mode,
+    /// buffers sharing a filename get just enough parent directories to
+    /// tell them apart.
+    pub fn display_name(&self, idx: usize) -> String {
+        let path = match self.buffers.get(idx).and_then(|b| b.file_path.as_ref()) {
+            Some(p) => p,
+            None => return String::from("[untitled]"),
+        };
+        match self.title_path {
+            TitlePath::Absolute => path.display().to_string(),
+            TitlePath::Relative => path
+                .strip_prefix(&self.project_root)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+            TitlePath::Filename => {
+                let others: Vec<&PathBuf> = self
+                    .buffers
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != idx)
+                    .filter_map(|(_, b)| b.file_path.as_ref())
+                    .filter(|p| p.file_name() == path.file_name())
+                    .collect();
+                let depth = path.components().count();
+                (1..=depth)
+                    .map(|n| path_suffix(path, n))
+                    .find(|suffix| {
+                        others
+                            .iter()
+                            .all(|o| path_suffix(o, suffix_len(suffix)) != *suffix)
+                    })
+                    .unwrap_or_else(|| path.display().to_string())
+            }
+        }
+    }
+
     pub fn get_content(&self) -> String {
         self.buf().get_content()
     }
@@ -145,32 +466,161 @@ impl EditorState {
         }
 
         let line = &mut buf.lines[buf.cursor_y];
-        buf.cursor_x = CursorOps::clamp_to_char_boundary(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
         if buf.cursor_x > line.len() {
             return;
         }
 
         let ln = buf.cursor_y;
         let col_b = buf.cursor_x;
-        let col_c = CursorOps::char_index_at_byte(line, col_b);
+        let col_c = utf8::char_index_at_byte(line, col_b);
 
         line.insert(col_b, c);
         buf.cursor_x = col_b + c.len_utf8();
         buf.modified = true;
         buf.sync_rope(); // Sync rope after modifying lines
 
-        self.undo_stack.push(EditorAction::InsertChar {
+        self.undo_tree.push(EditorAction::InsertChar {
             line: ln,
             col: col_c,
             ch: c,
         });
         self.clear_search();
+
+        if c == ' ' && self.auto_label {
+            self.fix_label(false);
+        }
+        if c == ';' && self.align_comments {
+            self.align_comment();
+        }
+    }
+
+    /// With the cursor just after a typed `;` that follows code, pad the
+    /// code so the comment starts at `comment_column`
+    fn align_comment(&mut self) {
+        let (column, tab_size) = (self.comment_column, self.tab_size);
+        let buf = self.buf_mut();
+        let ln = buf.cursor_y;
+        let Some(semicolon) = buf.cursor_x.checked_sub(1) else {
+            return;
+        };
+        let Some(new) = buf
+            .lines
+            .get(ln)
+            .and_then(|line| EditOps::align_comment(line, semicolon, column, tab_size))
+        else {
+            return;
+        };
+
+        let old = std::mem::replace(&mut buf.lines[ln], new.clone());
+        // Keep the cursor after the `;`, wherever it moved to
+        buf.cursor_x = semicolon + 1 + new.len() - old.len();
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::ReplaceLine {
+            line_num: ln,
+            old,
+            new,
+        });
     }
 
     pub fn insert_newline(&mut self) {
+        if self.auto_label {
+            self.fix_label(true);
+        }
+        if self.auto_close_blocks && self.close_block() {
+            return;
+        }
         self.insert_newline_with_indent(self.auto_indent);
     }
 
+    /// With the cursor at the end of a MASM/TASM line starting with a
+    /// label, add its missing colon: on Enter for a label alone, otherwise
+    /// once an instruction follows it, which is also moved to
+    /// `instruction_column`
+    fn fix_label(&mut self, at_enter: bool) {
+        let column = self.instruction_column;
+        let buf = self.buf_mut();
+        if !matches!(
+            buf.dialect,
+            masm_lang::Dialect::Masm | masm_lang::Dialect::Tasm
+        ) {
+            return;
+        }
+        let ln = buf.cursor_y;
+        let Some(line) = buf.lines.get(ln) else {
+            return;
+        };
+        if buf.cursor_x != line.len() {
+            return;
+        }
+        let fixed = if at_enter {
+            EditOps::label_colon(line)
+        } else {
+            EditOps::align_label(line, column)
+        };
+        let Some(new) = fixed else {
+            return;
+        };
+
+        let old = std::mem::replace(&mut buf.lines[ln], new.clone());
+        buf.cursor_x = new.len();
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::ReplaceLine {
+            line_num: ln,
+            old,
+            new,
+        });
+    }
+
+    /// Enter at the end of a block opener: add an indented body line and the
+    /// matching closer as one undoable edit, leaving the cursor in the body.
+    /// Returns false (doing nothing) if the line opens no block or the
+    /// closer is already there.
+    fn close_block(&mut self) -> bool {
+        let tab = self.tab_size.max(1);
+        let buf = self.buf();
+        let ln = buf.cursor_y;
+        let Some(line) = buf.lines.get(ln) else {
+            return false;
+        };
+        if !line[buf.cursor_x.min(line.len())..].trim().is_empty() {
+            return false;
+        }
+        let Some(closer) = EditOps::block_closer(line) else {
+            return false;
+        };
+
+        // Skip if a closer below matches it, counting nested blocks of the
+        // same kind opened and closed in between
+        let mut depth = 0usize;
+        for l in &buf.lines[ln + 1..] {
+            let code = l.split(';').next().unwrap_or("").trim();
+            if code.eq_ignore_ascii_case(&closer) {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            } else if EditOps::block_closer(l).is_some_and(|c| c.eq_ignore_ascii_case(&closer)) {
+                depth += 1;
+            }
+        }
+
+        let base: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+
+        let body = format!("{}{}", base, " ".repeat(tab));
+        let text = format!("\n{}\n{}{}", body, base, closer);
+        self.buf_mut().cursor_x = line.len();
+        self.insert_text(&text);
+
+        let buf = self.buf_mut();
+        buf.cursor_y = ln + 1;
+        buf.cursor_x = body.len();
+        self.clear_search();
+        true
+    }
+
     pub fn insert_newline_with_indent(&mut self, auto_indent: bool) {
         let buf = self.buf_mut();
         if buf.cursor_y >= buf.lines.len() {
@@ -179,9 +629,9 @@ impl EditorState {
 
         let ln = buf.cursor_y;
         let current_line = &buf.lines[buf.cursor_y];
-        buf.cursor_x = CursorOps::clamp_to_char_boundary(current_line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(current_line, buf.cursor_x);
         let col_b = buf.cursor_x;
-        let col_c = CursorOps::char_index_at_byte(current_line, col_b);
+        let col_c = utf8::char_index_at_byte(current_line, col_b);
 
         let remainder = current_line[col_b..].to_string();
         buf.lines[buf.cursor_y] = current_line[..col_b].to_string();
@@ -197,9 +647,11 @@ impl EditorState {
             .insert(buf.cursor_y, format!("{}{}", indent, remainder));
         buf.cursor_x = indent.len();
         buf.modified = true;
+        // Marks on the split line stay with its first half
+        buf.shift_line_refs(ln + 1, 1);
         buf.sync_rope(); // Sync rope after modifying lines
 
-        self.undo_stack.push(EditorAction::SplitLine {
+        self.undo_tree.push(EditorAction::SplitLine {
             line: ln,
             col: col_c,
         });
@@ -213,8 +665,8 @@ impl EditorState {
                 None
             } else if buf.cursor_x > 0 {
                 let line = &mut buf.lines[buf.cursor_y];
-                buf.cursor_x = CursorOps::clamp_to_char_boundary(line, buf.cursor_x);
-                let start = CursorOps::prev_char_boundary(line, buf.cursor_x);
+                buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
+                let start = utf8::prev_char_boundary(line, buf.cursor_x);
                 let end = buf.cursor_x;
 
                 if start == end {
@@ -222,7 +674,7 @@ impl EditorState {
                 } else {
                     let ch = line[start..end].chars().next().unwrap_or(' ');
                     let line_num = buf.cursor_y;
-                    let col_char = CursorOps::char_index_at_byte(line, start);
+                    let col_char = utf8::char_index_at_byte(line, start);
 
                     line.drain(start..end);
                     buf.cursor_x = start;
@@ -259,7 +711,7 @@ impl EditorState {
         };
 
         if let Some(act) = action {
-            self.undo_stack.push(act);
+            self.undo_tree.push(act);
         }
         self.clear_search();
     }
@@ -275,14 +727,14 @@ impl EditorState {
 
                 let cursor_x = {
                     let line_ref = &buf.lines[cursor_y];
-                    CursorOps::clamp_to_char_boundary(line_ref, buf.cursor_x)
+                    utf8::clamp_to_char_boundary(line_ref, buf.cursor_x)
                 };
                 buf.cursor_x = cursor_x;
 
                 if cursor_x < line_len {
                     let end = {
                         let line_ref = &buf.lines[cursor_y];
-                        CursorOps::next_char_boundary(line_ref, cursor_x)
+                        utf8::next_char_boundary(line_ref, cursor_x)
                     };
 
                     if end <= cursor_x {
@@ -294,7 +746,7 @@ impl EditorState {
                         };
                         let col_char = {
                             let line_ref = &buf.lines[cursor_y];
-                            CursorOps::char_index_at_byte(line_ref, cursor_x)
+                            utf8::char_index_at_byte(line_ref, cursor_x)
                         };
 
                         {
@@ -329,9 +781,89 @@ impl EditorState {
         };
 
         if let Some(act) = action {
-            self.undo_stack.push(act);
+            self.undo_tree.push(act);
+        }
+        self.clear_search();
+    }
+
+    /// Replace the cursor line and the `count - 1` lines below it with
+    /// `new_line` as one undoable edit, leaving the cursor at `cursor_x`.
+    /// Returns the lines that were replaced.
+    fn replace_lines(&mut self, count: usize, new_line: String, cursor_x: usize) -> Vec<String> {
+        let buf = self.buf_mut();
+        let first = buf.cursor_y;
+        let last = (first + count.max(1) - 1).min(buf.lines.len() - 1);
+
+        let mut actions = Vec::new();
+        let mut replaced = vec![buf.lines[first].clone()];
+        for _ in first..last {
+            let content = buf.lines.remove(first + 1);
+            replaced.push(content.clone());
+            actions.push(EditorAction::DeleteLine {
+                line_num: first + 1,
+                content,
+            });
         }
+        actions.push(EditorAction::ReplaceLine {
+            line_num: first,
+            old: replaced[0].clone(),
+            new: new_line.clone(),
+        });
+
+        buf.lines[first] = new_line;
+        buf.cursor_x = cursor_x;
+        buf.modified = true;
+        buf.sync_rope();
+
+        let action = if actions.len() == 1 {
+            actions.remove(0)
+        } else {
+            EditorAction::Batch(actions)
+        };
+        self.undo_tree.push(action);
         self.clear_search();
+        replaced
+    }
+
+    /// Clear `count` lines into one, keeping the first line's indent (cc, S)
+    pub fn change_lines(&mut self, count: usize) {
+        let buf = self.buf();
+        let indent: String = buf.lines[buf.cursor_y]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        let cursor_x = indent.len();
+        let replaced = self.replace_lines(count, indent, cursor_x);
+        self.yank(&(replaced.join("\n") + "\n"), YankType::Line);
+    }
+
+    /// Delete from the cursor to the end of the line, plus `count - 1`
+    /// following lines (C)
+    pub fn change_to_line_end(&mut self, count: usize) {
+        let buf = self.buf();
+        let line = &buf.lines[buf.cursor_y];
+        let col = utf8::clamp_to_char_boundary(line, buf.cursor_x.min(line.len()));
+        let kept = line[..col].to_string();
+        let mut replaced = self.replace_lines(count, kept, col);
+        replaced[0] = replaced[0][col..].to_string();
+        self.yank(&replaced.join("\n"), YankType::Char);
+    }
+
+    /// Delete `count` characters under the cursor (s)
+    pub fn substitute_chars(&mut self, count: usize) {
+        let buf = self.buf();
+        let line = &buf.lines[buf.cursor_y];
+        let start = utf8::clamp_to_char_boundary(line, buf.cursor_x.min(line.len()));
+        let end = line[start..]
+            .char_indices()
+            .nth(count.max(1))
+            .map_or(line.len(), |(i, _)| start + i);
+        let deleted = line[start..end].to_string();
+        let new_line = format!("{}{}", &line[..start], &line[end..]);
+        if !deleted.is_empty() {
+            self.replace_lines(1, new_line, start);
+            self.yank(&deleted, YankType::Char);
+        }
     }
 
     pub fn delete_line(&mut self) {
@@ -357,38 +889,261 @@ impl EditorState {
             }
         };
 
-        self.clipboard
-            .copy(&(content.clone() + "\n"), YankType::Line);
+        self.yank(&(content.clone() + "\n"), YankType::Line);
 
         if was_single {
             if !content.is_empty() {
-                self.undo_stack.push(EditorAction::ReplaceLine {
+                self.undo_tree.push(EditorAction::ReplaceLine {
                     line_num,
                     old: content,
                     new: String::new(),
                 });
             }
         } else {
-            self.undo_stack
+            self.undo_tree
                 .push(EditorAction::DeleteLine { line_num, content });
         }
         self.clear_search();
     }
 
+    /// Insert a tab. In the leading whitespace of a line (with `smart_tab`)
+    /// this indents to the next tab stop; elsewhere it inserts `tab_size`
+    /// spaces, or a literal tab when `insert_spaces` is off.
     pub fn insert_tab(&mut self) {
-        for _ in 0..self.tab_size {
+        let tab = self.tab_size.max(1);
+        let buf = self.buf();
+        let before = &buf.lines[buf.cursor_y][..buf.cursor_x.min(buf.lines[buf.cursor_y].len())];
+        let in_indent = before.chars().all(|c| c == ' ' || c == '\t');
+
+        if !self.insert_spaces {
+            self.insert_char('\t');
+        } else if self.smart_tab && in_indent {
+            let width = indent_width(before, tab);
+            let next_stop = (width / tab + 1) * tab;
+            for _ in width..next_stop {
+                self.insert_char(' ');
+            }
+        } else {
+            for _ in 0..tab {
+                self.insert_char(' ');
+            }
+        }
+    }
+
+    /// `>>`/`>`: indent lines `start..=end` by one `tab_size` (or a tab
+    /// without `insert_spaces`), leaving blank lines alone
+    pub fn indent_lines(&mut self, start: usize, end: usize) -> bool {
+        let indent = if self.insert_spaces {
+            " ".repeat(self.tab_size.max(1))
+        } else {
+            String::from("\t")
+        };
+        self.shift_lines(start, end, |line| {
+            (!line.trim().is_empty()).then(|| format!("{}{}", indent, line))
+        })
+    }
+
+    /// `<<`/`<`: remove up to `tab_size` leading spaces, or one leading
+    /// tab, from lines `start..=end`
+    pub fn dedent_lines(&mut self, start: usize, end: usize) -> bool {
+        let tab = self.tab_size.max(1);
+        self.shift_lines(start, end, |line| {
+            let removed = if line.starts_with('\t') {
+                1
+            } else {
+                line.len() - line.trim_start_matches(' ').len()
+            };
+            (removed > 0).then(|| line[removed.min(tab)..].to_string())
+        })
+    }
+
+    /// Rewrite each line `shift` returns a new version of as one undoable
+    /// edit, then put the cursor on the first non-blank of line `start`
+    fn shift_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        shift: impl Fn(&str) -> Option<String>,
+    ) -> bool {
+        let buf = self.buf_mut();
+        let end = end.min(buf.lines.len().saturating_sub(1));
+        let mut actions = Vec::new();
+        for line_num in start..=end {
+            if let Some(new) = shift(&buf.lines[line_num]) {
+                let old = std::mem::replace(&mut buf.lines[line_num], new.clone());
+                actions.push(EditorAction::ReplaceLine { line_num, old, new });
+            }
+        }
+        if actions.is_empty() {
+            return false;
+        }
+        buf.cursor_y = start;
+        let line = &buf.lines[start];
+        buf.cursor_x = line.len() - line.trim_start().len();
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        true
+    }
+
+    /// Remove one level of indentation from the current line (Shift+Tab),
+    /// down to the previous tab stop. The cursor stays on the same text.
+    pub fn dedent_line(&mut self) {
+        let tab = self.tab_size.max(1);
+        let buf = self.buf();
+        let leading: String = buf.lines[buf.cursor_y]
+            .chars()
+            .take_while(|&c| c == ' ' || c == '\t')
+            .collect();
+        let width = indent_width(&leading, tab);
+        if width == 0 {
+            return;
+        }
+        let target = (width - 1) / tab * tab;
+
+        // Drop whitespace from the end of the indent until at or below the
+        // target, then pad back up with spaces if a tab overshot it
+        let mut keep = leading.len();
+        while keep > 0 && indent_width(&leading[..keep], tab) > target {
+            keep -= 1;
+        }
+        let pad = target - indent_width(&leading[..keep], tab);
+        let removed = leading.len() - keep;
+
+        let cursor = self.buf().cursor_x;
+        self.buf_mut().cursor_x = keep;
+        for _ in 0..removed {
+            self.delete_char();
+        }
+        for _ in 0..pad {
             self.insert_char(' ');
         }
+        self.buf_mut().cursor_x = if cursor >= leading.len() {
+            cursor - removed + pad
+        } else {
+            cursor.min(keep + pad)
+        };
     }
 
     // ========== Cursor Movement ==========
 
     pub fn move_cursor_up(&mut self) {
-        CursorOps::move_up(self.buf_mut());
+        let col = self.cursor_display_col();
+        let folds = self.folds();
+        let wrap = self.wrap_width();
+        // Wrapped lines are stepped through a screen row at a time
+        if let Some(width) = wrap {
+            if col >= width && self.line_rows(self.buf().cursor_y, &folds) > 1 {
+                self.set_cursor_display_col(col - width);
+                return;
+            }
+        }
+        let buf = self.buf_mut();
+        let from = buf.cursor_y;
+        CursorOps::move_up(buf);
+        // Land on the summary line of the outermost fold rather than inside
+        if let Some(start) = folds
+            .iter()
+            .filter(|f| folded_away(f, buf.cursor_y))
+            .map(|&(start, _)| start)
+            .min()
+        {
+            buf.cursor_y = start;
+        }
+        let to = buf.cursor_y;
+        match wrap {
+            // Arrive on the last screen row of a wrapped line
+            Some(width) if to != from => {
+                let rows = self.line_rows(to, &folds);
+                self.set_cursor_display_col((rows - 1) * width + col % width);
+            }
+            _ => self.set_cursor_display_col(col),
+        }
     }
 
     pub fn move_cursor_down(&mut self) {
-        CursorOps::move_down(self.buf_mut());
+        let col = self.cursor_display_col();
+        let folds = self.folds();
+        let wrap = self.wrap_width();
+        if let Some(width) = wrap {
+            if col / width + 1 < self.line_rows(self.buf().cursor_y, &folds) {
+                self.set_cursor_display_col(col + width);
+                return;
+            }
+        }
+        let buf = self.buf_mut();
+        let from = buf.cursor_y;
+        CursorOps::move_down(buf);
+        if let Some(end) = folds
+            .iter()
+            .filter(|f| folded_away(f, buf.cursor_y))
+            .map(|&(_, end)| end)
+            .max()
+        {
+            buf.cursor_y = if end + 1 < buf.lines.len() {
+                end + 1
+            } else {
+                from
+            };
+        }
+        match wrap {
+            Some(width) if buf.cursor_y != from => self.set_cursor_display_col(col % width),
+            _ => self.set_cursor_display_col(col),
+        }
+    }
+
+    /// Columns per screen row while soft wrap is on
+    fn wrap_width(&self) -> Option<usize> {
+        (self.soft_wrap && self.text_width > 0).then_some(self.text_width)
+    }
+
+    /// Screen rows line `idx` takes: more than one only for a wrapped
+    /// line, which also keeps room for the cursor after its last
+    /// character. Fold summaries and lines drawn plain (over
+    /// `long_line_threshold`) always take one.
+    pub fn line_rows(&self, idx: usize, folds: &[(usize, usize)]) -> usize {
+        let Some(width) = self.wrap_width() else {
+            return 1;
+        };
+        let Some(line) = self.buf().lines.get(idx) else {
+            return 1;
+        };
+        if line.len() > self.long_line_threshold || folds.iter().any(|&(start, _)| start == idx) {
+            return 1;
+        }
+        CursorOps::display_col(line, line.len(), self.tab_size) / width + 1
+    }
+
+    /// Screen row and column of the cursor from the top left of the
+    /// text, following it onto the continuation rows of a wrapped line
+    pub fn cursor_screen_pos(&self) -> (usize, usize) {
+        let cursor_y = self.buf().cursor_y;
+        let row = self.screen_row(cursor_y);
+        let col = self.cursor_display_col();
+        match self.wrap_width() {
+            Some(width) if self.line_rows(cursor_y, &self.folds()) > 1 => {
+                (row + col / width, col % width)
+            }
+            _ => (row, col),
+        }
+    }
+
+    /// Screen column of the cursor within the text, with tabs expanded
+    pub fn cursor_display_col(&self) -> usize {
+        let buf = self.buf();
+        buf.lines.get(buf.cursor_y).map_or(0, |line| {
+            CursorOps::display_col(line, buf.cursor_x, self.tab_size)
+        })
+    }
+
+    /// Put the cursor on the character drawn at screen column `col`
+    fn set_cursor_display_col(&mut self, col: usize) {
+        let tab_size = self.tab_size;
+        let buf = self.buf_mut();
+        if let Some(line) = buf.lines.get(buf.cursor_y) {
+            buf.cursor_x = CursorOps::byte_at_display_col(line, col, tab_size);
+        }
     }
 
     pub fn move_cursor_left(&mut self) {
@@ -408,82 +1163,307 @@ impl EditorState {
     }
 
     pub fn ensure_cursor_visible(&mut self, visible_height: usize) {
-        CursorOps::ensure_visible(self.buf_mut(), visible_height);
+        if self.wrap_width().is_some() {
+            self.ensure_cursor_visible_wrapped(visible_height);
+            return;
+        }
+        let folds = self.folds();
+        let buf = self.buf_mut();
+        let old_offset = buf.scroll_offset;
+        CursorOps::ensure_visible(buf, visible_height);
+        if folds.is_empty() || buf.scroll_offset <= old_offset {
+            return;
+        }
+        // Folded lines take no rows, so scrolling down needs fewer lines
+        let cursor_y = buf.cursor_y;
+        let rows = |from: usize| {
+            (from..=cursor_y)
+                .filter(|&i| !folds.iter().any(|f| folded_away(f, i)))
+                .count()
+        };
+        let mut offset = old_offset;
+        while rows(offset) > visible_height {
+            offset += 1;
+        }
+        buf.scroll_offset = offset;
     }
 
-    // ========== Clipboard Operations ==========
-
-    pub fn yank_line(&mut self) {
-        let buf = &self.buffers[self.active_buffer];
-        if buf.cursor_y < buf.lines.len() {
-            let content = buf.lines[buf.cursor_y].clone() + "\n";
-            self.clipboard.copy(&content, YankType::Line);
+    /// `ensure_cursor_visible` counting screen rows of wrapped lines
+    fn ensure_cursor_visible_wrapped(&mut self, visible_height: usize) {
+        let cursor_y = self.buf().cursor_y;
+        if cursor_y < self.buf().scroll_offset {
+            self.buf_mut().scroll_offset = cursor_y;
+            return;
+        }
+        let (row, _) = self.cursor_screen_pos();
+        if row < visible_height {
+            return;
+        }
+        // Walk up from the cursor row until the lines above fill the screen
+        let folds = self.folds();
+        let mut used = row - self.screen_row(cursor_y) + 1;
+        let mut top = cursor_y;
+        while let Some(prev) = (0..top)
+            .rev()
+            .find(|&i| !folds.iter().any(|f| folded_away(f, i)))
+        {
+            let rows = self.line_rows(prev, &folds);
+            if used + rows > visible_height {
+                break;
+            }
+            used += rows;
+            top = prev;
         }
+        self.buf_mut().scroll_offset = top;
     }
 
-    pub fn paste_after(&mut self) {
-        let (text, yank_type) = match self.clipboard.paste() {
-            Some(v) => v,
-            None => return,
-        };
+    // ========== Folding ==========
 
-        if text.is_empty() {
-            return;
+    /// Every closed fold in the current buffer: comment blocks while
+    /// comment folding is on, and PROC/segment regions closed with `za`.
+    /// As with comment blocks, a region the cursor is inside stays open.
+    pub fn folds(&self) -> Vec<(usize, usize)> {
+        let mut folds = self.comment_folds();
+        let buf = self.buf();
+        if !buf.closed_folds.is_empty() {
+            folds.extend(
+                buf.fold_regions()
+                    .into_iter()
+                    .filter(|&(start, _)| buf.closed_folds.contains(&start))
+                    .filter(|fold| !folded_away(fold, buf.cursor_y)),
+            );
         }
+        folds
+    }
 
-        match yank_type {
-            YankType::Line => {
-                let line_content = text.trim_end_matches('\n').to_string();
-                let buf = self.buf_mut();
-                let at = buf.cursor_y + 1;
-                buf.lines.insert(at, line_content.clone());
-                buf.cursor_y = at;
-                buf.cursor_x = 0;
-                buf.modified = true;
-
-                self.undo_stack.push(EditorAction::InsertLine {
-                    line_num: at,
-                    content: line_content,
-                });
-            }
-            YankType::Char => {
-                let buf = &mut self.buffers[self.active_buffer];
-                clipboard::paste_text_inline(buf, &mut self.undo_stack, &text);
-            }
+    /// `za`: open the fold starting on the cursor line, or close the
+    /// innermost PROC or segment region around the cursor. Returns whether
+    /// a fold was closed, or None when there is nothing to fold.
+    pub fn toggle_fold(&mut self) -> Option<bool> {
+        let buf = self.buf_mut();
+        let cursor = buf.cursor_y;
+        if let Some(pos) = buf.closed_folds.iter().position(|&start| start == cursor) {
+            buf.closed_folds.remove(pos);
+            return Some(false);
         }
+        let (start, _) = buf
+            .fold_regions()
+            .into_iter()
+            .filter(|&(start, end)| start <= cursor && cursor <= end)
+            .max_by_key(|&(start, _)| start)?;
+        buf.closed_folds.push(start);
+        buf.cursor_y = start;
+        buf.cursor_x = 0;
+        Some(true)
     }
 
-    pub fn paste_before(&mut self) {
-        let (text, yank_type) = match self.clipboard.paste() {
-            Some(v) => v,
-            None => return,
-        };
+    /// Runs of at least `comment_fold_min_lines` comment-only lines, as
+    /// inclusive line ranges, while folding is on for the buffer. The
+    /// first line of each stays visible as a summary. A run the cursor has
+    /// moved into (by a search, say) is left open.
+    pub fn comment_folds(&self) -> Vec<(usize, usize)> {
+        let buf = self.buf();
+        if !buf.fold_comments {
+            return Vec::new();
+        }
+        let min_lines = self.comment_fold_min_lines.max(2);
+        buf.comment_runs()
+            .into_iter()
+            .filter(|&(start, end)| end + 1 - start >= min_lines)
+            .filter(|&(start, end)| !(buf.cursor_y > start && buf.cursor_y <= end))
+            .collect()
+    }
 
-        if text.is_empty() {
-            return;
+    /// Fold (or unfold) every comment block in the current buffer.
+    /// Returns how many blocks are folded.
+    pub fn set_comment_folding(&mut self, on: bool) -> usize {
+        self.buf_mut().fold_comments = on;
+        let folds = self.comment_folds();
+        // Put a cursor that is now hidden on its fold's summary line
+        let buf = self.buf_mut();
+        if let Some(&(start, _)) = folds.iter().find(|f| folded_away(f, buf.cursor_y)) {
+            buf.cursor_y = start;
+            buf.cursor_x = 0;
         }
+        folds.len()
+    }
 
-        match yank_type {
-            YankType::Line => {
-                let line_content = text.trim_end_matches('\n').to_string();
-                let buf = self.buf_mut();
-                let at = buf.cursor_y;
+    /// Screen row of `line` below the top of the editor, skipping folded
+    /// lines
+    pub fn screen_row(&self, line: usize) -> usize {
+        let folds = self.folds();
+        let top = self.buf().scroll_offset;
+        (top..line)
+            .filter(|&i| !folds.iter().any(|f| folded_away(f, i)))
+            .map(|i| self.line_rows(i, &folds))
+            .sum()
+    }
+
+    /// Columns taken by the diagnostic indicator and line numbers left of
+    /// the text
+    pub fn gutter_width(&self) -> usize {
+        self.buf().lines.len().to_string().len().max(3) + 3
+    }
+
+    /// Buffer (line, byte column) of the character drawn at screen `row`
+    /// and `col`, counted from the top left of the text. Rows below the
+    /// last line land on it, columns past a line's end on the line end.
+    pub fn position_at_screen(&self, row: usize, col: usize) -> (usize, usize) {
+        let folds = self.folds();
+        let buf = self.buf();
+        let mut line = buf.lines.len().saturating_sub(1);
+        let mut sub_row = 0;
+        let mut remaining = row;
+        for i in (buf.scroll_offset..buf.lines.len())
+            .filter(|&i| !folds.iter().any(|f| folded_away(f, i)))
+        {
+            line = i;
+            let rows = self.line_rows(i, &folds);
+            if remaining < rows {
+                sub_row = remaining;
+                break;
+            }
+            remaining -= rows;
+        }
+        let col = sub_row * self.text_width + col;
+        let x = buf.lines.get(line).map_or(0, |text| {
+            CursorOps::byte_at_display_col(text, col, self.tab_size)
+        });
+        (line, x)
+    }
+
+    // ========== Mouse Selection ==========
+
+    /// Move the cursor to a (line, byte column) from `position_at_screen`
+    pub fn set_cursor_position(&mut self, (line, col): (usize, usize)) {
+        let buf = self.buf_mut();
+        buf.cursor_y = line.min(buf.lines.len().saturating_sub(1));
+        buf.cursor_x = col;
+        CursorOps::clamp_cursor_x(buf);
+    }
+
+    /// Select the word at `pos` (double-click). Returns false when there
+    /// is no word there; the cursor still moves.
+    pub fn select_word_at(&mut self, pos: (usize, usize)) -> bool {
+        self.set_cursor_position(pos);
+        let Some((start, end)) = self.word_range_under_cursor() else {
+            return false;
+        };
+        let buf = self.buf_mut();
+        buf.selection_start = Some((buf.cursor_y, start));
+        buf.selection_end = Some((buf.cursor_y, end));
+        buf.cursor_x = end;
+        true
+    }
+
+    /// Select the whole of `line` (triple-click)
+    pub fn select_line_at(&mut self, line: usize) {
+        self.set_cursor_position((line, 0));
+        let buf = self.buf_mut();
+        let len = buf.lines.get(buf.cursor_y).map_or(0, |l| l.len());
+        buf.selection_start = Some((buf.cursor_y, 0));
+        buf.selection_end = Some((buf.cursor_y, len));
+    }
+
+    // ========== Clipboard Operations ==========
+
+    /// Store yanked or deleted text in the selected register, or the
+    /// clipboard when none is selected
+    fn yank(&mut self, text: &str, yank_type: YankType) {
+        match self.register {
+            Some(name) => {
+                self.registers.insert(name, (text.to_string(), yank_type));
+            }
+            None => self.clipboard.copy(text, yank_type),
+        }
+    }
+
+    /// Text a paste inserts: the selected register, or the clipboard
+    fn put_source(&mut self) -> Option<(String, YankType)> {
+        match self.register {
+            Some(name) => self.registers.get(&name).cloned(),
+            None => self.clipboard.paste(),
+        }
+    }
+
+    pub fn yank_line(&mut self) {
+        let buf = &self.buffers[self.active_buffer];
+        if buf.cursor_y < buf.lines.len() {
+            let content = buf.lines[buf.cursor_y].clone() + "\n";
+            self.yank(&content, YankType::Line);
+        }
+    }
+
+    pub fn paste_after(&mut self) {
+        let (text, yank_type) = match self.put_source() {
+            Some(v) => v,
+            None => return,
+        };
+
+        if text.is_empty() {
+            return;
+        }
+
+        match yank_type {
+            YankType::Line => {
+                let line_content = text.trim_end_matches('\n').to_string();
+                let buf = self.buf_mut();
+                let at = buf.cursor_y + 1;
+                buf.lines.insert(at, line_content.clone());
+                buf.cursor_y = at;
+                buf.cursor_x = 0;
+                buf.modified = true;
+
+                self.undo_tree.push(EditorAction::InsertLine {
+                    line_num: at,
+                    content: line_content,
+                });
+            }
+            YankType::Char => {
+                let buf = &mut self.buffers[self.active_buffer];
+                clipboard::paste_text_inline(buf, &mut self.undo_tree, &text);
+            }
+        }
+    }
+
+    pub fn paste_before(&mut self) {
+        let (text, yank_type) = match self.put_source() {
+            Some(v) => v,
+            None => return,
+        };
+
+        if text.is_empty() {
+            return;
+        }
+
+        match yank_type {
+            YankType::Line => {
+                let line_content = text.trim_end_matches('\n').to_string();
+                let buf = self.buf_mut();
+                let at = buf.cursor_y;
                 buf.lines.insert(at, line_content.clone());
                 buf.cursor_x = 0;
                 buf.modified = true;
 
-                self.undo_stack.push(EditorAction::InsertLine {
+                self.undo_tree.push(EditorAction::InsertLine {
                     line_num: at,
                     content: line_content,
                 });
             }
             YankType::Char => {
                 let buf = &mut self.buffers[self.active_buffer];
-                clipboard::paste_text_inline(buf, &mut self.undo_stack, &text);
+                clipboard::paste_text_inline(buf, &mut self.undo_tree, &text);
             }
         }
     }
 
+    /// Insert text at the cursor as a single undoable edit
+    pub fn insert_text(&mut self, text: &str) {
+        let buf = &mut self.buffers[self.active_buffer];
+        clipboard::paste_text_inline(buf, &mut self.undo_tree, text);
+        buf.sync_rope();
+    }
+
     // ========== Selection Operations ==========
 
     pub fn start_selection(&mut self) {
@@ -502,14 +1482,22 @@ impl EditorState {
         SelectionOps::get_selection_range(self.buf())
     }
 
+    /// Text covered by the current selection, if any
+    pub fn selected_text(&self) -> Option<String> {
+        if let Some(text) = self.block_text() {
+            return Some(text);
+        }
+        let buf = self.buf();
+        let ((start_line, start_col), (end_line, end_col)) =
+            SelectionOps::get_selection_range(buf)?;
+        Some(SelectionOps::extract_selection_text(
+            buf, start_line, start_col, end_line, end_col,
+        ))
+    }
+
     pub fn yank_selection(&mut self) -> bool {
-        let buf = &self.buffers[self.active_buffer];
-        if let Some(((start_line, start_col), (end_line, end_col))) =
-            SelectionOps::get_selection_range(buf)
-        {
-            let text =
-                SelectionOps::extract_selection_text(buf, start_line, start_col, end_line, end_col);
-            self.clipboard.copy(&text, YankType::Char);
+        if let Some(text) = self.selected_text() {
+            self.yank(&text, YankType::Char);
             true
         } else {
             false
@@ -517,8 +1505,162 @@ impl EditorState {
     }
 
     pub fn delete_selection(&mut self) -> bool {
+        if self.buf().block_selection {
+            return self.delete_block();
+        }
         let buf = &mut self.buffers[self.active_buffer];
-        SelectionOps::delete_selection(buf, &mut self.undo_stack, &mut self.clipboard)
+        SelectionOps::delete_selection(buf, &mut self.undo_tree, &mut self.clipboard)
+    }
+
+    // ========== Block Selection ==========
+
+    /// Start a rectangular selection at the cursor
+    pub fn start_block_selection(&mut self) {
+        self.start_selection();
+        self.buf_mut().block_selection = true;
+    }
+
+    /// Rectangle of a block selection as (top, bottom, left, right): lines
+    /// inclusive, display columns with `right` exclusive, so the column
+    /// of the corner characters is part of it
+    pub fn block_range(&self) -> Option<(usize, usize, usize, usize)> {
+        let buf = self.buf();
+        if !buf.block_selection {
+            return None;
+        }
+        let (start, end) = (buf.selection_start?, buf.selection_end?);
+        let col = |(line, x): (usize, usize)| {
+            buf.lines
+                .get(line)
+                .map_or(0, |l| CursorOps::display_col(l, x, self.tab_size))
+        };
+        let (a, b) = (col(start), col(end));
+        Some((
+            start.0.min(end.0),
+            start.0.max(end.0),
+            a.min(b),
+            a.max(b) + 1,
+        ))
+    }
+
+    /// Byte range of `line` drawn within display columns `left..right`
+    pub fn block_bytes(&self, line: &str, left: usize, right: usize) -> (usize, usize) {
+        (
+            CursorOps::byte_at_display_col(line, left, self.tab_size),
+            CursorOps::byte_at_display_col(line, right, self.tab_size),
+        )
+    }
+
+    /// The rectangle's text, one line per row
+    fn block_text(&self) -> Option<String> {
+        let (top, bottom, left, right) = self.block_range()?;
+        let rows: Vec<&str> = self.buf().lines[top..=bottom]
+            .iter()
+            .map(|line| {
+                let (from, to) = self.block_bytes(line, left, right);
+                &line[from..to]
+            })
+            .collect();
+        Some(rows.join("\n"))
+    }
+
+    /// Cut the rectangle out of every line it spans, as one undoable step
+    fn delete_block(&mut self) -> bool {
+        let (Some((top, bottom, left, right)), Some(text)) =
+            (self.block_range(), self.block_text())
+        else {
+            return false;
+        };
+        self.yank(&text, YankType::Char);
+
+        let mut actions = Vec::new();
+        for ln in top..=bottom {
+            let line = &self.buf().lines[ln];
+            let (from, to) = self.block_bytes(line, left, right);
+            if from == to {
+                continue;
+            }
+            let new = format!("{}{}", &line[..from], &line[to..]);
+            let old = std::mem::replace(&mut self.buf_mut().lines[ln], new.clone());
+            actions.push(EditorAction::ReplaceLine {
+                line_num: ln,
+                old,
+                new,
+            });
+        }
+
+        let tab_size = self.tab_size;
+        let buf = self.buf_mut();
+        buf.cursor_y = top;
+        buf.cursor_x = CursorOps::byte_at_display_col(&buf.lines[top], left, tab_size);
+        SelectionOps::clear_selection(buf);
+        if actions.is_empty() {
+            return false;
+        }
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        true
+    }
+
+    /// `I` in block mode: put the cursor at the block's left edge on its
+    /// first line. What is typed there is copied to the other lines by
+    /// `finish_block_insert` when insert mode ends.
+    pub fn begin_block_insert(&mut self) {
+        let Some((top, bottom, left, _)) = self.block_range() else {
+            return;
+        };
+        let tab_size = self.tab_size;
+        let buf = self.buf_mut();
+        buf.cursor_y = top;
+        buf.cursor_x = CursorOps::byte_at_display_col(&buf.lines[top], left, tab_size);
+        SelectionOps::clear_selection(buf);
+        self.block_insert = Some((top, bottom, left));
+    }
+
+    /// Repeat the text typed since `begin_block_insert` on the rest of the
+    /// block's lines, skipping lines too short to reach its column. Returns
+    /// how many lines got a copy.
+    pub fn finish_block_insert(&mut self) -> usize {
+        let Some((top, bottom, left)) = self.block_insert.take() else {
+            return 0;
+        };
+        let tab_size = self.tab_size;
+        let buf = self.buf();
+        // Nothing to copy if the cursor left the line (Enter, a motion)
+        let Some(first) = buf.lines.get(top).filter(|_| buf.cursor_y == top) else {
+            return 0;
+        };
+        let start = CursorOps::byte_at_display_col(first, left, tab_size);
+        if buf.cursor_x <= start {
+            return 0;
+        }
+        let text = first[start..buf.cursor_x].to_string();
+
+        let mut copies = 0;
+        let last = bottom.min(buf.lines.len() - 1);
+        for ln in top + 1..=last {
+            let line = &self.buf().lines[ln];
+            if CursorOps::display_col(line, line.len(), tab_size) < left {
+                continue;
+            }
+            let at = CursorOps::byte_at_display_col(line, left, tab_size);
+            let new = format!("{}{}{}", &line[..at], text, &line[at..]);
+            let old = std::mem::replace(&mut self.buf_mut().lines[ln], new.clone());
+            self.undo_tree.push(EditorAction::ReplaceLine {
+                line_num: ln,
+                old,
+                new,
+            });
+            copies += 1;
+        }
+        if copies > 0 {
+            let buf = self.buf_mut();
+            buf.modified = true;
+            buf.sync_rope();
+        }
+        copies
     }
 
     // ========== Search Operations ==========
@@ -527,18 +1669,47 @@ impl EditorState {
         self.search_query = query.to_string();
         self.search_matches.clear();
         self.current_match = 0;
+        self.search_regex = None;
 
         if query.is_empty() {
             return;
         }
 
-        let query_lower = query.to_lowercase();
+        let ignore_case = self.search_case.ignores_case(query);
+        if self.search_is_regex {
+            // An invalid pattern leaves no matches and search_status
+            // reports it
+            let Ok(regex) = RegexBuilder::new(query)
+                .case_insensitive(ignore_case)
+                .build()
+            else {
+                return;
+            };
+            let buf = &self.buffers[self.active_buffer];
+            let whole_word = self.search_whole_word;
+            for (line_idx, line) in buf.lines.iter().enumerate() {
+                self.search_matches.extend(
+                    regex
+                        .find_iter(line)
+                        .filter(|m| !m.is_empty())
+                        .filter(|m| !whole_word || is_whole_word(line, m.start(), m.end()))
+                        .map(|m| (line_idx, m.start())),
+                );
+            }
+            self.search_regex = Some(regex);
+            return;
+        }
+
+        let query_lower = fold_case(query, ignore_case);
         let lines: Vec<String> = self.buf().lines.clone();
         for (line_idx, line) in lines.iter().enumerate() {
-            let line_lower = line.to_lowercase();
+            let line_lower = fold_case(line, ignore_case);
             let mut start = 0;
-            while let Some(pos) = line_lower[start..].find(&query_lower) {
-                self.search_matches.push((line_idx, start + pos));
+            while let Some(pos) = line_lower[start..].find(&*query_lower) {
+                let at = start + pos;
+                if !self.search_whole_word || is_whole_word(line, at, at + query_lower.len()) {
+                    self.search_matches.push((line_idx, at));
+                }
                 start += pos + 1;
             }
         }
@@ -576,11 +1747,45 @@ impl EditorState {
         self.search_query.clear();
         self.search_matches.clear();
         self.current_match = 0;
+        self.search_regex = None;
+    }
+
+    /// Byte ranges in `line` the current search matches, for highlighting
+    pub fn search_ranges(&self, line: &str) -> Vec<(usize, usize)> {
+        if self.search_query.is_empty() {
+            return Vec::new();
+        }
+        let ranges: Vec<(usize, usize)> = match &self.search_regex {
+            Some(regex) => regex
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            None if self.search_is_regex => Vec::new(),
+            None => {
+                let ignore_case = self.search_case.ignores_case(&self.search_query);
+                let query = fold_case(&self.search_query, ignore_case);
+                fold_case(line, ignore_case)
+                    .match_indices(&*query)
+                    .map(|(start, _)| (start, start + query.len()))
+                    .collect()
+            }
+        };
+        if !self.search_whole_word {
+            return ranges;
+        }
+        ranges
+            .into_iter()
+            .filter(|&(start, end)| is_whole_word(line, start, end))
+            .collect()
     }
 
     pub fn search_status(&self) -> Option<String> {
         if self.search_matches.is_empty() {
-            if !self.search_query.is_empty() {
+            if self.search_is_regex && self.search_regex.is_none() && !self.search_query.is_empty()
+            {
+                Some(String::from("invalid regex"))
+            } else if !self.search_query.is_empty() {
                 Some(String::from("No matches"))
             } else {
                 None
@@ -597,9 +1802,9 @@ impl EditorState {
     // ========== Undo/Redo Operations ==========
 
     pub fn undo(&mut self) -> bool {
-        if let Some(action) = self.undo_stack.pop_undo() {
+        if let Some(action) = self.undo_tree.undo() {
             self.apply_undo_action(&action);
-            self.undo_stack.push_redo(action);
+            self.buf_mut().sync_rope();
             true
         } else {
             false
@@ -607,24 +1812,48 @@ impl EditorState {
     }
 
     pub fn redo(&mut self) -> bool {
-        if let Some(action) = self.undo_stack.pop_redo() {
+        if let Some(action) = self.undo_tree.redo() {
             self.apply_redo_action(&action);
-            self.undo_stack.undo_stack.push_back(action);
+            self.buf_mut().sync_rope();
             true
         } else {
             false
         }
     }
 
+    /// Move to any node of the undo tree, undoing and redoing along the way
+    pub fn undo_jump_to(&mut self, node: usize) -> bool {
+        let steps = self.undo_tree.jump_to(node);
+        if steps.is_empty() {
+            return false;
+        }
+        for step in &steps {
+            match step {
+                UndoStep::Undo(action) => self.apply_undo_action(action),
+                UndoStep::Redo(action) => self.apply_redo_action(action),
+            }
+        }
+        self.buf_mut().sync_rope();
+        true
+    }
+
+    /// Move backward (g-) or forward (g+) in time through the undo tree
+    pub fn undo_time_step(&mut self, forward: bool) -> bool {
+        match self.undo_tree.time_step(forward) {
+            Some(node) => self.undo_jump_to(node),
+            None => false,
+        }
+    }
+
     fn apply_undo_action(&mut self, action: &EditorAction) {
         let buf = self.buf_mut();
         match action {
             EditorAction::InsertChar { line, col, .. } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     if col_b < ln.len() {
-                        let end = CursorOps::next_char_boundary(ln, col_b);
+                        let end = utf8::next_char_boundary(ln, col_b);
                         ln.drain(col_b..end);
                         buf.cursor_y = *line;
                         buf.cursor_x = col_b;
@@ -635,7 +1864,7 @@ impl EditorState {
             EditorAction::DeleteChar { line, col, ch } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     ln.insert(col_b, *ch);
                     buf.cursor_y = *line;
                     buf.cursor_x = (col_b + ch.len_utf8()).min(ln.len());
@@ -670,7 +1899,7 @@ impl EditorState {
                     let next_line = buf.lines.remove(*line + 1);
                     let trimmed = next_line.trim_start();
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     ln.truncate(col_b);
                     ln.push_str(trimmed);
                     buf.cursor_y = *line;
@@ -685,7 +1914,7 @@ impl EditorState {
             } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     let tail = ln.get(col_b..).unwrap_or("").to_string();
                     ln.truncate(col_b);
                     buf.lines.insert(*line + 1, deleted_content.clone() + &tail);
@@ -724,7 +1953,7 @@ impl EditorState {
             EditorAction::InsertChar { line, col, ch } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     ln.insert(col_b, *ch);
                     buf.cursor_y = *line;
                     buf.cursor_x = (col_b + ch.len_utf8()).min(ln.len());
@@ -734,9 +1963,9 @@ impl EditorState {
             EditorAction::DeleteChar { line, col, .. } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     if col_b < ln.len() {
-                        let end = CursorOps::next_char_boundary(ln, col_b);
+                        let end = utf8::next_char_boundary(ln, col_b);
                         ln.drain(col_b..end);
                         buf.cursor_y = *line;
                         buf.cursor_x = col_b;
@@ -770,7 +1999,7 @@ impl EditorState {
             EditorAction::SplitLine { line, col } => {
                 if *line < buf.lines.len() {
                     let ln = &mut buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     let remainder = ln.get(col_b..).unwrap_or("").to_string();
                     ln.truncate(col_b);
                     buf.lines.insert(*line + 1, remainder);
@@ -785,7 +2014,7 @@ impl EditorState {
                     buf.lines[*line].push_str(&next);
                     buf.cursor_y = *line;
                     let ln = &buf.lines[*line];
-                    let col_b = CursorOps::byte_index_of_char(ln, *col);
+                    let col_b = utf8::byte_index_of_char(ln, *col);
                     buf.cursor_x = col_b.min(ln.len());
                     buf.modified = true;
                 }
@@ -816,28 +2045,62 @@ impl EditorState {
 
     // ========== Buffer Management ==========
 
+    /// Switch to buffer `idx`, remembering the current one as the alternate
+    pub fn set_active_buffer(&mut self, idx: usize) {
+        if idx == self.active_buffer || idx >= self.buffers.len() {
+            return;
+        }
+        let prev = self.active_buffer;
+        self.recent_buffers.retain(|&i| i != prev && i != idx);
+        self.recent_buffers.push(prev);
+        self.active_buffer = idx;
+    }
+
+    /// Swap to the previously active buffer (Ctrl+^). If that buffer was
+    /// closed this lands on the most recent one still open.
+    pub fn alternate_buffer(&mut self) -> bool {
+        match self.recent_buffers.last() {
+            Some(&idx) => {
+                self.set_active_buffer(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn next_buffer(&mut self) {
         if self.buffers.len() > 1 {
-            self.active_buffer = (self.active_buffer + 1) % self.buffers.len();
+            self.set_active_buffer((self.active_buffer + 1) % self.buffers.len());
         }
     }
 
     pub fn prev_buffer(&mut self) {
         if self.buffers.len() > 1 {
-            self.active_buffer = if self.active_buffer == 0 {
+            let idx = if self.active_buffer == 0 {
                 self.buffers.len() - 1
             } else {
                 self.active_buffer - 1
             };
+            self.set_active_buffer(idx);
         }
     }
 
     pub fn close_buffer(&mut self) -> bool {
         if self.buffers.len() > 1 {
-            self.buffers.remove(self.active_buffer);
+            let closed = self.active_buffer;
+            self.buffers.remove(closed);
+            // Forget the closed buffer and shift indices past it down
+            self.recent_buffers.retain(|&i| i != closed);
+            for idx in self.recent_buffers.iter_mut() {
+                if *idx > closed {
+                    *idx -= 1;
+                }
+            }
             if self.active_buffer >= self.buffers.len() {
                 self.active_buffer = self.buffers.len() - 1;
             }
+            let active = self.active_buffer;
+            self.recent_buffers.retain(|&i| i != active);
             true
         } else {
             false
@@ -860,179 +2123,739 @@ impl EditorState {
     }
 
     pub fn get_word_under_cursor(&self) -> Option<String> {
+        let (start, end) = self.word_range_under_cursor()?;
+        Some(self.buf().lines[self.buf().cursor_y][start..end].to_string())
+    }
+
+    /// Byte range of the identifier under the cursor. Identifiers include
+    /// `_`, `@` and `?` so MASM names like `@@loop` and `?x` stay whole.
+    pub fn word_range_under_cursor(&self) -> Option<(usize, usize)> {
         let buf = self.buf();
-        if buf.cursor_y >= buf.lines.len() {
+        let line = buf.lines.get(buf.cursor_y)?;
+        let col = utf8::clamp_to_char_boundary(line, buf.cursor_x.min(line.len()));
+        if !line[col..].chars().next().is_some_and(is_ident_char) {
             return None;
         }
 
-        let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
+        let start = line[..col]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_ident_char(c))
+            .last()
+            .map_or(col, |(i, _)| i);
+        let end = line[col..]
+            .char_indices()
+            .find(|&(_, c)| !is_ident_char(c))
+            .map_or(line.len(), |(i, _)| col + i);
+        Some((start, end))
+    }
 
-        if cursor_char_idx >= chars.len() {
+    /// Delete the identifier under the cursor (diw/ciw), yanking it.
+    /// Returns None when the cursor is not on an identifier.
+    pub fn delete_inner_word(&mut self) -> Option<String> {
+        let (start, end) = self.word_range_under_cursor()?;
+        let line = &self.buf().lines[self.buf().cursor_y];
+        let deleted = line[start..end].to_string();
+        let new_line = format!("{}{}", &line[..start], &line[end..]);
+        self.replace_lines(1, new_line, start);
+        self.yank(&deleted, YankType::Char);
+        Some(deleted)
+    }
+
+    /// Lines `:reflow` works on: the selection, or else the run of comment
+    /// lines around the cursor
+    pub fn reflow_range(&self) -> Option<(usize, usize)> {
+        let buf = self.buf();
+        if let Some(((start, _), (end, _))) = SelectionOps::get_selection_range(buf) {
+            return Some((start, end));
+        }
+        let is_comment = |idx: usize| EditOps::comment_parts(&buf.lines[idx]).is_some();
+        if !is_comment(buf.cursor_y) {
             return None;
         }
+        let mut start = buf.cursor_y;
+        while start > 0 && is_comment(start - 1) {
+            start -= 1;
+        }
+        let mut end = buf.cursor_y;
+        while end + 1 < buf.lines.len() && is_comment(end + 1) {
+            end += 1;
+        }
+        Some((start, end))
+    }
 
-        let c = chars[cursor_char_idx];
-        if !c.is_alphanumeric() && c != '_' && c != '@' && c != '?' {
-            return None;
+    /// Rewrap the `;` comments in lines `start..=end` to `width` columns as
+    /// one undo step, leaving code lines alone. False if nothing changed.
+    pub fn reflow_comments(&mut self, start: usize, end: usize, width: usize) -> bool {
+        let buf = self.buf_mut();
+        let end = end.min(buf.lines.len() - 1);
+        if start > end {
+            return false;
+        }
+        let new_lines = EditOps::reflow_comments(&buf.lines[start..=end], width);
+        if new_lines[..] == buf.lines[start..=end] {
+            return false;
         }
 
-        let mut start = cursor_char_idx;
-        while start > 0 {
-            let prev = chars[start - 1];
-            if prev.is_alphanumeric() || prev == '_' || prev == '@' || prev == '?' {
-                start -= 1;
-            } else {
-                break;
+        let old_lines: Vec<String> = buf.lines.splice(start..=end, new_lines.clone()).collect();
+        let mut actions: Vec<EditorAction> = old_lines
+            .into_iter()
+            .map(|content| EditorAction::DeleteLine {
+                line_num: start,
+                content,
+            })
+            .collect();
+        actions.extend(new_lines.into_iter().enumerate().map(|(i, content)| {
+            EditorAction::InsertLine {
+                line_num: start + i,
+                content,
             }
-        }
+        }));
 
-        let mut end = cursor_char_idx;
-        while end < chars.len() {
-            let ch = chars[end];
-            if ch.is_alphanumeric() || ch == '_' || ch == '@' || ch == '?' {
-                end += 1;
-            } else {
-                break;
+        buf.cursor_y = start;
+        buf.cursor_x = 0;
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        true
+    }
+
+    /// Apply `sub` to lines `start..=end`, as one undoable step.
+    /// Returns the number of replacements and of lines changed.
+    pub fn substitute(&mut self, sub: &Substitution, start: usize, end: usize) -> (usize, usize) {
+        let ignore_case = sub
+            .ignore_case
+            .unwrap_or_else(|| self.search_case.ignores_case(&sub.pattern));
+        let buf = self.buf_mut();
+        let end = end.min(buf.lines.len() - 1);
+        let mut actions = Vec::new();
+        let mut count = 0;
+        for line_num in start..=end {
+            if let Some((new, n)) = sub.apply(&buf.lines[line_num], ignore_case) {
+                let old = std::mem::replace(&mut buf.lines[line_num], new.clone());
+                actions.push(EditorAction::ReplaceLine { line_num, old, new });
+                count += n;
             }
         }
 
-        if start < end {
-            Some(chars[start..end].iter().collect())
+        let changed = actions.len();
+        let last_line = match actions.last() {
+            Some(EditorAction::ReplaceLine { line_num, .. }) => *line_num,
+            _ => return (0, 0),
+        };
+        buf.cursor_y = last_line;
+        CursorOps::clamp_cursor_x(buf);
+        buf.modified = true;
+        buf.sync_rope();
+
+        // A whole-file or multi-line substitute undoes at once
+        let action = if sub.whole_file || changed > 1 {
+            EditorAction::Batch(actions)
         } else {
-            None
-        }
+            actions.remove(0)
+        };
+        self.undo_tree.push(action);
+        self.clear_search();
+        (count, changed)
     }
 
-    pub fn find_definition_in_buffer(&self, symbol: &str) -> Option<(usize, usize)> {
-        let buf = self.buf();
-        let symbol_lower = symbol.to_lowercase();
-
-        for (line_idx, line) in buf.lines.iter().enumerate() {
-            let trimmed = line.trim();
-            let trimmed_lower = trimmed.to_lowercase();
-
-            if let Some(colon_pos) = trimmed.find(':') {
-                let label_part = trimmed[..colon_pos].trim();
-                if label_part.to_lowercase() == symbol_lower {
-                    let col = line.find(label_part).unwrap_or(0);
-                    return Some((line_idx, col));
-                }
-            }
+    /// First line of the current buffer over `long_line_threshold` bytes
+    pub fn first_long_line(&self) -> Option<usize> {
+        self.buf()
+            .lines
+            .iter()
+            .position(|line| line.len() > self.long_line_threshold)
+    }
 
-            if trimmed_lower.contains(" proc") || trimmed_lower.ends_with(" proc") {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if let Some(name) = parts.first() {
-                    if name.to_lowercase() == symbol_lower {
-                        let col = line.find(*name).unwrap_or(0);
-                        return Some((line_idx, col));
-                    }
-                }
+    /// Split every line longer than `width` characters at whitespace or
+    /// after commas (see `EditOps::hard_wrap_line`), as one undoable step.
+    /// Returns how many lines were split.
+    pub fn hard_wrap(&mut self, width: usize) -> usize {
+        let width = width.max(1);
+        let buf = self.buf_mut();
+        let mut actions = Vec::new();
+        let mut wrapped = 0;
+        let mut line_num = 0;
+        while line_num < buf.lines.len() {
+            let line = &buf.lines[line_num];
+            if line.len() <= width {
+                line_num += 1;
+                continue;
             }
-
-            if trimmed_lower.contains(" macro") || trimmed_lower.ends_with(" macro") {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if let Some(name) = parts.first() {
-                    if name.to_lowercase() == symbol_lower {
-                        let col = line.find(*name).unwrap_or(0);
-                        return Some((line_idx, col));
-                    }
-                }
+            let pieces = EditOps::hard_wrap_line(line, width);
+            if pieces.len() == 1 {
+                line_num += 1;
+                continue;
             }
 
-            if trimmed_lower.contains(" equ ") || trimmed_lower.contains(" equ\t") {
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if let Some(name) = parts.first() {
-                    if name.to_lowercase() == symbol_lower {
-                        let col = line.find(*name).unwrap_or(0);
-                        return Some((line_idx, col));
-                    }
-                }
+            actions.push(EditorAction::DeleteLine {
+                line_num,
+                content: line.clone(),
+            });
+            for (i, piece) in pieces.iter().enumerate() {
+                actions.push(EditorAction::InsertLine {
+                    line_num: line_num + i,
+                    content: piece.clone(),
+                });
             }
+            let added = pieces.len();
+            buf.lines.splice(line_num..=line_num, pieces);
+            line_num += added;
+            wrapped += 1;
+        }
+        if wrapped == 0 {
+            return 0;
         }
 
-        None
+        buf.cursor_y = buf.cursor_y.min(buf.lines.len() - 1);
+        buf.cursor_x = 0;
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        wrapped
     }
 
-    pub fn go_to_definition(&mut self) -> Option<String> {
-        let word = self.get_word_under_cursor()?;
-        if let Some((line, col)) = self.find_definition_in_buffer(&word) {
-            let buf = self.buf();
-            if let Some(file_path) = buf.file_path.clone() {
-                self.jump_stack
-                    .push((file_path, buf.cursor_y, buf.cursor_x));
-            }
-            let buf = self.buf_mut();
-            buf.cursor_y = line;
-            buf.cursor_x = col;
-            Some(word)
-        } else {
-            None
+    /// Keep a commented-out copy of the cursor line next to a live one,
+    /// as one undoable step. The commented copy goes above and the cursor
+    /// moves to the live line below, unless `comment_below` is set, in
+    /// which case the commented copy goes below and the cursor stays put.
+    pub fn comment_and_duplicate(&mut self, comment_below: bool) -> bool {
+        let buf = self.buf_mut();
+        let line_num = buf.cursor_y;
+        let Some(line) = buf.lines.get(line_num).cloned() else {
+            return false;
+        };
+        if line.trim().is_empty() {
+            return false;
         }
-    }
 
-    pub fn go_back(&mut self) -> bool {
-        if let Some((file_path, line, col)) = self.jump_stack.pop() {
-            if self.buf().file_path.as_ref() == Some(&file_path) {
-                let buf = self.buf_mut();
-                buf.cursor_y = line;
-                buf.cursor_x = col;
-                true
-            } else {
-                false
-            }
+        let commented = EditOps::comment_out(&line);
+        let actions = if comment_below {
+            buf.lines.insert(line_num + 1, commented.clone());
+            vec![EditorAction::InsertLine {
+                line_num: line_num + 1,
+                content: commented,
+            }]
         } else {
-            false
-        }
-    }
+            buf.lines[line_num] = commented.clone();
+            buf.lines.insert(line_num + 1, line.clone());
+            buf.cursor_y += 1;
+            vec![
+                EditorAction::ReplaceLine {
+                    line_num,
+                    old: line.clone(),
+                    new: commented,
+                },
+                EditorAction::InsertLine {
+                    line_num: line_num + 1,
+                    content: line,
+                },
+            ]
+        };
 
-    pub fn move_word_forward(&mut self) {
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        true
+    }
+
+    /// Sort lines `start..=end` as one undoable edit. `numeric` orders by
+    /// the first integer on each line, with number-less lines first. The
+    /// sort is stable, and the cursor follows its line to where it lands.
+    /// Returns how many lines moved.
+    pub fn sort_lines(&mut self, start: usize, end: usize, reverse: bool, numeric: bool) -> usize {
         let buf = self.buf_mut();
-        if buf.cursor_y >= buf.lines.len() {
-            return;
+        let end = end.min(buf.lines.len().saturating_sub(1));
+        if start >= end {
+            return 0;
         }
 
-        let line = &buf.lines[buf.cursor_y];
+        let mut order: Vec<usize> = (start..=end).collect();
+        order.sort_by(|&a, &b| {
+            let ordering = if numeric {
+                first_integer(&buf.lines[a]).cmp(&first_integer(&buf.lines[b]))
+            } else {
+                buf.lines[a].cmp(&buf.lines[b])
+            };
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        let mut actions = Vec::new();
+        let sorted: Vec<String> = order.iter().map(|&i| buf.lines[i].clone()).collect();
+        for (offset, new) in sorted.into_iter().enumerate() {
+            let line_num = start + offset;
+            if buf.lines[line_num] != new {
+                let old = std::mem::replace(&mut buf.lines[line_num], new.clone());
+                actions.push(EditorAction::ReplaceLine { line_num, old, new });
+            }
+        }
+        let moved = actions.len();
+        if moved == 0 {
+            return 0;
+        }
+        if let Some(offset) = order.iter().position(|&i| i == buf.cursor_y) {
+            buf.cursor_y = start + offset;
+        }
+        CursorOps::clamp_cursor_x(buf);
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        moved
+    }
+
+    /// Strip trailing whitespace from every line as one undo step.
+    /// Returns how many lines changed.
+    pub fn trim_trailing_whitespace(&mut self) -> usize {
+        let buf = self.buf_mut();
+        let mut actions = Vec::new();
+        for (line_num, line) in buf.lines.iter_mut().enumerate() {
+            let trimmed = line.trim_end();
+            if trimmed.len() != line.len() {
+                let new = trimmed.to_string();
+                let old = std::mem::replace(line, new.clone());
+                actions.push(EditorAction::ReplaceLine { line_num, old, new });
+            }
+        }
+        let changed = actions.len();
+        if changed == 0 {
+            return 0;
+        }
+        CursorOps::clamp_cursor_x(buf);
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        changed
+    }
+
+    /// Line up the trailing comments of lines `start..=end` at `column`,
+    /// or two columns past the longest code before a comment, as one
+    /// undoable edit. Returns how many lines changed.
+    pub fn align_comments(&mut self, start: usize, end: usize, column: Option<usize>) -> usize {
+        let tab_size = self.tab_size;
+        let buf = self.buf_mut();
+        let end = end.min(buf.lines.len().saturating_sub(1));
+        let comments: Vec<(usize, usize)> = (start..=end)
+            .filter_map(|ln| {
+                let line = &buf.lines[ln];
+                let semicolon = EditOps::comment_start(line)?;
+                let code = line[..semicolon].trim_end();
+                (!code.trim().is_empty()).then_some((ln, semicolon))
+            })
+            .collect();
+        let column = column.unwrap_or_else(|| {
+            comments
+                .iter()
+                .map(|&(ln, semicolon)| {
+                    let code = buf.lines[ln][..semicolon].trim_end();
+                    CursorOps::display_col(code, code.len(), tab_size) + 2
+                })
+                .max()
+                .unwrap_or(0)
+        });
+
+        let mut actions = Vec::new();
+        for (line_num, semicolon) in comments {
+            let old = &buf.lines[line_num];
+            if let Some(new) = EditOps::align_comment(old, semicolon, column, tab_size) {
+                let old = std::mem::replace(&mut buf.lines[line_num], new.clone());
+                actions.push(EditorAction::ReplaceLine { line_num, old, new });
+            }
+        }
+        let changed = actions.len();
+        if changed == 0 {
+            return 0;
+        }
+        CursorOps::clamp_cursor_x(buf);
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        changed
+    }
+
+    /// Insert `before` above line `start` and `after` below line `end` as
+    /// one undoable edit, leaving the cursor on the first non-blank of the
+    /// original first line
+    pub fn surround_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        before: Vec<String>,
+        after: Vec<String>,
+    ) -> bool {
+        let buf = self.buf_mut();
+        if start > end || end >= buf.lines.len() {
+            return false;
+        }
+
+        let mut actions = Vec::new();
+        let added_below = after.len();
+        // Bottom first so `start` stays valid
+        for (i, content) in after.into_iter().enumerate() {
+            let line_num = end + 1 + i;
+            buf.lines.insert(line_num, content.clone());
+            actions.push(EditorAction::InsertLine { line_num, content });
+        }
+        let count = before.len();
+        for (i, content) in before.into_iter().enumerate() {
+            let line_num = start + i;
+            buf.lines.insert(line_num, content.clone());
+            actions.push(EditorAction::InsertLine { line_num, content });
+        }
+
+        // Lines went in at two places: below the range, then above it
+        buf.shift_line_refs(end + 1, added_below as isize);
+        buf.shift_line_refs(start, count as isize);
+        let body = start + count;
+        buf.cursor_y = body;
+        buf.cursor_x = buf.lines[body].len() - buf.lines[body].trim_start().len();
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree.push(EditorAction::Batch(actions));
+        self.clear_search();
+        true
+    }
+
+    /// Append `text` as a trailing `;` comment on the cursor line as one
+    /// undoable edit. False when the line is blank or already commented.
+    pub fn append_comment(&mut self, text: &str) -> bool {
+        let buf = self.buf_mut();
+        let line_num = buf.cursor_y;
+        let Some(new) = buf
+            .lines
+            .get(line_num)
+            .and_then(|line| EditOps::append_comment(line, text))
+        else {
+            return false;
+        };
+
+        let old = std::mem::replace(&mut buf.lines[line_num], new.clone());
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree
+            .push(EditorAction::ReplaceLine { line_num, old, new });
+        self.clear_search();
+        true
+    }
+
+    /// Contents of the current buffer encoded for writing to disk
+    pub fn file_content(&self) -> String {
+        let buf = self.buf();
+        buf.to_disk(&buf.get_content())
+    }
+
+    /// Switch the current buffer to `ending`, marking it modified when that
+    /// changes anything; the file itself changes on the next save. Stray
+    /// `\r` left by mixed endings are stripped as one undoable step.
+    pub fn set_line_ending(&mut self, ending: LineEnding) {
+        let buf = self.buf_mut();
+        let actions: Vec<EditorAction> = buf
+            .lines
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, line)| line.ends_with('\r'))
+            .map(|(line_num, line)| {
+                let old = line.clone();
+                line.pop();
+                EditorAction::ReplaceLine {
+                    line_num,
+                    old,
+                    new: line.clone(),
+                }
+            })
+            .collect();
+        if buf.line_ending != ending || !actions.is_empty() {
+            buf.modified = true;
+        }
+        buf.line_ending = ending;
+        let line_len = buf.lines[buf.cursor_y].len();
+        buf.cursor_x = buf.cursor_x.min(line_len);
+        if !actions.is_empty() {
+            buf.sync_rope();
+            self.undo_tree.push(EditorAction::Batch(actions));
+        }
+    }
+
+    /// Drop the byte order mark when the current buffer is next saved
+    pub fn remove_bom(&mut self) -> bool {
+        let buf = self.buf_mut();
+        let had_bom = std::mem::take(&mut buf.has_bom);
+        if had_bom {
+            buf.modified = true;
+        }
+        had_bom
+    }
+
+    /// Rewrite the number literal under the cursor in the next base
+    /// (decimal → hex → binary → decimal) as one undoable edit.
+    /// Returns the new literal, or None if the cursor is not on a number.
+    pub fn cycle_number_base(&mut self) -> Option<String> {
+        let buf = self.buf();
+        let line = buf.lines.get(buf.cursor_y)?;
         let chars: Vec<char> = line.chars().collect();
-        let mut cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
+        let cursor = utf8::char_index_at_byte(line, buf.cursor_x);
+        let is_word = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+        if !chars.get(cursor).is_some_and(is_word) {
+            return None;
+        }
 
-        while cursor_char_idx < chars.len()
-            && (chars[cursor_char_idx].is_alphanumeric() || chars[cursor_char_idx] == '_')
-        {
-            cursor_char_idx += 1;
+        let start = chars[..cursor]
+            .iter()
+            .rposition(|c| !is_word(c))
+            .map_or(0, |i| i + 1);
+        let end = chars[cursor..]
+            .iter()
+            .position(|c| !is_word(c))
+            .map_or(chars.len(), |i| cursor + i);
+        let literal: String = chars[start..end].iter().collect();
+        let (value, radix) = masm_lang::parse_number(&literal)?;
+        let replacement = masm_lang::format_number(value, radix.next());
+
+        let line_num = buf.cursor_y;
+        let old = line.clone();
+        let new = format!(
+            "{}{}{}",
+            chars[..start].iter().collect::<String>(),
+            replacement,
+            chars[end..].iter().collect::<String>()
+        );
+
+        let buf = self.buf_mut();
+        buf.lines[line_num] = new.clone();
+        buf.cursor_x = chars[..start].iter().map(|c| c.len_utf8()).sum();
+        buf.modified = true;
+        buf.sync_rope();
+        self.undo_tree
+            .push(EditorAction::ReplaceLine { line_num, old, new });
+        self.clear_search();
+        Some(replacement)
+    }
+
+    pub fn find_definition_in_buffer(&self, symbol: &str) -> Option<(usize, usize)> {
+        find_definition(&self.buf().lines, symbol)
+    }
+
+    /// Like `find_definition_in_buffer`, but across the project: open
+    /// buffers first (current one first, unsaved text included), then the
+    /// `.asm`/`.inc` files under the project root that aren't open
+    pub fn find_definition_in_project(&self, symbol: &str) -> Option<(PathBuf, usize, usize)> {
+        self.find_in_project(|lines| find_definition(lines, symbol))
+    }
+
+    /// Search the project in `find_definition_in_project` order with `find`
+    fn find_in_project(
+        &self,
+        find: impl Fn(&[String]) -> Option<(usize, usize)>,
+    ) -> Option<(PathBuf, usize, usize)> {
+        let canonical =
+            |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let order = std::iter::once(self.active_buffer)
+            .chain((0..self.buffers.len()).filter(|&i| i != self.active_buffer));
+        for idx in order {
+            let buf = &self.buffers[idx];
+            if let (Some(path), Some((line, col))) = (buf.file_path.as_ref(), find(&buf.lines)) {
+                return Some((path.clone(), line, col));
+            }
+        }
+
+        let open: Vec<PathBuf> = self
+            .buffers
+            .iter()
+            .filter_map(|buf| buf.file_path.as_deref().map(canonical))
+            .collect();
+        crate::project::source_files(&self.project_root)
+            .into_iter()
+            .filter(|path| !open.contains(&canonical(path)))
+            .find_map(|path| {
+                let content = std::fs::read_to_string(&path).ok()?;
+                let lines: Vec<String> = content.lines().map(String::from).collect();
+                let (line, col) = find(&lines)?;
+                Some((path, line, col))
+            })
+    }
+
+    /// Line of the anonymous `@@:` label an `@B` (backward) or `@F`
+    /// (forward) reference on the cursor line points to
+    pub fn find_anonymous_label(&self, forward: bool) -> Option<usize> {
+        let buf = self.buf();
+        let defines = |idx: &usize| buf.lines[*idx].trim_start().starts_with("@@:");
+        if forward {
+            (buf.cursor_y + 1..buf.lines.len()).find(defines)
+        } else {
+            // A label earlier on the same line counts as preceding
+            (0..=buf.cursor_y.min(buf.lines.len().saturating_sub(1)))
+                .rev()
+                .find(defines)
+        }
+    }
+
+    pub fn go_to_definition(&mut self) -> Option<String> {
+        let word = self.get_word_under_cursor()?;
+        let target = match word.to_lowercase().as_str() {
+            "@b" => self.find_anonymous_label(false).map(|line| {
+                let col = self.buf().lines[line].find("@@").unwrap_or(0);
+                (line, col)
+            }),
+            "@f" => self.find_anonymous_label(true).map(|line| {
+                let col = self.buf().lines[line].find("@@").unwrap_or(0);
+                (line, col)
+            }),
+            _ => self.find_definition_in_buffer(&word),
+        };
+        if let Some((line, col)) = target {
+            let buf = self.buf();
+            if let Some(file_path) = buf.file_path.clone() {
+                self.jump_stack
+                    .push((file_path, buf.cursor_y, buf.cursor_x));
+            }
+            let buf = self.buf_mut();
+            buf.cursor_y = line;
+            buf.cursor_x = col;
+            Some(word)
+        } else {
+            None
         }
+    }
 
-        while cursor_char_idx < chars.len() && chars[cursor_char_idx].is_whitespace() {
-            cursor_char_idx += 1;
+    /// Jump to the project-wide definition of the word under the cursor
+    /// (gD), opening its file if needed. Returns the symbol and the file it
+    /// was found in.
+    pub fn go_to_project_definition(&mut self) -> Option<(String, PathBuf)> {
+        let word = self.get_word_under_cursor()?;
+        let (path, line, col) = self.find_definition_in_project(&word)?;
+        self.jump_to_location(Some(&path), line, col)
+            .then_some((word, path))
+    }
+
+    /// `:proc name`: jump to `name PROC` in the current buffer, or else
+    /// anywhere in the project, opening its file. False if there is none.
+    pub fn go_to_proc(&mut self, name: &str) -> bool {
+        if let Some((line, col)) = find_proc(&self.buf().lines, name) {
+            return self.jump_to_location(None, line, col);
+        }
+        match self.find_in_project(|lines| find_proc(lines, name)) {
+            Some((path, line, col)) => self.jump_to_location(Some(&path), line, col),
+            None => false,
         }
+    }
 
-        buf.cursor_x = CursorOps::byte_index_of_char(line, cursor_char_idx);
+    /// Move the cursor to `line`/`col` of `path` (the current buffer if
+    /// None), opening the file if needed. The old position goes on the
+    /// jump stack.
+    fn jump_to_location(&mut self, path: Option<&PathBuf>, line: usize, col: usize) -> bool {
+        let buf = self.buf();
+        if let Some(file_path) = buf.file_path.clone() {
+            self.jump_stack
+                .push((file_path, buf.cursor_y, buf.cursor_x));
+        }
+        if let Some(path) = path {
+            if self.buf().file_path.as_ref() != Some(path) && self.open_file(path).is_err() {
+                self.jump_stack.pop();
+                return false;
+            }
+        }
+        let buf = self.buf_mut();
+        buf.cursor_y = line;
+        buf.cursor_x = col;
+        true
     }
 
-    pub fn move_word_backward(&mut self) {
+    /// `m{a-z}`: remember the cursor position in this buffer
+    pub fn set_mark(&mut self, name: char) {
+        let buf = self.buf_mut();
+        let pos = (buf.cursor_y, buf.cursor_x);
+        buf.marks.insert(name, pos);
+    }
+
+    /// `` `{a-z} ``: jump to a mark, or with `line_only` (`'{a-z}`) to the
+    /// first non-blank of its line. Clamped when its line has gone. False
+    /// when the mark is unset.
+    pub fn jump_to_mark(&mut self, name: char, line_only: bool) -> bool {
+        let Some(&(line, col)) = self.buf().marks.get(&name) else {
+            return false;
+        };
+        let buf = self.buf();
+        if let Some(file_path) = buf.file_path.clone() {
+            self.jump_stack
+                .push((file_path, buf.cursor_y, buf.cursor_x));
+        }
+        let buf = self.buf_mut();
+        buf.cursor_y = line.min(buf.lines.len().saturating_sub(1));
+        let text = &buf.lines[buf.cursor_y];
+        buf.cursor_x = if line_only {
+            text.len() - text.trim_start().len()
+        } else {
+            col
+        };
+        CursorOps::clamp_cursor_x(buf);
+        true
+    }
+
+    /// Pop the jump stack, switching back to the jump's file if gD left it
+    pub fn go_back(&mut self) -> bool {
+        let Some((file_path, line, col)) = self.jump_stack.pop() else {
+            return false;
+        };
+        if self.buf().file_path.as_ref() != Some(&file_path) && self.open_file(&file_path).is_err()
+        {
+            return false;
+        }
+        let buf = self.buf_mut();
+        buf.cursor_y = line.min(buf.lines.len().saturating_sub(1));
+        buf.cursor_x = col;
+        CursorOps::clamp_cursor_x(buf);
+        true
+    }
+
+    // Motions below walk the line from the cursor instead of collecting it
+    // into a Vec<char>, so they stay cheap on very long lines.
+
+    pub fn move_word_forward(&mut self) {
         let buf = self.buf_mut();
         if buf.cursor_y >= buf.lines.len() {
             return;
         }
 
         let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let mut cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
+        buf.cursor_x = utf8::clamp_to_char_boundary(line, buf.cursor_x);
+        let rest = &line[buf.cursor_x..];
+        let after_word = rest
+            .char_indices()
+            .find(|&(_, c)| !is_word_char(c))
+            .map_or(rest.len(), |(i, _)| i);
+        let after_space = rest[after_word..]
+            .char_indices()
+            .find(|&(_, c)| !c.is_whitespace())
+            .map_or(rest.len(), |(i, _)| after_word + i);
 
-        cursor_char_idx = cursor_char_idx.saturating_sub(1);
+        buf.cursor_x += after_space;
+    }
 
-        while cursor_char_idx > 0 && chars[cursor_char_idx].is_whitespace() {
-            cursor_char_idx -= 1;
+    pub fn move_word_backward(&mut self) {
+        let buf = self.buf_mut();
+        if buf.cursor_y >= buf.lines.len() {
+            return;
         }
 
-        while cursor_char_idx > 0
-            && (chars[cursor_char_idx - 1].is_alphanumeric() || chars[cursor_char_idx - 1] == '_')
-        {
-            cursor_char_idx -= 1;
+        let line = &buf.lines[buf.cursor_y];
+        let mut idx = utf8::prev_char_boundary(line, buf.cursor_x);
+        while idx > 0 && line[idx..].starts_with(char::is_whitespace) {
+            idx = utf8::prev_char_boundary(line, idx);
+        }
+        while idx > 0 && line[..idx].chars().next_back().is_some_and(is_word_char) {
+            idx = utf8::prev_char_boundary(line, idx);
         }
 
-        buf.cursor_x = CursorOps::byte_index_of_char(line, cursor_char_idx);
+        buf.cursor_x = idx;
     }
 
     pub fn move_word_end(&mut self) {
@@ -1042,24 +2865,20 @@ impl EditorState {
         }
 
         let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let mut cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
-
-        if cursor_char_idx + 1 < chars.len() {
-            cursor_char_idx += 1;
+        let mut idx = utf8::clamp_to_char_boundary(line, buf.cursor_x);
+        if utf8::next_char_boundary(line, idx) < line.len() {
+            idx = utf8::next_char_boundary(line, idx);
         }
-
-        while cursor_char_idx < chars.len() && chars[cursor_char_idx].is_whitespace() {
-            cursor_char_idx += 1;
+        while idx < line.len() && line[idx..].starts_with(char::is_whitespace) {
+            idx = utf8::next_char_boundary(line, idx);
         }
-
-        while cursor_char_idx + 1 < chars.len()
-            && (chars[cursor_char_idx + 1].is_alphanumeric() || chars[cursor_char_idx + 1] == '_')
+        while utf8::next_char_boundary(line, idx) < line.len()
+            && line[utf8::next_char_boundary(line, idx)..].starts_with(is_word_char)
         {
-            cursor_char_idx += 1;
+            idx = utf8::next_char_boundary(line, idx);
         }
 
-        buf.cursor_x = CursorOps::byte_index_of_char(line, cursor_char_idx);
+        buf.cursor_x = idx;
     }
 
     pub fn move_to_first_non_blank(&mut self) {
@@ -1078,80 +2897,67 @@ impl EditorState {
         buf.cursor_x = first_non_blank;
     }
 
-    pub fn find_char_forward(&mut self, target: char) -> bool {
-        let buf = self.buf_mut();
-        if buf.cursor_y >= buf.lines.len() {
-            return false;
-        }
+    /// Byte offset of `target` after the cursor on the current line
+    fn find_after_cursor(&self, target: char) -> Option<usize> {
+        let buf = self.buf();
+        let line = buf.lines.get(buf.cursor_y)?;
+        let start = utf8::next_char_boundary(line, buf.cursor_x);
+        line[start..].find(target).map(|i| start + i)
+    }
 
-        let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
+    /// Byte offset of `target` before the cursor on the current line
+    fn find_before_cursor(&self, target: char) -> Option<usize> {
+        let buf = self.buf();
+        let line = buf.lines.get(buf.cursor_y)?;
+        line[..utf8::clamp_to_char_boundary(line, buf.cursor_x)].rfind(target)
+    }
 
-        for (i, &ch) in chars.iter().enumerate().skip(cursor_char_idx + 1) {
-            if ch == target {
-                buf.cursor_x = CursorOps::byte_index_of_char(line, i);
-                return true;
+    pub fn find_char_forward(&mut self, target: char) -> bool {
+        match self.find_after_cursor(target) {
+            Some(idx) => {
+                self.buf_mut().cursor_x = idx;
+                true
             }
+            None => false,
         }
-        false
     }
 
     pub fn find_char_backward(&mut self, target: char) -> bool {
-        let buf = self.buf_mut();
-        if buf.cursor_y >= buf.lines.len() {
-            return false;
-        }
-
-        let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
-
-        for i in (0..cursor_char_idx).rev() {
-            if chars[i] == target {
-                buf.cursor_x = CursorOps::byte_index_of_char(line, i);
-                return true;
+        match self.find_before_cursor(target) {
+            Some(idx) => {
+                self.buf_mut().cursor_x = idx;
+                true
             }
+            None => false,
         }
-        false
     }
 
     pub fn find_char_till_forward(&mut self, target: char) -> bool {
-        let buf = self.buf_mut();
-        if buf.cursor_y >= buf.lines.len() {
-            return false;
-        }
-
-        let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
-
-        for (i, &ch) in chars.iter().enumerate().skip(cursor_char_idx + 1) {
-            if ch == target {
-                buf.cursor_x = CursorOps::byte_index_of_char(line, i.saturating_sub(1));
-                return true;
+        match self.find_after_cursor(target) {
+            Some(idx) => {
+                let buf = self.buf_mut();
+                buf.cursor_x = utf8::prev_char_boundary(&buf.lines[buf.cursor_y], idx);
+                true
             }
+            None => false,
         }
-        false
     }
 
     pub fn find_char_till_backward(&mut self, target: char) -> bool {
-        let buf = self.buf_mut();
-        if buf.cursor_y >= buf.lines.len() {
-            return false;
-        }
-
-        let line = &buf.lines[buf.cursor_y];
-        let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
-
-        for i in (0..cursor_char_idx).rev() {
-            if chars[i] == target {
-                buf.cursor_x = CursorOps::byte_index_of_char(line, (i + 1).min(chars.len() - 1));
-                return true;
+        match self.find_before_cursor(target) {
+            Some(idx) => {
+                let buf = self.buf_mut();
+                let line = &buf.lines[buf.cursor_y];
+                let after = utf8::next_char_boundary(line, idx);
+                buf.cursor_x = if after < line.len() {
+                    after
+                } else {
+                    utf8::prev_char_boundary(line, line.len())
+                };
+                true
             }
+            None => false,
         }
-        false
     }
 
     pub fn find_matching_bracket(&mut self) -> bool {
@@ -1166,8 +2972,15 @@ impl EditorState {
     }
 
     pub fn get_matching_bracket_pos(&self) -> Option<(usize, usize)> {
+        self.matching_bracket_within(0, usize::MAX)
+    }
+
+    /// Like `get_matching_bracket_pos`, but only looks at lines `first` to
+    /// `last`, so the render can stay within the visible window
+    pub fn matching_bracket_within(&self, first: usize, last: usize) -> Option<(usize, usize)> {
         let buf = self.buf();
-        if buf.cursor_y >= buf.lines.len() {
+        let last = last.min(buf.lines.len().saturating_sub(1));
+        if buf.cursor_y < first || buf.cursor_y > last {
             return None;
         }
 
@@ -1177,34 +2990,52 @@ impl EditorState {
         }
 
         let chars: Vec<char> = line.chars().collect();
-        let cursor_char_idx = CursorOps::char_index_at_byte(line, buf.cursor_x);
+        let cursor_char_idx = utf8::char_index_at_byte(line, buf.cursor_x);
 
         if cursor_char_idx >= chars.len() {
             return None;
         }
 
         let ch = chars[cursor_char_idx];
-        let (opening, closing, direction) = match ch {
-            '(' => ('(', ')', 1),
-            ')' => ('(', ')', -1),
-            '[' => ('[', ']', 1),
-            ']' => ('[', ']', -1),
-            '{' => ('{', '}', 1),
-            '}' => ('{', '}', -1),
-            _ => return None,
-        };
+        let (opening, closing, direction) =
+            self.match_pairs.iter().find_map(|&(open, close)| {
+                if ch == open {
+                    Some((open, close, 1))
+                } else if ch == close {
+                    Some((open, close, -1))
+                } else {
+                    None
+                }
+            })?;
+
+        // `<`/`>` are only brackets around text-macro arguments; elsewhere
+        // (.IF comparisons, shifts) they are operators
+        let angle = opening == '<';
+        if angle && !EditOps::angle_brackets_allowed(line) {
+            return None;
+        }
 
         let mut depth = 0;
         let mut current_line = buf.cursor_y;
-        let mut current_col_char = cursor_char_idx;
+        // Backward scans pre-decrement, so start just past the cursor
+        let mut current_col_char = if direction == 1 {
+            cursor_char_idx
+        } else {
+            cursor_char_idx + 1
+        };
 
         loop {
-            if current_line >= buf.lines.len() {
+            if current_line > last {
                 break;
             }
 
             let line = &buf.lines[current_line];
-            let chars: Vec<char> = line.chars().collect();
+            let chars: Vec<char> = if angle && !EditOps::angle_brackets_allowed(line) {
+                Vec::new()
+            } else {
+                line.chars().collect()
+            };
+            current_col_char = current_col_char.min(chars.len());
 
             if direction == 1 {
                 while current_col_char < chars.len() {
@@ -1215,7 +3046,7 @@ impl EditorState {
                         if depth == 0 {
                             return Some((
                                 current_line,
-                                CursorOps::byte_index_of_char(line, current_col_char),
+                                utf8::byte_index_of_char(line, current_col_char),
                             ));
                         }
                     }
@@ -1233,20 +3064,20 @@ impl EditorState {
                         if depth == 0 {
                             return Some((
                                 current_line,
-                                CursorOps::byte_index_of_char(line, current_col_char),
+                                utf8::byte_index_of_char(line, current_col_char),
                             ));
                         }
                     }
                 }
-                if current_line == 0 {
+                if current_line <= first {
                     break;
                 }
                 current_line -= 1;
                 current_col_char = buf.lines[current_line].chars().count();
             }
 
-            if (direction == 1 && current_line >= buf.lines.len())
-                || (direction == -1 && current_line == 0 && current_col_char == 0)
+            if (direction == 1 && current_line > last)
+                || (direction == -1 && current_line <= first && current_col_char == 0)
             {
                 break;
             }
@@ -1277,6 +3108,65 @@ impl EditorState {
     }
 }
 
+/// Pairs matched by `%` unless configured otherwise
+pub const DEFAULT_MATCH_PAIRS: &str = "()[]{}<>";
+
+/// Parse a pair list such as `"()[]<>"` into (open, close) pairs.
+/// A trailing unpaired character is ignored.
+pub fn parse_match_pairs(spec: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = spec.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
+
+/// The first decimal integer in `line`, with its sign, for `:sort n`
+fn first_integer(line: &str) -> Option<i64> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let digits = &line[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let value: i64 = digits[..end].parse().ok()?;
+    Some(if line[..start].ends_with('-') {
+        -value
+    } else {
+        value
+    })
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `text` lowercased when a search ignores case. Stays borrowed otherwise.
+fn fold_case(text: &str, ignore_case: bool) -> std::borrow::Cow<'_, str> {
+    if ignore_case {
+        std::borrow::Cow::Owned(text.to_lowercase())
+    } else {
+        std::borrow::Cow::Borrowed(text)
+    }
+}
+
+/// Identifier character in the sense of `word_range_under_cursor`
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '@' || c == '?'
+}
+
+/// Whether the bytes `start..end` of `line` are not joined to identifier
+/// characters on either side, for whole-word search
+fn is_whole_word(line: &str, start: usize, end: usize) -> bool {
+    let before = line.get(..start).and_then(|s| s.chars().next_back());
+    let after = line.get(end..).and_then(|s| s.chars().next());
+    !before.is_some_and(is_ident_char) && !after.is_some_and(is_ident_char)
+}
+
+/// Display width of leading whitespace, with tabs advancing to the next stop
+fn indent_width(indent: &str, tab_size: usize) -> usize {
+    indent.chars().fold(0, |width, c| match c {
+        '\t' => (width / tab_size + 1) * tab_size,
+        _ => width + 1,
+    })
+}
+
 impl std::ops::Deref for EditorState {
     type Target = Buffer;
 
@@ -1287,3 +3177,1145 @@ impl std::ops::Deref for EditorState {
 
 // Re-export the render function from editor_render module
 pub use crate::ui::editor_render::render;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn editor_with_files(paths: &[&str]) -> EditorState {
+        let mut editor = EditorState::new(4);
+        editor.buffers = paths
+            .iter()
+            .map(|p| {
+                let mut buf = Buffer::new();
+                buf.file_path = Some(PathBuf::from(p));
+                buf
+            })
+            .collect();
+        editor
+    }
+
+    #[test]
+    fn test_display_name_disambiguates_shared_filenames() {
+        let editor = editor_with_files(&["/p/lab1/main.asm", "/p/lab2/main.asm", "/p/util.asm"]);
+        assert_eq!(editor.display_name(0), "lab1/main.asm");
+        assert_eq!(editor.display_name(1), "lab2/main.asm");
+        assert_eq!(editor.display_name(2), "util.asm");
+    }
+
+    #[test]
+    fn test_display_name_relative() {
+        let mut editor = editor_with_files(&["/p/lab1/main.asm"]);
+        editor.project_root = PathBuf::from("/p");
+        editor.title_path = TitlePath::Relative;
+        assert_eq!(editor.display_name(0), "lab1/main.asm");
+    }
+
+    #[test]
+    fn test_alternate_buffer_toggles_back() {
+        let mut editor = editor_with_files(&["/p/a.asm", "/p/b.asm", "/p/c.asm"]);
+        assert!(!editor.alternate_buffer());
+
+        editor.next_buffer();
+        editor.next_buffer();
+        assert_eq!(editor.active_buffer, 2);
+        assert!(editor.alternate_buffer());
+        assert_eq!(editor.active_buffer, 1);
+        assert!(editor.alternate_buffer());
+        assert_eq!(editor.active_buffer, 2);
+
+        // After a close the alternate is the most recent buffer still open
+        editor.set_active_buffer(1);
+        editor.set_active_buffer(0);
+        editor.set_active_buffer(1);
+        editor.close_buffer();
+        assert_eq!(editor.active_buffer, 1);
+        assert!(editor.alternate_buffer());
+        assert_eq!(editor.active_buffer, 0);
+    }
+
+    #[test]
+    fn test_smart_tab_and_dedent_use_tab_stops() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![String::from("  mov eax, 1")];
+        editor.buf_mut().cursor_x = 2;
+
+        // Only whitespace before the cursor: indent to the next stop
+        editor.insert_tab();
+        assert_eq!(editor.buf().lines[0], "    mov eax, 1");
+        editor.insert_tab();
+        assert_eq!(editor.buf().lines[0], "        mov eax, 1");
+
+        // Mid-line with spaces off inserts a literal tab
+        editor.insert_spaces = false;
+        editor.buf_mut().cursor_x = 11;
+        editor.insert_tab();
+        assert_eq!(editor.buf().lines[0], "        mov\t eax, 1");
+
+        editor.buf_mut().lines = vec![String::from("      add eax, 2")];
+        editor.buf_mut().cursor_x = 6;
+        editor.dedent_line();
+        assert_eq!(editor.buf().lines[0], "    add eax, 2");
+        assert_eq!(editor.buf().cursor_x, 4);
+        editor.dedent_line();
+        editor.dedent_line();
+        assert_eq!(editor.buf().lines[0], "add eax, 2");
+    }
+
+    #[test]
+    fn test_enter_after_proc_inserts_endp() {
+        let mut editor = EditorState::new(4);
+        editor.auto_close_blocks = true;
+        editor.buf_mut().lines = vec![String::from("main PROC")];
+        editor.buf_mut().cursor_x = 9;
+        editor.insert_newline();
+        assert_eq!(editor.buf().lines, vec!["main PROC", "    ", "main ENDP"]);
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (1, 4));
+
+        // One undo removes the whole insertion
+        editor.undo();
+        assert_eq!(editor.buf().lines, vec!["main PROC"]);
+
+        // An existing closer is left alone
+        editor.buf_mut().lines = vec![String::from("  if DEBUG"), String::from("  endif")];
+        editor.buf_mut().cursor_y = 0;
+        editor.buf_mut().cursor_x = 10;
+        editor.insert_newline();
+        assert_eq!(editor.buf().lines.len(), 3);
+        assert_eq!(editor.buf().lines[2], "  endif");
+
+        // Also with a flush-left body before it
+        editor.buf_mut().lines = ["main PROC", "mov eax, 1", "main ENDP"]
+            .map(String::from)
+            .to_vec();
+        editor.buf_mut().cursor_x = 9;
+        editor.insert_newline();
+        assert_eq!(editor.buf().lines.len(), 4);
+        assert_eq!(editor.buf().lines[3], "main ENDP");
+
+        // A nested block's closer is not taken for this one's
+        editor.buf_mut().lines = ["IF A", "IF B", "ENDIF"].map(String::from).to_vec();
+        editor.buf_mut().cursor_y = 0;
+        editor.buf_mut().cursor_x = 4;
+        editor.insert_newline();
+        assert_eq!(
+            editor.buf().lines,
+            vec!["IF A", "    ", "ENDIF", "IF B", "ENDIF"]
+        );
+    }
+
+    #[test]
+    fn test_cycle_number_base_in_place() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![String::from("mov al, 255 ; mask")];
+        editor.buf_mut().cursor_x = 9;
+
+        assert_eq!(editor.cycle_number_base().as_deref(), Some("0FFh"));
+        assert_eq!(editor.buf().lines[0], "mov al, 0FFh ; mask");
+        assert_eq!(editor.cycle_number_base().as_deref(), Some("11111111b"));
+        assert_eq!(editor.cycle_number_base().as_deref(), Some("255"));
+        assert_eq!(editor.buf().lines[0], "mov al, 255 ; mask");
+
+        editor.buf_mut().cursor_x = 4;
+        assert_eq!(editor.cycle_number_base(), None);
+    }
+
+    #[test]
+    fn test_change_operators() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    mov eax, 1 ; init"),
+            String::from("    add eax, 2"),
+            String::from("    ret"),
+        ];
+
+        // C at mid-line keeps the text before the cursor
+        editor.buf_mut().cursor_x = 14;
+        editor.change_to_line_end(1);
+        assert_eq!(editor.buf().lines[0], "    mov eax, 1");
+        assert_eq!(editor.buf().cursor_x, 14);
+        assert_eq!(editor.buf().lines.len(), 3);
+        editor.undo();
+        assert_eq!(editor.buf().lines[0], "    mov eax, 1 ; init");
+
+        // 2cc collapses two lines into their indent
+        editor.buf_mut().cursor_y = 0;
+        editor.change_lines(2);
+        assert_eq!(editor.buf().lines, vec!["    ", "    ret"]);
+        assert_eq!(editor.buf().cursor_x, 4);
+        editor.undo();
+        assert_eq!(editor.buf().lines.len(), 3);
+        assert_eq!(editor.buf().lines[1], "    add eax, 2");
+
+        editor.buf_mut().cursor_y = 2;
+        editor.buf_mut().cursor_x = 4;
+        editor.substitute_chars(3);
+        assert_eq!(editor.buf().lines[2], "    ");
+    }
+
+    #[test]
+    fn test_file_state_restored_on_open() {
+        let dir = TempDir::new("state");
+        let path = dir.join("main.asm");
+        std::fs::write(&path, "a\nbb\nccc\n").unwrap();
+
+        let mut editor = EditorState::new(4);
+        editor.file_states = Some(FileStateStore::default());
+        editor.open_file(&path).unwrap();
+        editor.buf_mut().cursor_y = 2;
+        editor.buf_mut().cursor_x = 1;
+        editor.jump_stack.push((path.clone(), 1, 0));
+        editor.set_mark('a');
+        editor.buf_mut().closed_folds.push(1);
+        editor.remember_file_state(0);
+
+        let mut reopened = EditorState::new(4);
+        reopened.file_states = editor.file_states.take();
+        reopened.open_file(&path).unwrap();
+        assert_eq!((reopened.buf().cursor_y, reopened.buf().cursor_x), (2, 1));
+        assert_eq!(reopened.jump_stack, vec![(path.clone(), 1, 0)]);
+        assert_eq!(reopened.buf().marks[&'a'], (2, 1));
+        assert_eq!(reopened.buf().closed_folds, vec![1]);
+
+        // Closing and reopening in the same session doesn't repeat the jumps
+        let other = dir.join("other.asm");
+        std::fs::write(&other, "x\n").unwrap();
+        reopened.open_file(&other).unwrap();
+        reopened.set_active_buffer(0);
+        reopened.remember_file_state(0);
+        assert!(reopened.close_buffer());
+        reopened.open_file(&path).unwrap();
+        assert_eq!(reopened.jump_stack, vec![(path.clone(), 1, 0)]);
+    }
+
+    #[test]
+    fn test_project_definition_jumps_across_files() {
+        let dir = TempDir::new("gd");
+        let main = dir.join("main.asm");
+        std::fs::write(&main, "INCLUDE io.inc\n.code\n    call PrintIt\n").unwrap();
+        std::fs::write(
+            dir.join("io.inc"),
+            "; helpers\nPrintIt PROC\n    ret\nPrintIt ENDP\n",
+        )
+        .unwrap();
+
+        let mut editor = EditorState::new(4);
+        editor.project_root = dir.to_path_buf();
+        editor.open_file(&main).unwrap();
+        editor.buf_mut().cursor_y = 2;
+        editor.buf_mut().cursor_x = 10;
+        assert!(editor.go_to_definition().is_none());
+
+        let (symbol, path) = editor.go_to_project_definition().unwrap();
+        assert_eq!(
+            (symbol.as_str(), path.file_name().unwrap()),
+            ("PrintIt", "io.inc".as_ref())
+        );
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (1, 0));
+
+        assert!(editor.go_back());
+        assert_eq!(editor.current_file(), Some(&main));
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (2, 10));
+
+        // :proc finds PROCs only, in any case
+        assert!(editor.go_to_proc("printit"));
+        assert_eq!(
+            editor.buf().file_path.as_deref(),
+            Some(dir.join("io.inc").as_path())
+        );
+        assert_eq!(editor.buf().cursor_y, 1);
+        assert!(!editor.go_to_proc("helpers"));
+    }
+
+    #[test]
+    fn test_comment_folds_skip_on_vertical_moves() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = [
+            "; Assignment 3",
+            "; Author: ...",
+            "; Read the spec carefully",
+            "main PROC",
+            "    ; one-liner",
+            "    ; kept open",
+            "    mov eax, 1",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        editor.buf_mut().cursor_y = 3;
+
+        assert_eq!(editor.set_comment_folding(true), 1);
+        assert_eq!(editor.comment_folds(), vec![(0, 2)]);
+        assert_eq!(editor.screen_row(3), 1);
+
+        editor.move_cursor_up();
+        assert_eq!(editor.buf().cursor_y, 0);
+        editor.move_cursor_down();
+        assert_eq!(editor.buf().cursor_y, 3);
+
+        // A search landing inside a fold opens it
+        editor.buf_mut().cursor_y = 1;
+        assert!(editor.comment_folds().is_empty());
+
+        editor.set_comment_folding(false);
+        editor.buf_mut().cursor_y = 0;
+        editor.move_cursor_down();
+        assert_eq!(editor.buf().cursor_y, 1);
+    }
+
+    #[test]
+    fn test_mouse_positions_map_to_bytes() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = [
+            "; header",
+            "; more",
+            "; and more",
+            "msg BYTE \"héllo wörld\", 0",
+            "\tmov eax, 1",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        editor.buf_mut().cursor_y = 3;
+        editor.set_comment_folding(true);
+
+        // The folded header takes one row, so row 1 is the string line
+        assert_eq!(editor.position_at_screen(1, 11), (3, 11));
+        // Inside the tab, and past the end of the last line
+        assert_eq!(editor.position_at_screen(2, 2), (4, 0));
+        assert_eq!(editor.position_at_screen(9, 40), (4, 11));
+
+        assert!(editor.select_word_at((3, 11)));
+        assert_eq!(editor.get_selection_range(), Some(((3, 10), (3, 16))));
+        editor.select_line_at(4);
+        assert_eq!(editor.get_selection_range(), Some(((4, 0), (4, 11))));
+        assert!(!editor.select_word_at((3, 3)));
+    }
+
+    #[test]
+    fn test_block_selection_yank_delete_insert() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = ["val1 DWORD 1", "v2   BYTE  2", "x", "big  WORD  3"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        editor.buf_mut().cursor_x = 5;
+        editor.start_block_selection();
+        editor.buf_mut().cursor_y = 3;
+        editor.buf_mut().cursor_x = 9;
+        editor.update_selection();
+        assert_eq!(editor.block_range(), Some((0, 3, 5, 10)));
+        assert_eq!(editor.selected_text().unwrap(), "DWORD\nBYTE \n\nWORD ");
+
+        assert!(editor.delete_selection());
+        assert_eq!(
+            editor.buf().lines,
+            vec!["val1  1", "v2    2", "x", "big   3"]
+        );
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (0, 5));
+        editor.undo();
+        assert_eq!(editor.buf().lines[1], "v2   BYTE  2");
+
+        // I types on the first line; Esc copies it to lines reaching the column
+        editor.buf_mut().cursor_y = 0;
+        editor.buf_mut().cursor_x = 5;
+        editor.start_block_selection();
+        editor.buf_mut().cursor_y = 3;
+        editor.update_selection();
+        editor.begin_block_insert();
+        for c in "X ".chars() {
+            editor.insert_char(c);
+        }
+        assert_eq!(editor.finish_block_insert(), 2);
+        assert_eq!(editor.buf().lines[1], "v2   X BYTE  2");
+        assert_eq!(editor.buf().lines[2], "x");
+        assert_eq!(editor.buf().lines[3], "big  X WORD  3");
+    }
+
+    #[test]
+    fn test_align_command_lines_up_trailing_comments() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = vec![
+            "    mov eax, 1 ; one".into(),
+            "    ; full-line comment".into(),
+            "msg BYTE \";\",0 ; semicolon".into(),
+            "    call WriteString                ; print".into(),
+        ];
+        assert_eq!(editor.align_comments(0, 3, None), 3);
+        assert_eq!(editor.lines()[0], "    mov eax, 1        ; one");
+        assert_eq!(editor.lines()[1], "    ; full-line comment");
+        assert_eq!(editor.lines()[2], "msg BYTE \";\",0        ; semicolon");
+        assert_eq!(editor.lines()[3], "    call WriteString  ; print");
+
+        assert!(editor.undo());
+        assert_eq!(editor.lines()[0], "    mov eax, 1 ; one");
+        assert_eq!(editor.align_comments(2, 3, Some(30)), 2);
+        assert_eq!(editor.lines()[3], "    call WriteString          ; print");
+    }
+
+    #[test]
+    fn test_soft_wrap_moves_by_screen_row() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = vec!["a".repeat(25), "short".into(), "b".repeat(10)];
+        editor.text_width = 10;
+        editor.buffers[0].cursor_x = 3;
+        // Without wrap, j and k move between lines as before
+        editor.move_cursor_down();
+        assert_eq!((editor.cursor_y(), editor.buffers[0].cursor_x), (1, 3));
+        editor.move_cursor_up();
+
+        editor.soft_wrap = true;
+        assert_eq!(editor.line_rows(0, &[]), 3);
+        // The 10-wide line keeps a second row for the cursor at its end
+        assert_eq!(editor.line_rows(2, &[]), 2);
+        editor.move_cursor_down();
+        assert_eq!((editor.cursor_y(), editor.buffers[0].cursor_x), (0, 13));
+        assert_eq!(editor.cursor_screen_pos(), (1, 3));
+        editor.move_cursor_down();
+        editor.move_cursor_down();
+        assert_eq!((editor.cursor_y(), editor.buffers[0].cursor_x), (1, 3));
+        assert_eq!(editor.cursor_screen_pos(), (3, 3));
+        editor.move_cursor_up();
+        assert_eq!((editor.cursor_y(), editor.buffers[0].cursor_x), (0, 23));
+        assert_eq!(editor.position_at_screen(2, 1), (0, 21));
+        assert_eq!(editor.position_at_screen(4, 2), (2, 2));
+
+        // Scrolling counts screen rows: the cursor on line 2 needs line 0
+        // (3 rows) off a 3-row screen
+        editor.buffers[0].cursor_y = 2;
+        editor.buffers[0].cursor_x = 0;
+        editor.ensure_cursor_visible(3);
+        assert_eq!(editor.buffers[0].scroll_offset, 1);
+        assert_eq!(editor.cursor_screen_pos(), (1, 0));
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace_is_one_undo_step() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = vec!["mov eax, 1  ".into(), "ret".into(), "\t".into()];
+        editor.buffers[0].cursor_x = 11;
+        assert_eq!(editor.trim_trailing_whitespace(), 2);
+        assert_eq!(editor.lines()[..], ["mov eax, 1", "ret", ""]);
+        assert_eq!(editor.buffers[0].cursor_x, 10);
+        assert_eq!(editor.trim_trailing_whitespace(), 0);
+
+        assert!(editor.undo());
+        assert_eq!(editor.lines()[..], ["mov eax, 1  ", "ret", "\t"]);
+    }
+
+    #[test]
+    fn test_sort_lines_stable_numeric_and_reverse() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = vec![
+            "header".into(),
+            "    dd 10 ; b".into(),
+            "    dd -3".into(),
+            "    dd 10 ; a".into(),
+            "    dd 2".into(),
+            "; none".into(),
+        ];
+        editor.buffers[0].cursor_y = 2;
+        assert!(editor.sort_lines(1, 5, false, true) > 0);
+        assert_eq!(
+            editor.lines()[1..],
+            [
+                "; none",
+                "    dd -3",
+                "    dd 2",
+                "    dd 10 ; b",
+                "    dd 10 ; a"
+            ]
+        );
+        assert_eq!(editor.cursor_y(), 2);
+
+        assert!(editor.undo());
+        assert_eq!(editor.lines()[1], "    dd 10 ; b");
+        editor.sort_lines(1, 4, true, false);
+        assert_eq!(
+            editor.lines()[1..5],
+            ["    dd 2", "    dd 10 ; b", "    dd 10 ; a", "    dd -3"]
+        );
+        assert_eq!(editor.sort_lines(0, 0, false, false), 0);
+    }
+
+    #[test]
+    fn test_shift_lines_by_tab_size() {
+        let mut editor = EditorState::new(4);
+        editor.insert_spaces = true;
+        editor.buffers[0].lines = vec![
+            "main PROC".into(),
+            "".into(),
+            "  mov eax, 1".into(),
+            "\tret".into(),
+        ];
+        assert!(editor.indent_lines(0, 2));
+        assert_eq!(
+            editor.lines()[..3],
+            ["    main PROC", "", "      mov eax, 1"]
+        );
+        assert_eq!((editor.cursor_y(), editor.cursor_x()), (0, 4));
+
+        assert!(editor.dedent_lines(2, 3));
+        assert_eq!(editor.lines()[2..], ["  mov eax, 1", "ret"]);
+        assert_eq!((editor.cursor_y(), editor.cursor_x()), (2, 2));
+
+        // One undo per shift
+        assert!(editor.undo());
+        assert_eq!(editor.lines()[3], "\tret");
+        assert!(!editor.dedent_lines(1, 1));
+    }
+
+    #[test]
+    fn test_marks_follow_lines_and_clamp() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = (0..5).map(|i| format!("    line{}", i)).collect();
+        editor.buffers[0].sync_rope();
+        editor.buffers[0].cursor_y = 3;
+        editor.buffers[0].cursor_x = 6;
+        editor.set_mark('a');
+        assert!(!editor.jump_to_mark('b', false));
+
+        // A line opened above the mark pushes it down
+        editor.buffers[0].cursor_y = 1;
+        editor.move_to_line_end();
+        editor.insert_newline();
+        editor.buffers[0].cursor_y = 0;
+        assert!(editor.jump_to_mark('a', false));
+        assert_eq!((editor.cursor_y(), editor.cursor_x()), (4, 6));
+
+        // Deleting the marked line leaves the mark on what replaced it
+        editor.delete_line();
+        editor.buffers[0].cursor_y = 0;
+        assert!(editor.jump_to_mark('a', true));
+        assert_eq!((editor.cursor_y(), editor.cursor_x()), (4, 4));
+
+        editor.buffers[0].lines.truncate(2);
+        editor.buffers[0].sync_rope();
+        assert!(editor.jump_to_mark('a', false));
+        assert_eq!(editor.cursor_y(), 1);
+    }
+
+    #[test]
+    fn test_fold_cache_follows_edits() {
+        let mut editor = EditorState::new(4);
+        editor.comment_fold_min_lines = 3;
+        editor.buf_mut().lines = ["; a", "; b", "; c", "main PROC", "main ENDP"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        editor.buf_mut().sync_rope();
+        editor.buf_mut().cursor_y = 3;
+        editor.set_comment_folding(true);
+        assert_eq!(editor.comment_folds(), vec![(0, 2)]);
+
+        // Uncommenting a line leaves runs too short to fold
+        editor.buf_mut().lines[1] = String::from("b");
+        editor.buf_mut().sync_rope();
+        assert!(editor.comment_folds().is_empty());
+
+        assert_eq!(editor.toggle_fold(), Some(true));
+        assert_eq!(editor.folds(), vec![(3, 4)]);
+        editor.buf_mut().lines.insert(0, String::from("; new"));
+        editor.buf_mut().sync_rope();
+        editor.buf_mut().cursor_y = 0;
+        assert_eq!(editor.folds(), vec![(4, 5)]);
+    }
+
+    #[test]
+    fn test_marks_and_folds_follow_surround() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = ["main PROC", "    mov eax, 1", "    ret", "main ENDP"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        editor.buffers[0].sync_rope();
+        editor.buffers[0].cursor_y = 1;
+        editor.set_mark('a');
+        editor.buffers[0].cursor_y = 0;
+        assert_eq!(editor.toggle_fold(), Some(true));
+
+        // The cursor sits at the end of the range, away from the insertions
+        editor.buffers[0].cursor_y = 2;
+        assert!(editor.surround_lines(
+            1,
+            2,
+            vec![String::from("    push ebx")],
+            vec![String::from("    pop ebx")],
+        ));
+        assert_eq!(editor.buffers[0].marks[&'a'].0, 2);
+        assert_eq!(editor.buffers[0].closed_folds, vec![0]);
+
+        // Lines added before the PROC push its fold down with it
+        assert!(editor.surround_lines(0, 5, vec![String::from(".code")], vec![]));
+        assert_eq!(editor.buffers[0].closed_folds, vec![1]);
+        assert_eq!(editor.buffers[0].marks[&'a'].0, 3);
+    }
+
+    #[test]
+    fn test_za_folds_procs_and_segments() {
+        let mut editor = EditorState::new(4);
+        editor.buffers[0].lines = [
+            ".data",
+            "msg BYTE \"hi\",0",
+            ".code",
+            "main PROC",
+            "    call Crlf",
+            "    ret",
+            "main ENDP",
+            "END main",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        editor.buffers[0].sync_rope();
+        assert_eq!(
+            EditOps::fold_regions(editor.lines()),
+            vec![(0, 1), (2, 6), (3, 6)]
+        );
+
+        // From inside the PROC, za closes it; j skips the hidden body
+        editor.buffers[0].cursor_y = 4;
+        assert_eq!(editor.toggle_fold(), Some(true));
+        assert_eq!(editor.cursor_y(), 3);
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_y(), 7);
+        editor.move_cursor_up();
+        assert_eq!(editor.cursor_y(), 3);
+        assert_eq!(editor.screen_row(7), 4);
+
+        // A line added above keeps the fold on its PROC
+        editor.buffers[0].cursor_y = 0;
+        editor.move_to_line_end();
+        editor.insert_newline();
+        assert_eq!(editor.folds(), vec![(4, 7)]);
+
+        editor.buffers[0].cursor_y = 4;
+        assert_eq!(editor.toggle_fold(), Some(false));
+        assert!(editor.folds().is_empty());
+        editor.buffers[0].cursor_y = 8;
+        assert_eq!(editor.toggle_fold(), None);
+    }
+
+    #[test]
+    fn test_percent_skips_comparison_angles() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from(".IF (eax < 5) && (ebx > 2)"),
+            String::from("msg TEXTEQU <a <b> c>"),
+        ];
+
+        // `<` in a comparison is an operator, not a bracket
+        editor.buf_mut().cursor_x = 9;
+        assert_eq!(editor.get_matching_bracket_pos(), None);
+
+        // Parentheses still match, in both directions
+        editor.buf_mut().cursor_x = 4;
+        assert_eq!(editor.get_matching_bracket_pos(), Some((0, 12)));
+        editor.buf_mut().cursor_x = 12;
+        assert_eq!(editor.get_matching_bracket_pos(), Some((0, 4)));
+
+        // Text macro arguments nest
+        editor.buf_mut().cursor_y = 1;
+        editor.buf_mut().cursor_x = 12;
+        assert_eq!(editor.get_matching_bracket_pos(), Some((1, 20)));
+
+        assert_eq!(editor.matching_bracket_within(0, 1), Some((1, 20)));
+
+        editor.match_pairs = parse_match_pairs("()");
+        assert_eq!(editor.get_matching_bracket_pos(), None);
+
+        // A window that ends before the pair leaves it unmatched
+        editor.buf_mut().cursor_y = 0;
+        editor.buf_mut().cursor_x = 4;
+        editor.buf_mut().lines[0] = String::from(".IF ((eax < 5) &&");
+        editor.buf_mut().lines[1] = String::from("    (ebx > 2))");
+        assert_eq!(editor.matching_bracket_within(0, 0), None);
+        assert_eq!(editor.get_matching_bracket_pos(), Some((1, 13)));
+    }
+
+    #[test]
+    fn test_bom_and_line_endings_round_trip() {
+        let dir = TempDir::new("eol");
+        let dos = dir.join("dos.asm");
+        std::fs::write(&dos, "\u{feff}mov eax, 1\r\nret\r\n").unwrap();
+        let mixed = dir.join("mixed.asm");
+        std::fs::write(&mixed, "mov eax, 1\r\nret\n").unwrap();
+
+        let mut editor = EditorState::new(4);
+        editor.open_file(&dos).unwrap();
+        assert!(editor.buf().has_bom);
+        assert_eq!(editor.buf().line_ending, LineEnding::CrLf);
+        assert_eq!(editor.buf().lines[0], "mov eax, 1");
+        assert_eq!(editor.file_content(), "\u{feff}mov eax, 1\r\nret\r\n");
+        assert!(editor.remove_bom());
+        editor.set_line_ending(LineEnding::Lf);
+        assert_eq!(editor.file_content(), "mov eax, 1\nret\n");
+
+        editor.open_file(&mixed).unwrap();
+        assert_eq!(editor.buf().line_ending, LineEnding::Mixed);
+        assert_eq!(editor.file_content(), "mov eax, 1\r\nret\n");
+        editor.set_line_ending(LineEnding::Lf);
+        assert_eq!(editor.buf().lines[0], "mov eax, 1");
+        editor.undo();
+        assert_eq!(editor.buf().lines[0], "mov eax, 1\r");
+    }
+
+    #[test]
+    fn test_preview_buffer_is_reused_until_edited() {
+        let dir = TempDir::new("preview");
+        let paths: Vec<PathBuf> = ["a.asm", "b.asm", "c.asm"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+        for path in &paths {
+            std::fs::write(path, "ret\n").unwrap();
+        }
+
+        let mut editor = EditorState::new(4);
+        editor.preview_file(&paths[0]).unwrap();
+        editor.preview_file(&paths[1]).unwrap();
+        assert_eq!(editor.buffers.len(), 1);
+        assert!(editor.buf().preview);
+        assert_eq!(editor.current_file(), Some(&paths[1]));
+
+        // Opening explicitly keeps it, so the next preview gets a new buffer
+        editor.open_file(&paths[1]).unwrap();
+        assert!(!editor.buf().preview);
+        editor.preview_file(&paths[2]).unwrap();
+        assert_eq!(editor.buffers.len(), 2);
+
+        // So does an unsaved edit
+        editor.buf_mut().modified = true;
+        editor.preview_file(&paths[0]).unwrap();
+        assert_eq!(editor.buffers.len(), 3);
+    }
+
+    #[test]
+    fn test_anonymous_label_jumps() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    mov ecx, 5"),
+            String::from("@@: dec ecx"),
+            String::from("    jz @F"),
+            String::from("    jmp @B"),
+            String::from("    @@: ret"),
+        ];
+        editor.buf_mut().cursor_y = 2;
+        editor.buf_mut().cursor_x = 8;
+        assert_eq!(editor.go_to_definition().as_deref(), Some("@F"));
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (4, 4));
+
+        editor.buf_mut().cursor_y = 3;
+        editor.buf_mut().cursor_x = 9;
+        assert_eq!(editor.go_to_definition().as_deref(), Some("@B"));
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (1, 0));
+    }
+
+    #[test]
+    fn test_reflow_comments_skips_code() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    ; Reads a number from"),
+            String::from("    ; the user and"),
+            String::from("    ; returns it in EAX"),
+            String::from("    mov eax, 1 ; not a comment line"),
+            String::from(";----------"),
+        ];
+        editor.buf_mut().cursor_y = 1;
+
+        let (start, end) = editor.reflow_range().unwrap();
+        assert_eq!((start, end), (0, 2));
+        assert!(editor.reflow_comments(0, 4, 30));
+        assert_eq!(
+            editor.buf().lines,
+            vec![
+                "    ; Reads a number from the",
+                "    ; user and returns it in",
+                "    ; EAX",
+                "    mov eax, 1 ; not a comment line",
+                ";----------",
+            ]
+        );
+        assert!(!editor.reflow_comments(0, 4, 30));
+
+        editor.undo();
+        assert_eq!(editor.buf().lines.len(), 5);
+        assert_eq!(editor.buf().lines[1], "    ; the user and");
+    }
+
+    #[test]
+    fn test_comment_and_duplicate_undoes_together() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![String::from("    add eax, ebx"), String::from("    ret")];
+        editor.buf_mut().cursor_x = 8;
+
+        assert!(editor.comment_and_duplicate(false));
+        assert_eq!(
+            editor.buf().lines,
+            vec!["    ; add eax, ebx", "    add eax, ebx", "    ret"]
+        );
+        assert_eq!((editor.buf().cursor_y, editor.buf().cursor_x), (1, 8));
+
+        editor.undo();
+        assert_eq!(editor.buf().lines, vec!["    add eax, ebx", "    ret"]);
+
+        editor.buf_mut().cursor_y = 0;
+        assert!(editor.comment_and_duplicate(true));
+        assert_eq!(
+            editor.buf().lines,
+            vec!["    add eax, ebx", "    ; add eax, ebx", "    ret"]
+        );
+        assert_eq!(editor.buf().cursor_y, 0);
+    }
+
+    #[test]
+    fn test_delete_inner_word_uses_identifier_class() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    jmp @@next_?x ; go"),
+            String::from("    mov eax, [ebx+4]"),
+        ];
+        editor.buf_mut().cursor_x = 12;
+
+        assert_eq!(editor.delete_inner_word().as_deref(), Some("@@next_?x"));
+        assert_eq!(editor.buf().lines[0], "    jmp  ; go");
+        assert_eq!(editor.buf().cursor_x, 8);
+
+        // Brackets and operators are not words: nothing is deleted
+        editor.buf_mut().cursor_y = 1;
+        editor.buf_mut().cursor_x = 13;
+        assert_eq!(editor.delete_inner_word(), None);
+        editor.buf_mut().cursor_x = 14;
+        assert_eq!(editor.delete_inner_word().as_deref(), Some("ebx"));
+        assert_eq!(editor.buf().lines[1], "    mov eax, [+4]");
+
+        editor.undo();
+        assert_eq!(editor.buf().lines[1], "    mov eax, [ebx+4]");
+    }
+
+    #[test]
+    fn test_named_registers_keep_separate_yanks() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("first"),
+            String::from("second"),
+            String::from("third"),
+        ];
+
+        editor.register = Some('a');
+        editor.yank_line();
+        editor.buf_mut().cursor_y = 1;
+        editor.register = Some('b');
+        editor.delete_line();
+        assert_eq!(editor.buf().lines, vec!["first", "third"]);
+
+        editor.register = Some('a');
+        editor.paste_after();
+        assert_eq!(editor.buf().lines, vec!["first", "third", "first"]);
+        editor.register = Some('b');
+        editor.paste_before();
+        assert_eq!(
+            editor.buf().lines,
+            vec!["first", "third", "second", "first"]
+        );
+
+        // An empty register pastes nothing
+        editor.register = Some('z');
+        editor.paste_after();
+        assert_eq!(editor.buf().lines.len(), 4);
+    }
+
+    #[test]
+    fn test_vertical_moves_keep_screen_column_across_tabs() {
+        let mut ed = EditorState::new(4);
+        ed.buffers[0].lines = vec![
+            String::from("\tmov"),
+            String::from("ab\tcd"),
+            String::from("x"),
+        ];
+        ed.buffers[0].cursor_x = 2; // the 'o', drawn at column 5
+        assert_eq!(ed.cursor_display_col(), 5);
+
+        ed.move_cursor_down();
+        assert_eq!(ed.buffers[0].cursor_x, 4);
+        assert_eq!(ed.cursor_display_col(), 5);
+
+        ed.move_cursor_down();
+        assert_eq!(ed.buffers[0].cursor_x, 1);
+    }
+
+    #[test]
+    fn test_substitute_undo_granularity() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    mov eax, 1"),
+            String::from("    add eax, eax"),
+            String::from("    ret"),
+        ];
+
+        let sub = Substitution::parse("%s/eax/ecx/g").unwrap().unwrap();
+        assert_eq!(editor.substitute(&sub, 0, 2), (3, 2));
+        assert_eq!(editor.buf().lines[1], "    add ecx, ecx");
+        assert_eq!(editor.buf().cursor_y, 1);
+        editor.undo();
+        assert_eq!(editor.buf().lines[0], "    mov eax, 1");
+        assert_eq!(editor.buf().lines[1], "    add eax, eax");
+
+        // Each :s on one line is its own step
+        let sub = Substitution::parse("s/eax/edx/").unwrap().unwrap();
+        assert_eq!(editor.substitute(&sub, 0, 0), (1, 1));
+        assert_eq!(editor.substitute(&sub, 1, 1), (1, 1));
+        assert_eq!(editor.buf().lines[1], "    add edx, eax");
+        editor.undo();
+        assert_eq!(editor.buf().lines[1], "    add eax, eax");
+        assert_eq!(editor.buf().lines[0], "    mov edx, 1");
+        assert_eq!(editor.substitute(&sub, 2, 2), (0, 0));
+    }
+
+    #[test]
+    fn test_auto_label_colon_and_alignment() {
+        let mut editor = EditorState::new(4);
+        editor.auto_label = true;
+        editor.auto_close_blocks = false;
+        for c in "next_digit mov ".chars() {
+            editor.insert_char(c);
+        }
+        assert_eq!(editor.buf().lines[0], "next_digit: mov ");
+        assert_eq!(editor.buf().cursor_x, editor.buf().lines[0].len());
+
+        editor.buf_mut().lines[0] = String::from("L1:");
+        editor.buf_mut().cursor_x = 3;
+        for c in " inc ".chars() {
+            editor.insert_char(c);
+        }
+        assert_eq!(editor.buf().lines[0], "L1:     inc ");
+
+        // Definitions and directives keep their shape
+        for line in ["count EQU ", "x = ", "main PROC ", "msg BYTE ", "end main"] {
+            editor.buf_mut().lines[0] = line[..line.len() - 1].to_string();
+            editor.buf_mut().cursor_x = line.len() - 1;
+            editor.insert_char(line.chars().last().unwrap());
+            assert_eq!(editor.buf().lines[0], line);
+        }
+
+        // A label alone gets its colon on Enter
+        editor.buf_mut().lines[0] = String::from("done");
+        editor.buf_mut().cursor_x = 4;
+        editor.insert_newline();
+        assert_eq!(editor.buf().lines[0], "done:");
+        editor.buf_mut().lines[1] = String::from("ret");
+        editor.buf_mut().cursor_x = 3;
+        editor.insert_newline();
+        assert_eq!(editor.buf().lines[1], "ret");
+    }
+
+    #[test]
+    fn test_align_comments_on_semicolon() {
+        let mut editor = EditorState::new(4);
+        editor.align_comments = true;
+        editor.comment_column = 20;
+        for c in "    mov eax, 1 ;".chars() {
+            editor.insert_char(c);
+        }
+        assert_eq!(
+            editor.buf().lines[0],
+            format!("    mov eax, 1{};", " ".repeat(6))
+        );
+        assert_eq!(editor.buf().cursor_x, editor.buf().lines[0].len());
+        editor.undo();
+        assert_eq!(editor.buf().lines[0], "    mov eax, 1 ;");
+
+        // Full-line comments, strings, comments and long code are left alone
+        for line in [
+            "    ",
+            "msg BYTE \"a",
+            "    ret ; done ",
+            "    call WriteString_long ",
+        ] {
+            editor.buf_mut().lines[0] = line.to_string();
+            editor.buf_mut().cursor_x = line.len();
+            editor.insert_char(';');
+            assert_eq!(editor.buf().lines[0], format!("{};", line));
+        }
+
+        // A tab before the comment counts to its next stop
+        editor.buf_mut().lines[0] = String::from("\tret");
+        editor.buf_mut().cursor_x = 4;
+        editor.insert_char(';');
+        assert_eq!(editor.buf().lines[0], format!("\tret{};", " ".repeat(13)));
+    }
+
+    #[test]
+    fn test_regex_search_mode() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    mov  eax, 1"),
+            String::from("    MOV edx, ecx"),
+            String::from("    movzx esi, al"),
+        ];
+
+        editor.search(r"mov\s+e[a-d]x");
+        assert!(editor.search_matches.is_empty());
+
+        editor.search_is_regex = true;
+        editor.search(r"mov\s+e[a-d]x");
+        assert_eq!(editor.search_matches, vec![(0, 4), (1, 4)]);
+        assert_eq!(editor.search_ranges("  mov ebx"), vec![(2, 9)]);
+
+        editor.search("mov(");
+        assert!(editor.search_matches.is_empty());
+        assert_eq!(editor.search_status().as_deref(), Some("invalid regex"));
+        assert!(editor.search_ranges("mov(").is_empty());
+    }
+
+    #[test]
+    fn test_whole_word_search() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    mov ax, AX"),
+            String::from("    mov eax, max"),
+            String::from("    add ax,@ax"),
+        ];
+
+        editor.search("ax");
+        assert_eq!(editor.search_matches.len(), 6);
+
+        editor.search_whole_word = true;
+        editor.search("ax");
+        assert_eq!(editor.search_matches, vec![(0, 8), (0, 12), (2, 8)]);
+        assert_eq!(editor.search_status().as_deref(), Some("1/3"));
+        assert_eq!(editor.search_ranges("ax+max"), vec![(0, 2)]);
+
+        editor.search_is_regex = true;
+        editor.search("a.");
+        assert_eq!(editor.search_matches, vec![(0, 8), (0, 12), (2, 8)]);
+    }
+
+    #[test]
+    fn test_search_case_modes() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![String::from("    mov eax, EAX ; Eax")];
+
+        // Smart case by default: an uppercase letter makes it exact
+        editor.search("eax");
+        assert_eq!(editor.search_matches.len(), 3);
+        editor.search("EAX");
+        assert_eq!(editor.search_matches, vec![(0, 13)]);
+        assert_eq!(editor.search_ranges("EAX eax"), vec![(0, 3)]);
+
+        editor.search_case = SearchCase::Insensitive;
+        editor.search("EAX");
+        assert_eq!(editor.search_matches.len(), 3);
+        editor.search_case = SearchCase::Sensitive;
+        editor.search("eax");
+        assert_eq!(editor.search_matches, vec![(0, 8)]);
+
+        // :s follows the setting unless given i
+        editor.search_case = SearchCase::Smart;
+        let sub = Substitution::parse("s/eax/ecx/g").unwrap().unwrap();
+        assert_eq!(editor.substitute(&sub, 0, 0), (3, 1));
+        let sub = Substitution::parse("s/ECX/edx/").unwrap().unwrap();
+        assert_eq!(editor.substitute(&sub, 0, 0), (0, 0));
+        let sub = Substitution::parse("s/ECX/edx/i").unwrap().unwrap();
+        assert_eq!(editor.substitute(&sub, 0, 0), (1, 1));
+        assert_eq!(editor.buf().lines[0], "    mov edx, ecx ; ecx");
+    }
+
+    #[test]
+    fn test_append_comment_skips_commented_lines() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![
+            String::from("    cdq"),
+            String::from("    mov al, ';'"),
+            String::from("    idiv ecx ; signed"),
+        ];
+        assert!(editor.append_comment("Sign-extends EAX"));
+        assert_eq!(editor.buf().lines[0], "    cdq  ; Sign-extends EAX");
+
+        editor.buf_mut().cursor_y = 1;
+        assert!(editor.append_comment("Move"));
+        assert_eq!(editor.buf().lines[1], "    mov al, ';'  ; Move");
+
+        editor.buf_mut().cursor_y = 2;
+        assert!(!editor.append_comment("Divide"));
+
+        editor.undo();
+        assert_eq!(editor.buf().lines[1], "    mov al, ';'");
+    }
+
+    #[test]
+    fn test_long_line_motions_and_hard_wrap() {
+        let mut editor = EditorState::new(4);
+        editor.long_line_threshold = 16;
+        editor.buf_mut().lines = vec![String::from("mov"), format!("db {}", "ab, ".repeat(8))];
+        assert_eq!(editor.first_long_line(), Some(1));
+
+        editor.buf_mut().cursor_y = 1;
+        editor.move_word_forward();
+        assert_eq!(editor.buf().cursor_x, 3);
+        editor.move_word_end();
+        assert_eq!(editor.buf().cursor_x, 4);
+        assert!(editor.find_char_till_forward(','));
+        assert_eq!(editor.buf().cursor_x, 4);
+        assert!(editor.find_char_forward(','));
+        assert_eq!(editor.buf().cursor_x, 5);
+        assert!(editor.find_char_forward(','));
+        assert_eq!(editor.buf().cursor_x, 9);
+        editor.move_word_backward();
+        assert_eq!(editor.buf().cursor_x, 7);
+
+        editor
+            .buf_mut()
+            .lines
+            .push(String::from("msg db 'one, two', 0, 1 ; x, y, z"));
+        editor.buf_mut().lines.push("x".repeat(12));
+        assert_eq!(editor.hard_wrap(10), 2);
+        assert_eq!(
+            editor.buf().lines,
+            vec![
+                "mov",
+                "db ab, ab,",
+                "ab, ab,",
+                "ab, ab,",
+                "ab, ab, ",
+                "msg db",
+                "'one, two',",
+                "0, 1",
+                "; x, y, z",
+                "xxxxxxxxxxxx",
+            ]
+        );
+        assert_eq!(editor.first_long_line(), None);
+        assert_eq!(editor.hard_wrap(10), 0);
+
+        editor.undo();
+        assert_eq!(editor.buf().lines.len(), 4);
+        assert_eq!(editor.first_long_line(), Some(1));
+    }
+
+    #[test]
+    fn test_insert_session_undoes_as_one_step() {
+        let mut editor = EditorState::new(4);
+        editor.buf_mut().lines = vec![String::from("main PROC")];
+        editor.auto_close_blocks = false;
+
+        // o, type "ret", Escape
+        let start = editor.undo_tree.current();
+        editor.move_to_line_end();
+        editor.insert_newline();
+        editor.undo_tree.begin_group(start);
+        for c in "ret".chars() {
+            editor.insert_char(c);
+        }
+        editor.undo_tree.end_group();
+
+        assert_eq!(editor.buf().lines.len(), 2);
+        assert!(editor.undo());
+        assert_eq!(editor.buf().lines, vec!["main PROC"]);
+        assert!(!editor.undo());
+        assert!(editor.redo());
+        assert_eq!(editor.buf().lines[1].trim(), "ret");
+    }
+}
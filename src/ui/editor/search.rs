@@ -1,4 +1,5 @@
 use super::buffer::Buffer;
+use anyhow::{bail, Result};
 
 /// Search state and operations
 pub struct SearchState {
@@ -87,3 +88,128 @@ impl SearchState {
         }
     }
 }
+
+/// A parsed `:s/pattern/replacement/flags` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub pattern: String,
+    pub replacement: String,
+    /// `%s`: every line of the buffer rather than the current line
+    pub whole_file: bool,
+    /// `g`: every match on a line rather than just the first
+    pub global: bool,
+    /// `i` ignores case and `I` matches it; without either, the search
+    /// case setting decides
+    pub ignore_case: Option<bool>,
+}
+
+impl Substitution {
+    /// Parse `s/old/new/[giI]` or `%s/old/new/[giI]`. `\/` is a literal slash
+    /// and the closing `/` may be left off. Returns None when `cmd` is not
+    /// a substitute command at all.
+    pub fn parse(cmd: &str) -> Option<Result<Self>> {
+        let (whole_file, rest) = match cmd.strip_prefix('%') {
+            Some(rest) => (true, rest),
+            None => (false, cmd),
+        };
+        let body = rest.strip_prefix("s/")?;
+        Some(Self::parse_body(body, whole_file))
+    }
+
+    fn parse_body(body: &str, whole_file: bool) -> Result<Self> {
+        let mut fields = vec![String::new()];
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(next @ ('/' | '\\')) => fields.last_mut().unwrap().push(next),
+                    Some(next) => fields.last_mut().unwrap().extend(['\\', next]),
+                    None => fields.last_mut().unwrap().push('\\'),
+                },
+                '/' if fields.len() < 3 => fields.push(String::new()),
+                _ => fields.last_mut().unwrap().push(c),
+            }
+        }
+
+        let pattern = fields[0].clone();
+        if pattern.is_empty() {
+            bail!("Usage: :s/pattern/replacement/[giI]");
+        }
+        let mut sub = Self {
+            pattern,
+            replacement: fields.get(1).cloned().unwrap_or_default(),
+            whole_file,
+            global: false,
+            ignore_case: None,
+        };
+        for flag in fields.get(2).map_or("", |f| f.as_str()).chars() {
+            match flag {
+                'g' => sub.global = true,
+                'i' => sub.ignore_case = Some(true),
+                'I' => sub.ignore_case = Some(false),
+                _ => bail!("Unknown substitute flag: {}", flag),
+            }
+        }
+        Ok(sub)
+    }
+
+    /// `line` with the pattern replaced, and how many matches were replaced.
+    /// None when nothing matches.
+    pub fn apply(&self, line: &str, ignore_case: bool) -> Option<(String, usize)> {
+        // ASCII case folding keeps byte offsets valid in `line`
+        let (haystack, needle) = if ignore_case {
+            (line.to_ascii_lowercase(), self.pattern.to_ascii_lowercase())
+        } else {
+            (line.to_string(), self.pattern.clone())
+        };
+
+        let mut result = String::with_capacity(line.len());
+        let mut last = 0;
+        let mut count = 0;
+        for (pos, _) in haystack.match_indices(&needle) {
+            result.push_str(&line[last..pos]);
+            result.push_str(&self.replacement);
+            last = pos + needle.len();
+            count += 1;
+            if !self.global {
+                break;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        result.push_str(&line[last..]);
+        Some((result, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_substitution() {
+        assert!(Substitution::parse("set ff=unix").is_none());
+        assert!(Substitution::parse("s//x/").unwrap().is_err());
+        assert!(Substitution::parse("s/a/b/q").unwrap().is_err());
+
+        let sub = Substitution::parse(r"%s/a\/b/[esi]/g").unwrap().unwrap();
+        assert_eq!(sub.pattern, "a/b");
+        assert_eq!(sub.replacement, "[esi]");
+        assert!(sub.whole_file && sub.global && sub.ignore_case.is_none());
+
+        let first = Substitution::parse("s/eax/ebx").unwrap().unwrap();
+        assert_eq!(
+            first.apply("mov eax, eax ; EAX", false),
+            Some((String::from("mov ebx, eax ; EAX"), 1))
+        );
+        assert_eq!(first.apply("mov EAX, 1", false), None);
+
+        let all = Substitution::parse("s/eax/ebx/gi").unwrap().unwrap();
+        assert_eq!(all.ignore_case, Some(true));
+        assert_eq!(
+            all.apply("mov eax, eax ; EAX", true),
+            Some((String::from("mov ebx, ebx ; ebx"), 3))
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use super::buffer::Buffer;
 use super::clipboard::{Clipboard, YankType};
-use super::cursor::CursorOps;
-use super::undo::{EditorAction, UndoStack};
+use super::undo::{EditorAction, UndoTree};
+use crate::utf8;
 
 /// Selection operations for visual mode
 pub struct SelectionOps;
@@ -11,6 +11,7 @@ impl SelectionOps {
     pub fn start_selection(buf: &mut Buffer) {
         buf.selection_start = Some((buf.cursor_y, buf.cursor_x));
         buf.selection_end = Some((buf.cursor_y, buf.cursor_x));
+        buf.block_selection = false;
     }
 
     /// Update selection end to cursor
@@ -24,6 +25,7 @@ impl SelectionOps {
     pub fn clear_selection(buf: &mut Buffer) {
         buf.selection_start = None;
         buf.selection_end = None;
+        buf.block_selection = false;
     }
 
     /// Check if there's an active selection
@@ -60,7 +62,7 @@ impl SelectionOps {
     /// Delete selected text
     pub fn delete_selection(
         buf: &mut Buffer,
-        undo_stack: &mut UndoStack,
+        undo_tree: &mut UndoTree,
         clipboard: &mut Clipboard,
     ) -> bool {
         if let Some(((start_line, start_col), (end_line, end_col))) = Self::get_selection_range(buf)
@@ -73,8 +75,8 @@ impl SelectionOps {
                 // Single line deletion
                 if start_line < buf.lines.len() {
                     let line = &mut buf.lines[start_line];
-                    let start_byte = CursorOps::clamp_to_char_boundary(line, start_col);
-                    let end_byte = CursorOps::clamp_to_char_boundary(line, end_col);
+                    let start_byte = utf8::clamp_to_char_boundary(line, start_col);
+                    let end_byte = utf8::clamp_to_char_boundary(line, end_col);
                     line.drain(start_byte..end_byte);
                     buf.cursor_x = start_byte;
                     buf.cursor_y = start_line;
@@ -83,13 +85,13 @@ impl SelectionOps {
             } else {
                 // Multi-line deletion
                 let start_byte = if start_line < buf.lines.len() {
-                    CursorOps::clamp_to_char_boundary(&buf.lines[start_line], start_col)
+                    utf8::clamp_to_char_boundary(&buf.lines[start_line], start_col)
                 } else {
                     0
                 };
 
                 let end_byte = if end_line < buf.lines.len() {
-                    CursorOps::clamp_to_char_boundary(&buf.lines[end_line], end_col)
+                    utf8::clamp_to_char_boundary(&buf.lines[end_line], end_col)
                 } else {
                     0
                 };
@@ -114,7 +116,7 @@ impl SelectionOps {
                 buf.modified = true;
             }
 
-            undo_stack.push(EditorAction::DeleteText {
+            undo_tree.push(EditorAction::DeleteText {
                 start_line,
                 start_col,
                 end_line,
@@ -141,8 +143,8 @@ impl SelectionOps {
             // Single line
             if start_line < buf.lines.len() {
                 let line = &buf.lines[start_line];
-                let start_byte = CursorOps::clamp_to_char_boundary(line, start_col);
-                let end_byte = CursorOps::clamp_to_char_boundary(line, end_col);
+                let start_byte = utf8::clamp_to_char_boundary(line, start_col);
+                let end_byte = utf8::clamp_to_char_boundary(line, end_col);
                 line[start_byte..end_byte].to_string()
             } else {
                 String::new()
@@ -158,10 +160,10 @@ impl SelectionOps {
 
                 let line = &buf.lines[line_idx];
                 if line_idx == start_line {
-                    let start_byte = CursorOps::clamp_to_char_boundary(line, start_col);
+                    let start_byte = utf8::clamp_to_char_boundary(line, start_col);
                     result.push_str(&line[start_byte..]);
                 } else if line_idx == end_line {
-                    let end_byte = CursorOps::clamp_to_char_boundary(line, end_col);
+                    let end_byte = utf8::clamp_to_char_boundary(line, end_col);
                     result.push('\n');
                     result.push_str(&line[..end_byte]);
                 } else {
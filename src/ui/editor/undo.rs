@@ -1,4 +1,4 @@
-use std::collections::VecDeque;
+use std::time::Instant;
 
 /// Represents a single editor action for undo/redo
 #[derive(Debug, Clone)]
@@ -52,53 +52,413 @@ pub enum EditorAction {
     Batch(Vec<EditorAction>),
 }
 
-/// Undo/Redo stack for editor actions using VecDeque for O(1) front removal
+impl EditorAction {
+    /// Short human-readable description, used by the undo tree panel
+    pub fn describe(&self) -> String {
+        match self {
+            EditorAction::InsertChar { line, ch, .. } => {
+                format!("insert '{}' on line {}", ch, line + 1)
+            }
+            EditorAction::DeleteChar { line, ch, .. } => {
+                format!("delete '{}' on line {}", ch, line + 1)
+            }
+            EditorAction::InsertLine { line_num, .. } => format!("insert line {}", line_num + 1),
+            EditorAction::DeleteLine { line_num, .. } => format!("delete line {}", line_num + 1),
+            EditorAction::ReplaceLine { line_num, .. } => format!("change line {}", line_num + 1),
+            EditorAction::SplitLine { line, .. } => format!("split line {}", line + 1),
+            EditorAction::JoinLines { line, .. } => format!("join lines {}-{}", line + 1, line + 2),
+            EditorAction::InsertText {
+                start_line, text, ..
+            } => format!(
+                "insert {} chars at line {}",
+                text.chars().count(),
+                start_line + 1
+            ),
+            EditorAction::DeleteText {
+                start_line, text, ..
+            } => format!(
+                "delete {} chars at line {}",
+                text.chars().count(),
+                start_line + 1
+            ),
+            EditorAction::Batch(actions) => match actions.first() {
+                Some(first) if actions.len() == 1 => first.describe(),
+                Some(first) => format!("{} (+{} more)", first.describe(), actions.len() - 1),
+                None => String::from("empty change"),
+            },
+        }
+    }
+}
+
+/// A single node in the undo tree. The root node carries no action.
+#[derive(Debug, Clone)]
+pub struct UndoNode {
+    pub action: Option<EditorAction>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Sequence number in creation order (root is 0), used by g-/g+
+    pub seq: usize,
+    pub created: Instant,
+    /// Child that redo follows - the most recently visited branch
+    redo_child: Option<usize>,
+}
+
+/// One step needed to travel between two nodes of the tree
+#[derive(Debug, Clone)]
+pub enum UndoStep {
+    Undo(EditorAction),
+    Redo(EditorAction),
+}
+
+/// Undo history as a tree: making an edit after undoing starts a new branch
+/// instead of discarding the redo history.
 #[derive(Debug, Clone)]
-pub struct UndoStack {
-    pub(crate) undo_stack: VecDeque<EditorAction>,
-    pub(crate) redo_stack: VecDeque<EditorAction>,
+pub struct UndoTree {
+    pub(crate) nodes: Vec<UndoNode>,
+    pub(crate) root: usize,
+    pub(crate) current: usize,
+    next_seq: usize,
     max_size: usize,
+    /// Node that was current when the open insert session began
+    group_start: Option<usize>,
 }
 
-impl Default for UndoStack {
+impl Default for UndoTree {
     fn default() -> Self {
         Self::new(1000)
     }
 }
 
-impl UndoStack {
+impl UndoTree {
     pub fn new(max_size: usize) -> Self {
         Self {
-            undo_stack: VecDeque::new(),
-            redo_stack: VecDeque::new(),
+            nodes: vec![Self::root_node()],
+            root: 0,
+            current: 0,
+            next_seq: 1,
             max_size,
+            group_start: None,
+        }
+    }
+
+    fn root_node() -> UndoNode {
+        UndoNode {
+            action: None,
+            parent: None,
+            children: Vec::new(),
+            seq: 0,
+            created: Instant::now(),
+            redo_child: None,
         }
     }
 
     pub fn push(&mut self, action: EditorAction) {
-        self.undo_stack.push_back(action);
-        self.redo_stack.clear(); // Clear redo on new action
+        let idx = self.nodes.len();
+        self.nodes.push(UndoNode {
+            action: Some(action),
+            parent: Some(self.current),
+            children: Vec::new(),
+            seq: self.next_seq,
+            created: Instant::now(),
+            redo_child: None,
+        });
+        self.next_seq += 1;
+        let parent = &mut self.nodes[self.current];
+        parent.children.push(idx);
+        parent.redo_child = Some(idx);
+        self.current = idx;
 
-        // Trim from front if exceeds max size - O(1) with VecDeque
-        while self.undo_stack.len() > self.max_size {
-            self.undo_stack.pop_front();
+        if self.nodes.len() > self.max_size + 1 {
+            self.trim();
         }
     }
 
-    pub fn pop_undo(&mut self) -> Option<EditorAction> {
-        self.undo_stack.pop_back()
+    /// Start collecting edits into one undo step. `start` is the node that
+    /// was current before the edit that opened the group, so an `o` that
+    /// has already inserted its newline still belongs to the session.
+    pub fn begin_group(&mut self, start: usize) {
+        if start < self.nodes.len() {
+            self.group_start = Some(start);
+        }
+    }
+
+    /// Fold every edit pushed since `begin_group` into a single `Batch`
+    /// node. Nothing is merged if the session undid past its start or
+    /// branched, since the edits no longer form one straight run.
+    pub fn end_group(&mut self) {
+        let Some(start) = self.group_start.take() else {
+            return;
+        };
+        let path = self.path_from_root(self.current);
+        let Some(pos) = path.iter().position(|&i| i == start) else {
+            return;
+        };
+        let chain = &path[pos + 1..];
+        if chain.len() < 2 {
+            return;
+        }
+        let first = chain[0];
+        let fresh_run = first + chain.len() == self.nodes.len()
+            && chain.iter().enumerate().all(|(k, &i)| i == first + k);
+        if !fresh_run {
+            return;
+        }
+
+        let actions: Vec<EditorAction> = chain
+            .iter()
+            .filter_map(|&i| self.nodes[i].action.clone())
+            .collect();
+        self.nodes.truncate(first + 1);
+        let node = &mut self.nodes[first];
+        node.action = Some(EditorAction::Batch(actions));
+        node.children.clear();
+        node.redo_child = None;
+        self.next_seq = node.seq + 1;
+        self.current = first;
     }
 
-    pub fn push_redo(&mut self, action: EditorAction) {
-        self.redo_stack.push_back(action);
+    /// Step back to the parent node, returning the action to revert
+    pub fn undo(&mut self) -> Option<EditorAction> {
+        let node = &self.nodes[self.current];
+        let parent = node.parent?;
+        let action = node.action.clone();
+        self.nodes[parent].redo_child = Some(self.current);
+        self.current = parent;
+        action
     }
 
-    pub fn pop_redo(&mut self) -> Option<EditorAction> {
-        self.redo_stack.pop_back()
+    /// Step forward along the most recently visited branch
+    pub fn redo(&mut self) -> Option<EditorAction> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+        self.nodes[child].action.clone()
     }
 
     pub fn clear(&mut self) {
-        self.undo_stack.clear();
-        self.redo_stack.clear();
+        *self = Self::new(self.max_size);
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_seq(&self) -> usize {
+        self.nodes[self.current].seq
+    }
+
+    pub fn node(&self, idx: usize) -> Option<&UndoNode> {
+        self.nodes.get(idx)
+    }
+
+    /// Node whose sequence number is closest to the current one in the
+    /// given direction (g- / g+)
+    pub fn time_step(&self, forward: bool) -> Option<usize> {
+        let seq = self.current_seq();
+        let reachable = self.preorder();
+        if forward {
+            reachable
+                .into_iter()
+                .filter(|&i| self.nodes[i].seq > seq)
+                .min_by_key(|&i| self.nodes[i].seq)
+        } else {
+            reachable
+                .into_iter()
+                .filter(|&i| self.nodes[i].seq < seq)
+                .max_by_key(|&i| self.nodes[i].seq)
+        }
+    }
+
+    /// Move the current pointer to `target`, returning the undo/redo steps
+    /// the editor has to apply (in order) to get there.
+    pub fn jump_to(&mut self, target: usize) -> Vec<UndoStep> {
+        if target >= self.nodes.len() || target == self.current {
+            return Vec::new();
+        }
+        let target_path = self.path_from_root(target);
+        if target_path.first() != Some(&self.root) {
+            return Vec::new();
+        }
+
+        let mut steps = Vec::new();
+        // Walk up from current until we hit an ancestor of the target
+        while !target_path.contains(&self.current) {
+            match self.undo() {
+                Some(action) => steps.push(UndoStep::Undo(action)),
+                None => return steps,
+            }
+        }
+        // Then walk down the target's path
+        let start = target_path
+            .iter()
+            .position(|&i| i == self.current)
+            .unwrap_or(0);
+        for &idx in &target_path[start + 1..] {
+            self.nodes[self.current].redo_child = Some(idx);
+            if let Some(action) = self.redo() {
+                steps.push(UndoStep::Redo(action));
+            }
+        }
+        steps
+    }
+
+    fn path_from_root(&self, mut idx: usize) -> Vec<usize> {
+        let mut path = vec![idx];
+        while let Some(parent) = self.nodes[idx].parent {
+            path.push(parent);
+            idx = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Reachable nodes in depth-first order, newest branch first
+    pub fn preorder(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(idx) = stack.pop() {
+            order.push(idx);
+            // Push oldest first so the newest branch is visited first
+            stack.extend(self.nodes[idx].children.iter().copied());
+        }
+        order
+    }
+
+    /// Rows for the undo tree panel: (node, branch level). Older branches
+    /// are indented one level deeper than the branch they split from.
+    pub fn branch_rows(&self) -> Vec<(usize, usize)> {
+        let mut levels = vec![0; self.nodes.len()];
+        self.preorder()
+            .into_iter()
+            .map(|idx| {
+                if let Some(parent) = self.nodes[idx].parent {
+                    let newest = self.nodes[parent].children.last() == Some(&idx);
+                    levels[idx] = levels[parent] + usize::from(!newest);
+                }
+                (idx, levels[idx])
+            })
+            .collect()
+    }
+
+    /// Depth of a node below the root
+    pub fn depth(&self, idx: usize) -> usize {
+        self.path_from_root(idx).len() - 1
+    }
+
+    /// Drop the oldest history once the tree grows past `max_size`.
+    /// The root moves down along the branch that leads to the current node,
+    /// so the history you can actually reach is kept.
+    fn trim(&mut self) {
+        let path = self.path_from_root(self.current);
+        let excess = self.nodes.len() - (self.max_size + 1);
+        // Never drop the current node itself
+        let advance = excess.min(path.len().saturating_sub(1));
+        if advance == 0 {
+            return;
+        }
+        let new_root = path[advance];
+
+        // Collect the nodes that stay reachable and remap their indices
+        let old_root = self.root;
+        self.root = new_root;
+        let keep = self.preorder();
+        self.root = old_root;
+
+        let mut remap = vec![usize::MAX; self.nodes.len()];
+        for (new_idx, &old_idx) in keep.iter().enumerate() {
+            remap[old_idx] = new_idx;
+        }
+        let mut nodes = Vec::with_capacity(keep.len());
+        for &old_idx in &keep {
+            let mut node = self.nodes[old_idx].clone();
+            node.parent = node.parent.map(|p| remap[p]).filter(|&p| p != usize::MAX);
+            node.children = node.children.iter().map(|&c| remap[c]).collect();
+            node.redo_child = node.redo_child.map(|c| remap[c]);
+            nodes.push(node);
+        }
+        // The new root becomes the sentinel
+        nodes[0].action = None;
+        nodes[0].parent = None;
+
+        self.current = remap[self.current];
+        self.group_start = self
+            .group_start
+            .map(|g| remap[g])
+            .filter(|&g| g != usize::MAX);
+        self.root = 0;
+        self.nodes = nodes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ins(ch: char) -> EditorAction {
+        EditorAction::InsertChar {
+            line: 0,
+            col: 0,
+            ch,
+        }
+    }
+
+    fn ch(action: Option<EditorAction>) -> Option<char> {
+        match action {
+            Some(EditorAction::InsertChar { ch, .. }) => Some(ch),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_new_edit_after_undo_branches() {
+        let mut tree = UndoTree::default();
+        tree.push(ins('a'));
+        tree.push(ins('b'));
+        assert_eq!(ch(tree.undo()), Some('b'));
+        tree.push(ins('c'));
+
+        // Linear redo follows the newest branch
+        assert_eq!(ch(tree.undo()), Some('c'));
+        assert_eq!(ch(tree.redo()), Some('c'));
+
+        // But the 'b' branch is still reachable
+        let b = tree
+            .preorder()
+            .into_iter()
+            .find(|&i| ch(tree.node(i).unwrap().action.clone()) == Some('b'))
+            .unwrap();
+        let steps = tree.jump_to(b);
+        assert_eq!(steps.len(), 2);
+        assert!(matches!(steps[0], UndoStep::Undo(_)));
+        assert!(matches!(steps[1], UndoStep::Redo(_)));
+        assert_eq!(tree.current(), b);
+    }
+
+    #[test]
+    fn test_time_step_follows_sequence() {
+        let mut tree = UndoTree::default();
+        tree.push(ins('a'));
+        tree.push(ins('b'));
+        tree.undo();
+        tree.push(ins('c'));
+
+        // g- from 'c' (seq 3) goes to 'b' (seq 2) on the other branch
+        let prev = tree.time_step(false).unwrap();
+        assert_eq!(ch(tree.node(prev).unwrap().action.clone()), Some('b'));
+        tree.jump_to(prev);
+        let next = tree.time_step(true).unwrap();
+        assert_eq!(ch(tree.node(next).unwrap().action.clone()), Some('c'));
+    }
+
+    #[test]
+    fn test_trim_keeps_current_branch() {
+        let mut tree = UndoTree::new(3);
+        for c in ['a', 'b', 'c', 'd', 'e'] {
+            tree.push(ins(c));
+        }
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(ch(tree.undo()), Some('e'));
+        assert_eq!(ch(tree.undo()), Some('d'));
+        assert_eq!(ch(tree.undo()), Some('c'));
+        assert!(tree.undo().is_none());
     }
 }
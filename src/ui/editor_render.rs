@@ -7,9 +7,12 @@ use ratatui::{
 use std::fs;
 use std::path::PathBuf;
 
-use crate::diagnostics::{Diagnostic, DiagnosticSeverity};
+use crate::diagnostics::{worst_severity, Diagnostic, DiagnosticSeverity};
 use crate::syntax::Highlighter;
 use crate::theme::Theme;
+use crate::ui::editor::cursor::CursorOps;
+use crate::ui::editor::folded_away;
+use crate::utf8;
 
 /// Represents a single editor action for undo/redo
 #[derive(Debug, Clone)]
@@ -2165,16 +2168,17 @@ pub fn render(
         1 // Just diagnostic indicator
     };
 
-    // Build a map of line numbers to diagnostics for the current file
-    let diag_map: std::collections::HashMap<usize, &Diagnostic> = diagnostics
-        .iter()
-        .filter(|d| current_file == Some(&d.file))
-        .map(|d| (d.line, d))
-        .collect();
+    // Build a map of line numbers to every diagnostic on that line for the
+    // current file, in report order
+    let mut diag_map: std::collections::HashMap<usize, Vec<&Diagnostic>> =
+        std::collections::HashMap::new();
+    for d in diagnostics.iter().filter(|d| current_file == Some(&d.file)) {
+        diag_map.entry(d.line).or_default().push(d);
+    }
 
     let title = match &buf.file_path {
-        Some(path) => {
-            let name = path.file_name().unwrap_or_default().to_string_lossy();
+        Some(_) => {
+            let name = state.display_name(state.active_buffer);
             if buf.modified {
                 format!(" {} ● ", name)
             } else {
@@ -2205,6 +2209,8 @@ pub fn render(
         .border_style(border_style);
 
     let inner = block.inner(area);
+    let text_columns = (inner.width as usize).saturating_sub(state.gutter_width());
+    let cursor_line_bg = state.cursor_line.then(|| theme.ui.cursor_line.to_color());
     frame.render_widget(block, area);
 
     let visible_height = inner.height as usize;
@@ -2216,129 +2222,204 @@ pub fn render(
         vec![]
     };
 
-    // Get selection range for visual mode highlighting
-    let selection_range = state.get_selection_range();
+    // Get selection range for visual mode highlighting. A block selection
+    // covers the same columns on each of its lines instead.
+    let block_range = state.block_range();
+    let selection_range = state
+        .get_selection_range()
+        .filter(|_| block_range.is_none());
+
+    // Comment blocks folded with zC and regions folded with za show only
+    // their first line
+    let folds = state.folds();
+
+    // The bracket under the cursor and its pair, when both are on screen
+    let brackets: Vec<(usize, usize)> = if focused {
+        let last_visible = (buf.scroll_offset..buf.lines.len())
+            .filter(|idx| !folds.iter().any(|f| folded_away(f, *idx)))
+            .take(visible_height)
+            .last()
+            .unwrap_or(buf.scroll_offset);
+        state
+            .matching_bracket_within(buf.scroll_offset, last_visible)
+            .map(|pair| vec![(buf.cursor_y, buf.cursor_x), pair])
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
 
     let visible_lines: Vec<Line> = buf
         .lines
         .iter()
         .enumerate()
         .skip(buf.scroll_offset)
+        .filter(|(idx, _)| !folds.iter().any(|f| folded_away(f, *idx)))
         .take(visible_height)
-        .map(|(idx, line)| {
+        .flat_map(|(idx, line)| {
             let mut spans = Vec::new();
             let line_num_1based = idx + 1;
+            // Rows of the cursor line are shaded under any other highlight
+            let shade = |row| match cursor_line_bg {
+                Some(bg) if idx == buf.cursor_y => shade_row(row, text_columns, bg),
+                _ => row,
+            };
 
-            // Check if this line has a diagnostic
-            let line_diagnostic = diag_map.get(&line_num_1based);
+            // Check if this line has diagnostics; the first is shown as
+            // virtual text, the most severe colors the gutter
+            let line_diagnostics = diag_map.get(&line_num_1based);
+            let line_diagnostic = line_diagnostics.and_then(|ds| ds.first());
 
             // Diagnostic gutter indicator
-            let diag_indicator = match line_diagnostic {
-                Some(d) if d.severity == DiagnosticSeverity::Error => Span::styled(
+            let severity = line_diagnostics.and_then(|ds| worst_severity(ds.iter().copied()));
+            let diag_indicator = match severity {
+                Some(DiagnosticSeverity::Error) => Span::styled(
                     "● ",
                     Style::default().fg(theme.ui.diagnostic_error.to_color()),
                 ),
-                Some(d) if d.severity == DiagnosticSeverity::Warning => Span::styled(
+                Some(DiagnosticSeverity::Warning) => Span::styled(
                     "▲ ",
                     Style::default().fg(theme.ui.diagnostic_warning.to_color()),
                 ),
-                _ => Span::raw("  "),
+                None => Span::raw("  "),
             };
             spans.push(diag_indicator);
 
             // Line number
             if show_line_numbers {
                 let num_width = line_number_width - 2; // Subtract diagnostic indicator width
-                let line_num = format!("{:>width$} ", line_num_1based, width = num_width);
+                let number = state.line_number_mode.number(idx, buf.cursor_y);
+                let line_num = format!("{:>width$} ", number, width = num_width);
                 spans.push(Span::styled(
                     line_num,
                     Style::default().fg(theme.ui.line_numbers.to_color()),
                 ));
             }
 
-            // Check if this line is part of a selection
-            let line_selection =
-                if let Some(((start_line, start_col), (end_line, end_col))) = selection_range {
-                    if idx >= start_line && idx <= end_line {
-                        let sel_start = if idx == start_line { start_col } else { 0 };
-                        let sel_end = if idx == end_line { end_col } else { line.len() };
-                        Some((sel_start, sel_end))
-                    } else {
-                        None
-                    }
+            if let Some(&(_, end)) = folds.iter().find(|(start, _)| *start == idx) {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let code = line.split(';').next().unwrap_or("").trim();
+                let summary = if code.is_empty() {
+                    format!("{}{} ··· ({} lines)", indent, line.trim(), end + 1 - idx)
                 } else {
-                    None
+                    format!("{}+-- {} ({} lines)", indent, code, end + 1 - idx)
                 };
+                spans.extend(shade(expand_tabs(
+                    vec![Span::styled(
+                        summary,
+                        Style::default()
+                            .fg(theme.syntax.comment.to_color())
+                            .add_modifier(Modifier::ITALIC),
+                    )],
+                    state.tab_size,
+                )));
+                return vec![Line::from(spans)];
+            }
+
+            // Very long lines skip highlighting and selection; only the
+            // part that fits on screen is drawn
+            if line.len() > state.long_line_threshold {
+                let end = line
+                    .char_indices()
+                    .nth(inner.width as usize)
+                    .map_or(line.len(), |(i, _)| i);
+                let plain = Span::styled(
+                    &line[..end],
+                    Style::default().fg(theme.ui.foreground.to_color()),
+                );
+                spans.extend(shade(expand_tabs(vec![plain], state.tab_size)));
+                return vec![Line::from(spans)];
+            }
 
-            // Syntax highlighted content with search and selection highlighting
-            let search_query = if state.search_query.is_empty() {
-                None
-            } else {
-                Some(state.search_query.as_str())
-            };
-
-            if let Some((sel_start, sel_end)) = line_selection {
-                // Apply selection highlighting
-                let chars: Vec<char> = line.chars().collect();
-                let sel_start = sel_start.min(chars.len());
-                let sel_end = sel_end.min(chars.len()).max(sel_start);
-
-                // Before selection
-                if sel_start > 0 {
-                    let before: String = chars[..sel_start].iter().collect();
-                    let highlighted = Highlighter::highlight_line_with_search(
-                        &before,
-                        &theme.syntax,
-                        search_query,
-                        &theme.ui.search_match,
-                        &current_match_pos,
-                        idx,
-                        &theme.ui.search_match_current,
-                    );
-                    spans.extend(highlighted);
-                }
+            // Syntax highlighted content with search highlighting
+            let mut content = Highlighter::highlight_line_with_search(
+                line,
+                buf.dialect,
+                &theme.syntax,
+                &state.search_ranges(line),
+                &theme.ui.search_match,
+                &current_match_pos,
+                idx,
+                &theme.ui.search_match_current,
+            );
+
+            if state.rainbow_brackets {
+                content = Highlighter::rainbow_brackets(content, &theme.syntax.rainbow);
+            }
+
+            // Trailing whitespace, except on the line being typed on
+            let trimmed = line.trim_end().len();
+            if trimmed < line.len() && idx != buf.cursor_y {
+                let marker = Style::default().bg(theme.ui.whitespace_marker.to_color());
+                content = restyle_range(content, trimmed, line.len(), marker);
+            }
+
+            // Search matches keep their own background
+            for &(_, col) in brackets.iter().filter(|(line, _)| *line == idx) {
+                let end = utf8::next_char_boundary(line, col);
+                content = restyle_range_with(content, col, end, |style| {
+                    if style.bg.is_some() {
+                        style
+                    } else {
+                        style.bg(theme.ui.bracket_match.to_color())
+                    }
+                });
+            }
 
-                // Selected portion
-                if sel_end > sel_start {
-                    let selected: String = chars[sel_start..sel_end].iter().collect();
-                    spans.push(Span::styled(
-                        selected,
-                        Style::default()
-                            .bg(theme.ui.selection.to_color())
-                            .fg(theme.ui.selection_fg.to_color()),
-                    ));
+            let selection_style = Style::default()
+                .bg(theme.ui.selection.to_color())
+                .fg(theme.ui.selection_fg.to_color());
+            if let Some((top, bottom, left, right)) = block_range {
+                if idx >= top && idx <= bottom {
+                    let (from, to) = state.block_bytes(line, left, right);
+                    content = restyle_range(content, from, to, selection_style);
                 }
+            }
 
-                // After selection
-                if sel_end < chars.len() {
-                    let after: String = chars[sel_end..].iter().collect();
-                    let highlighted = Highlighter::highlight_line_with_search(
-                        &after,
-                        &theme.syntax,
-                        search_query,
-                        &theme.ui.search_match,
-                        &current_match_pos,
-                        idx,
-                        &theme.ui.search_match_current,
-                    );
-                    spans.extend(highlighted);
+            // Selection highlighting over the part of this line it covers
+            if let Some(((start_line, start_col), (end_line, end_col))) = selection_range {
+                if idx >= start_line && idx <= end_line {
+                    let sel_start = if idx == start_line { start_col } else { 0 };
+                    let sel_end = if idx == end_line { end_col } else { line.len() };
+                    content = restyle_range(content, sel_start, sel_end, selection_style);
                 }
-            } else {
-                // No selection, just syntax highlight
-                let highlighted = Highlighter::highlight_line_with_search(
-                    line,
-                    &theme.syntax,
-                    search_query,
-                    &theme.ui.search_match,
-                    &current_match_pos,
-                    idx,
-                    &theme.ui.search_match_current,
-                );
-                spans.extend(highlighted);
             }
 
-            Line::from(spans)
+            // Tabs are expanded last so every highlight above, worked out
+            // on byte offsets, lands on the screen columns it covers
+            let mut content = expand_tabs(content, state.tab_size);
+            let width = |spans: &[Span]| spans.iter().map(|s| s.content.chars().count()).sum();
+            if !state.soft_wrap || state.text_width == 0 {
+                if let Some(d) = line_diagnostic {
+                    let room = text_columns.saturating_sub(width(&content));
+                    content.extend(diagnostic_text(d, room, theme));
+                }
+                spans.extend(shade(content));
+                return vec![Line::from(spans)];
+            }
+
+            // Continuation rows of a wrapped line are marked in the gutter
+            let mut rows = wrap_spans(content, state.text_width);
+            if let (Some(d), Some(last)) = (line_diagnostic, rows.last_mut()) {
+                let room = state.text_width.saturating_sub(width(last));
+                last.extend(diagnostic_text(d, room, theme));
+            }
+            let mut rows = rows.into_iter().map(shade);
+            spans.extend(rows.next().unwrap_or_default());
+            let mut wrapped = vec![Line::from(spans)];
+            wrapped.extend(rows.map(|row| {
+                let mut spans = vec![
+                    Span::raw("  "),
+                    Span::styled(
+                        format!("{:>width$} ", "↪", width = line_number_width - 2),
+                        Style::default().fg(theme.ui.line_numbers.to_color()),
+                    ),
+                ];
+                spans.extend(row);
+                Line::from(spans)
+            }));
+            wrapped
         })
+        .take(visible_height)
         .collect();
 
     let paragraph =
@@ -2346,8 +2427,8 @@ pub fn render(
     frame.render_widget(paragraph, inner);
 
     if focused {
-        let cursor_screen_y = buf.cursor_y.saturating_sub(buf.scroll_offset);
-        let cursor_screen_x = line_number_width + 1 + buf.cursor_x;
+        let (cursor_screen_y, cursor_col) = state.cursor_screen_pos();
+        let cursor_screen_x = state.gutter_width() + cursor_col;
 
         if cursor_screen_y < visible_height {
             frame.set_cursor_position(Position::new(
@@ -2358,9 +2439,278 @@ pub fn render(
     }
 }
 
+/// The message of `diagnostic` as dim italic virtual text after its line,
+/// cut to the `room` columns left on the row. None if too little is left.
+fn diagnostic_text(diagnostic: &Diagnostic, room: usize, theme: &Theme) -> Option<Span<'static>> {
+    const MIN_ROOM: usize = 6;
+    if room < MIN_ROOM {
+        return None;
+    }
+    let mut text = format!(" \u{25c0} {}", diagnostic.message);
+    if text.chars().count() > room {
+        text = text.chars().take(room - 1).collect();
+        text.push('…');
+    }
+    let color = match diagnostic.severity {
+        DiagnosticSeverity::Error => &theme.ui.diagnostic_error,
+        DiagnosticSeverity::Warning => &theme.ui.diagnostic_warning,
+    };
+    Some(Span::styled(
+        text,
+        Style::default()
+            .fg(color.to_color())
+            .add_modifier(Modifier::DIM | Modifier::ITALIC),
+    ))
+}
+
+/// A row of the cursor line: `bg` under the text wherever no highlight
+/// set a background, padded with spaces to `width` columns so the shading
+/// runs across the editor
+fn shade_row(mut spans: Vec<Span>, width: usize, bg: Color) -> Vec<Span> {
+    let used: usize = spans.iter().map(|s| s.content.chars().count()).sum();
+    for span in &mut spans {
+        if span.style.bg.is_none() {
+            span.style = span.style.bg(bg);
+        }
+    }
+    if used < width {
+        spans.push(Span::styled(
+            " ".repeat(width - used),
+            Style::default().bg(bg),
+        ));
+    }
+    spans
+}
+
+/// Give bytes `start..end` of the text in `spans` the style `style`,
+/// splitting spans at the range edges
+fn restyle_range<'a>(
+    spans: Vec<Span<'a>>,
+    start: usize,
+    end: usize,
+    style: Style,
+) -> Vec<Span<'a>> {
+    restyle_range_with(spans, start, end, |_| style)
+}
+
+/// Like `restyle_range`, deriving the new style from each span's own
+fn restyle_range_with<'a>(
+    spans: Vec<Span<'a>>,
+    start: usize,
+    end: usize,
+    restyle: impl Fn(Style) -> Style,
+) -> Vec<Span<'a>> {
+    let mut result = Vec::with_capacity(spans.len() + 2);
+    let mut pos = 0;
+    for span in spans {
+        let text = span.content.as_ref();
+        let span_start = pos;
+        pos += text.len();
+        let from = utf8::clamp_to_char_boundary(text, start.saturating_sub(span_start));
+        let to = utf8::clamp_to_char_boundary(text, end.saturating_sub(span_start));
+        if from >= to {
+            result.push(span);
+            continue;
+        }
+        for (range, part_style) in [
+            (0..from, span.style),
+            (from..to, restyle(span.style)),
+            (to..text.len(), span.style),
+        ] {
+            if !range.is_empty() {
+                result.push(Span::styled(text[range].to_string(), part_style));
+            }
+        }
+    }
+    result
+}
+
+/// Cut `spans` into rows of `width` screen columns, splitting spans at
+/// the row edges. A full last row is followed by an empty one, where the
+/// cursor goes after the last character.
+fn wrap_spans(spans: Vec<Span<'_>>, width: usize) -> Vec<Vec<Span<'_>>> {
+    let mut rows = vec![Vec::new()];
+    let mut col = 0;
+    for span in spans {
+        let mut piece = String::new();
+        for c in span.content.chars() {
+            if col == width {
+                if !piece.is_empty() {
+                    let text = std::mem::take(&mut piece);
+                    rows.last_mut()
+                        .unwrap()
+                        .push(Span::styled(text, span.style));
+                }
+                rows.push(Vec::new());
+                col = 0;
+            }
+            piece.push(c);
+            col += 1;
+        }
+        if !piece.is_empty() {
+            rows.last_mut()
+                .unwrap()
+                .push(Span::styled(piece, span.style));
+        }
+    }
+    if col == width {
+        rows.push(Vec::new());
+    }
+    rows
+}
+
+/// Replace tabs with spaces up to the next `tab_size` stop. Spans are
+/// assumed to start at screen column 0 of the text area.
+fn expand_tabs(spans: Vec<Span<'_>>, tab_size: usize) -> Vec<Span<'_>> {
+    let mut col = 0;
+    spans
+        .into_iter()
+        .map(|span| {
+            if !span.content.contains('\t') {
+                col += span.content.chars().count();
+                return span;
+            }
+            let mut text = String::with_capacity(span.content.len());
+            for c in span.content.chars() {
+                let next = CursorOps::advance_col(col, c, tab_size);
+                if c == '\t' {
+                    text.extend(std::iter::repeat_n(' ', next - col));
+                } else {
+                    text.push(c);
+                }
+                col = next;
+            }
+            Span::styled(text, span.style)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::EditorState;
+    use super::{diagnostic_text, expand_tabs, restyle_range, shade_row, wrap_spans, EditorState};
+    use crate::diagnostics::Diagnostic;
+    use crate::masm_lang::Dialect;
+    use crate::syntax::Highlighter;
+    use crate::theme::Theme;
+    use ratatui::prelude::*;
+    use std::path::PathBuf;
+
+    /// Screen column and text of each span drawn with background `bg`
+    fn columns_with_bg(spans: &[Span], bg: Color) -> Vec<(usize, String)> {
+        let mut col = 0;
+        let mut found = Vec::new();
+        for span in spans {
+            if span.style.bg == Some(bg) {
+                found.push((col, span.content.to_string()));
+            }
+            col += span.content.chars().count();
+        }
+        found
+    }
+
+    #[test]
+    fn tabs_shift_selection_and_search_highlights() {
+        let theme = Theme::gruvbox();
+        let line = "\tadd\teax, 1";
+        let highlight = |matches: &[(usize, usize)]| {
+            Highlighter::highlight_line_with_search(
+                line,
+                Dialect::Masm,
+                &theme.syntax,
+                matches,
+                &theme.ui.search_match,
+                &[(0, 5)],
+                0,
+                &theme.ui.search_match_current,
+            )
+        };
+
+        // "eax" starts at byte 5 but is drawn after two tab stops
+        let spans = expand_tabs(highlight(&[(5, 8)]), 4);
+        let current = theme.ui.search_match_current.to_color();
+        assert_eq!(
+            columns_with_bg(&spans, current),
+            vec![(8, String::from("eax"))]
+        );
+
+        // Selecting bytes 1..7 ("add\tea") covers the expanded tab too
+        let selected = Style::default().bg(Color::Magenta);
+        let spans = expand_tabs(restyle_range(highlight(&[]), 1, 7, selected), 4);
+        assert_eq!(
+            columns_with_bg(&spans, Color::Magenta),
+            vec![
+                (4, String::from("add")),
+                (7, String::from(" ")),
+                (8, String::from("ea"))
+            ]
+        );
+        let text: String = spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, "    add eax, 1");
+    }
+
+    #[test]
+    fn diagnostic_text_is_cut_to_the_room_left() {
+        let theme = Theme::dark();
+        let d = Diagnostic::error(
+            PathBuf::from("main.asm"),
+            3,
+            String::from("undefined symbol : count"),
+        );
+        let full = diagnostic_text(&d, 80, &theme).unwrap();
+        assert_eq!(full.content, " \u{25c0} undefined symbol : count");
+        assert!(full.style.add_modifier.contains(Modifier::ITALIC));
+        let cut = diagnostic_text(&d, 12, &theme).unwrap();
+        assert_eq!(cut.content, " \u{25c0} undefine…");
+        assert!(diagnostic_text(&d, 3, &theme).is_none());
+    }
+
+    #[test]
+    fn cursor_line_shades_under_highlights_to_the_edge() {
+        let selected = Style::default().bg(Color::Magenta);
+        let spans = vec![
+            Span::styled("mov ", Style::default().fg(Color::Blue)),
+            Span::styled("eax", selected),
+        ];
+        let row = shade_row(spans, 10, Color::DarkGray);
+        let styled: Vec<(&str, Option<Color>)> = row
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style.bg))
+            .collect();
+        assert_eq!(
+            styled,
+            vec![
+                ("mov ", Some(Color::DarkGray)),
+                ("eax", Some(Color::Magenta)),
+                ("   ", Some(Color::DarkGray)),
+            ]
+        );
+        assert_eq!(row[0].style.fg, Some(Color::Blue));
+    }
+
+    #[test]
+    fn wrapped_rows_split_spans_at_the_width() {
+        let red = Style::default().fg(Color::Red);
+        let spans = vec![Span::raw("mov "), Span::styled("eax, 1", red)];
+        let rows: Vec<Vec<(String, Style)>> = wrap_spans(spans, 4)
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|span| (span.content.to_string(), span.style))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![(String::from("mov "), Style::default())],
+                vec![(String::from("eax,"), red)],
+                vec![(String::from(" 1"), red)],
+            ]
+        );
+        // A line filling its last row leaves an empty one for the cursor
+        assert_eq!(wrap_spans(vec![Span::raw("ret ")], 4).len(), 2);
+        assert_eq!(wrap_spans(Vec::new(), 4).len(), 1);
+    }
 
     #[test]
     fn utf8_insert_and_backspace_are_safe() {
@@ -225,6 +225,17 @@ impl FileTreeState {
         self.selected_entry().map(|e| e.path.clone())
     }
 
+    /// Select the entry for `path` if it is currently listed
+    pub fn select_path(&mut self, path: &Path) -> bool {
+        match self.entries.iter().position(|e| e.path == path) {
+            Some(idx) => {
+                self.list_state.select(Some(idx));
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn move_up(&mut self) {
         if let Some(selected) = self.list_state.selected() {
             if selected > 0 {
@@ -1,91 +1,13 @@
+use crate::keymap::{self, KeyContext};
 use crate::theme::Theme;
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
 };
 
-/// Help content organized by section - compact two-column format
-const HELP_SECTIONS: &[(&str, &[(&str, &str)])] = &[
-    (
-        "GLOBAL",
-        &[
-            ("F1", "Help"),
-            ("F5", "Build+Run"),
-            ("F6", "Build"),
-            ("F7", "Run"),
-            ("F8", "Output view"),
-            ("F9", "Save output"),
-            ("Ctrl+S", "Save"),
-            ("Ctrl+Q", "Quit"),
-            ("Ctrl+E", "File tree"),
-            ("Ctrl+O", "Output"),
-            ("Tab", "Cycle focus"),
-        ],
-    ),
-    (
-        "NORMAL MODE",
-        &[
-            ("i/a/A", "Insert"),
-            ("o/O", "New line ↓/↑"),
-            ("v/V", "Visual mode"),
-            ("hjkl", "←↓↑→"),
-            ("w/b", "Word →/←"),
-            ("0/$", "Line start/end"),
-            ("g/G", "File start/end"),
-            ("x/dd", "Delete"),
-            ("y/p", "Yank/paste"),
-            ("u/Ctrl+R", "Undo/redo"),
-            ("/n/N", "Search/next/prev"),
-            (":", "Command"),
-        ],
-    ),
-    (
-        "INSERT",
-        &[
-            ("Esc", "Normal mode"),
-            ("Ctrl+C/V/X", "Copy/paste/cut"),
-            ("Ctrl+Z/Y", "Undo/redo"),
-        ],
-    ),
-    (
-        "VISUAL",
-        &[("y/d", "Yank/delete"), ("Ctrl+C", "Copy"), ("Esc", "Exit")],
-    ),
-    (
-        "FILES (R=refresh)",
-        &[
-            ("j/k h/l", "Navigate"),
-            ("Enter", "Open"),
-            ("a/A", "New file/dir"),
-            ("r/d", "Rename/delete"),
-        ],
-    ),
-    (
-        "OUTPUT",
-        &[
-            ("jk/gG", "Scroll/jump"),
-            ("Ctrl+C", "Clear/copy"),
-            ("y", "Copy (F8)"),
-        ],
-    ),
-    (
-        "COMMANDS",
-        &[
-            (":w :q :wq", "Save/quit"),
-            (":e file", "Open"),
-            (":bn :bp :bd", "Buffers"),
-            (":theme n", "Theme"),
-            (":autosave", "Toggle"),
-            (":refresh", "File tree"),
-        ],
-    ),
-    (
-        "RESIZE",
-        &[("Ctrl+←→", "Tree width"), ("Ctrl+↑↓", "Output height")],
-    ),
-];
-
-pub fn render(frame: &mut Frame, area: Rect, theme: &Theme, scroll: usize) {
+/// Render the help popup, generated from the active keybinding table.
+/// Bindings for `context` are listed first.
+pub fn render(frame: &mut Frame, area: Rect, theme: &Theme, scroll: usize, context: KeyContext) {
     // Calculate centered popup area - more compact
     let popup_width = (area.width * 80 / 100).min(68);
     let popup_height = (area.height * 80 / 100).min(28);
@@ -115,20 +37,20 @@ pub fn render(frame: &mut Frame, area: Rect, theme: &Theme, scroll: usize) {
         .add_modifier(Modifier::BOLD);
     let dim_style = Style::default().fg(theme.ui.line_numbers.to_color());
 
-    for (section_name, bindings) in HELP_SECTIONS {
+    for (category, bindings) in keymap::grouped(context) {
         // Section header with decorative line
         lines.push(Line::from(vec![
             Span::styled("┌─ ", dim_style),
-            Span::styled(*section_name, section_style),
+            Span::styled(category.title(), section_style),
             Span::styled(" ─", dim_style),
         ]));
 
         // Two-column layout for bindings
         let mut row: Vec<Span> = Vec::new();
-        for (i, (key, desc)) in bindings.iter().enumerate() {
+        for (i, binding) in bindings.iter().enumerate() {
             let entry = vec![
-                Span::styled(format!(" {:11}", key), key_style),
-                Span::styled(format!("{:18}", desc), desc_style),
+                Span::styled(format!(" {:11}", binding.keys), key_style),
+                Span::styled(format!("{:18}", binding.description), desc_style),
             ];
             row.extend(entry);
 
@@ -201,9 +123,9 @@ pub fn render(frame: &mut Frame, area: Rect, theme: &Theme, scroll: usize) {
     }
 }
 
-pub fn total_lines() -> usize {
+pub fn total_lines(context: KeyContext) -> usize {
     let mut count = 2; // Header + empty line
-    for (_, bindings) in HELP_SECTIONS {
+    for (_, bindings) in keymap::grouped(context) {
         count += 1; // Section header
         count += bindings.len().div_ceil(2); // Bindings (2 per row)
         count += 1; // Empty line
@@ -75,25 +75,7 @@ pub fn render(frame: &mut Frame, doc: &DocEntry, cursor_screen_pos: (u16, u16),
 
     let popup_width = (content_width + 4).clamp(30, 60); // +4 for borders and padding
     let popup_height = (lines.len() as u16 + 2).min(20); // +2 for borders
-
-    // Position popup - try above cursor first, then below
-    let (cursor_x, cursor_y) = cursor_screen_pos;
-
-    let popup_x = if cursor_x + popup_width < area.width {
-        cursor_x
-    } else {
-        area.width.saturating_sub(popup_width)
-    };
-
-    let popup_y = if cursor_y > popup_height {
-        cursor_y - popup_height - 1 // Above cursor
-    } else if cursor_y + popup_height + 2 < area.height {
-        cursor_y + 1 // Below cursor
-    } else {
-        1 // Top of screen
-    };
-
-    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+    let popup_area = place_popup(area, cursor_screen_pos, popup_width, popup_height);
 
     // Clear the area behind the popup
     frame.render_widget(Clear, popup_area);
@@ -112,3 +94,50 @@ pub fn render(frame: &mut Frame, doc: &DocEntry, cursor_screen_pos: (u16, u16),
 
     frame.render_widget(paragraph, popup_area);
 }
+
+/// Render the one-line signature hint shown while typing operands
+pub fn render_signature(
+    frame: &mut Frame,
+    doc: &DocEntry,
+    cursor_screen_pos: (u16, u16),
+    theme: &Theme,
+) {
+    let area = frame.area();
+    let line = Line::from(Span::styled(
+        doc.syntax,
+        Style::default()
+            .fg(theme.syntax.keyword.to_color())
+            .add_modifier(Modifier::BOLD),
+    ));
+    let popup_width = (line.width() as u16 + 4).min(area.width);
+    let popup_area = place_popup(area, cursor_screen_pos, popup_width, 3);
+
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.ui.border.to_color()))
+        .style(Style::default().bg(theme.ui.background.to_color()));
+    frame.render_widget(Paragraph::new(line).block(block), popup_area);
+}
+
+/// Where a popup of the given size goes: above the cursor if it fits,
+/// else below it, else at the top of the screen
+fn place_popup(area: Rect, cursor_screen_pos: (u16, u16), width: u16, height: u16) -> Rect {
+    let (cursor_x, cursor_y) = cursor_screen_pos;
+
+    let popup_x = if cursor_x + width < area.width {
+        cursor_x
+    } else {
+        area.width.saturating_sub(width)
+    };
+
+    let popup_y = if cursor_y > height {
+        cursor_y - height - 1 // Above cursor
+    } else if cursor_y + height + 2 < area.height {
+        cursor_y + 1 // Below cursor
+    } else {
+        1 // Top of screen
+    };
+
+    Rect::new(popup_x, popup_y, width, height)
+}
@@ -41,6 +41,10 @@ pub fn render(frame: &mut Frame, app: &mut App) {
 
     h_constraints.push(Constraint::Min(30)); // Main area takes all remaining space
 
+    if app.show_outline {
+        h_constraints.push(Constraint::Length(super::outline::OUTLINE_WIDTH));
+    }
+
     let h_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(h_constraints)
@@ -64,6 +68,17 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     // Main editor/output area
     let main_area = h_chunks[chunk_idx];
 
+    // Symbol outline on the right
+    if app.show_outline {
+        super::outline::render(
+            frame,
+            h_chunks[chunk_idx + 1],
+            &app.outline,
+            app.focus == FocusedPanel::Outline,
+            &theme,
+        );
+    }
+
     // Check if we need tab bar (multiple buffers)
     let show_tabs = app.editor.buffers.len() > 1;
 
@@ -84,12 +99,17 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     };
 
     // Split vertically: editor on top, output on bottom
-    if app.show_output {
+    if let Some(view) = app.diff_view.as_mut() {
+        super::diff_view::render(frame, editor_area, view, &theme);
+    } else if app.show_output {
         let v_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Min(10), Constraint::Length(app.output_height)])
             .split(editor_area);
 
+        app.editor_area = v_chunks[0];
+        app.editor.text_width =
+            (v_chunks[0].width as usize).saturating_sub(2 + app.editor.gutter_width());
         super::editor::render(
             frame,
             v_chunks[0],
@@ -110,6 +130,9 @@ pub fn render(frame: &mut Frame, app: &mut App) {
             &theme,
         );
     } else {
+        app.editor_area = editor_area;
+        app.editor.text_width =
+            (editor_area.width as usize).saturating_sub(2 + app.editor.gutter_width());
         super::editor::render(
             frame,
             editor_area,
@@ -144,7 +167,41 @@ pub fn render(frame: &mut Frame, app: &mut App) {
 
     // Render help popup if visible
     if app.show_help {
-        super::help::render(frame, size, &theme, app.help_scroll);
+        super::help::render(frame, size, &theme, app.help_scroll, app.key_context());
+    }
+
+    // Render undo tree panel if visible
+    if app.show_undo_tree {
+        super::undo_tree::render(
+            frame,
+            size,
+            &app.editor.undo_tree,
+            app.undo_tree_selected,
+            &theme,
+        );
+    }
+
+    if let Some(picker) = &app.symbol_picker {
+        super::picker::render(frame, size, picker, &theme);
+    }
+
+    if let Some(picker) = &app.buffer_picker {
+        super::picker::render(frame, size, picker, &theme);
+    }
+
+    if app.show_quickfix {
+        super::quickfix::render(frame, size, &app.quickfix, &theme);
+    }
+
+    if app.show_diagnostics_panel {
+        super::diagnostics_panel::render(
+            frame,
+            size,
+            &app.diagnostics,
+            app.current_diagnostic,
+            &app.project_dir,
+            &theme,
+        );
     }
 
     // Render input popup if in that mode
@@ -158,28 +215,19 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         );
     }
 
+    // Render the signature hint above the cursor while typing operands
+    if app.mode == Mode::Insert {
+        if let Some(doc) = app.signature_hint {
+            super::hover::render_signature(frame, doc, cursor_screen_position(app), &theme);
+        }
+    }
+
     // Render autocomplete popup if visible
     if app.autocomplete.visible && app.mode == Mode::Insert {
-        // Calculate cursor screen position
-        let buf = &app.editor.buffers[app.editor.active_buffer];
-        let line_number_width = format!("{}", buf.lines.len()).len() + 2;
-
-        // Account for file tree width and editor position
-        let editor_x = if app.show_file_tree {
-            app.file_tree_width
-        } else {
-            0
-        };
-        let editor_y = if app.editor.buffers.len() > 1 { 1 } else { 0 }; // Tab bar
-
-        let cursor_screen_x = editor_x + line_number_width as u16 + 1 + buf.cursor_x as u16;
-        let cursor_screen_y =
-            editor_y + 1 + (buf.cursor_y.saturating_sub(buf.scroll_offset)) as u16;
-
         super::autocomplete::render(
             frame,
             &app.autocomplete,
-            (cursor_screen_x, cursor_screen_y),
+            cursor_screen_position(app),
             &theme,
         );
     }
@@ -187,23 +235,28 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     // Render hover documentation popup if visible
     if app.show_hover {
         if let Some(doc) = app.hover_doc {
-            let buf = &app.editor.buffers[app.editor.active_buffer];
-            let line_number_width = format!("{}", buf.lines.len()).len() + 2;
+            super::hover::render(frame, doc, cursor_screen_position(app), &theme);
+        }
+    }
+}
 
-            let editor_x = if app.show_file_tree {
-                app.file_tree_width
-            } else {
-                0
-            };
-            let editor_y = if app.editor.buffers.len() > 1 { 1 } else { 0 };
+/// Terminal position of the editor cursor, for popups anchored to it
+fn cursor_screen_position(app: &App) -> (u16, u16) {
+    let buf = &app.editor.buffers[app.editor.active_buffer];
+    let line_number_width = format!("{}", buf.lines.len()).len() + 2;
 
-            let cursor_screen_x = editor_x + line_number_width as u16 + 1 + buf.cursor_x as u16;
-            let cursor_screen_y =
-                editor_y + 1 + (buf.cursor_y.saturating_sub(buf.scroll_offset)) as u16;
+    // Account for file tree width and editor position
+    let editor_x = if app.show_file_tree {
+        app.file_tree_width
+    } else {
+        0
+    };
+    let editor_y = if app.editor.buffers.len() > 1 { 1 } else { 0 }; // Tab bar
 
-            super::hover::render(frame, doc, (cursor_screen_x, cursor_screen_y), &theme);
-        }
-    }
+    let (row, col) = app.editor.cursor_screen_pos();
+    let cursor_screen_x = editor_x + line_number_width as u16 + 1 + col as u16;
+    let cursor_screen_y = editor_y + 1 + row as u16;
+    (cursor_screen_x, cursor_screen_y)
 }
 
 /// Render fullscreen output-only view (for screenshots)
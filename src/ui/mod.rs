@@ -1,5 +1,8 @@
 pub mod autocomplete;
+pub mod buffer_picker;
 pub mod command_bar;
+pub mod diagnostics_panel;
+pub mod diff_view;
 pub mod editor;
 pub mod editor_render;
 pub mod file_tree;
@@ -7,10 +10,15 @@ pub mod help;
 pub mod hover;
 pub mod input_popup;
 pub mod layout;
+pub mod outline;
 pub mod output;
+pub mod picker;
+pub mod quickfix;
 pub mod search_bar;
 pub mod status_bar;
+pub mod symbol_picker;
 pub mod tabs;
+pub mod undo_tree;
 
 use crate::app::App;
 use ratatui::Frame;
@@ -0,0 +1,210 @@
+use crate::theme::Theme;
+use crate::ui::editor::{line_definition, DefinitionKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState},
+};
+
+/// Width of the outline sidebar
+pub const OUTLINE_WIDTH: u16 = 32;
+
+/// One symbol defined in the active buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    /// 0-based line of the definition
+    pub line: usize,
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// A label inside a PROC, drawn under it
+    pub nested: bool,
+}
+
+/// Symbols of the active buffer in line order, for the outline sidebar
+#[derive(Debug, Default)]
+pub struct OutlineState {
+    pub entries: Vec<OutlineEntry>,
+    pub selected: usize,
+    /// Buffer and buffer revision the entries were collected from
+    source: Option<(usize, u64)>,
+}
+
+impl OutlineState {
+    /// Recollect the entries if `lines` (of buffer `buffer`, at
+    /// `revision`) changed since the last call
+    pub fn refresh(&mut self, buffer: usize, revision: u64, lines: &[String]) {
+        let source = Some((buffer, revision));
+        if source == self.source {
+            return;
+        }
+        if self.source.is_some_and(|(old, _)| old != buffer) {
+            self.selected = 0;
+        }
+        self.source = source;
+        self.entries = outline_entries(lines);
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.entries.len().saturating_sub(1);
+    }
+
+    /// Line of the highlighted entry
+    pub fn selected_line(&self) -> Option<usize> {
+        self.entries.get(self.selected).map(|e| e.line)
+    }
+}
+
+/// Every PROC, MACRO, EQU and label in `lines`, found the way `gd` finds
+/// a definition
+pub fn outline_entries(lines: &[String]) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    let mut in_proc = false;
+    for (line, text) in lines.iter().enumerate() {
+        if let Some((name, _, kind)) = line_definition(text) {
+            entries.push(OutlineEntry {
+                line,
+                name: name.to_string(),
+                kind,
+                nested: in_proc && kind == DefinitionKind::Label,
+            });
+            in_proc |= kind == DefinitionKind::Proc;
+        } else if text
+            .split_whitespace()
+            .nth(1)
+            .is_some_and(|word| word.eq_ignore_ascii_case("endp"))
+        {
+            in_proc = false;
+        }
+    }
+    entries
+}
+
+pub fn render(frame: &mut Frame, area: Rect, state: &OutlineState, focused: bool, theme: &Theme) {
+    let (border_style, title_style) = if focused {
+        (
+            Style::default().fg(theme.ui.border_focused.to_color()),
+            Style::default()
+                .fg(theme.ui.title_focused.to_color())
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        (
+            Style::default().fg(theme.ui.border.to_color()),
+            Style::default().fg(theme.ui.title.to_color()),
+        )
+    };
+
+    let block = Block::default()
+        .title(Span::styled(" Outline ", title_style))
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(border_style)
+        .style(Style::default().bg(theme.ui.background.to_color()));
+
+    let dim_style = Style::default().fg(theme.ui.line_numbers.to_color());
+    let items: Vec<ListItem> = state
+        .entries
+        .iter()
+        .map(|entry| {
+            let (tag, color) = match entry.kind {
+                DefinitionKind::Proc => ("proc ", &theme.syntax.directive),
+                DefinitionKind::Macro => ("macro", &theme.syntax.macro_call),
+                DefinitionKind::Equ => ("equ  ", &theme.syntax.number),
+                DefinitionKind::Label => ("label", &theme.syntax.label),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>5} ", entry.line + 1), dim_style),
+                Span::styled(tag, Style::default().fg(color.to_color())),
+                Span::raw(if entry.nested { "   " } else { " " }),
+                Span::styled(
+                    entry.name.as_str(),
+                    Style::default().fg(theme.ui.foreground.to_color()),
+                ),
+            ]))
+        })
+        .collect();
+
+    if items.is_empty() {
+        let empty = ListItem::new(Span::styled(" No symbols", dim_style));
+        frame.render_widget(List::new(vec![empty]).block(block), area);
+        return;
+    }
+
+    let mut list_state = ListState::default();
+    if focused {
+        list_state.select(Some(state.selected));
+    }
+    let list = List::new(items).block(block).highlight_style(
+        Style::default()
+            .bg(theme.ui.selection.to_color())
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline_lists_definitions_in_order() {
+        let lines: Vec<String> = [
+            "BUFSIZE EQU 64",
+            "print MACRO text",
+            "ENDM",
+            ".code",
+            "main PROC",
+            "again:",
+            "    mov eax, ds:[ebx]",
+            "main ENDP",
+            "done:",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let entries = outline_entries(&lines);
+        let summary: Vec<(usize, &str, DefinitionKind, bool)> = entries
+            .iter()
+            .map(|e| (e.line, e.name.as_str(), e.kind, e.nested))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, "BUFSIZE", DefinitionKind::Equ, false),
+                (1, "print", DefinitionKind::Macro, false),
+                (4, "main", DefinitionKind::Proc, false),
+                (5, "again", DefinitionKind::Label, true),
+                (8, "done", DefinitionKind::Label, false),
+            ]
+        );
+
+        let mut state = OutlineState::default();
+        state.refresh(0, 1, &lines);
+        state.select_last();
+        assert_eq!(state.selected_line(), Some(8));
+        // An unchanged revision is not reparsed and keeps the selection
+        state.refresh(0, 1, &lines[..2]);
+        assert_eq!(state.entries.len(), 5);
+        assert_eq!(state.selected, 4);
+        state.refresh(0, 2, &lines[..5]);
+        assert_eq!(state.entries.len(), 3);
+        assert_eq!(state.selected, 2);
+        // Another buffer resets it
+        state.refresh(1, 3, &lines[..2]);
+        assert_eq!(state.selected_line(), Some(0));
+    }
+}
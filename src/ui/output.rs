@@ -4,6 +4,7 @@ use ratatui::{
     text::Span,
     widgets::{Block, Borders, Paragraph},
 };
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OutputType {
@@ -19,6 +20,19 @@ pub enum OutputType {
 pub struct OutputLine {
     pub text: String,
     pub output_type: OutputType,
+    pub elapsed: Duration, // Time since the output was last cleared
+}
+
+impl OutputType {
+    /// Short tag shown before lines when level tags are enabled
+    pub fn level_tag(&self) -> &'static str {
+        match self {
+            OutputType::Success | OutputType::Info => "[BUILD]",
+            OutputType::Stdout => "[RUN]",
+            OutputType::Error | OutputType::Stderr => "[ERR]",
+            OutputType::Divider => "",
+        }
+    }
 }
 
 pub struct OutputState {
@@ -26,6 +40,9 @@ pub struct OutputState {
     pub scroll_offset: usize,
     pub visible_height: usize,
     pub auto_scroll: bool,
+    pub show_timestamps: bool,
+    pub show_level_tags: bool,
+    started: Instant,
 }
 
 impl OutputState {
@@ -35,6 +52,9 @@ impl OutputState {
             scroll_offset: 0,
             visible_height: 10, // Default, will be updated on render
             auto_scroll: true,
+            show_timestamps: false,
+            show_level_tags: false,
+            started: Instant::now(),
         }
     }
 
@@ -42,15 +62,76 @@ impl OutputState {
         self.lines.clear();
         self.scroll_offset = 0;
         self.auto_scroll = true;
+        self.started = Instant::now();
+    }
+
+    fn push_line(&mut self, text: &str, output_type: OutputType) {
+        self.lines.push(OutputLine {
+            text: text.to_string(),
+            output_type,
+            elapsed: self.started.elapsed(),
+        });
+    }
+
+    /// Prefix for a line based on the timestamp/level tag settings.
+    /// Empty when both are disabled.
+    pub fn line_prefix(&self, line: &OutputLine) -> String {
+        if line.output_type == OutputType::Divider {
+            return String::new();
+        }
+        let mut prefix = String::new();
+        if self.show_timestamps {
+            let millis = line.elapsed.as_millis();
+            prefix.push_str(&format!(
+                "+{:02}:{:02}.{:03} ",
+                millis / 60_000,
+                (millis / 1000) % 60,
+                millis % 1000
+            ));
+        }
+        if self.show_level_tags {
+            prefix.push_str(&format!("{:<7} ", line.output_type.level_tag()));
+        }
+        prefix
+    }
+
+    /// Full text of every line for saving to a file, with the same
+    /// markers and timestamp/level prefixes as the panel. The panel clips
+    /// long lines to its width; this never does.
+    pub fn export_text(&self) -> String {
+        let mut content = String::new();
+        for line in &self.lines {
+            content.push_str(&self.line_prefix(line));
+            let marker = match line.output_type {
+                OutputType::Success => "✓ ",
+                OutputType::Error => "✗ ",
+                OutputType::Stderr => "⚠ ",
+                OutputType::Info => "→ ",
+                OutputType::Stdout => "  ",
+                OutputType::Divider => "",
+            };
+            content.push_str(marker);
+            content.push_str(&line.text);
+            content.push('\n');
+        }
+        content
+    }
+
+    /// Full text of every line without markers or prefixes, for the
+    /// clipboard
+    pub fn plain_text(&self) -> String {
+        let mut content = String::new();
+        for line in &self.lines {
+            content.push_str(&line.text);
+            content.push('\n');
+        }
+        content
     }
 
     pub fn append_stdout(&mut self, text: &str) {
         for line in text.lines() {
             // Keep empty lines for program output formatting
-            self.lines.push(OutputLine {
-                text: line.to_string(),
-                output_type: OutputType::Stdout,
-            });
+            self.push_line(line, OutputType::Stdout);
         }
         if self.auto_scroll {
             self.scroll_to_bottom();
@@ -70,10 +151,7 @@ impl OutputState {
             } else {
                 OutputType::Info
             };
-            self.lines.push(OutputLine {
-                text: line.to_string(),
-                output_type,
-            });
+            self.push_line(line, output_type);
         }
         if self.auto_scroll {
             self.scroll_to_bottom();
@@ -81,40 +159,28 @@ impl OutputState {
     }
 
     pub fn append_error(&mut self, text: &str) {
-        self.lines.push(OutputLine {
-            text: text.to_string(),
-            output_type: OutputType::Error,
-        });
+        self.push_line(text, OutputType::Error);
         if self.auto_scroll {
             self.scroll_to_bottom();
         }
     }
 
     pub fn append_info(&mut self, text: &str) {
-        self.lines.push(OutputLine {
-            text: text.to_string(),
-            output_type: OutputType::Info,
-        });
+        self.push_line(text, OutputType::Info);
         if self.auto_scroll {
             self.scroll_to_bottom();
         }
     }
 
     pub fn append_success(&mut self, text: &str) {
-        self.lines.push(OutputLine {
-            text: text.to_string(),
-            output_type: OutputType::Success,
-        });
+        self.push_line(text, OutputType::Success);
         if self.auto_scroll {
             self.scroll_to_bottom();
         }
     }
 
     pub fn append_divider(&mut self) {
-        self.lines.push(OutputLine {
-            text: String::new(),
-            output_type: OutputType::Divider,
-        });
+        self.push_line("", OutputType::Divider);
         if self.auto_scroll {
             self.scroll_to_bottom();
         }
@@ -154,6 +220,22 @@ impl OutputState {
         self.scroll_down(page_size);
     }
 
+    /// Row of the first error line, if any
+    pub fn first_error_row(&self) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| line.output_type == OutputType::Error)
+    }
+
+    /// Scroll so `row` is the top visible line (as far as the content allows)
+    /// and stop following new output
+    pub fn scroll_to_row(&mut self, row: usize) {
+        let content_height = self.visible_height.saturating_sub(2);
+        let max_scroll = self.lines.len().saturating_sub(content_height);
+        self.scroll_offset = row.min(max_scroll);
+        self.auto_scroll = false;
+    }
+
     pub fn scroll_to_top(&mut self) {
         self.scroll_offset = 0;
         self.auto_scroll = false;
@@ -208,7 +290,6 @@ pub fn render(
 
     let inner = block.inner(area);
     let visible_height = inner.height as usize;
-    let _inner_width = inner.width as usize;
 
     // Update state with current visible height for proper scrolling
     state.update_visible_height(visible_height);
@@ -328,7 +409,17 @@ pub fn render(
                 ))
             }
         };
-        text.push(styled_line);
+        let prefix = state.line_prefix(line);
+        if prefix.is_empty() {
+            text.push(styled_line);
+        } else {
+            let mut spans = vec![Span::styled(
+                prefix,
+                Style::default().fg(theme.ui.line_numbers.to_color()),
+            )];
+            spans.extend(styled_line.spans);
+            text.push(Line::from(spans));
+        }
     }
 
     // Add bottom padding (empty line)
@@ -338,3 +429,26 @@ pub fn render(
 
     frame.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_keeps_full_lines() {
+        let mut output = OutputState::new();
+        let long = "x".repeat(500);
+        output.append_info("Assembling...");
+        output.append_divider();
+        output.append_stdout(&format!("{}\nDone", long));
+        output.show_level_tags = true;
+
+        let exported = output.export_text();
+        assert!(exported.contains(&format!("[RUN]     {}\n", long)));
+        assert!(exported.starts_with("[BUILD] → Assembling...\n\n"));
+        assert_eq!(
+            output.plain_text(),
+            format!("Assembling...\n\n{}\nDone\n", long)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::fuzzy::fuzzy_score;
+use crate::theme::Theme;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+/// Something a picker lists: how it's named in the popup, matched
+/// against the query and drawn as a row
+pub trait PickerItem {
+    /// Popup title, followed by the match count
+    const TITLE: &'static str;
+    /// What Enter does, shown in the key hints
+    const ACTION: &'static str;
+    /// Shown when nothing matches the query
+    const EMPTY: &'static str;
+    /// Largest popup width and height
+    const MAX_SIZE: (u16, u16);
+
+    /// Text the query is fuzzily matched against
+    fn name(&self) -> &str;
+
+    /// Rank of this item for `query`, higher first; None hides it
+    fn score(&self, query: &str) -> Option<i64> {
+        fuzzy_score(query, self.name()).map(|(score, _)| score)
+    }
+
+    fn row<'a>(&'a self, styles: &PickerStyles, theme: &Theme) -> Line<'a>;
+}
+
+/// Styles shared by every picker's rows
+pub struct PickerStyles {
+    pub key: Style,
+    pub desc: Style,
+    pub dim: Style,
+}
+
+impl PickerStyles {
+    fn new(theme: &Theme) -> Self {
+        Self {
+            key: Style::default()
+                .fg(theme.ui.title_focused.to_color())
+                .add_modifier(Modifier::BOLD),
+            desc: Style::default().fg(theme.ui.foreground.to_color()),
+            dim: Style::default().fg(theme.ui.line_numbers.to_color()),
+        }
+    }
+}
+
+/// A popup list filtered by a typed query
+pub struct PickerState<T> {
+    pub items: Vec<T>,
+    pub query: String,
+    /// Indices into `items` that match `query`, best first
+    pub matches: Vec<usize>,
+    pub selected: usize,
+}
+
+impl<T: PickerItem> PickerState<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        let mut state = Self {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        state.refilter();
+        state
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.refilter();
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.refilter();
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Highlight the first listed item `pred` holds for, if any
+    pub fn select_first(&mut self, pred: impl Fn(&T) -> bool) {
+        if let Some(row) = self.matches.iter().position(|&i| pred(&self.items[i])) {
+            self.selected = row;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.matches
+            .get(self.selected)
+            .and_then(|&i| self.items.get(i))
+    }
+
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| item.score(&self.query).map(|score| (score, i)))
+            .collect();
+        // Stable, so equal scores keep index order
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+pub fn render<T: PickerItem>(frame: &mut Frame, area: Rect, state: &PickerState<T>, theme: &Theme) {
+    let (max_width, max_height) = T::MAX_SIZE;
+    let popup_width = (area.width * 80 / 100).min(max_width);
+    let popup_height = (area.height * 70 / 100).min(max_height);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(popup_width)) / 2,
+        area.y + (area.height.saturating_sub(popup_height)) / 3,
+        popup_width,
+        popup_height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let styles = PickerStyles::new(theme);
+
+    // One row for the query, the rest for results
+    let visible_rows = popup_height.saturating_sub(3) as usize;
+    let scroll = state
+        .selected
+        .saturating_sub(visible_rows.saturating_sub(1));
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", styles.key),
+        Span::styled(state.query.as_str(), styles.desc),
+        Span::styled("█", styles.dim),
+    ])];
+    for (row, &idx) in state
+        .matches
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(visible_rows)
+    {
+        let mut line = state.items[idx].row(&styles, theme);
+        if row == state.selected {
+            line = line.style(Style::default().bg(theme.ui.selection.to_color()));
+        }
+        lines.push(line);
+    }
+    if state.matches.is_empty() {
+        lines.push(Line::from(Span::styled(T::EMPTY, styles.dim)));
+    }
+
+    let block = Block::default()
+        .title(Span::styled(
+            format!(
+                " {} {}/{} ",
+                T::TITLE,
+                state.matches.len(),
+                state.items.len()
+            ),
+            styles.key,
+        ))
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" ↑↓ ", styles.key),
+                Span::styled("move ", styles.desc),
+                Span::styled("Enter ", styles.key),
+                Span::styled(format!("{} ", T::ACTION), styles.desc),
+                Span::styled("Esc ", styles.key),
+                Span::styled("close ", styles.desc),
+            ])
+            .right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.ui.border_focused.to_color()))
+        .style(Style::default().bg(theme.ui.background.to_color()));
+
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
@@ -0,0 +1,193 @@
+use crate::theme::Theme;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::path::PathBuf;
+
+/// One `:grep` hit
+#[derive(Debug, Clone)]
+pub struct QuickfixEntry {
+    pub path: PathBuf,
+    /// 0-based line and byte column of the match
+    pub line: usize,
+    pub col: usize,
+    /// File as shown in the list, relative to the project root
+    pub location: String,
+    pub text: String,
+}
+
+/// Results of the last `:grep`, stepped through with `]q`/`[q`
+#[derive(Debug, Default)]
+pub struct QuickfixList {
+    pub pattern: String,
+    pub entries: Vec<QuickfixEntry>,
+    /// Entry last jumped to, or highlighted in the panel
+    pub current: usize,
+}
+
+impl QuickfixList {
+    pub fn select_next(&mut self) {
+        if self.current + 1 < self.entries.len() {
+            self.current += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.current = self.current.saturating_sub(1);
+    }
+
+    /// Advance `current` by one in either direction, wrapping around
+    pub fn step(&mut self, forward: bool) -> Option<&QuickfixEntry> {
+        let len = self.entries.len();
+        if len == 0 {
+            return None;
+        }
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+        self.entries.get(self.current)
+    }
+
+    pub fn selected(&self) -> Option<&QuickfixEntry> {
+        self.entries.get(self.current)
+    }
+}
+
+/// Every case-insensitive occurrence of `pattern` in `lines`, as
+/// (line, byte column) pairs
+pub fn grep_lines(lines: &[String], pattern: &str) -> Vec<(usize, usize)> {
+    // ASCII lowering keeps byte offsets valid in the original line
+    let needle = pattern.to_ascii_lowercase();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let mut hits = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let haystack = line.to_ascii_lowercase();
+        hits.extend(haystack.match_indices(&needle).map(|(col, _)| (idx, col)));
+    }
+    hits
+}
+
+pub fn render(frame: &mut Frame, area: Rect, list: &QuickfixList, theme: &Theme) {
+    let popup_width = (area.width * 90 / 100).min(110);
+    let popup_height = (area.height * 70 / 100).min(24);
+    let popup_area = Rect::new(
+        area.x + (area.width.saturating_sub(popup_width)) / 2,
+        area.y + (area.height.saturating_sub(popup_height)) / 3,
+        popup_width,
+        popup_height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let key_style = Style::default()
+        .fg(theme.ui.title_focused.to_color())
+        .add_modifier(Modifier::BOLD);
+    let desc_style = Style::default().fg(theme.ui.foreground.to_color());
+    let dim_style = Style::default().fg(theme.ui.line_numbers.to_color());
+    let match_style = Style::default()
+        .bg(theme.ui.search_match.to_color())
+        .fg(theme.ui.background.to_color());
+
+    let visible_rows = popup_height.saturating_sub(2) as usize;
+    let scroll = list.current.saturating_sub(visible_rows.saturating_sub(1));
+
+    let lines: Vec<Line> = list
+        .entries
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(visible_rows)
+        .map(|(row, entry)| {
+            let text = entry.text.trim_end();
+            let start = entry.col.min(text.len());
+            let end = (start + list.pattern.len()).min(text.len());
+            let (before, rest) = text.split_at(start);
+            let (matched, after) = rest.split_at(end - start);
+            let mut line = Line::from(vec![
+                Span::styled(
+                    format!(" {}:{}: ", entry.location, entry.line + 1),
+                    dim_style,
+                ),
+                Span::styled(before.trim_start(), desc_style),
+                Span::styled(matched, match_style),
+                Span::styled(after, desc_style),
+            ]);
+            if row == list.current {
+                line = line.style(Style::default().bg(theme.ui.selection.to_color()));
+            }
+            line
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(Span::styled(
+            format!(
+                " Grep: {} ({} match{}) ",
+                list.pattern,
+                list.entries.len(),
+                if list.entries.len() == 1 { "" } else { "es" }
+            ),
+            key_style,
+        ))
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" jk ", key_style),
+                Span::styled("move ", desc_style),
+                Span::styled("Enter ", key_style),
+                Span::styled("open ", desc_style),
+                Span::styled("Esc ", key_style),
+                Span::styled("close ", desc_style),
+            ])
+            .right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.ui.border_focused.to_color()))
+        .style(Style::default().bg(theme.ui.background.to_color()));
+
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grep_matches_and_wrapping_steps() {
+        let lines: Vec<String> = [
+            "    call WriteString",
+            "; writestring twice: WRITESTRING",
+            "ret",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let hits = grep_lines(&lines, "WriteString");
+        assert_eq!(hits, vec![(0, 9), (1, 2), (1, 21)]);
+        assert!(grep_lines(&lines, "").is_empty());
+
+        let mut list = QuickfixList {
+            pattern: String::from("WriteString"),
+            entries: hits
+                .into_iter()
+                .map(|(line, col)| QuickfixEntry {
+                    path: PathBuf::from("main.asm"),
+                    line,
+                    col,
+                    location: String::from("main.asm"),
+                    text: lines[line].clone(),
+                })
+                .collect(),
+            current: 0,
+        };
+        assert_eq!(list.step(false).unwrap().col, 21);
+        assert_eq!(list.step(true).unwrap().col, 9);
+        list.select_prev();
+        assert_eq!(list.current, 0);
+    }
+}
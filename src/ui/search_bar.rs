@@ -1,14 +1,24 @@
+use crate::config::SearchCase;
 use crate::theme::Theme;
 use crate::ui::editor::EditorState;
 use ratatui::{prelude::*, widgets::Paragraph};
 
 pub fn render(frame: &mut Frame, area: Rect, input: &str, editor: &EditorState, theme: &Theme) {
-    let status = editor.search_status().unwrap_or_default();
-    let text = if status.is_empty() {
-        format!("/{}", input)
-    } else {
-        format!("/{}  [{}]", input, status)
-    };
+    let mut text = format!("/{}", input);
+    if editor.search_is_regex {
+        text.push_str("  [regex]");
+    }
+    if editor.search_whole_word {
+        text.push_str("  [word]");
+    }
+    match editor.search_case {
+        SearchCase::Sensitive => text.push_str("  [case]"),
+        SearchCase::Insensitive => text.push_str("  [nocase]"),
+        SearchCase::Smart => {}
+    }
+    if let Some(status) = editor.search_status() {
+        text.push_str(&format!("  [{}]", status));
+    }
 
     let paragraph = Paragraph::new(text).style(
         Style::default()
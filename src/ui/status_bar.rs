@@ -1,5 +1,6 @@
 use crate::app::{App, Mode};
 use crate::diagnostics::{count_by_severity, DiagnosticSeverity};
+use crate::ui::editor::LineEnding;
 use ratatui::{prelude::*, text::Span, widgets::Paragraph};
 
 pub fn render(frame: &mut Frame, area: Rect, app: &App) {
@@ -14,6 +15,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
         Mode::InputPopup => " INPUT ",
         Mode::Visual => " VISUAL ",
         Mode::VisualLine => " V-LINE ",
+        Mode::VisualBlock => " V-BLOCK ",
     };
 
     let mode_style = match app.mode {
@@ -41,7 +43,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
             .bg(theme.ui.mode_command_bg.to_color())
             .fg(theme.ui.mode_command_fg.to_color())
             .add_modifier(Modifier::BOLD),
-        Mode::Visual | Mode::VisualLine => Style::default()
+        Mode::Visual | Mode::VisualLine | Mode::VisualBlock => Style::default()
             .bg(theme.ui.selection.to_color())
             .fg(theme.ui.selection_fg.to_color())
             .add_modifier(Modifier::BOLD),
@@ -56,6 +58,21 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
         None => String::from(" [No File] "),
     };
 
+    let buf = &app.editor.buffers[app.editor.active_buffer];
+    let mut format_flags = Vec::new();
+    if buf.has_bom {
+        format_flags.push("BOM");
+    }
+    if buf.line_ending != LineEnding::Lf {
+        format_flags.push(buf.line_ending.label());
+    }
+    let format_info = if format_flags.is_empty() {
+        String::new()
+    } else {
+        format!(" {} ", format_flags.join(" "))
+    };
+    let dialect_info = format!(" {} ", buf.dialect.name());
+
     // Buffer indicator
     let buffer_info = if app.editor.buffers.len() > 1 {
         format!(
@@ -77,14 +94,20 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
     let cursor_diagnostic = app.diagnostic_at_cursor();
 
     // Use diagnostic message if cursor is on an error line, otherwise use status message
-    let status_msg = if let Some(diag) = cursor_diagnostic {
+    let status_msg = if let Some(spinner) = app.build_spinner() {
+        format!(" {} Building... ", spinner)
+    } else if let Some((diag, others)) = cursor_diagnostic {
         let severity = match diag.severity {
             DiagnosticSeverity::Error => "Error",
             DiagnosticSeverity::Warning => "Warning",
         };
-        format!(" {}: {} ", severity, diag.message)
+        if others > 0 {
+            format!(" {}: {} (+{} more) ", severity, diag.message, others)
+        } else {
+            format!(" {}: {} ", severity, diag.message)
+        }
     } else {
-        format!(" {} ", app.status_message)
+        format!(" {} ", app.status_message())
     };
 
     // Diagnostic count indicator
@@ -109,6 +132,16 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
             .bg(theme.ui.tab_active_bg.to_color())
             .fg(theme.ui.tab_active_fg.to_color()),
     );
+    let format_span = Span::styled(
+        format_info,
+        Style::default()
+            .fg(theme.ui.diagnostic_warning.to_color())
+            .add_modifier(Modifier::BOLD),
+    );
+    let dialect_span = Span::styled(
+        dialect_info,
+        Style::default().fg(theme.ui.status_bar_fg.to_color()),
+    );
     let buffer_span = Span::styled(
         buffer_info,
         Style::default()
@@ -139,7 +172,7 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
         Span::raw("")
     };
 
-    let msg_color = if let Some(diag) = cursor_diagnostic {
+    let msg_color = if let Some((diag, _)) = cursor_diagnostic {
         match diag.severity {
             DiagnosticSeverity::Error => theme.ui.diagnostic_error.to_color(),
             DiagnosticSeverity::Warning => theme.ui.diagnostic_warning.to_color(),
@@ -153,10 +186,25 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
     // Calculate remaining space for right-aligned cursor position
     let left_len = mode_str.len()
         + file_span.content.len()
+        + dialect_span.content.len()
+        + format_span.content.len()
         + buffer_span.content.len()
         + diag_indicator.len()
         + status_msg.len();
-    let right_len = cursor_pos.len();
+    // Partially typed multi-key command, shown just left of the position
+    let pending = app.pending_keys();
+    let pending_str = if pending.is_empty() {
+        String::new()
+    } else {
+        format!(" {} ", pending)
+    };
+    // Bytes the data definition under the cursor occupies
+    let data_size_str = match app.data_size_at_cursor() {
+        Some(1) => String::from(" 1 byte "),
+        Some(bytes) => format!(" {} bytes ", bytes),
+        None => String::new(),
+    };
+    let right_len = pending_str.len() + data_size_str.len() + cursor_pos.len();
     let padding = if area.width as usize > left_len + right_len {
         area.width as usize - left_len - right_len
     } else {
@@ -164,6 +212,16 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
     };
 
     let padding_span = Span::raw(" ".repeat(padding));
+    let pending_span = Span::styled(
+        pending_str,
+        Style::default()
+            .fg(theme.ui.status_bar_fg.to_color())
+            .add_modifier(Modifier::BOLD),
+    );
+    let data_size_span = Span::styled(
+        data_size_str,
+        Style::default().fg(theme.ui.status_bar_fg.to_color()),
+    );
     let cursor_span = Span::styled(
         cursor_pos,
         Style::default()
@@ -174,10 +232,14 @@ pub fn render(frame: &mut Frame, area: Rect, app: &App) {
     let line = Line::from(vec![
         mode_span,
         file_span,
+        dialect_span,
+        format_span,
         buffer_span,
         diag_span,
         msg_span,
         padding_span,
+        pending_span,
+        data_size_span,
         cursor_span,
     ]);
     let paragraph =
@@ -0,0 +1,71 @@
+use crate::autocomplete::SuggestionKind;
+use crate::theme::Theme;
+use crate::ui::picker::{PickerItem, PickerState, PickerStyles};
+use ratatui::prelude::*;
+use std::path::PathBuf;
+
+/// A definition found while indexing the project
+#[derive(Debug, Clone)]
+pub struct ProjectSymbol {
+    pub name: String,
+    pub kind: SuggestionKind,
+    pub path: PathBuf,
+    /// 0-based line of the definition
+    pub line: usize,
+    /// Defining file as shown in the picker, relative to the project root
+    pub location: String,
+}
+
+pub type SymbolPickerState = PickerState<ProjectSymbol>;
+
+impl PickerItem for ProjectSymbol {
+    const TITLE: &'static str = "Symbols";
+    const ACTION: &'static str = "jump";
+    const EMPTY: &'static str = " No matching symbols";
+    const MAX_SIZE: (u16, u16) = (80, 24);
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn row<'a>(&'a self, styles: &PickerStyles, _theme: &Theme) -> Line<'a> {
+        Line::from(vec![
+            Span::styled(format!(" {:<6} ", self.kind.label()), styles.dim),
+            Span::styled(self.name.as_str(), styles.desc),
+            Span::styled(format!("  {}:{}", self.location, self.line + 1), styles.dim),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str) -> ProjectSymbol {
+        ProjectSymbol {
+            name: name.to_string(),
+            kind: SuggestionKind::Procedure,
+            path: PathBuf::from("main.asm"),
+            line: 0,
+            location: String::from("main.asm"),
+        }
+    }
+
+    #[test]
+    fn test_symbol_picker_filters_by_query() {
+        let mut picker = SymbolPickerState::new(vec![
+            symbol("loop_end"),
+            symbol("ReadInput"),
+            symbol("main"),
+        ]);
+        assert_eq!(picker.matches.len(), 3);
+        for c in "ri".chars() {
+            picker.push_char(c);
+        }
+        assert_eq!(picker.selected().unwrap().name, "ReadInput");
+        picker.push_char('z');
+        assert!(picker.selected().is_none());
+        picker.backspace();
+        assert_eq!(picker.matches.len(), 1);
+    }
+}
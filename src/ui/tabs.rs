@@ -2,15 +2,55 @@ use crate::theme::Theme;
 use crate::ui::editor::EditorState;
 use ratatui::{prelude::*, text::Span, widgets::Paragraph};
 
+/// Tabs that fit in `width` columns, scrolled so `active` stays visible.
+/// `widths` includes each tab's trailing separator. Returns the visible
+/// range; room is kept for the "◂N"/"N▸" counts of tabs cut off either side.
+fn visible_tabs(widths: &[usize], active: usize, width: usize) -> (usize, usize) {
+    if widths.iter().sum::<usize>() <= width {
+        return (0, widths.len());
+    }
+    // Both overflow markers are at most this wide
+    let budget = width.saturating_sub(10);
+    let mut start = active;
+    let mut end = active + 1;
+    let mut used = widths[active];
+    // Prefer showing the tabs after the active one, then fill backwards
+    while end < widths.len() && used + widths[end] <= budget {
+        used += widths[end];
+        end += 1;
+    }
+    while start > 0 && used + widths[start - 1] <= budget {
+        start -= 1;
+        used += widths[start];
+    }
+    (start, end)
+}
+
 pub fn render(frame: &mut Frame, area: Rect, editor: &EditorState, theme: &Theme) {
+    let texts: Vec<String> = editor
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(idx, buffer)| {
+            let modified = if buffer.modified { " ●" } else { "" };
+            format!(" {}{} ", editor.display_name(idx), modified)
+        })
+        .collect();
+    let widths: Vec<usize> = texts.iter().map(|t| t.chars().count() + 1).collect();
+    let (start, end) = visible_tabs(&widths, editor.active_buffer, area.width as usize);
+
+    let marker_style = Style::default()
+        .fg(theme.ui.title_focused.to_color())
+        .bg(theme.ui.tab_inactive_bg.to_color())
+        .add_modifier(Modifier::BOLD);
     let mut spans = Vec::new();
+    if start > 0 {
+        spans.push(Span::styled(format!("◂{} ", start), marker_style));
+    }
 
-    for (idx, buffer) in editor.buffers.iter().enumerate() {
+    for (idx, tab_text) in texts.into_iter().enumerate().take(end).skip(start) {
+        let buffer = &editor.buffers[idx];
         let is_active = idx == editor.active_buffer;
-        let modified = if buffer.modified { " ●" } else { "" };
-        let name = buffer.filename();
-
-        let tab_text = format!(" {}{} ", name, modified);
 
         let style = if is_active {
             Style::default()
@@ -23,6 +63,11 @@ pub fn render(frame: &mut Frame, area: Rect, editor: &EditorState, theme: &Theme
                 .bg(theme.ui.tab_inactive_bg.to_color())
         };
 
+        let style = if buffer.preview {
+            style.add_modifier(Modifier::ITALIC)
+        } else {
+            style
+        };
         spans.push(Span::styled(tab_text, style));
 
         // Add separator
@@ -34,6 +79,14 @@ pub fn render(frame: &mut Frame, area: Rect, editor: &EditorState, theme: &Theme
         }
     }
 
+    // Buffers scrolled off to the right (see :ls for the full list)
+    if end < editor.buffers.len() {
+        spans.push(Span::styled(
+            format!(" {}▸", editor.buffers.len() - end),
+            marker_style,
+        ));
+    }
+
     // Fill remaining space with background
     let line = Line::from(spans);
     let paragraph =
@@ -41,3 +94,19 @@ pub fn render(frame: &mut Frame, area: Rect, editor: &EditorState, theme: &Theme
 
     frame.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::visible_tabs;
+
+    #[test]
+    fn test_tab_bar_scrolls_to_active_buffer() {
+        assert_eq!(visible_tabs(&[10, 10, 10], 2, 40), (0, 3));
+
+        let widths = [12; 8];
+        let (start, end) = visible_tabs(&widths, 6, 50);
+        assert!(start > 0 && start <= 6 && end > 6);
+        assert!(widths[start..end].iter().sum::<usize>() <= 40);
+        assert_eq!(visible_tabs(&widths, 0, 50).0, 0);
+    }
+}
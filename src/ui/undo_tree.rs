@@ -0,0 +1,101 @@
+use crate::theme::Theme;
+use crate::ui::editor::UndoTree;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+fn format_age(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else {
+        format!("{}h ago", secs / 3600)
+    }
+}
+
+pub fn render(frame: &mut Frame, area: Rect, tree: &UndoTree, selected: usize, theme: &Theme) {
+    let popup_width = (area.width * 80 / 100).min(64);
+    let popup_height = (area.height * 80 / 100).min(24);
+
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+
+    let popup_area = Rect::new(
+        area.x + popup_x,
+        area.y + popup_y,
+        popup_width,
+        popup_height,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let key_style = Style::default()
+        .fg(theme.ui.title_focused.to_color())
+        .add_modifier(Modifier::BOLD);
+    let desc_style = Style::default().fg(theme.ui.foreground.to_color());
+    let dim_style = Style::default().fg(theme.ui.line_numbers.to_color());
+
+    let rows = tree.branch_rows();
+    let selected = selected.min(rows.len().saturating_sub(1));
+
+    let lines: Vec<Line> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, &(idx, level))| {
+            let node = tree.node(idx).expect("row refers to a live node");
+            let marker = if idx == tree.current() {
+                "● "
+            } else {
+                "○ "
+            };
+            let label = match &node.action {
+                Some(action) => action.describe(),
+                None => String::from("original"),
+            };
+            let mut line = Line::from(vec![
+                Span::styled("│ ".repeat(level), dim_style),
+                Span::styled(marker, key_style),
+                Span::styled(format!("{:>4} ", node.seq), dim_style),
+                Span::styled(label, desc_style),
+                Span::styled(
+                    format!("  {}", format_age(node.created.elapsed().as_secs())),
+                    dim_style,
+                ),
+            ]);
+            if i == selected {
+                line = line.style(Style::default().bg(theme.ui.selection.to_color()));
+            }
+            line
+        })
+        .collect();
+
+    // Keep the selected row in view
+    let visible_height = popup_height.saturating_sub(2) as usize;
+    let scroll = selected.saturating_sub(visible_height.saturating_sub(1));
+
+    let block = Block::default()
+        .title(Span::styled(" Undo Tree ", key_style))
+        .title_bottom(
+            Line::from(vec![
+                Span::styled(" jk ", key_style),
+                Span::styled("move ", desc_style),
+                Span::styled("Enter ", key_style),
+                Span::styled("restore ", desc_style),
+                Span::styled("Esc ", key_style),
+                Span::styled("close ", desc_style),
+            ])
+            .right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.ui.border_focused.to_color()))
+        .style(Style::default().bg(theme.ui.background.to_color()));
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((scroll as u16, 0));
+
+    frame.render_widget(paragraph, popup_area);
+}
@@ -0,0 +1,62 @@
+//! Byte/char index helpers for UTF-8 strings
+
+/// Largest char boundary of `s` at or before `idx`
+pub fn clamp_to_char_boundary(s: &str, idx: usize) -> usize {
+    let idx = idx.min(s.len());
+    if s.is_char_boundary(idx) {
+        return idx;
+    }
+
+    // Search left for the nearest boundary
+    let mut i = idx;
+    while i > 0 {
+        i -= 1;
+        if s.is_char_boundary(i) {
+            return i;
+        }
+    }
+    0
+}
+
+/// Find the byte offset of the previous character boundary
+pub fn prev_char_boundary(s: &str, idx: usize) -> usize {
+    let idx = clamp_to_char_boundary(s, idx);
+    if idx == 0 {
+        return 0;
+    }
+
+    // Find the start byte offset of the previous char
+    let mut i = idx - 1;
+    while i > 0 && !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Find the byte offset of the next character boundary
+pub fn next_char_boundary(s: &str, idx: usize) -> usize {
+    let idx = clamp_to_char_boundary(s, idx);
+    if idx >= s.len() {
+        return s.len();
+    }
+
+    let ch = s[idx..].chars().next().unwrap_or('\0');
+    (idx + ch.len_utf8()).min(s.len())
+}
+
+/// Convert byte index to character index
+pub fn char_index_at_byte(s: &str, byte_idx: usize) -> usize {
+    let byte_idx = clamp_to_char_boundary(s, byte_idx);
+    s[..byte_idx].chars().count()
+}
+
+/// Convert character index to byte index
+pub fn byte_index_of_char(s: &str, char_idx: usize) -> usize {
+    if char_idx == 0 {
+        return 0;
+    }
+    match s.char_indices().nth(char_idx) {
+        Some((b, _)) => b,
+        None => s.len(),
+    }
+}